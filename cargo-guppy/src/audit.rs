@@ -0,0 +1,668 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Offline matching of packages against a local checkout of the RustSec advisory database.
+
+use ahash::{AHashMap, AHashSet};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr, bail, eyre};
+use guppy::{
+    PackageId,
+    graph::{
+        DependencyDirection, PackageGraph, PackageLink, PackageMetadata,
+        cargo::{CargoOptions, CargoSet},
+    },
+};
+use guppy_cmdlib::{
+    CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures, string_to_platform_spec,
+};
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, fs};
+
+#[derive(Debug, Parser)]
+pub struct AuditOptions {
+    /// Path to a local checkout of the RustSec advisory database
+    #[clap(long = "advisory-db")]
+    advisory_db: Utf8PathBuf,
+
+    /// Advisory IDs to ignore (can be specified multiple times)
+    #[clap(long = "ignore")]
+    ignore: Vec<String>,
+
+    /// Also report informational advisories (e.g. unmaintained crates)
+    #[clap(long)]
+    include_informational: bool,
+
+    /// Output results as JSON
+    #[clap(long)]
+    json: bool,
+
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl AuditOptions {
+    pub fn exec(&self) -> Result<()> {
+        let advisories = AdvisoryDb::load(&self.advisory_db)?;
+
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let command = self.metadata_opts.make_command();
+        let pkg_graph = command.build_graph()?;
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(target_platform)
+            .set_host_platform(host_platform);
+
+        let (initials, features_only) = self.pf.make_feature_sets(&pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let ignore: AHashSet<&str> = self.ignore.iter().map(|s| s.as_str()).collect();
+        let hits = audit_cargo_set(&cargo_set, &advisories, |advisory| {
+            !ignore.contains(advisory.id.as_str())
+                && (self.include_informational || advisory.informational.is_none())
+        });
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&hits)?);
+        } else if hits.is_empty() {
+            println!("no advisories found ({} loaded)", advisories.len());
+        } else {
+            for hit in &hits {
+                print!("{hit}");
+            }
+        }
+
+        if !hits.is_empty() {
+            bail!("{} advisory matches found", hits.len());
+        }
+        Ok(())
+    }
+}
+
+/// A set of advisories loaded from a local checkout of the RustSec advisory database.
+#[derive(Clone, Debug, Default)]
+pub struct AdvisoryDb {
+    // Crate name -> advisories for that crate.
+    by_crate: AHashMap<String, Vec<Advisory>>,
+}
+
+impl AdvisoryDb {
+    /// Loads advisories from the `crates` directory of an advisory database checkout.
+    ///
+    /// Both the Markdown (TOML front matter in a ` ```toml ` fence) and the legacy plain TOML
+    /// formats are supported. Withdrawn advisories are skipped.
+    pub fn load(db_dir: &Utf8Path) -> Result<Self> {
+        let crates_dir = db_dir.join("crates");
+        if !crates_dir.is_dir() {
+            bail!(
+                "{} is not an advisory database: {} is not a directory",
+                db_dir,
+                crates_dir
+            );
+        }
+
+        let mut by_crate: AHashMap<String, Vec<Advisory>> = AHashMap::new();
+        for crate_dir in read_dir_sorted(&crates_dir)? {
+            if !crate_dir.is_dir() {
+                continue;
+            }
+            for advisory_path in read_dir_sorted(&crate_dir)? {
+                let advisory = match advisory_path.extension() {
+                    Some("md") | Some("toml") => Advisory::load(&advisory_path)?,
+                    _ => continue,
+                };
+                if advisory.withdrawn {
+                    continue;
+                }
+                by_crate
+                    .entry(advisory.package.clone())
+                    .or_default()
+                    .push(advisory);
+            }
+        }
+
+        Ok(Self { by_crate })
+    }
+
+    /// Returns the total number of advisories loaded.
+    pub fn len(&self) -> usize {
+        self.by_crate.values().map(|v| v.len()).sum()
+    }
+
+    /// Returns true if no advisories were loaded.
+    pub fn is_empty(&self) -> bool {
+        self.by_crate.is_empty()
+    }
+
+    /// Returns the advisories that apply to this package.
+    ///
+    /// Only packages from crates.io are considered, since that's what the advisory database covers.
+    pub fn matches<'a>(
+        &'a self,
+        package: &PackageMetadata<'_>,
+    ) -> impl Iterator<Item = &'a Advisory> + 'a {
+        let advisories = if package.source().is_crates_io() {
+            self.by_crate.get(package.name()).map(|v| v.as_slice())
+        } else {
+            None
+        };
+        let version = package.version().clone();
+        advisories
+            .unwrap_or_default()
+            .iter()
+            .filter(move |advisory| advisory.is_affected(&version))
+    }
+}
+
+/// A single security advisory.
+#[derive(Clone, Debug, Serialize)]
+pub struct Advisory {
+    /// The advisory ID, e.g. `RUSTSEC-2020-0071`.
+    pub id: String,
+    /// The name of the affected crate.
+    pub package: String,
+    /// The advisory title, if any.
+    pub title: Option<String>,
+    /// The URL for more information, if any.
+    pub url: Option<String>,
+    /// The kind of informational advisory (e.g. `unmaintained`), or `None` for vulnerabilities.
+    pub informational: Option<String>,
+    /// Version requirements for patched versions.
+    #[serde(serialize_with = "serialize_reqs")]
+    pub patched: Vec<VersionReq>,
+    /// Version requirements for versions that were never affected.
+    #[serde(serialize_with = "serialize_reqs")]
+    pub unaffected: Vec<VersionReq>,
+    #[serde(skip)]
+    withdrawn: bool,
+}
+
+impl Advisory {
+    fn load(path: &Utf8Path) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).wrap_err_with(|| eyre!("reading advisory {} failed", path))?;
+        Self::parse(&contents, path.extension() == Some("md"))
+            .wrap_err_with(|| eyre!("parsing advisory {} failed", path))
+    }
+
+    fn parse(contents: &str, markdown: bool) -> Result<Self> {
+        let (front_matter, title) = if markdown {
+            let rest = contents
+                .trim_start()
+                .strip_prefix("```toml")
+                .ok_or_else(|| eyre!("advisory does not start with a ```toml block"))?;
+            let end = rest
+                .find("\n```")
+                .ok_or_else(|| eyre!("unterminated ```toml block"))?;
+            let title = rest[end + 4..]
+                .lines()
+                .find_map(|line| line.strip_prefix("# "))
+                .map(|title| title.trim().to_owned());
+            (&rest[..end], title)
+        } else {
+            (contents, None)
+        };
+
+        let raw: RawAdvisoryFile = toml::from_str(front_matter)?;
+        let parse_reqs = |reqs: Vec<String>| -> Result<Vec<VersionReq>> {
+            reqs.iter()
+                .map(|req| {
+                    VersionReq::parse(req)
+                        .wrap_err_with(|| eyre!("invalid version requirement '{}'", req))
+                })
+                .collect()
+        };
+
+        Ok(Self {
+            id: raw.advisory.id,
+            package: raw.advisory.package,
+            title: title.or(raw.advisory.title),
+            url: raw.advisory.url,
+            informational: raw.advisory.informational,
+            patched: parse_reqs(raw.versions.patched)?,
+            unaffected: parse_reqs(raw.versions.unaffected)?,
+            withdrawn: raw.advisory.withdrawn.is_some(),
+        })
+    }
+
+    /// Returns true if the given version is affected by this advisory.
+    pub fn is_affected(&self, version: &Version) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .any(|req| req.matches(version))
+    }
+}
+
+#[derive(Deserialize)]
+struct RawAdvisoryFile {
+    advisory: RawAdvisory,
+    #[serde(default)]
+    versions: RawVersions,
+}
+
+#[derive(Deserialize)]
+struct RawAdvisory {
+    id: String,
+    package: String,
+    title: Option<String>,
+    url: Option<String>,
+    informational: Option<String>,
+    withdrawn: Option<toml::Value>,
+}
+
+#[derive(Default, Deserialize)]
+struct RawVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+/// A package in the resolved build that is affected by an advisory.
+#[derive(Debug, Serialize)]
+pub struct AuditHit<'a> {
+    advisory: &'a Advisory,
+    package: String,
+    version: String,
+    /// Dependency paths, each starting at an affected workspace package and ending at this
+    /// package.
+    paths: Vec<Vec<String>>,
+}
+
+impl fmt::Display for AuditHit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.advisory.id)?;
+        if let Some(title) = &self.advisory.title {
+            write!(f, ": {title}")?;
+        }
+        writeln!(f)?;
+        writeln!(f, "  package: {} {}", self.package, self.version)?;
+        if let Some(kind) = &self.advisory.informational {
+            writeln!(f, "  informational: {kind}")?;
+        }
+        if let Some(url) = &self.advisory.url {
+            writeln!(f, "  url: {url}")?;
+        }
+        if self.advisory.patched.is_empty() {
+            writeln!(f, "  patched: (none)")?;
+        } else {
+            writeln!(
+                f,
+                "  patched: {}",
+                itertools::join(&self.advisory.patched, ", ")
+            )?;
+        }
+        writeln!(f, "  paths:")?;
+        for path in &self.paths {
+            writeln!(f, "    {}", path.join(" -> "))?;
+        }
+        writeln!(f)
+    }
+}
+
+/// Matches the packages in `cargo_set`, across both the target and the host, against the advisory
+/// database.
+///
+/// Packages that aren't part of the simulated build (for example, dependencies that are disabled on
+/// the chosen platforms, or dev-only dependencies if dev-dependencies aren't included) are not
+/// reported.
+pub fn audit_cargo_set<'a>(
+    cargo_set: &CargoSet<'_>,
+    advisories: &'a AdvisoryDb,
+    mut filter: impl FnMut(&Advisory) -> bool,
+) -> Vec<AuditHit<'a>> {
+    let package_graph = cargo_set.package_graph();
+    let packages = cargo_set
+        .target_features()
+        .to_package_set()
+        .union(&cargo_set.host_features().to_package_set());
+
+    // Build a reverse adjacency map out of the links that are actually followed by the build.
+    let mut reverse_links: AHashMap<&PackageId, Vec<PackageLink<'_>>> = AHashMap::new();
+    let mut seen = AHashSet::new();
+    for link in cargo_set
+        .target_links()
+        .chain(cargo_set.host_links())
+        .chain(cargo_set.build_dep_links())
+        .chain(cargo_set.proc_macro_links())
+    {
+        if seen.insert((link.from().id(), link.to().id())) {
+            reverse_links.entry(link.to().id()).or_default().push(link);
+        }
+    }
+
+    let mut hits = Vec::new();
+    for package in packages.packages(DependencyDirection::Forward) {
+        for advisory in advisories.matches(&package) {
+            if !filter(advisory) {
+                continue;
+            }
+            hits.push(AuditHit {
+                advisory,
+                package: package.name().to_owned(),
+                version: package.version().to_string(),
+                paths: workspace_paths(package_graph, package.id(), &reverse_links),
+            });
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        (&a.advisory.id, &a.package, &a.version).cmp(&(&b.advisory.id, &b.package, &b.version))
+    });
+    hits
+}
+
+/// Returns the shortest dependency path from every workspace package that reaches `package_id`.
+fn workspace_paths(
+    package_graph: &PackageGraph,
+    package_id: &PackageId,
+    reverse_links: &AHashMap<&PackageId, Vec<PackageLink<'_>>>,
+) -> Vec<Vec<String>> {
+    // Breadth-first search backwards from the affected package, recording the next hop towards it.
+    let mut next_hop: AHashMap<&PackageId, Option<&PackageId>> = AHashMap::new();
+    next_hop.insert(package_id, None);
+    let mut queue = VecDeque::from([package_id]);
+    let mut workspace_ids = Vec::new();
+
+    while let Some(id) = queue.pop_front() {
+        let metadata = package_graph.metadata(id).expect("valid package ID");
+        if metadata.in_workspace() {
+            workspace_ids.push(id);
+        }
+        for link in reverse_links.get(id).into_iter().flatten() {
+            let from_id = link.from().id();
+            if !next_hop.contains_key(from_id) {
+                next_hop.insert(from_id, Some(id));
+                queue.push_back(from_id);
+            }
+        }
+    }
+
+    let mut paths: Vec<_> = workspace_ids
+        .into_iter()
+        .map(|mut id| {
+            let mut path = Vec::new();
+            loop {
+                let metadata = package_graph.metadata(id).expect("valid package ID");
+                if metadata.in_workspace() {
+                    path.push(metadata.name().to_owned());
+                } else {
+                    path.push(format!("{} {}", metadata.name(), metadata.version()));
+                }
+                match next_hop[id] {
+                    Some(next) => id = next,
+                    None => break,
+                }
+            }
+            path
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+fn read_dir_sorted(dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let mut paths = dir
+        .read_dir_utf8()
+        .wrap_err_with(|| eyre!("reading directory {} failed", dir))?
+        .map(|entry| Ok(entry?.into_path()))
+        .collect::<Result<Vec<_>>>()?;
+    paths.sort();
+    Ok(paths)
+}
+
+fn serialize_reqs<S>(reqs: &[VersionReq], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_seq(reqs.iter().map(|req| req.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::{JsonFixture, METADATA1_REGION, METADATA1_TESTCRATE};
+    use guppy::{
+        graph::feature::StandardFeatures,
+        platform::{Platform, TargetFeatures},
+    };
+
+    static REGION_ADVISORY_MD: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2020-0001"
+package = "region"
+date = "2020-01-01"
+url = "https://example.com/RUSTSEC-2020-0001"
+
+[versions]
+patched = [">= 2.2.0"]
+unaffected = ["< 2.0.0"]
+```
+
+# Memory corruption in region
+
+Some description.
+"#;
+
+    static MACH_ADVISORY_TOML: &str = r#"
+[advisory]
+id = "RUSTSEC-2020-0002"
+package = "mach"
+title = "mach is unmaintained"
+informational = "unmaintained"
+
+[versions]
+patched = []
+"#;
+
+    #[test]
+    fn parse_markdown() {
+        let advisory = Advisory::parse(REGION_ADVISORY_MD, true).expect("advisory parsed");
+        assert_eq!(advisory.id, "RUSTSEC-2020-0001");
+        assert_eq!(advisory.package, "region");
+        assert_eq!(
+            advisory.title.as_deref(),
+            Some("Memory corruption in region")
+        );
+        assert_eq!(
+            advisory.url.as_deref(),
+            Some("https://example.com/RUSTSEC-2020-0001")
+        );
+        assert_eq!(advisory.informational, None);
+        assert_eq!(
+            advisory.patched,
+            vec![VersionReq::parse(">= 2.2.0").unwrap()]
+        );
+        assert_eq!(
+            advisory.unaffected,
+            vec![VersionReq::parse("< 2.0.0").unwrap()]
+        );
+        assert!(!advisory.withdrawn);
+    }
+
+    #[test]
+    fn parse_toml() {
+        let advisory = Advisory::parse(MACH_ADVISORY_TOML, false).expect("advisory parsed");
+        assert_eq!(advisory.id, "RUSTSEC-2020-0002");
+        assert_eq!(advisory.title.as_deref(), Some("mach is unmaintained"));
+        assert_eq!(advisory.informational.as_deref(), Some("unmaintained"));
+        assert!(advisory.patched.is_empty());
+        assert!(advisory.unaffected.is_empty());
+
+        let withdrawn =
+            MACH_ADVISORY_TOML.replace("[versions]", "withdrawn = \"2021-01-01\"\n\n[versions]");
+        let advisory = Advisory::parse(&withdrawn, false).expect("advisory parsed");
+        assert!(advisory.withdrawn);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(
+            Advisory::parse(MACH_ADVISORY_TOML, true).is_err(),
+            "missing ```toml block"
+        );
+        assert!(
+            Advisory::parse("```toml\n[advisory]\n", true).is_err(),
+            "unterminated ```toml block"
+        );
+        assert!(
+            Advisory::parse(
+                &MACH_ADVISORY_TOML.replace("patched = []", "patched = [\"not a req\"]"),
+                false,
+            )
+            .is_err(),
+            "invalid version requirement"
+        );
+    }
+
+    #[test]
+    fn is_affected() {
+        let advisory = Advisory::parse(REGION_ADVISORY_MD, true).expect("advisory parsed");
+        for (version, affected) in [
+            ("1.9.0", false),
+            ("2.0.0", true),
+            ("2.1.2", true),
+            ("2.2.0", false),
+            ("3.0.0", false),
+        ] {
+            assert_eq!(
+                advisory.is_affected(&Version::parse(version).unwrap()),
+                affected,
+                "for version {version}"
+            );
+        }
+
+        // With no patched or unaffected versions, every version is affected.
+        let advisory = Advisory::parse(MACH_ADVISORY_TOML, false).expect("advisory parsed");
+        assert!(advisory.is_affected(&Version::parse("0.2.3").unwrap()));
+    }
+
+    fn make_db(advisories: &[(&str, bool)]) -> AdvisoryDb {
+        let mut by_crate: AHashMap<String, Vec<Advisory>> = AHashMap::new();
+        for (contents, markdown) in advisories {
+            let advisory = Advisory::parse(contents, *markdown).expect("advisory parsed");
+            by_crate
+                .entry(advisory.package.clone())
+                .or_default()
+                .push(advisory);
+        }
+        AdvisoryDb { by_crate }
+    }
+
+    fn make_cargo_set<'g>(
+        package_graph: &'g PackageGraph,
+        target_platform: Option<&str>,
+    ) -> CargoSet<'g> {
+        let initials = package_graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let features_only = package_graph
+            .resolve_none()
+            .to_feature_set(StandardFeatures::Default);
+        let mut cargo_opts = CargoOptions::new();
+        if let Some(triple) = target_platform {
+            let platform = Platform::new(triple.to_owned(), TargetFeatures::Unknown).unwrap();
+            cargo_opts.set_target_platform(platform);
+        }
+        CargoSet::new(initials, features_only, &cargo_opts).expect("cargo set computed")
+    }
+
+    #[test]
+    fn audit_metadata1() {
+        let package_graph = JsonFixture::metadata1().graph();
+        let db = make_db(&[(REGION_ADVISORY_MD, true), (MACH_ADVISORY_TOML, false)]);
+        assert_eq!(db.len(), 2);
+
+        // mach is only a dependency of region on macOS and iOS.
+        let cargo_set = make_cargo_set(package_graph, None);
+        let hits = audit_cargo_set(&cargo_set, &db, |_| true);
+        let summary: Vec<_> = hits
+            .iter()
+            .map(|hit| (hit.advisory.id.as_str(), hit.package.as_str(), &hit.paths))
+            .collect();
+        let region_path = vec![
+            "testcrate".to_owned(),
+            "datatest 0.4.2".to_owned(),
+            "region 2.1.2".to_owned(),
+        ];
+        let mach_path = vec![
+            "testcrate".to_owned(),
+            "datatest 0.4.2".to_owned(),
+            "region 2.1.2".to_owned(),
+            "mach 0.2.3".to_owned(),
+        ];
+        assert_eq!(
+            summary,
+            vec![
+                ("RUSTSEC-2020-0001", "region", &vec![region_path.clone()]),
+                ("RUSTSEC-2020-0002", "mach", &vec![mach_path]),
+            ]
+        );
+
+        let cargo_set = make_cargo_set(package_graph, Some("x86_64-unknown-linux-gnu"));
+        let hits = audit_cargo_set(&cargo_set, &db, |_| true);
+        let ids: Vec<_> = hits.iter().map(|hit| hit.advisory.id.as_str()).collect();
+        assert_eq!(ids, vec!["RUSTSEC-2020-0001"], "mach is not built on Linux");
+
+        // The filter is applied to every advisory.
+        let hits = audit_cargo_set(&cargo_set, &db, |advisory| {
+            advisory.id != "RUSTSEC-2020-0001"
+        });
+        assert!(hits.is_empty(), "region advisory filtered out");
+    }
+
+    #[test]
+    fn workspace_paths_basic() {
+        let package_graph = JsonFixture::metadata1().graph();
+        let mut reverse_links: AHashMap<&PackageId, Vec<PackageLink<'_>>> = AHashMap::new();
+        for link in package_graph
+            .query_workspace()
+            .resolve()
+            .links(DependencyDirection::Forward)
+        {
+            reverse_links.entry(link.to().id()).or_default().push(link);
+        }
+
+        let region_id = PackageId::new(METADATA1_REGION);
+        let testcrate_id = PackageId::new(METADATA1_TESTCRATE);
+        assert_eq!(
+            workspace_paths(package_graph, &region_id, &reverse_links),
+            vec![vec![
+                "testcrate".to_owned(),
+                "datatest 0.4.2".to_owned(),
+                "region 2.1.2".to_owned(),
+            ]],
+        );
+        assert_eq!(
+            workspace_paths(package_graph, &testcrate_id, &reverse_links),
+            vec![vec!["testcrate".to_owned()]],
+            "path from a workspace package to itself"
+        );
+
+        // Without any links, non-workspace packages have no paths.
+        assert!(workspace_paths(package_graph, &region_id, &AHashMap::new()).is_empty());
+    }
+}
@@ -27,6 +27,15 @@
 //! * `subtree-size`: print dependencies along with their unique subtree size
 //! * `dups`: print duplicate packages
 //!
+//! ## Lint commands
+//!
+//! * `lint`: check workspace dependency hygiene against a configurable set of lints
+//!
+//! ## Security commands
+//!
+//! * `audit`: match packages against a local checkout of the [RustSec advisory
+//!   database](https://github.com/rustsec/advisory-db)
+//!
 //! ## Diff commands
 //!
 //! * `diff`: perform a diff of two `cargo metadata` JSON outputs
@@ -35,12 +44,17 @@
 //! ## Workspace manipulations
 //!
 //! * `mv`: move crates to a new location in a workspace, updating paths along the way
+//! * `release-plan`: compute the publish order of workspace crates, check that they can be
+//!   published, and propagate version bumps to dependents
 
+mod audit;
 mod core;
 mod diff;
+mod lint;
 mod mv;
+mod release;
 
-pub use crate::{core::*, mv::*};
+pub use crate::{audit::*, core::*, lint::*, mv::*, release::*};
 
 use ahash::AHashMap;
 use camino::Utf8PathBuf;
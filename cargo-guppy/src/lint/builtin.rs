@@ -0,0 +1,693 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Lints shipped with cargo-guppy.
+
+use super::{Lint, LintContext, LintOutput, Severity};
+use ahash::AHashSet;
+use color_eyre::eyre::{Result, WrapErr, bail, eyre};
+use guppy::{
+    DependencyKind,
+    graph::{DependencyDirection, PackageMetadata, PackagePublish},
+    platform::PlatformStatus,
+};
+use semver::VersionReq;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Configuration for the [`BannedCrates`] lint.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BannedCratesConfig {
+    /// The crates to ban.
+    #[serde(default)]
+    pub crates: Vec<BannedCrate>,
+}
+
+/// A single banned crate.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct BannedCrate {
+    /// The name of the crate.
+    pub name: String,
+    /// The versions of the crate that are banned (default: all versions).
+    pub version: Option<String>,
+    /// The reason the crate is banned, printed along with the diagnostic.
+    pub reason: Option<String>,
+}
+
+/// Reports dependencies on banned crates in the simulated build.
+///
+/// Each dependency edge into a banned crate is reported against the package declaring it.
+#[derive(Clone, Debug)]
+pub struct BannedCrates {
+    banned: Vec<(BannedCrate, VersionReq)>,
+}
+
+impl BannedCrates {
+    /// Creates a new instance of this lint.
+    pub fn new(config: &BannedCratesConfig) -> Result<Self> {
+        let banned = config
+            .crates
+            .iter()
+            .map(|banned| {
+                let req = match &banned.version {
+                    Some(version) => VersionReq::parse(version).wrap_err_with(|| {
+                        eyre!(
+                            "in [banned-crates], invalid version requirement '{}' for {}",
+                            version,
+                            banned.name
+                        )
+                    })?,
+                    None => VersionReq::STAR,
+                };
+                Ok((banned.clone(), req))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { banned })
+    }
+}
+
+impl Lint for BannedCrates {
+    fn id(&self) -> &'static str {
+        "banned-crates"
+    }
+
+    fn description(&self) -> &'static str {
+        "dependencies on crates listed in [banned-crates]"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Deny
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let cargo_set = ctx.cargo_set();
+        let mut seen = AHashSet::new();
+        let mut out = Vec::new();
+
+        for link in cargo_set
+            .target_links()
+            .chain(cargo_set.host_links())
+            .chain(cargo_set.build_dep_links())
+            .chain(cargo_set.proc_macro_links())
+        {
+            let (from, to) = link.endpoints();
+            if !seen.insert((from.id(), to.id())) {
+                continue;
+            }
+            let banned = self
+                .banned
+                .iter()
+                .find(|(banned, req)| banned.name == to.name() && req.matches(to.version()));
+            if let Some((banned, _)) = banned {
+                let mut message = format!(
+                    "{} depends on banned crate {} {}",
+                    from.name(),
+                    to.name(),
+                    to.version()
+                );
+                if let Some(reason) = &banned.reason {
+                    message.push_str(&format!(" ({reason})"));
+                }
+                out.push(LintOutput::new(from, message));
+            }
+        }
+        out
+    }
+}
+
+/// Configuration for the [`DuplicateVersions`] lint.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct DuplicateVersionsConfig {
+    /// The maximum number of versions of a crate allowed in the build (default: 1).
+    #[serde(default = "default_max_versions")]
+    pub max_versions: usize,
+
+    /// Crate names to skip.
+    #[serde(default)]
+    pub skip: Vec<String>,
+}
+
+impl Default for DuplicateVersionsConfig {
+    fn default() -> Self {
+        Self {
+            max_versions: default_max_versions(),
+            skip: Vec::new(),
+        }
+    }
+}
+
+fn default_max_versions() -> usize {
+    1
+}
+
+/// Reports crates that have more versions in the simulated build than allowed.
+#[derive(Clone, Debug)]
+pub struct DuplicateVersions {
+    max_versions: usize,
+    skip: AHashSet<String>,
+}
+
+impl DuplicateVersions {
+    /// Creates a new instance of this lint.
+    pub fn new(config: &DuplicateVersionsConfig) -> Self {
+        Self {
+            max_versions: config.max_versions,
+            skip: config.skip.iter().cloned().collect(),
+        }
+    }
+}
+
+impl Lint for DuplicateVersions {
+    fn id(&self) -> &'static str {
+        "duplicate-versions"
+    }
+
+    fn description(&self) -> &'static str {
+        "crates with more versions in the build than allowed by [duplicate-versions]"
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let cargo_set = ctx.cargo_set();
+        let packages = cargo_set
+            .target_features()
+            .to_package_set()
+            .union(&cargo_set.host_features().to_package_set());
+
+        let mut by_name: BTreeMap<&str, Vec<PackageMetadata<'g>>> = BTreeMap::new();
+        for package in packages.packages(DependencyDirection::Forward) {
+            by_name.entry(package.name()).or_default().push(package);
+        }
+
+        by_name
+            .into_iter()
+            .filter(|(name, packages)| {
+                packages.len() > self.max_versions && !self.skip.contains(*name)
+            })
+            .map(|(name, mut packages)| {
+                packages.sort_by(|a, b| a.version().cmp(b.version()));
+                let versions = itertools::join(packages.iter().map(|p| p.version()), ", ");
+                let message = format!(
+                    "{} has {} versions in the build (max {}): {}",
+                    name,
+                    packages.len(),
+                    self.max_versions,
+                    versions
+                );
+                // Report against the newest version.
+                LintOutput::new(*packages.last().expect("at least one package"), message)
+            })
+            .collect()
+    }
+}
+
+/// Configuration for the [`MissingPublishMetadata`] lint.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct MissingPublishMetadataConfig {
+    /// The fields required to be present (default: `license`, `description` and `repository`).
+    #[serde(default = "default_publish_fields")]
+    pub fields: Vec<String>,
+}
+
+impl Default for MissingPublishMetadataConfig {
+    fn default() -> Self {
+        Self {
+            fields: default_publish_fields(),
+        }
+    }
+}
+
+fn default_publish_fields() -> Vec<String> {
+    vec![
+        "license".to_owned(),
+        "description".to_owned(),
+        "repository".to_owned(),
+    ]
+}
+
+/// Reports publishable workspace packages that are missing manifest metadata.
+///
+/// Packages with `license-file` set are considered to have a license.
+#[derive(Clone, Debug)]
+pub struct MissingPublishMetadata {
+    fields: Vec<String>,
+}
+
+impl MissingPublishMetadata {
+    const KNOWN_FIELDS: &'static [&'static str] = &[
+        "license",
+        "description",
+        "repository",
+        "homepage",
+        "documentation",
+        "readme",
+        "keywords",
+        "categories",
+        "rust-version",
+    ];
+
+    /// Creates a new instance of this lint.
+    pub fn new(config: &MissingPublishMetadataConfig) -> Result<Self> {
+        for field in &config.fields {
+            if !Self::KNOWN_FIELDS.contains(&field.as_str()) {
+                bail!(
+                    "in [missing-publish-metadata], unknown field '{}' (known fields: {})",
+                    field,
+                    Self::KNOWN_FIELDS.join(", ")
+                );
+            }
+        }
+        Ok(Self {
+            fields: config.fields.clone(),
+        })
+    }
+
+    fn has_field(package: &PackageMetadata<'_>, field: &str) -> bool {
+        match field {
+            "license" => package.license().is_some() || package.license_file().is_some(),
+            "description" => package.description().is_some(),
+            "repository" => package.repository().is_some(),
+            "homepage" => package.homepage().is_some(),
+            "documentation" => package.documentation().is_some(),
+            "readme" => package.readme().is_some(),
+            "keywords" => !package.keywords().is_empty(),
+            "categories" => !package.categories().is_empty(),
+            "rust-version" => package.minimum_rust_version().is_some(),
+            _ => unreachable!("fields are validated in MissingPublishMetadata::new"),
+        }
+    }
+}
+
+impl Lint for MissingPublishMetadata {
+    fn id(&self) -> &'static str {
+        "missing-publish-metadata"
+    }
+
+    fn description(&self) -> &'static str {
+        "publishable workspace crates missing fields listed in [missing-publish-metadata]"
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        ctx.package_graph()
+            .workspace()
+            .iter()
+            .filter(|package| !package.publish().is_never())
+            .filter_map(|package| {
+                let missing: Vec<_> = self
+                    .fields
+                    .iter()
+                    .filter(|field| !Self::has_field(&package, field))
+                    .map(|field| field.as_str())
+                    .collect();
+                (!missing.is_empty()).then(|| {
+                    LintOutput::new(
+                        package,
+                        format!(
+                            "{} is publishable but missing: {}",
+                            package.name(),
+                            missing.join(", ")
+                        ),
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reports wildcard (`*`) version requirements declared by workspace packages.
+///
+/// Path dependencies without a version are only reported if the depending package is publishable
+/// and the dependency isn't dev-only, since Cargo requires versions for those at publish time.
+#[derive(Clone, Copy, Debug)]
+pub struct WildcardDependencies;
+
+impl Lint for WildcardDependencies {
+    fn id(&self) -> &'static str {
+        "wildcard-dependencies"
+    }
+
+    fn description(&self) -> &'static str {
+        "dependencies with wildcard version requirements"
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let mut out = Vec::new();
+        for package in ctx.package_graph().workspace().iter() {
+            let publishable = !package.publish().is_never();
+            for link in package.direct_links() {
+                if *link.version_req() != VersionReq::STAR {
+                    continue;
+                }
+                let to = link.to();
+                let report = if to.source().is_local() {
+                    publishable && !link.dev_only()
+                } else {
+                    true
+                };
+                if report {
+                    out.push(LintOutput::new(
+                        package,
+                        format!(
+                            "{} depends on {} with a wildcard version requirement",
+                            package.name(),
+                            link.dep_name()
+                        ),
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Reports publishable workspace packages that depend on packages which can't be published to the
+/// same registries.
+///
+/// Dev-only dependencies are ignored, since Cargo strips them at publish time.
+#[derive(Clone, Copy, Debug)]
+pub struct UnpublishableDependency;
+
+impl UnpublishableDependency {
+    fn blocked_registries(from: PackagePublish<'_>, to: PackagePublish<'_>) -> Option<String> {
+        match from {
+            PackagePublish::Unrestricted => to.is_never().then(|| "any registry".to_owned()),
+            PackagePublish::Registries(registries) => {
+                let blocked: Vec<_> = registries
+                    .iter()
+                    .filter(|registry| !to.can_publish_to(registry))
+                    .map(|registry| registry.as_str())
+                    .collect();
+                (!blocked.is_empty()).then(|| blocked.join(", "))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Lint for UnpublishableDependency {
+    fn id(&self) -> &'static str {
+        "unpublishable-dependency"
+    }
+
+    fn description(&self) -> &'static str {
+        "publishable workspace crates depending on crates that can't be published"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Deny
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let mut out = Vec::new();
+        for package in ctx.package_graph().workspace().iter() {
+            let publish = package.publish();
+            if publish.is_never() {
+                continue;
+            }
+            for link in package.direct_links() {
+                let to = link.to();
+                if link.dev_only() || !to.source().is_local() {
+                    continue;
+                }
+                if let Some(blocked) = Self::blocked_registries(publish, to.publish()) {
+                    out.push(LintOutput::new(
+                        package,
+                        format!(
+                            "{} is publishable but depends on {}, which can't be published to {}",
+                            package.name(),
+                            to.name(),
+                            blocked
+                        ),
+                    ));
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Reports `cfg()` expressions in workspace dependency declarations that likely contain mistakes,
+/// such as `cfg(target_os = "linuz")`.
+///
+/// Such expressions are valid, but typically never match any platform. For the checks performed,
+/// see `TargetSpecExpression::lint` in target-spec.
+#[derive(Clone, Copy, Debug)]
+pub struct SuspiciousCfg;
+
+impl Lint for SuspiciousCfg {
+    fn id(&self) -> &'static str {
+        "suspicious-cfg"
+    }
+
+    fn description(&self) -> &'static str {
+        "platform-specific dependencies with likely misspelled cfg() expressions"
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let mut out = Vec::new();
+        for package in ctx.package_graph().workspace().iter() {
+            let mut seen = AHashSet::new();
+            for link in package.direct_links() {
+                for kind in DependencyKind::VALUES {
+                    let status = link.req_for_kind(*kind).status();
+                    for platform_status in [status.required_status(), status.optional_status()] {
+                        let PlatformStatus::PlatformDependent { eval } = platform_status else {
+                            continue;
+                        };
+                        for spec in eval.target_specs() {
+                            for lint in spec.lint() {
+                                let mut message = format!(
+                                    "{} depends on {} under `{}`: {}",
+                                    package.name(),
+                                    link.dep_name(),
+                                    lint.input,
+                                    lint
+                                );
+                                if let Some(suggestion) = lint.suggestion() {
+                                    message.push_str(&format!(" (did you mean `{suggestion}`?)"));
+                                }
+                                if seen.insert(message.clone()) {
+                                    out.push(LintOutput::new(package, message));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::tests::make_cargo_set;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::PackageGraph;
+
+    fn run_lint(package_graph: &PackageGraph, lint: &dyn Lint) -> Vec<(String, String)> {
+        let cargo_set = make_cargo_set(package_graph);
+        let ctx = LintContext::new(package_graph, &cargo_set);
+        let mut out: Vec<_> = lint
+            .check(&ctx)
+            .into_iter()
+            .map(|output| (output.package.name().to_owned(), output.message))
+            .collect();
+        out.sort();
+        out
+    }
+
+    fn owned(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(name, message)| ((*name).to_owned(), (*message).to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn banned_crates() {
+        let package_graph = JsonFixture::metadata_targets1().graph();
+        let config = BannedCratesConfig {
+            crates: vec![BannedCrate {
+                name: "lazy_static".to_owned(),
+                version: Some("< 1".to_owned()),
+                reason: Some("old".to_owned()),
+            }],
+        };
+        assert_eq!(
+            run_lint(package_graph, &BannedCrates::new(&config).unwrap()),
+            owned(&[(
+                "testcrate-targets",
+                "testcrate-targets depends on banned crate lazy_static 0.2.11 (old)"
+            )]),
+        );
+
+        let config = BannedCratesConfig {
+            crates: vec![BannedCrate {
+                name: "lazy_static".to_owned(),
+                version: Some(">= 2".to_owned()),
+                reason: None,
+            }],
+        };
+        assert!(run_lint(package_graph, &BannedCrates::new(&config).unwrap()).is_empty());
+
+        let config = BannedCratesConfig {
+            crates: vec![BannedCrate {
+                name: "lazy_static".to_owned(),
+                version: Some("not a req".to_owned()),
+                reason: None,
+            }],
+        };
+        assert!(BannedCrates::new(&config).is_err());
+    }
+
+    #[test]
+    fn duplicate_versions() {
+        let package_graph = JsonFixture::metadata_targets1().graph();
+        assert_eq!(
+            run_lint(
+                package_graph,
+                &DuplicateVersions::new(&DuplicateVersionsConfig::default())
+            ),
+            owned(&[(
+                "lazy_static",
+                "lazy_static has 2 versions in the build (max 1): 0.2.11, 1.4.0"
+            )]),
+        );
+
+        let config = DuplicateVersionsConfig {
+            max_versions: 2,
+            skip: vec![],
+        };
+        assert!(run_lint(package_graph, &DuplicateVersions::new(&config)).is_empty());
+
+        let config = DuplicateVersionsConfig {
+            max_versions: 1,
+            skip: vec!["lazy_static".to_owned()],
+        };
+        assert!(run_lint(package_graph, &DuplicateVersions::new(&config)).is_empty());
+    }
+
+    #[test]
+    fn missing_publish_metadata() {
+        let package_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let lint = MissingPublishMetadata::new(&MissingPublishMetadataConfig::default()).unwrap();
+        let out = run_lint(package_graph, &lint);
+        assert!(out.contains(&(
+            "cargo-guppy".to_owned(),
+            "cargo-guppy is publishable but missing: description, repository".to_owned()
+        )));
+        assert!(out.contains(&(
+            "guppy-cmdlib".to_owned(),
+            "guppy-cmdlib is publishable but missing: license, repository".to_owned()
+        )));
+        assert!(
+            !out.iter().any(|(name, _)| name == "fixtures"),
+            "unpublishable packages are skipped"
+        );
+
+        let config = MissingPublishMetadataConfig {
+            fields: vec!["license".to_owned()],
+        };
+        let out = run_lint(
+            package_graph,
+            &MissingPublishMetadata::new(&config).unwrap(),
+        );
+        assert_eq!(
+            out,
+            owned(&[
+                (
+                    "cargo-compare",
+                    "cargo-compare is publishable but missing: license"
+                ),
+                (
+                    "fixture-manager",
+                    "fixture-manager is publishable but missing: license"
+                ),
+                (
+                    "guppy-cmdlib",
+                    "guppy-cmdlib is publishable but missing: license"
+                ),
+            ]),
+        );
+
+        let config = MissingPublishMetadataConfig {
+            fields: vec!["authors".to_owned()],
+        };
+        assert!(MissingPublishMetadata::new(&config).is_err());
+    }
+
+    #[test]
+    fn wildcard_dependencies() {
+        let package_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let out = run_lint(package_graph, &WildcardDependencies);
+        assert!(out.contains(&(
+            "cargo-guppy".to_owned(),
+            "cargo-guppy depends on guppy-cmdlib with a wildcard version requirement".to_owned()
+        )));
+        assert!(
+            !out.iter().any(|(name, _)| name == "guppy-benchmarks"),
+            "path dependencies of unpublishable packages are skipped"
+        );
+
+        // Dev-only path dependencies are skipped.
+        let package_graph = JsonFixture::metadata_proc_macro1().graph();
+        assert_eq!(
+            run_lint(package_graph, &WildcardDependencies),
+            owned(&[
+                (
+                    "build-user",
+                    "build-user depends on macro with a wildcard version requirement"
+                ),
+                (
+                    "normal-user",
+                    "normal-user depends on macro with a wildcard version requirement"
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn unpublishable_dependency() {
+        let package_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        assert_eq!(
+            run_lint(package_graph, &UnpublishableDependency),
+            owned(&[
+                (
+                    "fixture-manager",
+                    "fixture-manager is publishable but depends on fixtures, which can't be \
+                     published to any registry"
+                ),
+                (
+                    "fixture-manager",
+                    "fixture-manager is publishable but depends on proptest-ext, which can't be \
+                     published to any registry"
+                ),
+            ]),
+        );
+    }
+
+    #[test]
+    fn suspicious_cfg() {
+        let package_graph = JsonFixture::metadata_targets1().graph();
+        assert!(run_lint(package_graph, &SuspiciousCfg).is_empty());
+
+        let json = JsonFixture::metadata_targets1()
+            .json()
+            .replace(r#""cfg(windows)""#, r#""cfg(widows)""#);
+        let package_graph = PackageGraph::from_json(json).expect("valid package graph");
+        let out = run_lint(&package_graph, &SuspiciousCfg);
+        assert!(!out.is_empty(), "misspelled cfg reported");
+        for (name, message) in &out {
+            assert_eq!(name, "testcrate-targets");
+            assert!(
+                message.contains("under `cfg(widows)`"),
+                "message mentions the expression: {message}"
+            );
+        }
+    }
+}
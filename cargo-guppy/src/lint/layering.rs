@@ -0,0 +1,447 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Architecture layering rules between packages.
+
+use super::{Lint, LintContext, LintOutput, Severity};
+use color_eyre::eyre::{Result, WrapErr, eyre};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use guppy::{
+    DependencyKind,
+    graph::{PackageLink, PackageMetadata},
+};
+use serde::Deserialize;
+use std::fmt::Write;
+
+/// Configuration for the [`Layering`] lint.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LayeringConfig {
+    /// Workspace-wide layering rules.
+    #[serde(default)]
+    pub rules: Vec<LayeringRule>,
+}
+
+/// A single workspace-wide layering rule.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LayeringRule {
+    /// Globs for the workspace packages this rule applies to.
+    pub from: Vec<String>,
+    /// Globs for the packages that matching packages must not depend on.
+    pub deny: Vec<String>,
+    /// The dependency kinds this rule applies to (default: all kinds).
+    #[serde(default = "LayeringKind::all")]
+    pub kinds: Vec<LayeringKind>,
+    /// The reason for this rule, printed along with violations.
+    pub reason: Option<String>,
+}
+
+/// Per-package allowlist, read from `[package.metadata.guppy.layering]`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct LayeringMetadata {
+    allow: Vec<String>,
+    #[serde(default = "LayeringKind::all")]
+    kinds: Vec<LayeringKind>,
+}
+
+/// A dependency kind that a layering rule applies to.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LayeringKind {
+    /// Dependencies in `[dependencies]`.
+    Normal,
+    /// Dependencies in `[build-dependencies]`.
+    Build,
+    /// Dependencies in `[dev-dependencies]`.
+    Dev,
+}
+
+impl LayeringKind {
+    fn all() -> Vec<Self> {
+        vec![LayeringKind::Normal, LayeringKind::Build, LayeringKind::Dev]
+    }
+
+    fn to_guppy(self) -> DependencyKind {
+        match self {
+            LayeringKind::Normal => DependencyKind::Normal,
+            LayeringKind::Build => DependencyKind::Build,
+            LayeringKind::Dev => DependencyKind::Development,
+        }
+    }
+}
+
+/// Reports dependency links that violate architecture layering rules.
+///
+/// Layering rules are specified in two ways:
+///
+/// * Workspace-wide rules in the `[layering]` section of the lint configuration:
+///
+///   ```toml
+///   [[layering.rules]]
+///   # Globs are matched against workspace paths and package names.
+///   from = ["core/**"]
+///   deny = ["services/**"]
+///   # Optional: the dependency kinds this rule applies to (default: all kinds).
+///   kinds = ["normal", "build"]
+///   reason = "core crates must not depend on services"
+///   ```
+///
+/// * Per-package allowlists in `[package.metadata.guppy.layering]`. If present, a package may only
+///   depend on workspace packages matching `allow`:
+///
+///   ```toml
+///   [package.metadata.guppy.layering]
+///   allow = ["core/**", "my-utils"]
+///   kinds = ["normal", "build"]
+///   ```
+///
+/// In globs, `*` doesn't match path separators: use `**` to match packages in nested directories.
+///
+/// Violations are reported against the package whose manifest declares the offending dependency.
+#[derive(Clone, Debug)]
+pub struct Layering {
+    rules: Vec<RuleImpl>,
+}
+
+#[derive(Clone, Debug)]
+struct RuleImpl {
+    from: GlobSet,
+    deny: GlobSet,
+    kinds: Vec<DependencyKind>,
+    reason: Option<String>,
+}
+
+impl Layering {
+    /// The key in `[package.metadata.guppy]` that per-package allowlists are read from.
+    pub const METADATA_KEY: &'static str = "layering";
+
+    /// Creates a new instance of this lint.
+    pub fn new(config: &LayeringConfig) -> Result<Self> {
+        let rules = config
+            .rules
+            .iter()
+            .enumerate()
+            .map(|(idx, rule)| {
+                let make_err = || eyre!("in [layering], invalid globs for rule {}", idx);
+                Ok(RuleImpl {
+                    from: build_glob_set(&rule.from).wrap_err_with(make_err)?,
+                    deny: build_glob_set(&rule.deny).wrap_err_with(make_err)?,
+                    kinds: rule.kinds.iter().map(|kind| kind.to_guppy()).collect(),
+                    reason: rule.reason.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    fn check_rules<'g>(&self, package: PackageMetadata<'g>, out: &mut Vec<LintOutput<'g>>) {
+        for (idx, rule) in self.rules.iter().enumerate() {
+            if !matches(&rule.from, &package) {
+                continue;
+            }
+            for link in package.direct_links() {
+                if !matches(&rule.deny, &link.to()) {
+                    continue;
+                }
+                if let Some(kinds) = violating_kinds(&link, &rule.kinds) {
+                    let mut message = format!(
+                        "{} violates layering rule {}{}",
+                        describe_link(&link),
+                        idx,
+                        kinds,
+                    );
+                    if let Some(reason) = &rule.reason {
+                        write!(message, ": {reason}").expect("writing to a String is infallible");
+                    }
+                    out.push(LintOutput::new(package, message));
+                }
+            }
+        }
+    }
+
+    fn check_allowlist<'g>(&self, package: PackageMetadata<'g>, out: &mut Vec<LintOutput<'g>>) {
+        let value = match package
+            .metadata_table()
+            .get("guppy")
+            .and_then(|guppy| guppy.get(Self::METADATA_KEY))
+        {
+            Some(value) => value,
+            None => return,
+        };
+
+        let allowlist = serde_json::from_value::<LayeringMetadata>(value.clone())
+            .map_err(|err| err.to_string())
+            .and_then(|metadata| {
+                let allow = build_glob_set(&metadata.allow).map_err(|err| format!("{err:#}"))?;
+                let kinds: Vec<_> = metadata.kinds.iter().map(|kind| kind.to_guppy()).collect();
+                Ok((allow, kinds))
+            });
+        let (allow, kinds) = match allowlist {
+            Ok(allowlist) => allowlist,
+            Err(err) => {
+                out.push(LintOutput::new(
+                    package,
+                    format!(
+                        "invalid [package.metadata.guppy.{}] in {}: {}",
+                        Self::METADATA_KEY,
+                        package.name(),
+                        err
+                    ),
+                ));
+                return;
+            }
+        };
+
+        for link in package.direct_links() {
+            let to = link.to();
+            if !to.in_workspace() || matches(&allow, &to) {
+                continue;
+            }
+            if let Some(kinds) = violating_kinds(&link, &kinds) {
+                out.push(LintOutput::new(
+                    package,
+                    format!(
+                        "{} is not in the layering allowlist for {}{}",
+                        describe_link(&link),
+                        package.name(),
+                        kinds,
+                    ),
+                ));
+            }
+        }
+    }
+}
+
+impl Lint for Layering {
+    fn id(&self) -> &'static str {
+        "layering"
+    }
+
+    fn description(&self) -> &'static str {
+        "dependencies violating [layering] rules or per-package allowlists"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Deny
+    }
+
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>> {
+        let mut out = Vec::new();
+        for package in ctx.package_graph().workspace().iter() {
+            self.check_rules(package, &mut out);
+            self.check_allowlist(package, &mut out);
+        }
+        out
+    }
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = GlobBuilder::new(glob)
+            .literal_separator(true)
+            .build()
+            .wrap_err_with(|| eyre!("invalid glob '{}'", glob))?;
+        builder.add(glob);
+    }
+    Ok(builder.build()?)
+}
+
+/// Matches a package by its workspace path if it's in the workspace, and by its name.
+fn matches(glob_set: &GlobSet, package: &PackageMetadata<'_>) -> bool {
+    glob_set.is_match(package.name())
+        || package
+            .source()
+            .workspace_path()
+            .is_some_and(|path| glob_set.is_match(path))
+}
+
+/// Returns a description of the dependency kinds on this link that are covered by `kinds`, or
+/// `None` if there are none.
+fn violating_kinds(link: &PackageLink<'_>, kinds: &[DependencyKind]) -> Option<String> {
+    let present: Vec<_> = kinds
+        .iter()
+        .filter(|kind| link.req_for_kind(**kind).is_present())
+        .map(|kind| kind.to_str())
+        .collect();
+    (!present.is_empty()).then(|| format!(" ({} dependency)", present.join(", ")))
+}
+
+fn describe_link(link: &PackageLink<'_>) -> String {
+    let (from, to) = link.endpoints();
+    let to_desc = match to.source().workspace_path() {
+        Some(path) => format!("{} ({})", to.name(), path),
+        None => format!("{} {}", to.name(), to.version()),
+    };
+    format!("{} -> {}", from.name(), to_desc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::tests::make_cargo_set;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::PackageGraph;
+
+    fn run_layering(package_graph: &PackageGraph, config: &str) -> Vec<(String, String)> {
+        let config: LayeringConfig = toml::from_str(config).expect("valid layering config");
+        let lint = Layering::new(&config).expect("valid layering rules");
+        let cargo_set = make_cargo_set(package_graph);
+        let ctx = LintContext::new(package_graph, &cargo_set);
+        let mut out: Vec<_> = lint
+            .check(&ctx)
+            .into_iter()
+            .map(|output| (output.package.name().to_owned(), output.message))
+            .collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn workspace_rules() {
+        let package_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let expected = vec![
+            (
+                "cargo-compare".to_owned(),
+                "cargo-compare -> guppy-cmdlib (guppy-cmdlib) violates layering rule 0 \
+                 (normal dependency)"
+                    .to_owned(),
+            ),
+            (
+                "fixture-manager".to_owned(),
+                "fixture-manager -> guppy-cmdlib (guppy-cmdlib) violates layering rule 0 \
+                 (normal dependency)"
+                    .to_owned(),
+            ),
+        ];
+        assert_eq!(
+            run_layering(
+                package_graph,
+                r#"
+                [[rules]]
+                from = ["internal-tools/**"]
+                deny = ["guppy-cmdlib"]
+                "#
+            ),
+            expected,
+        );
+        assert_eq!(
+            run_layering(
+                package_graph,
+                r#"
+                [[rules]]
+                from = ["internal-tools/*"]
+                deny = ["guppy-cmdlib"]
+                "#
+            ),
+            expected,
+        );
+
+        // `*` doesn't match across path separators.
+        assert!(
+            run_layering(
+                package_graph,
+                r#"
+                [[rules]]
+                from = ["internal*"]
+                deny = ["guppy-cmdlib"]
+                "#
+            )
+            .is_empty()
+        );
+    }
+
+    #[test]
+    fn rule_kinds_and_reason() {
+        let package_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        assert_eq!(
+            run_layering(
+                package_graph,
+                r#"
+                [[rules]]
+                from = ["guppy"]
+                deny = ["fixtures"]
+                reason = "guppy must not depend on test fixtures"
+                "#
+            ),
+            vec![(
+                "guppy".to_owned(),
+                "guppy -> fixtures (fixtures) violates layering rule 0 (dev dependency): \
+                 guppy must not depend on test fixtures"
+                    .to_owned()
+            )],
+        );
+        assert!(
+            run_layering(
+                package_graph,
+                r#"
+                [[rules]]
+                from = ["guppy"]
+                deny = ["fixtures"]
+                kinds = ["normal", "build"]
+                "#
+            )
+            .is_empty(),
+            "dev-only link not reported"
+        );
+    }
+
+    #[test]
+    fn allowlist() {
+        let make_graph = |layering: serde_json::Value| {
+            let mut json: serde_json::Value =
+                serde_json::from_str(JsonFixture::metadata_guppy_78cb7e8().json()).unwrap();
+            let package = json["packages"]
+                .as_array_mut()
+                .unwrap()
+                .iter_mut()
+                .find(|package| package["name"] == "fixture-manager")
+                .expect("fixture-manager is in the fixture");
+            package["metadata"] = serde_json::json!({ "guppy": { "layering": layering } });
+            PackageGraph::from_json(json.to_string()).expect("valid package graph")
+        };
+
+        let package_graph = make_graph(serde_json::json!({ "allow": ["guppy*", "fixtures"] }));
+        assert_eq!(
+            run_layering(&package_graph, ""),
+            vec![(
+                "fixture-manager".to_owned(),
+                "fixture-manager -> proptest-ext (internal-tools/proptest-ext) is not in the \
+                 layering allowlist for fixture-manager (normal dependency)"
+                    .to_owned()
+            )],
+        );
+
+        let package_graph = make_graph(serde_json::json!({
+            "allow": ["guppy*", "fixtures"],
+            "kinds": ["dev"],
+        }));
+        assert!(run_layering(&package_graph, "").is_empty());
+
+        let package_graph = make_graph(serde_json::json!({ "allow": ["a["] }));
+        let out = run_layering(&package_graph, "");
+        assert_eq!(out.len(), 1);
+        assert!(
+            out[0]
+                .1
+                .starts_with("invalid [package.metadata.guppy.layering] in fixture-manager: "),
+            "invalid allowlist reported: {}",
+            out[0].1
+        );
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let config: LayeringConfig = toml::from_str(
+            r#"
+            [[rules]]
+            from = ["a["]
+            deny = ["b"]
+            "#,
+        )
+        .unwrap();
+        assert!(Layering::new(&config).is_err(), "invalid glob");
+    }
+}
@@ -0,0 +1,563 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A lint framework for workspace dependency hygiene.
+//!
+//! Lints implement the [`Lint`] trait and are run against a [`LintContext`], which contains the
+//! package graph and a simulated Cargo build. Each lint has a stable ID, which is used to configure
+//! it and to identify its results.
+//!
+//! Lints are configured through a TOML file, by default `.config/guppy-lint.toml` in the workspace
+//! root. For example:
+//!
+//! ```toml
+//! # Override severities: "deny", "warn" or "allow".
+//! [lints]
+//! duplicate-versions = "deny"
+//! wildcard-dependencies = "allow"
+//!
+//! [banned-crates]
+//! crates = [{ name = "openssl", reason = "use rustls instead" }]
+//!
+//! [duplicate-versions]
+//! max-versions = 1
+//! skip = ["windows-sys"]
+//!
+//! [missing-publish-metadata]
+//! fields = ["license", "description", "repository"]
+//!
+//! [[layering.rules]]
+//! from = ["core/**"]
+//! deny = ["services/**"]
+//! ```
+//!
+//! For more about layering rules, see the documentation for [`Layering`].
+
+mod builtin;
+mod layering;
+
+pub use builtin::*;
+pub use layering::*;
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::Parser;
+use color_eyre::eyre::{Result, WrapErr, bail, eyre};
+use guppy::graph::{
+    PackageGraph, PackageMetadata,
+    cargo::{CargoOptions, CargoSet},
+};
+use guppy_cmdlib::{
+    CargoMetadataOptions, CargoResolverOpts, PackagesAndFeatures, string_to_platform_spec,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, fs};
+
+#[derive(Debug, Parser)]
+pub struct LintOptions {
+    /// Path to the lint configuration file (default: .config/guppy-lint.toml in the workspace root)
+    #[clap(long)]
+    config: Option<Utf8PathBuf>,
+
+    /// Output results as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// List available lints and exit
+    #[clap(long)]
+    list: bool,
+
+    #[clap(flatten)]
+    pf: PackagesAndFeatures,
+
+    #[clap(flatten)]
+    resolver_opts: CargoResolverOpts,
+
+    #[clap(long = "target-platform")]
+    /// Evaluate against target platform, "current" or "any" (default: any)
+    target_platform: Option<String>,
+
+    #[clap(long = "host-platform")]
+    /// Evaluate against host platform, "current" or "any" (default: any)
+    host_platform: Option<String>,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl LintOptions {
+    pub fn exec(&self) -> Result<()> {
+        let target_platform = string_to_platform_spec(self.target_platform.as_deref())?;
+        let host_platform = string_to_platform_spec(self.host_platform.as_deref())?;
+        let command = self.metadata_opts.make_command();
+        let pkg_graph = command.build_graph()?;
+
+        let config = match &self.config {
+            Some(path) => LintConfig::load(path)?,
+            None => {
+                let default_path = pkg_graph
+                    .workspace()
+                    .root()
+                    .join(LintConfig::DEFAULT_CONFIG_PATH);
+                if default_path.exists() {
+                    LintConfig::load(&default_path)?
+                } else {
+                    LintConfig::default()
+                }
+            }
+        };
+        let linter = Linter::new(&config)?;
+
+        if self.list {
+            for lint in linter.lints() {
+                println!(
+                    "{} ({}): {}",
+                    lint.id(),
+                    linter.severity(lint.as_ref()),
+                    lint.description()
+                );
+            }
+            return Ok(());
+        }
+
+        let mut cargo_opts = CargoOptions::new();
+        cargo_opts
+            .set_include_dev(self.resolver_opts.include_dev)
+            .set_resolver(self.resolver_opts.resolver_version.to_guppy())
+            .set_initials_platform(self.resolver_opts.initials_platform.to_guppy())
+            .set_target_platform(target_platform)
+            .set_host_platform(host_platform);
+
+        let (initials, features_only) = self.pf.make_feature_sets(&pkg_graph)?;
+        let cargo_set = CargoSet::new(initials, features_only, &cargo_opts)?;
+
+        let ctx = LintContext::new(&pkg_graph, &cargo_set);
+        let diagnostics = linter.run(&ctx);
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&diagnostics)?);
+        } else {
+            for diagnostic in &diagnostics {
+                print!("{diagnostic}");
+            }
+        }
+
+        let deny_count = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Deny)
+            .count();
+        if deny_count > 0 {
+            bail!("{} lint errors found", deny_count);
+        }
+        Ok(())
+    }
+}
+
+/// The severity of a lint.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// The lint is not run.
+    Allow,
+    /// Lint results are reported but do not cause failures.
+    Warn,
+    /// Lint results are reported and cause the command to fail.
+    Deny,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Allow => write!(f, "allow"),
+            Severity::Warn => write!(f, "warn"),
+            Severity::Deny => write!(f, "deny"),
+        }
+    }
+}
+
+/// A lint that can be run against a workspace.
+pub trait Lint {
+    /// Returns the stable ID for this lint, e.g. `"duplicate-versions"`.
+    fn id(&self) -> &'static str;
+
+    /// Returns a short, human-readable description of this lint.
+    fn description(&self) -> &'static str;
+
+    /// Returns the severity of this lint if it isn't overridden in the configuration.
+    fn default_severity(&self) -> Severity {
+        Severity::Warn
+    }
+
+    /// Runs this lint, returning any problems found.
+    fn check<'g>(&self, ctx: &LintContext<'g, '_>) -> Vec<LintOutput<'g>>;
+}
+
+/// The data lints are run against.
+#[derive(Clone, Debug)]
+pub struct LintContext<'g, 'a> {
+    package_graph: &'g PackageGraph,
+    cargo_set: &'a CargoSet<'g>,
+}
+
+impl<'g, 'a> LintContext<'g, 'a> {
+    /// Creates a new `LintContext`.
+    pub fn new(package_graph: &'g PackageGraph, cargo_set: &'a CargoSet<'g>) -> Self {
+        Self {
+            package_graph,
+            cargo_set,
+        }
+    }
+
+    /// Returns the package graph.
+    pub fn package_graph(&self) -> &'g PackageGraph {
+        self.package_graph
+    }
+
+    /// Returns the simulated Cargo build.
+    ///
+    /// Lints that care about what's actually built (as opposed to what's declared in manifests)
+    /// should use this.
+    pub fn cargo_set(&self) -> &'a CargoSet<'g> {
+        self.cargo_set
+    }
+}
+
+/// A single problem found by a lint.
+#[derive(Clone, Debug)]
+pub struct LintOutput<'g> {
+    /// The package the problem was found in.
+    pub package: PackageMetadata<'g>,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl<'g> LintOutput<'g> {
+    /// Creates a new `LintOutput`.
+    pub fn new(package: PackageMetadata<'g>, message: impl Into<String>) -> Self {
+        Self {
+            package,
+            message: message.into(),
+        }
+    }
+}
+
+/// A lint result, annotated with the lint that produced it.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Diagnostic {
+    /// The ID of the lint that produced this diagnostic.
+    pub lint: &'static str,
+    /// The severity of the lint.
+    pub severity: Severity,
+    /// The name of the package.
+    pub package: String,
+    /// The version of the package.
+    pub version: String,
+    /// The manifest path of the package.
+    pub manifest_path: Utf8PathBuf,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Deny => "error",
+            Severity::Warn | Severity::Allow => "warning",
+        };
+        writeln!(f, "{}[{}]: {}", level, self.lint, self.message)?;
+        writeln!(f, "  --> {}", self.manifest_path)?;
+        writeln!(f)
+    }
+}
+
+/// Lint configuration, typically read from a TOML file.
+///
+/// For an example, see the documentation for the `lint` module.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LintConfig {
+    /// Severity overrides, keyed by lint ID.
+    #[serde(default)]
+    pub lints: BTreeMap<String, Severity>,
+
+    /// Configuration for the `banned-crates` lint.
+    #[serde(default)]
+    pub banned_crates: BannedCratesConfig,
+
+    /// Configuration for the `duplicate-versions` lint.
+    #[serde(default)]
+    pub duplicate_versions: DuplicateVersionsConfig,
+
+    /// Configuration for the `missing-publish-metadata` lint.
+    #[serde(default)]
+    pub missing_publish_metadata: MissingPublishMetadataConfig,
+
+    /// Configuration for the `layering` lint.
+    #[serde(default)]
+    pub layering: LayeringConfig,
+}
+
+impl LintConfig {
+    /// The default location of the configuration file, relative to the workspace root.
+    pub const DEFAULT_CONFIG_PATH: &'static str = ".config/guppy-lint.toml";
+
+    /// Deserializes lint configuration from the given TOML string.
+    pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(s)
+    }
+
+    /// Reads and deserializes lint configuration from the given path.
+    pub fn load(path: &Utf8Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .wrap_err_with(|| eyre!("reading lint config {} failed", path))?;
+        Self::parse(&contents).wrap_err_with(|| eyre!("parsing lint config {} failed", path))
+    }
+}
+
+/// A set of lints along with their severities.
+pub struct Linter {
+    lints: Vec<Box<dyn Lint>>,
+    overrides: BTreeMap<String, Severity>,
+}
+
+impl Linter {
+    /// Creates a new `Linter` with all the built-in lints, configured as specified.
+    ///
+    /// Returns an error if the configuration refers to an unknown lint.
+    pub fn new(config: &LintConfig) -> Result<Self> {
+        let mut linter = Self {
+            lints: Vec::new(),
+            overrides: config.lints.clone(),
+        };
+        linter
+            .add_lint(BannedCrates::new(&config.banned_crates)?)?
+            .add_lint(DuplicateVersions::new(&config.duplicate_versions))?
+            .add_lint(MissingPublishMetadata::new(
+                &config.missing_publish_metadata,
+            )?)?
+            .add_lint(WildcardDependencies)?
+            .add_lint(UnpublishableDependency)?
+            .add_lint(SuspiciousCfg)?;
+        linter.add_lint(Layering::new(&config.layering)?)?;
+
+        linter.check_overrides()?;
+        Ok(linter)
+    }
+
+    /// Adds a lint to this set.
+    ///
+    /// Returns an error if a lint with the same ID was already added.
+    pub fn add_lint(&mut self, lint: impl Lint + 'static) -> Result<&mut Self> {
+        if self.lints.iter().any(|existing| existing.id() == lint.id()) {
+            bail!("lint ID '{}' registered multiple times", lint.id());
+        }
+        self.lints.push(Box::new(lint));
+        Ok(self)
+    }
+
+    /// Returns the lints in this set.
+    pub fn lints(&self) -> &[Box<dyn Lint>] {
+        &self.lints
+    }
+
+    /// Returns the effective severity of a lint, after applying overrides.
+    pub fn severity(&self, lint: &dyn Lint) -> Severity {
+        self.overrides
+            .get(lint.id())
+            .copied()
+            .unwrap_or_else(|| lint.default_severity())
+    }
+
+    /// Checks that every severity override refers to a known lint.
+    pub fn check_overrides(&self) -> Result<()> {
+        for id in self.overrides.keys() {
+            if !self.lints.iter().any(|lint| lint.id() == id) {
+                bail!("unknown lint '{}' in [lints]", id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs all lints that aren't allowed, and returns diagnostics sorted by lint ID and package.
+    pub fn run(&self, ctx: &LintContext<'_, '_>) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for lint in &self.lints {
+            let severity = self.severity(lint.as_ref());
+            if severity == Severity::Allow {
+                continue;
+            }
+            diagnostics.extend(lint.check(ctx).into_iter().map(|output| Diagnostic {
+                lint: lint.id(),
+                severity,
+                package: output.package.name().to_owned(),
+                version: output.package.version().to_string(),
+                manifest_path: output.package.manifest_path().to_path_buf(),
+                message: output.message,
+            }));
+        }
+
+        diagnostics.sort_by(|a, b| {
+            (a.lint, &a.package, &a.version, &a.message)
+                .cmp(&(b.lint, &b.package, &b.version, &b.message))
+        });
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+    use guppy::graph::feature::StandardFeatures;
+
+    pub(super) fn make_cargo_set(package_graph: &PackageGraph) -> CargoSet<'_> {
+        let initials = package_graph
+            .resolve_workspace()
+            .to_feature_set(StandardFeatures::Default);
+        let features_only = package_graph
+            .resolve_none()
+            .to_feature_set(StandardFeatures::Default);
+        CargoSet::new(initials, features_only, &CargoOptions::new()).expect("cargo set computed")
+    }
+
+    #[test]
+    fn config_parse() {
+        let config = LintConfig::parse(
+            r#"
+            [lints]
+            duplicate-versions = "deny"
+            wildcard-dependencies = "allow"
+
+            [banned-crates]
+            crates = [{ name = "openssl", reason = "use rustls instead" }]
+
+            [duplicate-versions]
+            max-versions = 2
+            skip = ["windows-sys"]
+
+            [missing-publish-metadata]
+            fields = ["license"]
+
+            [[layering.rules]]
+            from = ["core/**"]
+            deny = ["services/**"]
+            "#,
+        )
+        .expect("config parsed");
+
+        assert_eq!(
+            config.lints,
+            BTreeMap::from([
+                ("duplicate-versions".to_owned(), Severity::Deny),
+                ("wildcard-dependencies".to_owned(), Severity::Allow),
+            ])
+        );
+        assert_eq!(config.banned_crates.crates.len(), 1);
+        assert_eq!(config.banned_crates.crates[0].name, "openssl");
+        assert_eq!(config.banned_crates.crates[0].version, None);
+        assert_eq!(
+            config.banned_crates.crates[0].reason.as_deref(),
+            Some("use rustls instead")
+        );
+        assert_eq!(config.duplicate_versions.max_versions, 2);
+        assert_eq!(config.duplicate_versions.skip, vec!["windows-sys"]);
+        assert_eq!(config.missing_publish_metadata.fields, vec!["license"]);
+        assert_eq!(config.layering.rules.len(), 1);
+
+        let config = LintConfig::parse("").expect("empty config parsed");
+        assert!(config.lints.is_empty());
+        assert_eq!(config.duplicate_versions.max_versions, 1);
+        assert_eq!(
+            config.missing_publish_metadata.fields,
+            vec!["license", "description", "repository"]
+        );
+
+        for invalid in [
+            "[unknown-section]\nfoo = 1\n",
+            "[lints]\nduplicate-versions = \"forbid\"\n",
+            "[duplicate-versions]\nmax-version = 2\n",
+        ] {
+            assert!(
+                LintConfig::parse(invalid).is_err(),
+                "config should be rejected: {invalid}"
+            );
+        }
+    }
+
+    #[test]
+    fn linter_config_errors() {
+        let config = LintConfig::parse("[lints]\nno-such-lint = \"deny\"\n").unwrap();
+        assert!(Linter::new(&config).is_err(), "unknown lint in [lints]");
+
+        let config =
+            LintConfig::parse("[missing-publish-metadata]\nfields = [\"authors\"]\n").unwrap();
+        assert!(Linter::new(&config).is_err(), "unknown publish field");
+
+        let config = LintConfig::parse(
+            "[banned-crates]\ncrates = [{ name = \"openssl\", version = \"not a req\" }]\n",
+        )
+        .unwrap();
+        assert!(Linter::new(&config).is_err(), "invalid banned version");
+    }
+
+    #[test]
+    fn add_lint_duplicate() {
+        let mut linter = Linter::new(&LintConfig::default()).expect("default linter created");
+        let lint_count = linter.lints().len();
+        assert!(
+            linter.add_lint(WildcardDependencies).is_err(),
+            "wildcard-dependencies is already registered"
+        );
+        assert_eq!(linter.lints().len(), lint_count, "no lint added on error");
+    }
+
+    #[test]
+    fn severity_overrides() {
+        let package_graph = JsonFixture::metadata1().graph();
+        let cargo_set = make_cargo_set(package_graph);
+        let ctx = LintContext::new(package_graph, &cargo_set);
+
+        let linter = Linter::new(&LintConfig::default()).unwrap();
+        let diagnostics = linter.run(&ctx);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].lint, "missing-publish-metadata");
+        assert_eq!(diagnostics[0].severity, Severity::Warn);
+
+        let config = LintConfig::parse("[lints]\nmissing-publish-metadata = \"deny\"\n").unwrap();
+        let linter = Linter::new(&config).unwrap();
+        let diagnostics = linter.run(&ctx);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Deny);
+
+        let config = LintConfig::parse("[lints]\nmissing-publish-metadata = \"allow\"\n").unwrap();
+        let linter = Linter::new(&config).unwrap();
+        assert!(linter.run(&ctx).is_empty(), "allowed lints are not run");
+    }
+
+    #[test]
+    fn diagnostic_output() {
+        let package_graph = JsonFixture::metadata1().graph();
+        let cargo_set = make_cargo_set(package_graph);
+        let ctx = LintContext::new(package_graph, &cargo_set);
+        let diagnostics = Linter::new(&LintConfig::default()).unwrap().run(&ctx);
+
+        let json = serde_json::to_value(&diagnostics).expect("diagnostics serialized");
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "lint": "missing-publish-metadata",
+                "severity": "warn",
+                "package": "testcrate",
+                "version": "0.1.0",
+                "manifest-path": "/fakepath/testcrate/Cargo.toml",
+                "message": "testcrate is publishable but missing: license, description, repository",
+            }])
+        );
+
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "warning[missing-publish-metadata]: testcrate is publishable but missing: \
+             license, description, repository\n  --> /fakepath/testcrate/Cargo.toml\n\n"
+        );
+    }
+}
@@ -2,8 +2,8 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use cargo_guppy::{
-    CmdSelectOptions, DiffSummariesOptions, DupsOptions, MvOptions, ResolveCargoOptions,
-    SubtreeSizeOptions,
+    AuditOptions, CmdSelectOptions, DiffSummariesOptions, DupsOptions, LintOptions, MvOptions,
+    ReleasePlanOptions, ResolveCargoOptions, SubtreeSizeOptions,
 };
 use clap::Parser;
 use color_eyre::Result;
@@ -19,6 +19,9 @@ struct Args {
 // Ensure this list is kept up to date with the doc comment in lib.rs.
 #[derive(Debug, Parser)]
 enum Command {
+    #[clap(name = "audit")]
+    /// Match packages against a local RustSec advisory database
+    Audit(AuditOptions),
     #[clap(name = "cycles")]
     /// Print cycles in the dependency graph
     Cycles {
@@ -43,6 +46,9 @@ enum Command {
     #[clap(name = "dups")]
     /// Print the number of duplicate packages
     Duplicates(DupsOptions),
+    #[clap(name = "lint")]
+    /// Check workspace dependency hygiene
+    Lint(LintOptions),
     #[clap(name = "resolve-cargo")]
     /// Return packages and features that would be built by Cargo
     ResolveCargo(ResolveCargoOptions),
@@ -58,6 +64,9 @@ enum Command {
     /// The source directories must be crates, and the destination must be within the same
     /// workspace.
     Mv(MvOptions),
+    #[clap(name = "release-plan")]
+    /// Plan a release: publish order, publishability checks and version bumps
+    ReleasePlan(ReleasePlanOptions),
 }
 
 // On Unix-like operating systems, the executable name of the Cargo subcommand usually doesn't have
@@ -92,6 +101,7 @@ fn main() -> Result<()> {
     let args = Args::parse_from(args());
 
     match args.cmd {
+        Command::Audit(ref options) => options.exec(),
         Command::Cycles {
             metadata_opts,
             features,
@@ -99,9 +109,11 @@ fn main() -> Result<()> {
         Command::Diff { json, old, new } => cargo_guppy::cmd_diff(json, &old, &new),
         Command::DiffSummaries(options) => options.exec(),
         Command::Duplicates(ref options) => cargo_guppy::cmd_dups(options),
+        Command::Lint(ref options) => options.exec(),
         Command::ResolveCargo(ref options) => cargo_guppy::cmd_resolve_cargo(options),
         Command::Select(ref options) => cargo_guppy::cmd_select(options),
         Command::SubtreeSize(ref options) => cargo_guppy::cmd_subtree_size(options),
         Command::Mv(ref options) => options.exec(),
+        Command::ReleasePlan(ref options) => options.exec(),
     }
 }
@@ -511,7 +511,7 @@ fn update_root_toml(
     Ok(())
 }
 
-fn read_toml(manifest_path: &Utf8Path) -> Result<DocumentMut> {
+pub(crate) fn read_toml(manifest_path: &Utf8Path) -> Result<DocumentMut> {
     let toml = fs::read_to_string(manifest_path)
         .wrap_err_with(|| eyre!("error while reading manifest {}", manifest_path))?;
     toml.parse::<DocumentMut>()
@@ -519,7 +519,7 @@ fn read_toml(manifest_path: &Utf8Path) -> Result<DocumentMut> {
 }
 
 /// Replace the value while retaining the decor.
-fn replace_decorated(dest: &mut Value, new_value: impl Into<Value>) -> Value {
+pub(crate) fn replace_decorated(dest: &mut Value, new_value: impl Into<Value>) -> Value {
     let decor = dest.decor();
     let mut new_value = new_value.into();
     // Copy over the decor from dest into new_value.
@@ -0,0 +1,975 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Plan releases of workspace crates: publish order, publishability checks and version bumps.
+
+use crate::mv::{read_toml, replace_decorated};
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{Result, WrapErr, bail, eyre};
+use guppy::{
+    PackageId,
+    graph::{PackageGraph, PackageLink, PackageMetadata, PackagePublish},
+};
+use guppy_cmdlib::CargoMetadataOptions;
+use semver::{Comparator, Op, Version, VersionReq};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque, btree_map::Entry},
+    fmt, fs,
+};
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+
+#[derive(Debug, Parser)]
+pub struct ReleasePlanOptions {
+    /// The registry to plan the release for
+    #[clap(long, default_value = PackagePublish::CRATES_IO)]
+    registry: String,
+
+    /// Packages to release (default: all packages publishable to the registry, or the packages
+    /// affected by --bump if specified)
+    #[clap(long = "package", short = 'p')]
+    packages: Vec<String>,
+
+    /// Version bumps, as NAME=LEVEL where LEVEL is major, minor, patch, breaking or an exact
+    /// version (can be specified multiple times)
+    #[clap(long = "bump", value_name = "NAME=LEVEL")]
+    bumps: Vec<String>,
+
+    /// The bump level for dependents whose version requirements no longer match
+    #[clap(long, value_enum, default_value = "breaking")]
+    dependent_bump: BumpLevel,
+
+    /// Output the plan as JSON
+    #[clap(long)]
+    json: bool,
+
+    /// Write version bumps and requirement updates to manifests
+    #[clap(long)]
+    apply: bool,
+
+    #[clap(flatten)]
+    metadata_opts: CargoMetadataOptions,
+}
+
+impl ReleasePlanOptions {
+    pub fn exec(&self) -> Result<()> {
+        let command = self.metadata_opts.make_command();
+        let pkg_graph = command.build_graph()?;
+
+        let bumps = self
+            .bumps
+            .iter()
+            .map(|bump| parse_bump(&pkg_graph, bump))
+            .collect::<Result<Vec<_>>>()?;
+        let packages = self
+            .packages
+            .iter()
+            .map(|name| {
+                pkg_graph
+                    .workspace()
+                    .member_by_name(name)
+                    .wrap_err_with(|| eyre!("unknown workspace package '{}'", name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let plan = ReleasePlan::new(
+            &pkg_graph,
+            &self.registry,
+            packages,
+            bumps,
+            self.dependent_bump,
+        );
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&plan.to_serializable())?);
+        } else {
+            print!("{plan}");
+        }
+
+        if !plan.errors.is_empty() {
+            bail!(
+                "release plan has {} error(s) for registry '{}'",
+                plan.errors.len(),
+                self.registry
+            );
+        }
+
+        if self.apply {
+            plan.apply(pkg_graph.workspace().root())?;
+        }
+
+        Ok(())
+    }
+}
+
+/// How to bump a version.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, ValueEnum)]
+pub enum BumpLevel {
+    /// Increment the major version.
+    Major,
+    /// Increment the minor version.
+    Minor,
+    /// Increment the patch version.
+    Patch,
+    /// Increment the leftmost nonzero component, following Cargo's semver compatibility rules.
+    Breaking,
+}
+
+impl BumpLevel {
+    /// Returns the version that results from applying this bump to `version`.
+    pub fn apply(self, version: &Version) -> Version {
+        let level = match self {
+            BumpLevel::Breaking if version.major > 0 => BumpLevel::Major,
+            BumpLevel::Breaking if version.minor > 0 => BumpLevel::Minor,
+            BumpLevel::Breaking => BumpLevel::Patch,
+            other => other,
+        };
+        match level {
+            BumpLevel::Major => Version::new(version.major + 1, 0, 0),
+            BumpLevel::Minor => Version::new(version.major, version.minor + 1, 0),
+            BumpLevel::Patch => Version::new(version.major, version.minor, version.patch + 1),
+            BumpLevel::Breaking => unreachable!("breaking bumps were resolved above"),
+        }
+    }
+}
+
+fn parse_bump<'g>(
+    pkg_graph: &'g PackageGraph,
+    bump: &str,
+) -> Result<(PackageMetadata<'g>, Version)> {
+    let (name, level) = bump
+        .split_once('=')
+        .ok_or_else(|| eyre!("invalid bump '{}': expected NAME=LEVEL", bump))?;
+    let package = pkg_graph
+        .workspace()
+        .member_by_name(name)
+        .wrap_err_with(|| eyre!("in bump '{}', unknown workspace package '{}'", bump, name))?;
+    let new_version = match BumpLevel::from_str(level, true) {
+        Ok(level) => level.apply(package.version()),
+        Err(_) => Version::parse(level).wrap_err_with(|| {
+            eyre!(
+                "in bump '{}', '{}' is neither a bump level nor a version",
+                bump,
+                level
+            )
+        })?,
+    };
+    if &new_version <= package.version() {
+        bail!(
+            "in bump '{}', new version {} is not greater than current version {}",
+            bump,
+            new_version,
+            package.version()
+        );
+    }
+    Ok((package, new_version))
+}
+
+/// A release plan for a set of workspace packages.
+#[derive(Clone, Debug)]
+pub struct ReleasePlan<'g> {
+    registry: String,
+    releases: Vec<PlannedRelease<'g>>,
+    requirement_updates: Vec<RequirementUpdate<'g>>,
+    ignored_cycles: Vec<Vec<PackageMetadata<'g>>>,
+    errors: Vec<String>,
+}
+
+/// A package to publish, in publish order.
+#[derive(Clone, Debug)]
+pub struct PlannedRelease<'g> {
+    package: PackageMetadata<'g>,
+    new_version: Option<Version>,
+    reason: BumpReason<'g>,
+}
+
+#[derive(Clone, Debug)]
+enum BumpReason<'g> {
+    None,
+    Requested,
+    Dependency(PackageMetadata<'g>),
+}
+
+/// A version requirement that must be updated because the dependency is being bumped.
+#[derive(Clone, Debug)]
+pub struct RequirementUpdate<'g> {
+    link: PackageLink<'g>,
+    new_req: VersionReq,
+}
+
+impl<'g> ReleasePlan<'g> {
+    /// Computes a release plan.
+    ///
+    /// The packages released are `packages`, bumped packages and dependents that must be bumped
+    /// as a result. If all of those are empty, every package publishable to `registry` is
+    /// released.
+    pub fn new(
+        pkg_graph: &'g PackageGraph,
+        registry: &str,
+        packages: impl IntoIterator<Item = PackageMetadata<'g>>,
+        bumps: impl IntoIterator<Item = (PackageMetadata<'g>, Version)>,
+        dependent_bump: BumpLevel,
+    ) -> Self {
+        let mut new_versions: BTreeMap<&'g PackageId, (Version, BumpReason<'g>)> = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        for (package, new_version) in bumps {
+            new_versions.insert(package.id(), (new_version, BumpReason::Requested));
+            queue.push_back(package);
+        }
+
+        // Propagate bumps to dependents whose version requirements no longer match.
+        let mut requirement_updates = Vec::new();
+        while let Some(package) = queue.pop_front() {
+            let new_version = new_versions[package.id()].0.clone();
+            for link in package.reverse_direct_links() {
+                let from = link.from();
+                // Path dependencies without a version (e.g. dev-only ones) don't need updating.
+                if !from.in_workspace()
+                    || *link.version_req() == VersionReq::STAR
+                    || link.version_req().matches(&new_version)
+                {
+                    continue;
+                }
+                requirement_updates.push(RequirementUpdate {
+                    link,
+                    new_req: updated_req(link.version_req(), &new_version),
+                });
+
+                // Dev-dependencies are stripped at publish time, and unpublished packages don't
+                // need new versions.
+                if link.dev_only()
+                    || !from.publish().can_publish_to(registry)
+                    || new_versions.contains_key(from.id())
+                {
+                    continue;
+                }
+                new_versions.insert(
+                    from.id(),
+                    (
+                        dependent_bump.apply(from.version()),
+                        BumpReason::Dependency(package),
+                    ),
+                );
+                queue.push_back(from);
+            }
+        }
+        requirement_updates.sort_by(|a, b| {
+            (a.link.from().name(), a.link.dep_name())
+                .cmp(&(b.link.from().name(), b.link.dep_name()))
+        });
+
+        let mut release_set: BTreeSet<&'g PackageId> = packages
+            .into_iter()
+            .map(|package| package.id())
+            .chain(new_versions.keys().copied())
+            .collect();
+        if release_set.is_empty() {
+            release_set = pkg_graph
+                .workspace()
+                .iter()
+                .filter(|package| package.publish().can_publish_to(registry))
+                .map(|package| package.id())
+                .collect();
+        }
+
+        let mut errors = Vec::new();
+        for package_id in &release_set {
+            let package = pkg_graph.metadata(package_id).expect("valid package ID");
+            if !package.publish().can_publish_to(registry) {
+                errors.push(format!(
+                    "{} can't be published to {}",
+                    package.name(),
+                    registry
+                ));
+            }
+            for link in package.direct_links() {
+                let to = link.to();
+                if link.dev_only() || !to.source().is_local() {
+                    continue;
+                }
+                if !to.publish().can_publish_to(registry) {
+                    errors.push(format!(
+                        "{} depends on {}, which can't be published to {}",
+                        package.name(),
+                        to.name(),
+                        registry
+                    ));
+                } else if *link.version_req() == VersionReq::STAR {
+                    errors.push(format!(
+                        "{} depends on {} without a version requirement",
+                        package.name(),
+                        link.dep_name()
+                    ));
+                }
+            }
+        }
+
+        let releases = publish_order(pkg_graph, &release_set)
+            .into_iter()
+            .map(|package| {
+                let (new_version, reason) = match new_versions.remove(package.id()) {
+                    Some((new_version, reason)) => (Some(new_version), reason),
+                    None => (None, BumpReason::None),
+                };
+                PlannedRelease {
+                    package,
+                    new_version,
+                    reason,
+                }
+            })
+            .collect();
+
+        // Cycles can only be formed through dev-dependencies, which are ignored for publish
+        // order.
+        let ignored_cycles = pkg_graph
+            .cycles()
+            .all_cycles()
+            .filter(|cycle| cycle.iter().any(|id| release_set.contains(id)))
+            .map(|cycle| {
+                cycle
+                    .into_iter()
+                    .map(|id| pkg_graph.metadata(id).expect("valid package ID"))
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            registry: registry.to_owned(),
+            releases,
+            requirement_updates,
+            ignored_cycles,
+            errors,
+        }
+    }
+
+    /// Returns the packages to publish, in the order they should be published in.
+    pub fn releases(&self) -> &[PlannedRelease<'g>] {
+        &self.releases
+    }
+
+    /// Returns the version requirements that must be updated.
+    pub fn requirement_updates(&self) -> &[RequirementUpdate<'g>] {
+        &self.requirement_updates
+    }
+
+    /// Returns errors that would prevent this plan from being published.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// Writes version bumps and requirement updates to manifests.
+    pub fn apply(&self, workspace_root: &Utf8Path) -> Result<()> {
+        let mut edits: BTreeMap<&Utf8Path, Vec<ManifestEdit<'_>>> = BTreeMap::new();
+        for release in &self.releases {
+            if let Some(new_version) = &release.new_version {
+                edits
+                    .entry(release.package.manifest_path())
+                    .or_default()
+                    .push(ManifestEdit::Version(new_version));
+            }
+        }
+        for update in &self.requirement_updates {
+            edits
+                .entry(update.link.from().manifest_path())
+                .or_default()
+                .push(ManifestEdit::Requirement(update));
+        }
+
+        let mut documents: BTreeMap<Utf8PathBuf, DocumentMut> = BTreeMap::new();
+        let mut workspace_edits = Vec::new();
+        for (manifest_path, edits) in &edits {
+            let mut document = read_toml(manifest_path)?;
+            for edit in edits {
+                let workspace_edit = edit
+                    .apply(document.as_table_mut())
+                    .wrap_err_with(|| eyre!("error while applying edits to {}", manifest_path))?;
+                workspace_edits.extend(workspace_edit);
+            }
+            documents.insert(manifest_path.to_path_buf(), document);
+        }
+
+        // Dependencies inherited from the workspace are updated in the root manifest.
+        if !workspace_edits.is_empty() {
+            let root_manifest_path = workspace_root.join("Cargo.toml");
+            let document = match documents.entry(root_manifest_path.clone()) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(read_toml(&root_manifest_path)?),
+            };
+            apply_workspace_edits(document.as_table_mut(), &mut workspace_edits)
+                .wrap_err_with(|| eyre!("error while applying edits to {}", root_manifest_path))?;
+        }
+
+        // Only write manifests out once every edit has succeeded, so that errors don't leave the
+        // workspace partially edited.
+        for (manifest_path, document) in &documents {
+            fs::write(manifest_path, document.to_string())
+                .wrap_err_with(|| eyre!("error while writing manifest {}", manifest_path))?;
+        }
+
+        Ok(())
+    }
+
+    fn to_serializable(&self) -> SerializableReleasePlan<'_> {
+        SerializableReleasePlan {
+            registry: &self.registry,
+            releases: self
+                .releases
+                .iter()
+                .map(|release| SerializableRelease {
+                    name: release.package.name(),
+                    version: release.package.version(),
+                    new_version: release.new_version.as_ref(),
+                    bumped_because_of: match &release.reason {
+                        BumpReason::Dependency(dep) => Some(dep.name()),
+                        BumpReason::None | BumpReason::Requested => None,
+                    },
+                    manifest_path: release.package.manifest_path(),
+                })
+                .collect(),
+            requirement_updates: self
+                .requirement_updates
+                .iter()
+                .map(|update| SerializableRequirementUpdate {
+                    package: update.link.from().name(),
+                    dependency: update.link.dep_name(),
+                    old_req: update.link.version_req().to_string(),
+                    new_req: update.new_req.to_string(),
+                    manifest_path: update.link.from().manifest_path(),
+                })
+                .collect(),
+            ignored_cycles: self
+                .ignored_cycles
+                .iter()
+                .map(|cycle| cycle.iter().map(|package| package.name()).collect())
+                .collect(),
+            errors: &self.errors,
+        }
+    }
+}
+
+impl fmt::Display for ReleasePlan<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "publish order for registry '{}':", self.registry)?;
+        for (idx, release) in self.releases.iter().enumerate() {
+            write!(
+                f,
+                "  {}. {} {}",
+                idx + 1,
+                release.package.name(),
+                release.package.version()
+            )?;
+            if let Some(new_version) = &release.new_version {
+                write!(f, " -> {new_version}")?;
+            }
+            match &release.reason {
+                BumpReason::None => {}
+                BumpReason::Requested => write!(f, " (requested)")?,
+                BumpReason::Dependency(dep) => write!(f, " (dependency {} bumped)", dep.name())?,
+            }
+            writeln!(f)?;
+        }
+
+        if !self.requirement_updates.is_empty() {
+            writeln!(f, "version requirement updates:")?;
+            for update in &self.requirement_updates {
+                writeln!(
+                    f,
+                    "  {}: {} {} -> {}",
+                    update.link.from().name(),
+                    update.link.dep_name(),
+                    update.link.version_req(),
+                    update.new_req
+                )?;
+            }
+        }
+
+        if !self.ignored_cycles.is_empty() {
+            writeln!(f, "dev-dependency cycles (ignored for publish order):")?;
+            for cycle in &self.ignored_cycles {
+                let names = itertools::join(cycle.iter().map(|package| package.name()), " -> ");
+                writeln!(f, "  {} -> {}", names, cycle[0].name())?;
+            }
+        }
+
+        if !self.errors.is_empty() {
+            writeln!(f, "errors:")?;
+            for error in &self.errors {
+                writeln!(f, "  {error}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SerializableReleasePlan<'a> {
+    registry: &'a str,
+    releases: Vec<SerializableRelease<'a>>,
+    requirement_updates: Vec<SerializableRequirementUpdate<'a>>,
+    ignored_cycles: Vec<Vec<&'a str>>,
+    errors: &'a [String],
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SerializableRelease<'a> {
+    name: &'a str,
+    version: &'a Version,
+    new_version: Option<&'a Version>,
+    bumped_because_of: Option<&'a str>,
+    manifest_path: &'a Utf8Path,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct SerializableRequirementUpdate<'a> {
+    package: &'a str,
+    dependency: &'a str,
+    old_req: String,
+    new_req: String,
+    manifest_path: &'a Utf8Path,
+}
+
+/// Returns the packages in `release_set` in publish order: dependencies before dependents.
+///
+/// Dev-dependency edges are ignored, since Cargo strips them at publish time. Ties are broken by
+/// name so that the order is stable.
+fn publish_order<'g>(
+    pkg_graph: &'g PackageGraph,
+    release_set: &BTreeSet<&'g PackageId>,
+) -> Vec<PackageMetadata<'g>> {
+    let packages: BTreeMap<&'g PackageId, PackageMetadata<'g>> = release_set
+        .iter()
+        .map(|id| (*id, pkg_graph.metadata(id).expect("valid package ID")))
+        .collect();
+    let in_set_deps = |package: &PackageMetadata<'g>| -> BTreeSet<&'g PackageId> {
+        package
+            .direct_links()
+            .filter(|link| !link.dev_only() && release_set.contains(link.to().id()))
+            .map(|link| link.to().id())
+            .collect()
+    };
+
+    let mut remaining: BTreeMap<&'g PackageId, BTreeSet<&'g PackageId>> = packages
+        .iter()
+        .map(|(id, package)| (*id, in_set_deps(package)))
+        .collect();
+    let mut order = Vec::with_capacity(packages.len());
+    while !remaining.is_empty() {
+        let next = remaining
+            .iter()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(id, _)| packages[id])
+            .min_by(|a, b| (a.name(), a.version()).cmp(&(b.name(), b.version())))
+            .expect("with dev-dependencies ignored, the package graph is acyclic");
+        remaining.remove(next.id());
+        for deps in remaining.values_mut() {
+            deps.remove(next.id());
+        }
+        order.push(next);
+    }
+    order
+}
+
+/// Returns a version requirement that matches `new_version`, in the style of `old_req`.
+///
+/// Single-comparator requirements keep their operator and precision, so `0.3` becomes `0.4` and
+/// `~1.2.3` becomes `~2.0.0`. Anything else is replaced with a caret requirement. Pre-release
+/// versions always get a full version, since only those can match pre-releases.
+fn updated_req(old_req: &VersionReq, new_version: &Version) -> VersionReq {
+    let full = !new_version.pre.is_empty();
+    let comparator = match old_req.comparators.as_slice() {
+        [comparator] if matches!(comparator.op, Op::Caret | Op::Tilde | Op::Exact) => Comparator {
+            op: comparator.op,
+            major: new_version.major,
+            minor: (full || comparator.minor.is_some()).then_some(new_version.minor),
+            patch: (full || comparator.patch.is_some()).then_some(new_version.patch),
+            pre: new_version.pre.clone(),
+        },
+        _ => Comparator {
+            op: Op::Caret,
+            major: new_version.major,
+            minor: Some(new_version.minor),
+            patch: Some(new_version.patch),
+            pre: new_version.pre.clone(),
+        },
+    };
+    VersionReq {
+        comparators: vec![comparator],
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ManifestEdit<'a> {
+    Version(&'a Version),
+    Requirement(&'a RequirementUpdate<'a>),
+}
+
+/// A requirement update to make in `[workspace.dependencies]`, for dependencies inherited from
+/// the workspace.
+#[derive(Clone, Debug)]
+struct WorkspaceEdit {
+    dep_name: String,
+    new_req: VersionReq,
+}
+
+impl ManifestEdit<'_> {
+    fn apply(&self, table: &mut Table) -> Result<Vec<WorkspaceEdit>> {
+        match self {
+            ManifestEdit::Version(new_version) => {
+                let package = table
+                    .get_mut("package")
+                    .and_then(|item| item.as_table_like_mut())
+                    .ok_or_else(|| eyre!("[package] is not a table"))?;
+                match package.get_mut("version") {
+                    Some(Item::Value(value)) if value.is_str() => {
+                        replace_decorated(value, new_version.to_string());
+                    }
+                    Some(_) => bail!(
+                        "package.version is not a string \
+                         (versions inherited from the workspace are not supported)"
+                    ),
+                    None => bail!("package.version not found"),
+                }
+                Ok(Vec::new())
+            }
+            ManifestEdit::Requirement(update) => {
+                let mut workspace_edits = Vec::new();
+                let dep_name = update.link.dep_name();
+
+                if let Some(Item::Table(targets)) = table.get_mut("target") {
+                    // Also update [target.'foo'.dependencies] and friends.
+                    for (target, item) in targets.iter_mut() {
+                        if let Some(target_table) = item.as_table_mut() {
+                            update_dep_sections(
+                                target_table,
+                                &format!("target.'{target}'."),
+                                dep_name,
+                                &update.new_req,
+                                &mut workspace_edits,
+                            )?;
+                        }
+                    }
+                }
+                update_dep_sections(table, "", dep_name, &update.new_req, &mut workspace_edits)?;
+                Ok(workspace_edits)
+            }
+        }
+    }
+}
+
+fn update_dep_sections(
+    table: &mut Table,
+    prefix: &str,
+    dep_name: &str,
+    new_req: &VersionReq,
+    workspace_edits: &mut Vec<WorkspaceEdit>,
+) -> Result<()> {
+    static SECTION_NAMES: &[&str] = &["dependencies", "build-dependencies", "dev-dependencies"];
+
+    for &section_name in SECTION_NAMES {
+        let section_table = match table.get_mut(section_name) {
+            None => continue,
+            Some(Item::Table(table)) => table,
+            Some(_) => bail!("section [{}{}] is not a table", prefix, section_name),
+        };
+        let item = match section_table.get_mut(dep_name) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        if let Item::Value(value) = item {
+            if let Some(old_req) = value.as_str() {
+                let new_req = format_req(old_req, new_req);
+                replace_decorated(value, new_req);
+                continue;
+            }
+        }
+
+        let dep_table = item.as_table_like_mut().ok_or_else(|| {
+            eyre!(
+                "in section [{}{}], {} is not a string or table",
+                prefix,
+                section_name,
+                dep_name
+            )
+        })?;
+        if update_version_key(dep_table, new_req) {
+            continue;
+        }
+        if dep_table
+            .get("workspace")
+            .and_then(|item| item.as_bool())
+            .unwrap_or(false)
+        {
+            workspace_edits.push(WorkspaceEdit {
+                dep_name: dep_name.to_owned(),
+                new_req: new_req.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_workspace_edits(
+    table: &mut Table,
+    workspace_edits: &mut Vec<WorkspaceEdit>,
+) -> Result<()> {
+    if workspace_edits.is_empty() {
+        return Ok(());
+    }
+    let deps = table
+        .get_mut("workspace")
+        .and_then(|item| item.as_table_like_mut())
+        .and_then(|workspace| workspace.get_mut("dependencies"))
+        .and_then(|item| item.as_table_like_mut())
+        .ok_or_else(|| eyre!("[workspace.dependencies] not found"))?;
+    for edit in workspace_edits.drain(..) {
+        let item = deps
+            .get_mut(&edit.dep_name)
+            .ok_or_else(|| eyre!("in [workspace.dependencies], {} not found", edit.dep_name))?;
+        if let Item::Value(value) = item {
+            if let Some(old_req) = value.as_str() {
+                let new_req = format_req(old_req, &edit.new_req);
+                replace_decorated(value, new_req);
+                continue;
+            }
+        }
+        let dep_table = item.as_table_like_mut().ok_or_else(|| {
+            eyre!(
+                "in [workspace.dependencies], {} is not a string or table",
+                edit.dep_name
+            )
+        })?;
+        if !update_version_key(dep_table, &edit.new_req) {
+            bail!(
+                "in [workspace.dependencies], {}.version not found",
+                edit.dep_name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Updates the `version` key in a dependency table, returning false if it isn't present.
+fn update_version_key(dep_table: &mut dyn TableLike, new_req: &VersionReq) -> bool {
+    match dep_table.get_mut("version") {
+        Some(Item::Value(value)) => match value.as_str() {
+            Some(old_req) => {
+                let new_req = format_req(old_req, new_req);
+                replace_decorated(value, new_req);
+                true
+            }
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+/// Formats `new_req`, omitting the `^` operator if `old_req` omitted it.
+fn format_req(old_req: &str, new_req: &VersionReq) -> String {
+    let new_req = new_req.to_string();
+    if old_req.trim_start().starts_with('^') {
+        new_req
+    } else {
+        new_req.strip_prefix('^').unwrap_or(&new_req).to_owned()
+    }
+}
+
+impl<'g> PlannedRelease<'g> {
+    /// Returns the package being released.
+    pub fn package(&self) -> PackageMetadata<'g> {
+        self.package
+    }
+
+    /// Returns the new version of the package, if it's being bumped.
+    pub fn new_version(&self) -> Option<&Version> {
+        self.new_version.as_ref()
+    }
+}
+
+impl<'g> RequirementUpdate<'g> {
+    /// Returns the dependency link whose version requirement is being updated.
+    pub fn link(&self) -> PackageLink<'g> {
+        self.link
+    }
+
+    /// Returns the new version requirement.
+    pub fn new_req(&self) -> &VersionReq {
+        &self.new_req
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::json::JsonFixture;
+    use guppy::MetadataCommand;
+
+    #[test]
+    fn bump_level_apply() {
+        let cases = [
+            ("1.2.3", BumpLevel::Major, "2.0.0"),
+            ("1.2.3", BumpLevel::Minor, "1.3.0"),
+            ("1.2.3", BumpLevel::Patch, "1.2.4"),
+            ("1.2.3", BumpLevel::Breaking, "2.0.0"),
+            ("0.2.3", BumpLevel::Breaking, "0.3.0"),
+            ("0.0.3", BumpLevel::Breaking, "0.0.4"),
+            ("0.2.3", BumpLevel::Major, "1.0.0"),
+            // Pre-release and build metadata are dropped.
+            ("1.2.3-alpha.1+build", BumpLevel::Patch, "1.2.4"),
+        ];
+        for (version, level, expected) in cases {
+            assert_eq!(
+                level.apply(&Version::parse(version).unwrap()),
+                Version::parse(expected).unwrap(),
+                "{level:?} bump of {version}"
+            );
+        }
+    }
+
+    #[test]
+    fn updated_req_preserves_style() {
+        let cases = [
+            ("0.3", "0.4.0", "^0.4"),
+            ("^1.2.3", "2.0.0", "^2.0.0"),
+            ("~1.2.3", "2.0.0", "~2.0.0"),
+            ("~1.2", "1.3.0", "~1.3"),
+            ("=0.1.0", "0.2.0", "=0.2.0"),
+            ("1", "2.0.0", "^2"),
+            // Multiple comparators and other operators are replaced with a caret requirement.
+            (">=1.0, <1.5", "1.5.0", "^1.5.0"),
+            (">= 0.1", "0.2.0", "^0.2.0"),
+            // Pre-release identifiers come from the new version.
+            ("0.3", "0.4.0-rc.1", "^0.4.0-rc.1"),
+            ("~1.2", "1.3.0-beta", "~1.3.0-beta"),
+        ];
+        for (old_req, new_version, expected) in cases {
+            let new_req = updated_req(
+                &VersionReq::parse(old_req).unwrap(),
+                &Version::parse(new_version).unwrap(),
+            );
+            assert_eq!(
+                new_req.to_string(),
+                expected,
+                "updating {old_req} to {new_version}"
+            );
+            assert!(
+                new_req.matches(&Version::parse(new_version).unwrap()),
+                "{new_req} matches {new_version}"
+            );
+        }
+    }
+
+    #[test]
+    fn format_req_caret() {
+        let new_req = VersionReq::parse("^0.4").unwrap();
+        assert_eq!(format_req("0.3", &new_req), "0.4");
+        assert_eq!(format_req("^0.3", &new_req), "^0.4");
+        assert_eq!(format_req(" ^0.3", &new_req), "^0.4");
+
+        let new_req = VersionReq::parse("~0.4").unwrap();
+        assert_eq!(format_req("~0.3", &new_req), "~0.4");
+        assert_eq!(format_req("0.3", &new_req), "~0.4");
+    }
+
+    #[test]
+    fn publish_order_dependencies_first() {
+        let pkg_graph = JsonFixture::metadata_guppy_78cb7e8().graph();
+        let release_set: BTreeSet<_> = pkg_graph.workspace().member_ids().collect();
+        let order: Vec<_> = publish_order(pkg_graph, &release_set)
+            .into_iter()
+            .map(|package| package.name())
+            .collect();
+        // guppy has a dev-dependency on fixtures, which is ignored.
+        assert_eq!(
+            order,
+            vec![
+                "guppy-summaries",
+                "proptest-ext",
+                "target-spec",
+                "guppy",
+                "fixtures",
+                "guppy-benchmarks",
+                "guppy-cmdlib",
+                "cargo-compare",
+                "cargo-guppy",
+                "fixture-manager",
+            ]
+        );
+
+        // Packages outside the release set don't constrain the order.
+        let release_set: BTreeSet<_> = ["guppy-cmdlib", "cargo-guppy", "target-spec"]
+            .into_iter()
+            .map(|name| pkg_graph.workspace().member_by_name(name).unwrap().id())
+            .collect();
+        let order: Vec<_> = publish_order(pkg_graph, &release_set)
+            .into_iter()
+            .map(|package| package.name())
+            .collect();
+        assert_eq!(order, vec!["guppy-cmdlib", "cargo-guppy", "target-spec"]);
+    }
+
+    #[test]
+    fn apply_is_all_or_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = Utf8Path::from_path(dir.path()).expect("temp dir is valid UTF-8");
+        let files = [
+            (
+                "Cargo.toml",
+                "[workspace]\nmembers = [\"a\", \"b\"]\n\n[workspace.package]\nversion = \"0.1.0\"\n",
+            ),
+            (
+                "a/Cargo.toml",
+                "[package]\nname = \"a\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+            ),
+            (
+                "b/Cargo.toml",
+                "[package]\nname = \"b\"\nversion.workspace = true\nedition = \"2021\"\n\n\
+                 [dependencies]\na = { path = \"../a\", version = \"0.1\" }\n",
+            ),
+            ("a/src/lib.rs", ""),
+            ("b/src/lib.rs", ""),
+        ];
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        let mut command = MetadataCommand::new();
+        command.current_dir(root).other_options(["--offline"]);
+        let pkg_graph = command.build_graph().expect("package graph built");
+        let workspace_root = pkg_graph.workspace().root();
+        let a = pkg_graph.workspace().member_by_name("a").unwrap();
+        let plan = ReleasePlan::new(
+            &pkg_graph,
+            PackagePublish::CRATES_IO,
+            [],
+            [(a, Version::new(0, 2, 0))],
+            BumpLevel::Breaking,
+        );
+        assert_eq!(plan.requirement_updates().len(), 1);
+
+        // b's version is inherited from the workspace, so bumping it fails. a's manifest must not
+        // be written either.
+        let err = plan
+            .apply(workspace_root)
+            .expect_err("inherited versions are not supported");
+        assert!(
+            format!("{err:#}").contains("versions inherited from the workspace are not supported"),
+            "unexpected error: {err:#}"
+        );
+        for (path, contents) in files {
+            assert_eq!(
+                fs::read_to_string(workspace_root.join(path)).unwrap(),
+                contents,
+                "{path} is unchanged"
+            );
+        }
+    }
+}
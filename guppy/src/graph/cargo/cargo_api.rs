@@ -8,12 +8,12 @@ use crate::{
         cargo::build::CargoSetBuildState,
         feature::{FeatureGraph, FeatureSet},
     },
-    platform::PlatformSpec,
+    platform::{Platform, PlatformSpec},
     sorted_set::SortedSet,
 };
 use petgraph::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, fmt};
+use std::{collections::HashSet, fmt, sync::Arc};
 
 /// Options for queries which simulate what Cargo does.
 ///
@@ -88,6 +88,9 @@ impl<'a> CargoOptions<'a> {
     }
 
     /// Sets the target platform to the provided spec.
+    ///
+    /// To simulate a Cargo invocation with several `--target` arguments, pass in a
+    /// [`PlatformSpec::Union`].
     pub fn set_target_platform(&mut self, target_platform: impl Into<PlatformSpec>) -> &mut Self {
         self.target_platform = target_platform.into();
         self
@@ -279,6 +282,34 @@ impl<'g> CargoSet<'g> {
         Self::new_internal(initials, features_only, Some(&mut resolver), opts)
     }
 
+    /// Simulates a Cargo build of this feature set separately for each individual target platform
+    /// in `opts`, returning per-platform results.
+    ///
+    /// With a [`PlatformSpec::Union`] target platform, [`CargoSet::new`] simulates a single Cargo
+    /// invocation with several `--target` arguments, where features are unified across all of the
+    /// target platforms. This method instead simulates a build for each target platform on its
+    /// own, which can be used to find out what's built for each platform.
+    ///
+    /// The host platform and other options are unchanged. The results are returned in the same
+    /// order as [`PlatformSpec::platforms`], and are empty if the target platform is
+    /// [`PlatformSpec::Always`] or [`PlatformSpec::Any`].
+    pub fn new_per_target_platform(
+        initials: FeatureSet<'g>,
+        features_only: FeatureSet<'g>,
+        opts: &CargoOptions<'_>,
+    ) -> Result<Vec<(Arc<Platform>, Self)>, Error> {
+        opts.target_platform
+            .platforms()
+            .iter()
+            .map(|platform| {
+                let mut platform_opts = opts.clone();
+                platform_opts.set_target_platform(platform.clone());
+                let cargo_set = Self::new(initials.clone(), features_only.clone(), &platform_opts)?;
+                Ok((platform.clone(), cargo_set))
+            })
+            .collect()
+    }
+
     /// Internal helper to deduplicate code across `CargoSet::new` and `CargoSet::with_resolver`.
     fn new_internal(
         initials: FeatureSet<'g>,
@@ -16,6 +16,7 @@ use crate::{
     },
     platform::PlatformSpecSummary,
 };
+use camino::Utf8Path;
 pub use guppy_summaries::*;
 pub use package_set::*;
 use serde::{Deserialize, Serialize};
@@ -204,7 +205,19 @@ impl CargoOptionsSummary {
         })
     }
 
+    /// Resolves relative custom platform paths in the host and target platforms against
+    /// `base_dir`, typically the directory containing the configuration file this summary was
+    /// read from.
+    ///
+    /// For more, see [`PlatformSpecSummary::resolve_paths`].
+    pub fn resolve_paths(&mut self, base_dir: &Utf8Path) {
+        self.host_platform.resolve_paths(base_dir);
+        self.target_platform.resolve_paths(base_dir);
+    }
+
     /// Creates a new `CargoOptions` from this summary.
+    ///
+    /// Custom platforms that refer to files are read as part of this conversion.
     pub fn to_cargo_options<'g>(
         &'g self,
         package_graph: &'g PackageGraph,
@@ -345,4 +358,43 @@ proc-macros-on-target = false
             InitialsPlatform::Standard
         );
     }
+    #[test]
+    fn resolve_custom_platform_paths() {
+        let metadata = r#"
+version = 'v2'
+include-dev = true
+initials-platform = 'standard'
+
+[host-platform]
+triple = 'x86_64-unknown-linux-gnu'
+
+[target-platform]
+triple = 'thumbv7em-acme-none-eabihf'
+custom-json-path = 'targets/thumbv7em-acme-none-eabihf.json'
+"#;
+
+        let mut summary: CargoOptionsSummary = toml::from_str(metadata).expect("parsed correctly");
+        summary.resolve_paths(Utf8Path::new("/workspace/.config"));
+        let PlatformSpecSummary::Platform(target_platform) = &summary.target_platform else {
+            panic!(
+                "target platform is a platform: {:?}",
+                summary.target_platform
+            );
+        };
+        assert_eq!(
+            target_platform
+                .custom_json_path
+                .as_deref()
+                .map(Utf8Path::new),
+            Some(Utf8Path::new(
+                "/workspace/.config/targets/thumbv7em-acme-none-eabihf.json"
+            )),
+        );
+
+        // Round-trip through serialization.
+        let serialized = toml::to_string(&summary).expect("serialized correctly");
+        let summary2: CargoOptionsSummary =
+            toml::from_str(&serialized).expect("deserialized correctly");
+        assert_eq!(summary, summary2, "summary roundtrips");
+    }
 }
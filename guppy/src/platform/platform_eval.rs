@@ -64,6 +64,8 @@ impl<'g> PlatformStatus<'g> {
     ///
     /// Returns `Unknown` if the result was unknown, which may happen if evaluating against an
     /// individual platform and its target features are unknown.
+    ///
+    /// For a [`PlatformSpec::Union`], this is the logical OR of the results for each platform.
     pub fn enabled_on(&self, platform_spec: &PlatformSpec) -> EnabledTernary {
         match (self, platform_spec) {
             (PlatformStatus::Always, _) => EnabledTernary::Enabled,
@@ -74,6 +76,17 @@ impl<'g> PlatformStatus<'g> {
             (PlatformStatus::PlatformDependent { eval }, PlatformSpec::Platform(platform)) => {
                 eval.eval(platform)
             }
+            (PlatformStatus::PlatformDependent { eval }, PlatformSpec::Union(platforms)) => {
+                let mut res = EnabledTernary::Disabled;
+                for platform in platforms.iter() {
+                    res = res | eval.eval(platform);
+                    // Short-circuit evaluation if possible.
+                    if res == EnabledTernary::Enabled {
+                        break;
+                    }
+                }
+                res
+            }
             (PlatformStatus::PlatformDependent { .. }, PlatformSpec::Always) => {
                 EnabledTernary::Disabled
             }
@@ -182,6 +195,29 @@ impl<'g> PlatformEval<'g> {
     pub fn target_specs(&self) -> &'g [TargetSpec] {
         self.specs
     }
+
+    /// Returns the [`TargetSpec`] instances backing this evaluator, with specs equivalent to an
+    /// earlier one removed.
+    ///
+    /// Specs coming from different dependency edges are often equivalent, e.g. `cfg(unix)` and
+    /// `cfg(any(unix))`. Equivalence is determined by [`TargetSpec::is_equivalent`], which is
+    /// exact over builtin platforms but doesn't consider custom platforms: the result may evaluate
+    /// differently from [`Self::target_specs`] against them.
+    ///
+    /// This is quadratic in the number of specs, and each check evaluates the specs against every
+    /// builtin platform, so it's best used for reporting rather than during evaluation.
+    pub fn merged_target_specs(&self) -> Vec<&'g TargetSpec> {
+        let mut merged: Vec<&'g TargetSpec> = Vec::with_capacity(self.specs.len());
+        for spec in self.specs {
+            if !merged
+                .iter()
+                .any(|existing| existing.is_equivalent(spec) == Some(true))
+            {
+                merged.push(spec);
+            }
+        }
+        merged
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -8,9 +8,10 @@ use std::sync::Arc;
 
 /// A specifier for a single platform, or for a range of platforms.
 ///
-/// Some uses of `guppy` care about a single platform, and others care about queries against the
-/// intersection of all hypothetical platforms, or against a union of any of them. `PlatformSpec`
-/// handles the
+/// Some uses of `guppy` care about a single platform or a few of them, and others care about
+/// queries against the intersection of all hypothetical platforms, or against a union of any of
+/// them.
+/// `PlatformSpec` handles all of these cases.
 ///
 /// `PlatformSpec` does not currently support expressions, but it might in the future, using an
 /// [SMT solver](https://en.wikipedia.org/wiki/Satisfiability_modulo_theories).
@@ -35,6 +36,18 @@ pub enum PlatformSpec {
     /// [`EnabledTernary::Unknown`] if a platform is not enabled.
     Platform(Arc<Platform>),
 
+    /// A union of individual platforms.
+    ///
+    /// This corresponds to a single Cargo invocation with several `--target` arguments. Dependency
+    /// queries performed against this variant will return [`EnabledTernary::Enabled`] if a
+    /// dependency is enabled on any of the platforms, [`EnabledTernary::Disabled`] if it is
+    /// disabled on all of them, and [`EnabledTernary::Unknown`] otherwise.
+    ///
+    /// A union of no platforms never enables platform-dependent dependencies.
+    ///
+    /// Create a union with [`PlatformSpec::union`].
+    Union(Arc<[Arc<Platform>]>),
+
     /// The union of all platforms.
     ///
     /// Dependency queries performed against this variant will return [`EnabledTernary::Enabled`] if
@@ -68,6 +81,41 @@ impl PlatformSpec {
     pub fn build_target() -> Result<Self, TargetSpecError> {
         Ok(PlatformSpec::Platform(Arc::new(Platform::build_target()?)))
     }
+
+    /// Returns a `PlatformSpec` corresponding to the union of the given platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use guppy::platform::{Platform, PlatformSpec, TargetFeatures};
+    ///
+    /// let spec = PlatformSpec::union([
+    ///     Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap(),
+    ///     Platform::new("aarch64-apple-darwin", TargetFeatures::Unknown).unwrap(),
+    /// ]);
+    /// let triples: Vec<_> = spec.platforms().iter().map(|p| p.triple_str()).collect();
+    /// assert_eq!(triples, ["x86_64-unknown-linux-gnu", "aarch64-apple-darwin"]);
+    /// ```
+    pub fn union(platforms: impl IntoIterator<Item = impl Into<Arc<Platform>>>) -> Self {
+        PlatformSpec::Union(
+            platforms
+                .into_iter()
+                .map(|platform| platform.into())
+                .collect(),
+        )
+    }
+
+    /// Returns the individual platforms in this spec.
+    ///
+    /// This is a single platform for [`PlatformSpec::Platform`], the list of platforms for
+    /// [`PlatformSpec::Union`], and empty for [`PlatformSpec::Always`] and [`PlatformSpec::Any`].
+    pub fn platforms(&self) -> &[Arc<Platform>] {
+        match self {
+            PlatformSpec::Platform(platform) => std::slice::from_ref(platform),
+            PlatformSpec::Union(platforms) => platforms,
+            PlatformSpec::Always | PlatformSpec::Any => &[],
+        }
+    }
 }
 
 impl<T: Into<Arc<Platform>>> From<T> for PlatformSpec {
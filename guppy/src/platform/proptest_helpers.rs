@@ -14,10 +14,18 @@ use proptest::prelude::*;
 impl PlatformSpec {
     /// Returns a [`Strategy`] that generates a random `PlatformSpec` instance.
     pub fn strategy(platform: impl Strategy<Value = Platform>) -> impl Strategy<Value = Self> {
+        // Generate a single platform most of the time, and a union of platforms otherwise.
+        let platforms = prop::collection::vec(platform, 1..4).prop_map(|mut platforms| {
+            if platforms.len() == 1 {
+                PlatformSpec::from(platforms.pop().expect("platforms has one element"))
+            } else {
+                PlatformSpec::union(platforms)
+            }
+        });
         prop_oneof![
             1 => Just(PlatformSpec::Any),
             1 => Just(PlatformSpec::Always),
-            2 => platform.prop_map(PlatformSpec::from),
+            2 => platforms,
         ]
     }
 }
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{errors::TargetSpecError, platform::PlatformSpec};
+use camino::Utf8Path;
 use std::sync::Arc;
 pub use target_spec::summaries::{PlatformSummary, TargetFeaturesSummary};
 
@@ -74,8 +75,51 @@ pub enum PlatformSpecSummary {
     ///     )
     /// );
     /// ```
+    ///
+    /// Deserialize a custom platform whose `rustc --print=cfg` output is stored in a file.
+    ///
+    /// ```
+    /// # use guppy::platform::{PlatformSummary, PlatformSpecSummary};
+    /// let spec: PlatformSpecSummary = toml::from_str(r#"
+    /// triple = "thumbv7em-acme-none-eabihf"
+    /// custom-cfg-path = "targets/thumbv7em-acme-none-eabihf.cfg"
+    /// "#).unwrap();
+    /// assert_eq!(
+    ///     spec,
+    ///     PlatformSpecSummary::Platform(
+    ///         PlatformSummary::new("thumbv7em-acme-none-eabihf")
+    ///             .with_custom_cfg_path("targets/thumbv7em-acme-none-eabihf.cfg")
+    ///     )
+    /// );
+    /// ```
     Platform(PlatformSummary),
 
+    /// A union of individual platforms.
+    ///
+    /// This is converted to and from [`PlatformSpec::Union`], and is serialized as a map with a
+    /// `union` key containing a list of platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use guppy::platform::{PlatformSummary, PlatformSpecSummary};
+    /// let spec: PlatformSpecSummary = toml::from_str(r#"
+    /// [[union]]
+    /// triple = "x86_64-unknown-linux-gnu"
+    ///
+    /// [[union]]
+    /// triple = "aarch64-apple-darwin"
+    /// "#).unwrap();
+    /// assert_eq!(
+    ///     spec,
+    ///     PlatformSpecSummary::Union(vec![
+    ///         PlatformSummary::new("x86_64-unknown-linux-gnu"),
+    ///         PlatformSummary::new("aarch64-apple-darwin"),
+    ///     ]),
+    /// );
+    /// ```
+    Union(Vec<PlatformSummary>),
+
     /// The union of all platforms.
     ///
     /// This is converted to and from [`PlatformSpec::Any`], and is serialized as the string
@@ -113,6 +157,12 @@ impl PlatformSpecSummary {
             PlatformSpec::Platform(platform) => {
                 PlatformSpecSummary::Platform(platform.to_summary())
             }
+            PlatformSpec::Union(platforms) => PlatformSpecSummary::Union(
+                platforms
+                    .iter()
+                    .map(|platform| platform.to_summary())
+                    .collect(),
+            ),
             PlatformSpec::Any => PlatformSpecSummary::Any,
         }
     }
@@ -126,10 +176,33 @@ impl PlatformSpecSummary {
             PlatformSpecSummary::Platform(platform) => {
                 Ok(PlatformSpec::Platform(Arc::new(platform.to_platform()?)))
             }
+            PlatformSpecSummary::Union(platforms) => {
+                let platforms = platforms
+                    .iter()
+                    .map(|platform| platform.to_platform())
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(PlatformSpec::union(platforms))
+            }
             PlatformSpecSummary::Any => Ok(PlatformSpec::Any),
         }
     }
 
+    /// Resolves relative custom platform paths against `base_dir`, typically the directory
+    /// containing the configuration file this summary was read from.
+    ///
+    /// For more, see [`PlatformSummary::resolve_paths`].
+    pub fn resolve_paths(&mut self, base_dir: &Utf8Path) {
+        match self {
+            PlatformSpecSummary::Platform(platform) => platform.resolve_paths(base_dir),
+            PlatformSpecSummary::Union(platforms) => {
+                for platform in platforms {
+                    platform.resolve_paths(base_dir);
+                }
+            }
+            PlatformSpecSummary::Always | PlatformSpecSummary::Any => {}
+        }
+    }
+
     /// Returns true if `self` is `PlatformSpecSummary::Any`.
     pub fn is_any(&self) -> bool {
         matches!(self, PlatformSpecSummary::Any)
@@ -139,8 +212,6 @@ impl PlatformSpecSummary {
 mod serde_impl {
     use super::*;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
-    use std::collections::BTreeSet;
-    use target_spec::summaries::TargetFeaturesSummary;
 
     impl Serialize for PlatformSpecSummary {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -151,6 +222,9 @@ mod serde_impl {
                 PlatformSpecSummary::Always => Spec { spec: "always" }.serialize(serializer),
                 PlatformSpecSummary::Any => Spec { spec: "any" }.serialize(serializer),
                 PlatformSpecSummary::Platform(platform) => platform.serialize(serializer),
+                PlatformSpecSummary::Union(platforms) => {
+                    Union { union: platforms }.serialize(serializer)
+                }
             }
         }
     }
@@ -162,6 +236,11 @@ mod serde_impl {
         spec: &'static str,
     }
 
+    #[derive(Serialize)]
+    struct Union<'a> {
+        union: &'a [PlatformSummary],
+    }
+
     impl<'de> Deserialize<'de> for PlatformSpecSummary {
         fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
         where
@@ -179,18 +258,10 @@ mod serde_impl {
                         }
                     }
                 }
-                PlatformSpecSummaryDeserialize::PlatformFull {
-                    triple,
-                    custom_json,
-                    custom_cfg,
-                    target_features,
-                    flags,
-                } => {
-                    let mut summary = PlatformSummary::new(triple);
-                    summary.custom_json = custom_json;
-                    summary.custom_cfg = custom_cfg;
-                    summary.target_features = target_features;
-                    summary.flags = flags;
+                PlatformSpecSummaryDeserialize::Union { union } => {
+                    Ok(PlatformSpecSummary::Union(union))
+                }
+                PlatformSpecSummaryDeserialize::Platform(summary) => {
                     Ok(PlatformSpecSummary::Platform(summary))
                 }
             }
@@ -201,29 +272,17 @@ mod serde_impl {
     #[serde(untagged)]
     enum PlatformSpecSummaryDeserialize {
         String(String),
-        Spec {
-            spec: String,
-        },
-        #[serde(rename_all = "kebab-case")]
-        PlatformFull {
-            // TODO: there doesn't appear to be any way to defer to the PlatformSummary
-            // deserializer, so copy-paste its logic here. Find a better way?
-            triple: String,
-            #[serde(skip_serializing_if = "Option::is_none", default)]
-            custom_json: Option<String>,
-            #[serde(skip_serializing_if = "Option::is_none", default)]
-            custom_cfg: Option<String>,
-            #[serde(default)]
-            target_features: TargetFeaturesSummary,
-            #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
-            flags: BTreeSet<String>,
-        },
+        Spec { spec: String },
+        Union { union: Vec<PlatformSummary> },
+        // Other maps are platform summaries.
+        Platform(PlatformSummary),
     }
 }
 
 #[cfg(all(test, feature = "proptest1"))]
 mod proptests {
     use super::*;
+    use crate::platform::Platform;
     use proptest::prelude::*;
     use std::collections::HashSet;
 
@@ -241,12 +300,34 @@ mod proptests {
                 (PlatformSpec::Any, PlatformSpec::Any)
                 | (PlatformSpec::Always, PlatformSpec::Always) => {},
                 (PlatformSpec::Platform(platform), PlatformSpec::Platform(platform2)) => {
-                    assert_eq!(platform.triple_str(), platform2.triple_str(), "triples match");
-                    assert_eq!(platform.target_features(), platform2.target_features(), "target features match");
-                    assert_eq!(platform.flags().collect::<HashSet<_>>(), platform2.flags().collect::<HashSet<_>>(), "flags match");
+                    assert_platforms_match(&platform, &platform2);
+                }
+                (PlatformSpec::Union(platforms), PlatformSpec::Union(platforms2)) => {
+                    assert_eq!(platforms.len(), platforms2.len(), "union lengths match");
+                    for (platform, platform2) in platforms.iter().zip(platforms2.iter()) {
+                        assert_platforms_match(platform, platform2);
+                    }
                 }
                 (other, other2) => panic!("platform specs do not match: original: {other:?}, roundtrip: {other2:?}"),
             }
         }
     }
+
+    fn assert_platforms_match(platform: &Platform, platform2: &Platform) {
+        assert_eq!(
+            platform.triple_str(),
+            platform2.triple_str(),
+            "triples match"
+        );
+        assert_eq!(
+            platform.target_features(),
+            platform2.target_features(),
+            "target features match"
+        );
+        assert_eq!(
+            platform.flags().collect::<HashSet<_>>(),
+            platform2.flags().collect::<HashSet<_>>(),
+            "flags match"
+        );
+    }
 }
@@ -2,10 +2,13 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use fixtures::json::JsonFixture;
-use guppy::graph::{
-    DependencyDirection, PackageLink, PackageQuery, PackageResolver,
-    cargo::{CargoOptions, CargoSet},
-    feature::StandardFeatures,
+use guppy::{
+    graph::{
+        DependencyDirection, PackageLink, PackageQuery, PackageResolver,
+        cargo::{CargoOptions, CargoResolverVersion, CargoSet},
+        feature::StandardFeatures,
+    },
+    platform::{Platform, PlatformSpec, TargetFeatures},
 };
 use std::collections::HashSet;
 
@@ -371,3 +374,82 @@ fn test_package_resolver_filtering_links_on_host() {
     assert!(trace.contains("datatest-derive@0.4.0 => quote@1.0.2"));
     assert!(trace.contains("datatest-derive@0.4.0 => proc-macro2@1.0.3"));
 }
+
+fn target_package_strings(cargo_set: &CargoSet) -> Vec<String> {
+    let mut result = cargo_set
+        .target_features()
+        .packages_with_features(DependencyDirection::Forward)
+        .map(|feature_list| {
+            let package = feature_list.package();
+            format!("{}@{}", package.name(), package.version())
+        })
+        .collect::<Vec<_>>();
+    result.sort();
+    result
+}
+
+#[test]
+fn test_union_target_platforms() {
+    let package_graph = JsonFixture::metadata_targets1().graph();
+    let initials = package_graph
+        .resolve_package_name("testcrate-targets")
+        .to_feature_set(StandardFeatures::Default);
+    let no_extra_features = package_graph
+        .resolve_none()
+        .to_feature_set(StandardFeatures::Default);
+
+    let x86_64_linux = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+    let x86_64_windows = Platform::new("x86_64-pc-windows-msvc", TargetFeatures::Unknown).unwrap();
+
+    let mut cargo_options = CargoOptions::new();
+    cargo_options
+        .set_resolver(CargoResolverVersion::V2)
+        .set_target_platform(PlatformSpec::union([x86_64_linux, x86_64_windows]));
+
+    let union_set =
+        CargoSet::new(initials.clone(), no_extra_features.clone(), &cargo_options).unwrap();
+    assert_eq!(
+        target_package_strings(&union_set),
+        vec![
+            "bytes@0.5.3",
+            "dep-a@0.1.0",
+            "lazy_static@0.2.11",
+            "lazy_static@1.4.0",
+            "serde@1.0.105",
+            "testcrate-targets@0.1.0",
+        ],
+        "union includes packages enabled on any platform"
+    );
+
+    let per_platform =
+        CargoSet::new_per_target_platform(initials, no_extra_features, &cargo_options).unwrap();
+    let per_platform: Vec<_> = per_platform
+        .iter()
+        .map(|(platform, cargo_set)| (platform.triple_str(), target_package_strings(cargo_set)))
+        .collect();
+    assert_eq!(
+        per_platform,
+        vec![
+            (
+                "x86_64-unknown-linux-gnu",
+                vec![
+                    "bytes@0.5.3".to_owned(),
+                    "dep-a@0.1.0".to_owned(),
+                    "lazy_static@0.2.11".to_owned(),
+                    "lazy_static@1.4.0".to_owned(),
+                    "serde@1.0.105".to_owned(),
+                    "testcrate-targets@0.1.0".to_owned(),
+                ]
+            ),
+            (
+                "x86_64-pc-windows-msvc",
+                vec![
+                    "bytes@0.5.3".to_owned(),
+                    "lazy_static@1.4.0".to_owned(),
+                    "serde@1.0.105".to_owned(),
+                    "testcrate-targets@0.1.0".to_owned(),
+                ]
+            ),
+        ],
+    );
+}
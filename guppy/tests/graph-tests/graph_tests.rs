@@ -456,6 +456,48 @@ mod small {
 
     proptest_suite!(metadata_targets1);
 
+    #[test]
+    fn metadata_targets1_merged_specs() {
+        use guppy::platform::PlatformStatus;
+
+        // Make the two optional build dependency conditions equivalent.
+        let json = JsonFixture::metadata_targets1().json().replace(
+            r#"cfg(all(unix, target_feature = \"sse\"))"#,
+            r#"cfg(any(target_feature = \"sse\", unix))"#,
+        );
+        let package_graph = PackageGraph::from_json(json).expect("valid package graph");
+        let testcrate = package_graph
+            .metadata(&package_id(json::METADATA_TARGETS1_TESTCRATE))
+            .expect("valid package ID");
+        let link = testcrate
+            .direct_links()
+            .find(|link| *link.to().id() == package_id(json::METADATA_TARGETS1_DEP_A))
+            .expect("testcrate depends on dep-a");
+        let PlatformStatus::PlatformDependent { eval } = link.build().status().optional_status()
+        else {
+            panic!("optional build dependency on dep-a is platform-dependent");
+        };
+
+        let specs: Vec<_> = eval
+            .target_specs()
+            .iter()
+            .map(|spec| spec.to_string())
+            .collect();
+        let merged: Vec<_> = eval
+            .merged_target_specs()
+            .into_iter()
+            .map(|spec| spec.to_string())
+            .collect();
+        assert_eq!(
+            specs,
+            vec![
+                "any(target_feature = \"sse\", unix)",
+                "any(unix, target_feature = \"sse\")",
+            ]
+        );
+        assert_eq!(merged, vec!["any(target_feature = \"sse\", unix)"]);
+    }
+
     #[test]
     fn metadata_build_targets1() {
         let metadata_build_targets1 = JsonFixture::metadata_build_targets1();
@@ -26,6 +26,11 @@ pub static CUSTOM_INVALID: include_dir::Dir<'static> =
 pub static EXPR_INVALID: include_dir::Dir<'static> =
     include_dir::include_dir!("$CARGO_MANIFEST_DIR/tests/fixtures/expr-invalid");
 
+/// A set of valid `cfg` expressions with likely mistakes, reported by
+/// [`TargetSpecExpression::lint`](target_spec::TargetSpecExpression::lint).
+pub static EXPR_LINT: include_dir::Dir<'static> =
+    include_dir::include_dir!("$CARGO_MANIFEST_DIR/tests/fixtures/expr-lint");
+
 /// A set of invalid `rustc --print=cfg` outputs.
 pub static CFG_INVALID: include_dir::Dir<'static> =
     include_dir::include_dir!("$CARGO_MANIFEST_DIR/tests/fixtures/cfg-invalid");
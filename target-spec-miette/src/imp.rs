@@ -1,11 +1,14 @@
 // Copyright (c) The cargo-guppy Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use miette::{Diagnostic, LabeledSpan, SourceCode, SourceOffset, SourceSpan};
+use miette::{Diagnostic, LabeledSpan, Severity, SourceCode, SourceOffset, SourceSpan};
 use std::{error::Error as StdError, fmt};
-use target_spec::errors::{
-    CustomTripleCreateError, Error as TargetSpecError, ExpressionParseError, PlainStringParseError,
-    TripleParseError,
+use target_spec::{
+    ExpressionLint,
+    errors::{
+        CustomTripleCreateError, Error as TargetSpecError, ExpressionParseError,
+        PlainStringParseError, TripleParseError,
+    },
 };
 
 /// Extension trait that converts errors into a [`miette::Diagnostic`].
@@ -83,6 +86,61 @@ impl IntoMietteDiagnostic for ExpressionParseError {
     }
 }
 
+/// A wrapper around [`ExpressionLint`] that implements [`Diagnostic`].
+///
+/// Lints are reported with [`Severity::Warning`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct ExpressionLintDiagnostic(ExpressionLint);
+
+impl ExpressionLintDiagnostic {
+    /// Creates a new `ExpressionLintDiagnostic`.
+    pub fn new(lint: ExpressionLint) -> Self {
+        Self(lint)
+    }
+}
+
+impl fmt::Debug for ExpressionLintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for ExpressionLintDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl StdError for ExpressionLintDiagnostic {}
+
+impl Diagnostic for ExpressionLintDiagnostic {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn fmt::Display + 'a>> {
+        let suggestion = self.0.suggestion()?;
+        Some(Box::new(format!("did you mean `{suggestion}`?")))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.0.input)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let label = LabeledSpan::new_with_span(Some(self.0.kind.to_string()), self.0.span.clone());
+        Some(Box::new(std::iter::once(label)))
+    }
+}
+
+impl IntoMietteDiagnostic for ExpressionLint {
+    type IntoDiagnostic = ExpressionLintDiagnostic;
+
+    fn into_diagnostic(self) -> Self::IntoDiagnostic {
+        ExpressionLintDiagnostic::new(self)
+    }
+}
+
 /// A wrapper around [`TripleParseError`] that implements [`Diagnostic`].
 #[derive(Clone, PartialEq, Eq)]
 pub struct TripleParseDiagnostic {
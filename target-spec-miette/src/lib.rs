@@ -6,6 +6,10 @@
 //! This crate has implementations of `Diagnostic` for the various kinds of errors that target-spec
 //! produces. This can be used to pretty-print errors returned by target-spec.
 //!
+//! Lints returned by target-spec, such as
+//! [`ExpressionLint`](target_spec::ExpressionLint), are also supported, and are reported as
+//! warnings.
+//!
 //! ## Features
 //!
 //! - `fixtures`: Include [a set of fixtures](crate::fixtures) for testing
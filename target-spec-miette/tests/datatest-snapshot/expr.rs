@@ -22,3 +22,25 @@ pub(crate) fn expr_invalid(path: &Utf8Path, contents: String) -> datatest_stable
     snapbox_assert_ansi("expr-invalid", path, output);
     Ok(())
 }
+
+pub(crate) fn expr_lint(path: &Utf8Path, contents: String) -> datatest_stable::Result<()> {
+    // SAFETY: Tests run under nextest where it is safe to alter the
+    // environment.
+    unsafe {
+        std::env::set_var("CLICOLOR_FORCE", "1");
+    }
+
+    let spec = target_spec::TargetSpec::new(contents.trim_end().to_owned())
+        .expect("expected input to parse");
+    let lints = spec.lint();
+    assert!(!lints.is_empty(), "expected input to produce lints");
+
+    // Use Debug output on each report to get the nicely formatted output.
+    let output: String = lints
+        .into_iter()
+        .map(|lint| format!("{:?}", miette::Report::new(lint.into_diagnostic())))
+        .collect();
+
+    snapbox_assert_ansi("expr-lint", path, output);
+    Ok(())
+}
@@ -10,4 +10,5 @@ datatest_stable::harness! {
     { test = cfg::cfg_invalid, root = &target_spec_miette::fixtures::CFG_INVALID, pattern = r"^.*/*" },
     { test = custom::custom_invalid, root = &target_spec_miette::fixtures::CUSTOM_INVALID, pattern = r"^.*/*" },
     { test = expr::expr_invalid, root = &target_spec_miette::fixtures::EXPR_INVALID, pattern = r"^.*/*" },
+    { test = expr::expr_lint, root = &target_spec_miette::fixtures::EXPR_LINT, pattern = r"^.*/*" },
 }
@@ -26,6 +26,12 @@ pub enum Error {
     CustomPlatformCreate(CustomTripleCreateError),
     /// An error occurred while parsing `rustc -vV` output.
     RustcVersionVerboseParse(RustcVersionVerboseParseError),
+    /// An error occurred while reading rustflags from Cargo configuration.
+    #[cfg(feature = "rustflags")]
+    RustFlagsConfig(RustFlagsConfigError),
+    /// An error occurred while loading a platform registry.
+    #[cfg(feature = "custom-cfg")]
+    PlatformRegistry(PlatformRegistryError),
 }
 
 impl fmt::Display for Error {
@@ -46,6 +52,14 @@ impl fmt::Display for Error {
             Error::RustcVersionVerboseParse(_) => {
                 write!(f, "error parsing `rustc -vV` output")
             }
+            #[cfg(feature = "rustflags")]
+            Error::RustFlagsConfig(_) => {
+                write!(f, "error reading rustflags from Cargo configuration")
+            }
+            #[cfg(feature = "custom-cfg")]
+            Error::PlatformRegistry(_) => {
+                write!(f, "error loading platform registry")
+            }
         }
     }
 }
@@ -60,6 +74,10 @@ impl error::Error for Error {
             Error::CustomTripleCreate(err) => Some(err),
             Error::CustomPlatformCreate(err) => Some(err),
             Error::RustcVersionVerboseParse(err) => Some(err),
+            #[cfg(feature = "rustflags")]
+            Error::RustFlagsConfig(err) => Some(err),
+            #[cfg(feature = "custom-cfg")]
+            Error::PlatformRegistry(err) => Some(err),
         }
     }
 }
@@ -396,12 +414,31 @@ pub enum CustomTripleCreateError {
         line: usize,
     },
 
-    /// Both `custom_json` and `custom_cfg` were specified for a
-    /// platform summary, but only one is allowed.
+    /// More than one of `custom_json`, `custom_cfg`,
+    /// `custom_json_path` and `custom_cfg_path` were specified for
+    /// a platform summary, but only one is allowed.
     ConflictingCustomPlatformSources {
         /// The specified triple.
         triple: String,
     },
+
+    /// An error occurred while reading a file containing a custom
+    /// platform definition.
+    ///
+    /// Currently, this can only happen if a custom platform is
+    /// deserialized from a
+    /// [`PlatformSummary`](crate::summaries::PlatformSummary) that
+    /// refers to a file.
+    ReadFile {
+        /// The specified triple.
+        triple: String,
+
+        /// The path that was read.
+        path: String,
+
+        /// The I/O error that occurred.
+        error: std::sync::Arc<std::io::Error>,
+    },
 }
 
 impl CustomTripleCreateError {
@@ -427,7 +464,8 @@ impl CustomTripleCreateError {
             Self::ParseCfg { input, .. } => Some(input),
             Self::CustomJsonUnavailable
             | Self::CustomCfgUnavailable
-            | Self::ConflictingCustomPlatformSources { .. } => None,
+            | Self::ConflictingCustomPlatformSources { .. }
+            | Self::ReadFile { .. } => None,
             #[allow(deprecated)]
             Self::Unavailable => None,
         }
@@ -450,7 +488,8 @@ impl CustomTripleCreateError {
             Self::ParseCfg { line, .. } => Some((*line, 0)),
             Self::CustomJsonUnavailable
             | Self::CustomCfgUnavailable
-            | Self::ConflictingCustomPlatformSources { .. } => None,
+            | Self::ConflictingCustomPlatformSources { .. }
+            | Self::ReadFile { .. } => None,
             #[allow(deprecated)]
             Self::Unavailable => None,
         }
@@ -480,7 +519,8 @@ impl CustomTripleCreateError {
             Self::ParseCfg { message, .. } => Some(message.clone()),
             Self::CustomJsonUnavailable
             | Self::CustomCfgUnavailable
-            | Self::ConflictingCustomPlatformSources { .. } => None,
+            | Self::ConflictingCustomPlatformSources { .. }
+            | Self::ReadFile { .. } => None,
             #[allow(deprecated)]
             Self::Unavailable => None,
         }
@@ -519,11 +559,19 @@ impl fmt::Display for CustomTripleCreateError {
                 write!(
                     f,
                     "conflicting custom platform sources for \
-                     `{triple}`: both `custom_json` and \
-                     `custom_cfg` are specified, but only one \
+                     `{triple}`: more than one of `custom_json`, \
+                     `custom_cfg`, `custom_json_path` and \
+                     `custom_cfg_path` is specified, but only one \
                      is allowed"
                 )
             }
+            Self::ReadFile { triple, path, .. } => {
+                write!(
+                    f,
+                    "error reading custom platform definition for \
+                     `{triple}` from `{path}`"
+                )
+            }
             #[allow(deprecated)]
             Self::Unavailable => {
                 write!(
@@ -544,6 +592,7 @@ impl error::Error for CustomTripleCreateError {
             Self::DeserializeJson { error, .. } | Self::Deserialize { error, .. } => Some(error),
             #[cfg(feature = "custom-cfg")]
             Self::ParseCfg { .. } => None,
+            Self::ReadFile { error, .. } => Some(error),
             Self::CustomJsonUnavailable
             | Self::CustomCfgUnavailable
             | Self::ConflictingCustomPlatformSources { .. } => None,
@@ -553,6 +602,46 @@ impl error::Error for CustomTripleCreateError {
     }
 }
 
+/// An error that occurred while reading a platform registry's snapshot directory.
+///
+/// Returned by [`PlatformRegistry::from_snapshot_dir`](crate::PlatformRegistry::from_snapshot_dir).
+#[cfg(feature = "custom-cfg")]
+#[derive(Clone, Debug)]
+pub struct PlatformRegistryError {
+    dir: std::path::PathBuf,
+    error: std::sync::Arc<std::io::Error>,
+}
+
+#[cfg(feature = "custom-cfg")]
+impl PlatformRegistryError {
+    pub(crate) fn new(dir: std::path::PathBuf, error: std::sync::Arc<std::io::Error>) -> Self {
+        Self { dir, error }
+    }
+
+    /// Returns the snapshot directory that could not be read.
+    pub fn dir(&self) -> &std::path::Path {
+        &self.dir
+    }
+}
+
+#[cfg(feature = "custom-cfg")]
+impl fmt::Display for PlatformRegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "error reading snapshot directory `{}`",
+            self.dir.display()
+        )
+    }
+}
+
+#[cfg(feature = "custom-cfg")]
+impl error::Error for PlatformRegistryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
 /// An error occurred while parsing `rustc -vV` output.
 ///
 /// Returned by [`Platform::from_rustc_version_verbose`](crate::Platform::from_rustc_version_verbose).
@@ -613,3 +702,71 @@ mod tests {
         assert_eq!(err.span(), expected_span);
     }
 }
+
+/// An error returned while reading rustflags from a Cargo configuration file.
+///
+/// Requires the `rustflags` feature to be enabled.
+#[cfg(feature = "rustflags")]
+#[derive(Clone, Debug)]
+pub struct RustFlagsConfigError {
+    path: std::path::PathBuf,
+    kind: RustFlagsConfigErrorKind,
+}
+
+#[cfg(feature = "rustflags")]
+impl RustFlagsConfigError {
+    pub(crate) fn new(path: std::path::PathBuf, kind: RustFlagsConfigErrorKind) -> Self {
+        Self { path, kind }
+    }
+
+    /// Returns the path to the configuration file that caused the error.
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+#[cfg(feature = "rustflags")]
+impl fmt::Display for RustFlagsConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error reading Cargo config at `{}`", self.path.display())
+    }
+}
+
+#[cfg(feature = "rustflags")]
+impl error::Error for RustFlagsConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.kind)
+    }
+}
+
+#[cfg(feature = "rustflags")]
+#[derive(Clone, Debug)]
+pub(crate) enum RustFlagsConfigErrorKind {
+    Read(std::sync::Arc<std::io::Error>),
+    Parse(toml::de::Error),
+    InvalidValue { key: String },
+}
+
+#[cfg(feature = "rustflags")]
+impl fmt::Display for RustFlagsConfigErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(_) => write!(f, "failed to read file"),
+            Self::Parse(_) => write!(f, "failed to parse file as TOML"),
+            Self::InvalidValue { key } => {
+                write!(f, "`{key}` must be a string or an array of strings")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "rustflags")]
+impl error::Error for RustFlagsConfigErrorKind {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Read(error) => Some(error.as_ref()),
+            Self::Parse(error) => Some(error),
+            Self::InvalidValue { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,637 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! An owned tree representation of `cfg()` expressions, used for symbolic manipulation.
+//!
+//! `cfg-expr` stores expressions in postfix form, which is great for evaluation but doesn't allow
+//! rewriting. [`ExprTree`] is built by evaluating an expression with [`Logic`] operations that
+//! construct tree nodes.
+//!
+//! Operands within `all()` and `any()` aren't always in the order they were written in: the
+//! postfix form emits nested `all()`, `any()` and `not()` operands before plain predicates.
+
+use crate::{FlagEval, Platform, Triple, target_features::implied_features};
+use cfg_expr::{
+    Expression, Predicate, TargetPredicate,
+    expr::Logic,
+    targets::{Endian, get_builtin_target_by_triple},
+};
+use std::{cmp::Ordering, collections::BTreeSet, fmt, hash};
+
+/// A node in an expression tree.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub(crate) enum ExprTree {
+    /// A constant, written as `all()` (true) or `any()` (false).
+    Const(bool),
+    Pred(TreePredicate),
+    Not(Box<ExprTree>),
+    All(Vec<ExprTree>),
+    Any(Vec<ExprTree>),
+}
+
+impl ExprTree {
+    pub(crate) fn new(expr: &Expression) -> Self {
+        expr.eval(|pred| ExprTree::Pred(TreePredicate::new(pred)))
+    }
+
+    /// Replaces every predicate with the result of `f`, which returns `None` to keep the
+    /// predicate, then simplifies the result.
+    pub(crate) fn substitute(&self, f: &mut impl FnMut(&TreePredicate) -> Option<bool>) -> Self {
+        match self {
+            ExprTree::Const(value) => ExprTree::Const(*value),
+            ExprTree::Pred(pred) => match f(pred) {
+                Some(value) => ExprTree::Const(value),
+                None => ExprTree::Pred(pred.clone()),
+            },
+            ExprTree::Not(inner) => ExprTree::Not(Box::new(inner.substitute(f))),
+            ExprTree::All(children) => {
+                ExprTree::All(children.iter().map(|child| child.substitute(f)).collect())
+            }
+            ExprTree::Any(children) => {
+                ExprTree::Any(children.iter().map(|child| child.substitute(f)).collect())
+            }
+        }
+        .simplify()
+    }
+
+    /// Simplifies this tree: constants are folded, double negations are removed, nested
+    /// `all()`/`any()` nodes are flattened, and single-element `all()`/`any()` nodes are unwrapped.
+    pub(crate) fn simplify(self) -> Self {
+        match self {
+            ExprTree::Const(_) | ExprTree::Pred(_) => self,
+            ExprTree::Not(inner) => match inner.simplify() {
+                ExprTree::Const(value) => ExprTree::Const(!value),
+                ExprTree::Not(inner) => *inner,
+                other => ExprTree::Not(Box::new(other)),
+            },
+            ExprTree::All(children) => Self::simplify_op(children, true),
+            ExprTree::Any(children) => Self::simplify_op(children, false),
+        }
+    }
+
+    /// Simplifies `all()` (if `is_all` is true) or `any()` (if false).
+    fn simplify_op(children: Vec<ExprTree>, is_all: bool) -> Self {
+        // For all(), true is the identity and false absorbs everything; for any(), the reverse.
+        let identity = is_all;
+        let mut out = Vec::with_capacity(children.len());
+        for child in children {
+            match child.simplify() {
+                ExprTree::Const(value) if value == identity => {}
+                ExprTree::Const(_) => return ExprTree::Const(!identity),
+                ExprTree::All(grandchildren) if is_all => out.extend(grandchildren),
+                ExprTree::Any(grandchildren) if !is_all => out.extend(grandchildren),
+                other => out.push(other),
+            }
+        }
+
+        match out.len() {
+            0 => ExprTree::Const(identity),
+            1 => out.pop().expect("out has one element"),
+            _ if is_all => ExprTree::All(out),
+            _ => ExprTree::Any(out),
+        }
+    }
+
+    /// Returns the canonical form of this tree.
+    ///
+    /// In addition to [`Self::simplify`], operands are sorted and deduplicated, operations
+    /// containing both an operand and its complement (`all(x, not(x))`) are replaced with
+    /// constants, and absorbed operands (`x` in `all(x, any(x, y))`) are removed.
+    ///
+    /// Only syntactic rewrites are performed, so the result doesn't depend on the set of builtin
+    /// targets.
+    pub(crate) fn canonicalize(self) -> Self {
+        match self.simplify() {
+            ExprTree::Not(inner) => ExprTree::Not(Box::new(inner.canonicalize())).simplify(),
+            ExprTree::All(children) => Self::canonicalize_op(children, true),
+            ExprTree::Any(children) => Self::canonicalize_op(children, false),
+            other => other,
+        }
+    }
+
+    /// Canonicalizes `all()` (if `is_all` is true) or `any()` (if false).
+    fn canonicalize_op(children: Vec<ExprTree>, is_all: bool) -> Self {
+        let children = children.into_iter().map(Self::canonicalize).collect();
+        // Canonicalizing children may have produced constants or nested operations of the same
+        // kind, so simplify again.
+        let mut children = match Self::simplify_op(children, is_all) {
+            ExprTree::All(children) if is_all => children,
+            ExprTree::Any(children) if !is_all => children,
+            other => return other,
+        };
+        children.sort();
+        children.dedup();
+
+        // all(x, not(x)) is always false, and any(x, not(x)) is always true.
+        let has_complement = children.iter().any(|child| match child {
+            ExprTree::Not(inner) => children.binary_search(inner).is_ok(),
+            _ => false,
+        });
+        if has_complement {
+            return ExprTree::Const(!is_all);
+        }
+
+        // all(x, any(x, y)) is x, and any(x, all(x, y)) is x.
+        let is_absorbed = |child: &ExprTree| match child {
+            ExprTree::Any(grandchildren) if is_all => grandchildren
+                .iter()
+                .any(|grandchild| children.binary_search(grandchild).is_ok()),
+            ExprTree::All(grandchildren) if !is_all => grandchildren
+                .iter()
+                .any(|grandchild| children.binary_search(grandchild).is_ok()),
+            _ => false,
+        };
+        let absorbed: Vec<bool> = children.iter().map(is_absorbed).collect();
+        let mut absorbed = absorbed.into_iter();
+        children.retain(|_| !absorbed.next().expect("one entry per child"));
+
+        match children.len() {
+            0 => ExprTree::Const(is_all),
+            1 => children.pop().expect("children has one element"),
+            _ if is_all => ExprTree::All(children),
+            _ => ExprTree::Any(children),
+        }
+    }
+
+    fn collect_predicates<'a>(&'a self, out: &mut BTreeSet<&'a TreePredicate>) {
+        match self {
+            ExprTree::Const(_) => {}
+            ExprTree::Pred(pred) => {
+                out.insert(pred);
+            }
+            ExprTree::Not(inner) => inner.collect_predicates(out),
+            ExprTree::All(children) | ExprTree::Any(children) => {
+                for child in children {
+                    child.collect_predicates(out);
+                }
+            }
+        }
+    }
+
+    /// Returns the constant value of this tree, if it's a constant.
+    pub(crate) fn as_const(&self) -> Option<bool> {
+        match self {
+            ExprTree::Const(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// The maximum number of symbolic predicates [`check_all`] will enumerate assignments for.
+const MAX_SYMBOLIC: usize = 16;
+
+/// Checks whether `check(a, b)` holds for the values of `a` and `b` on every builtin triple, and
+/// for every assignment of values to symbolic predicates.
+///
+/// Assignments to target features respect the implications between them on each triple (e.g. on
+/// x86_64, `avx2` can't be enabled without `sse2`), so that the result agrees with
+/// [`TargetSpecExpression::eval`](crate::TargetSpecExpression::eval).
+///
+/// Returns `Some(false)` if a counterexample was found, and `Some(true)` if there are none. Returns
+/// `None` if no counterexample was found, but there were too many symbolic predicates (more than
+/// [`MAX_SYMBOLIC`]) to consider at once to be sure.
+pub(crate) fn check_all(
+    a: &ExprTree,
+    b: &ExprTree,
+    check: impl Fn(bool, bool) -> bool,
+) -> Option<bool> {
+    let mut predicates = BTreeSet::new();
+    a.collect_predicates(&mut predicates);
+    b.collect_predicates(&mut predicates);
+    let symbolic: BTreeSet<&TreePredicate> = predicates
+        .into_iter()
+        .filter(|pred| pred.is_symbolic())
+        .collect();
+    let features: Vec<&TreePredicate> = symbolic
+        .iter()
+        .copied()
+        .filter(|pred| pred.target_feature().is_some())
+        .collect();
+
+    // Many triples produce the same residuals and feature implications, so deduplicate them.
+    let residuals: BTreeSet<_> = Triple::all_builtins()
+        .map(|triple| {
+            let mut eval = |pred: &TreePredicate| {
+                if symbolic.contains(pred) {
+                    None
+                } else {
+                    pred.eval_triple(&triple)
+                }
+            };
+            // (x, y) means that enabling x also enables y on this triple.
+            let mut implications = Vec::new();
+            for x in &features {
+                let implied = implied_features(&triple, x.target_feature().expect("feature"));
+                for y in &features {
+                    if implied.contains(y.target_feature().expect("feature")) {
+                        implications.push((*x, *y));
+                    }
+                }
+            }
+            (
+                a.substitute(&mut eval),
+                b.substitute(&mut eval),
+                implications,
+            )
+        })
+        .collect();
+
+    let mut undecided = false;
+    for (a, b, implications) in &residuals {
+        let mut atoms = BTreeSet::new();
+        a.collect_predicates(&mut atoms);
+        b.collect_predicates(&mut atoms);
+        if atoms.len() > MAX_SYMBOLIC {
+            undecided = true;
+            continue;
+        }
+        let atoms: Vec<_> = atoms.into_iter().collect();
+        // Implications as bitmasks over atoms. Features that were simplified away don't matter.
+        let implications: Vec<(u32, u32)> = implications
+            .iter()
+            .filter_map(|(x, y)| {
+                let x = atoms.binary_search(x).ok()?;
+                let y = atoms.binary_search(y).ok()?;
+                Some((1 << x, 1 << y))
+            })
+            .collect();
+
+        let counterexample = (0..1u32 << atoms.len()).any(|assignment| {
+            let consistent = implications
+                .iter()
+                .all(|(x, y)| assignment & x == 0 || assignment & y != 0);
+            if !consistent {
+                return false;
+            }
+            let mut eval = |pred: &TreePredicate| {
+                let idx = atoms.binary_search(&pred).expect("atom was collected");
+                Some(assignment & (1 << idx) != 0)
+            };
+            let a = a.substitute(&mut eval).as_const();
+            let b = b.substitute(&mut eval).as_const();
+            !check(
+                a.expect("all atoms are assigned"),
+                b.expect("all atoms are assigned"),
+            )
+        });
+        if counterexample {
+            return Some(false);
+        }
+    }
+
+    (!undecided).then_some(true)
+}
+
+impl Logic for ExprTree {
+    // Expression::eval folds operands into an accumulator that starts at top() or bottom(), popping
+    // them off a stack in reverse order. Inserting each operand at the front preserves the order
+    // operands appear in the postfix form.
+    fn top() -> Self {
+        ExprTree::All(Vec::new())
+    }
+
+    fn bottom() -> Self {
+        ExprTree::Any(Vec::new())
+    }
+
+    fn and(self, other: Self) -> Self {
+        match self {
+            ExprTree::All(mut children) => {
+                children.insert(0, other);
+                ExprTree::All(children)
+            }
+            _ => unreachable!("and() is only called with an all() accumulator"),
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match self {
+            ExprTree::Any(mut children) => {
+                children.insert(0, other);
+                ExprTree::Any(children)
+            }
+            _ => unreachable!("or() is only called with an any() accumulator"),
+        }
+    }
+
+    fn not(self) -> Self {
+        ExprTree::Not(Box::new(self))
+    }
+}
+
+impl fmt::Display for ExprTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let write_list = |f: &mut fmt::Formatter<'_>, name: &str, children: &[ExprTree]| {
+            write!(f, "{name}(")?;
+            for (idx, child) in children.iter().enumerate() {
+                if idx > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{child}")?;
+            }
+            f.write_str(")")
+        };
+
+        match self {
+            ExprTree::Const(true) => f.write_str("all()"),
+            ExprTree::Const(false) => f.write_str("any()"),
+            ExprTree::Pred(pred) => write!(f, "{pred}"),
+            ExprTree::Not(inner) => write!(f, "not({inner})"),
+            ExprTree::All(children) => write_list(f, "all", children),
+            ExprTree::Any(children) => write_list(f, "any", children),
+        }
+    }
+}
+
+/// An owned predicate within an [`ExprTree`].
+///
+/// Predicates are compared by their string representation.
+#[derive(Clone, Debug)]
+pub(crate) struct TreePredicate {
+    kind: TreePredicateKind,
+    repr: String,
+}
+
+#[derive(Clone, Debug)]
+enum TreePredicateKind {
+    Target(TargetPredicate),
+    Test,
+    DebugAssertions,
+    ProcMacro,
+    Feature(String),
+    TargetFeature(String),
+    Flag(String),
+    KeyValue {
+        key: String,
+        val: String,
+    },
+    /// A plain triple string, as in [`TargetSpec::PlainString`](crate::TargetSpec::PlainString).
+    /// This is never part of a `cfg()` expression.
+    Triple(String),
+}
+
+impl TreePredicate {
+    fn new(pred: &Predicate<'_>) -> Self {
+        let kind = match pred {
+            Predicate::Target(target) => TreePredicateKind::Target(target.clone()),
+            Predicate::Test => TreePredicateKind::Test,
+            Predicate::DebugAssertions => TreePredicateKind::DebugAssertions,
+            Predicate::ProcMacro => TreePredicateKind::ProcMacro,
+            Predicate::Feature(feature) => TreePredicateKind::Feature((*feature).to_owned()),
+            Predicate::TargetFeature(feature) => {
+                TreePredicateKind::TargetFeature((*feature).to_owned())
+            }
+            Predicate::Flag(flag) => TreePredicateKind::Flag((*flag).to_owned()),
+            Predicate::KeyValue { key, val } => TreePredicateKind::KeyValue {
+                key: (*key).to_owned(),
+                val: (*val).to_owned(),
+            },
+        };
+        let repr = kind.to_string();
+        Self { kind, repr }
+    }
+
+    /// Creates a predicate that matches a plain triple string.
+    pub(crate) fn triple(triple_str: &str) -> Self {
+        let kind = TreePredicateKind::Triple(triple_str.to_owned());
+        let repr = kind.to_string();
+        Self { kind, repr }
+    }
+
+    /// Returns true if this predicate is treated as a free variable by [`check_all`].
+    ///
+    /// Flags, key-value flags and target features are symbolic, as are target predicates and
+    /// triple strings that don't match any builtin platform (typically indicating a custom
+    /// platform).
+    fn is_symbolic(&self) -> bool {
+        match &self.kind {
+            TreePredicateKind::Target(target) => {
+                !Triple::all_builtins().any(|triple| triple.matches(target))
+            }
+            TreePredicateKind::Triple(triple_str) => {
+                get_builtin_target_by_triple(triple_str).is_none()
+            }
+            TreePredicateKind::TargetFeature(_)
+            | TreePredicateKind::Flag(_)
+            | TreePredicateKind::KeyValue { .. } => true,
+            TreePredicateKind::Test
+            | TreePredicateKind::DebugAssertions
+            | TreePredicateKind::ProcMacro
+            | TreePredicateKind::Feature(_) => false,
+        }
+    }
+
+    /// Returns the feature name if this is a `target_feature` predicate.
+    fn target_feature(&self) -> Option<&str> {
+        match &self.kind {
+            TreePredicateKind::TargetFeature(feature) => Some(feature),
+            _ => None,
+        }
+    }
+
+    /// Evaluates this predicate against a platform, in the same manner as
+    /// [`TargetSpecExpression::eval`](crate::TargetSpecExpression::eval).
+    pub(crate) fn eval_platform(&self, platform: &Platform, flag_eval: FlagEval) -> Option<bool> {
+        match &self.kind {
+            TreePredicateKind::TargetFeature(feature) => platform
+                .target_features()
+                .matches_for_triple(platform.triple(), feature),
+            TreePredicateKind::Flag(flag) => match flag_eval {
+                _ if platform.has_flag(flag) => Some(true),
+                FlagEval::Known => Some(false),
+                FlagEval::Unknown => None,
+            },
+            TreePredicateKind::KeyValue { key, val } => match flag_eval {
+                _ if platform.has_key_value_flag(key, val) => Some(true),
+                FlagEval::Known => Some(false),
+                FlagEval::Unknown => None,
+            },
+            _ => self.eval_triple(platform.triple()),
+        }
+    }
+
+    /// Evaluates this predicate against a triple, returning `None` for predicates that don't only
+    /// depend on the triple.
+    pub(crate) fn eval_triple(&self, triple: &Triple) -> Option<bool> {
+        match &self.kind {
+            TreePredicateKind::Target(target) => Some(triple.matches(target)),
+            TreePredicateKind::Test
+            | TreePredicateKind::DebugAssertions
+            | TreePredicateKind::ProcMacro
+            | TreePredicateKind::Feature(_) => {
+                // These are always evaluated to false by Cargo. See TargetSpecExpression::eval.
+                Some(false)
+            }
+            TreePredicateKind::Triple(triple_str) => Some(triple.as_str() == triple_str),
+            TreePredicateKind::TargetFeature(_)
+            | TreePredicateKind::Flag(_)
+            | TreePredicateKind::KeyValue { .. } => None,
+        }
+    }
+}
+
+impl PartialEq for TreePredicate {
+    fn eq(&self, other: &Self) -> bool {
+        self.repr == other.repr
+    }
+}
+
+impl Eq for TreePredicate {}
+
+impl PartialOrd for TreePredicate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TreePredicate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.repr.cmp(&other.repr)
+    }
+}
+
+impl hash::Hash for TreePredicate {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.repr.hash(state);
+    }
+}
+
+impl fmt::Display for TreePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.repr)
+    }
+}
+
+impl fmt::Display for TreePredicateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TreePredicateKind::Target(target) => match target {
+                TargetPredicate::Abi(abi) => write!(f, "target_abi = \"{abi}\""),
+                TargetPredicate::Arch(arch) => write!(f, "target_arch = \"{arch}\""),
+                TargetPredicate::Endian(endian) => {
+                    let endian = match endian {
+                        Endian::big => "big",
+                        Endian::little => "little",
+                    };
+                    write!(f, "target_endian = \"{endian}\"")
+                }
+                TargetPredicate::Env(env) => write!(f, "target_env = \"{env}\""),
+                // unix and windows are usually written in their shorthand forms.
+                TargetPredicate::Family(family)
+                    if family.as_str() == "unix" || family.as_str() == "windows" =>
+                {
+                    f.write_str(family.as_str())
+                }
+                TargetPredicate::Family(family) => write!(f, "target_family = \"{family}\""),
+                TargetPredicate::HasAtomic(has_atomic) => {
+                    write!(f, "target_has_atomic = \"{has_atomic}\"")
+                }
+                TargetPredicate::Os(os) => write!(f, "target_os = \"{os}\""),
+                TargetPredicate::Panic(panic) => write!(f, "panic = \"{panic}\""),
+                TargetPredicate::PointerWidth(width) => {
+                    write!(f, "target_pointer_width = \"{width}\"")
+                }
+                TargetPredicate::Vendor(vendor) => write!(f, "target_vendor = \"{vendor}\""),
+            },
+            TreePredicateKind::Test => f.write_str("test"),
+            TreePredicateKind::DebugAssertions => f.write_str("debug_assertions"),
+            TreePredicateKind::ProcMacro => f.write_str("proc_macro"),
+            TreePredicateKind::Feature(feature) => write!(f, "feature = \"{feature}\""),
+            TreePredicateKind::TargetFeature(feature) => {
+                write!(f, "target_feature = \"{feature}\"")
+            }
+            TreePredicateKind::Flag(flag) => f.write_str(flag),
+            TreePredicateKind::KeyValue { key, val } => write!(f, "{key} = \"{val}\""),
+            // A quoted string is never a valid cfg predicate, so this can't be confused with one.
+            TreePredicateKind::Triple(triple_str) => write!(f, "\"{triple_str}\""),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(input: &str) -> ExprTree {
+        ExprTree::new(&Expression::parse(input).expect("valid expression"))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let inputs = [
+            "unix",
+            "all()",
+            "any()",
+            "not(windows)",
+            "all(any(target_arch = \"x86_64\", target_arch = \"aarch64\"), target_os = \"linux\")",
+            "any(target_feature = \"sse2\", foo, bar = \"baz\", feature = \"std\")",
+            "all(target_has_atomic = \"ptr\", target_pointer_width = \"64\", panic = \"abort\")",
+            "any(all(), target_family = \"wasm\", target_endian = \"big\")",
+        ];
+        for input in inputs {
+            assert_eq!(tree(input).to_string(), input, "input roundtrips");
+        }
+    }
+
+    #[test]
+    fn simplify() {
+        let cases = [
+            ("not(not(unix))", "unix"),
+            ("all(unix)", "unix"),
+            ("all(all(unix, foo), any(bar))", "all(unix, foo, bar)"),
+            (
+                "any(any(unix, foo), all(bar, baz))",
+                "any(unix, foo, all(bar, baz))",
+            ),
+            ("all(any(), unix)", "any()"),
+            ("any(all(), unix)", "all()"),
+            ("all(all(), unix)", "unix"),
+            ("not(any())", "all()"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(
+                tree(input).simplify().to_string(),
+                expected,
+                "{input} simplified correctly"
+            );
+        }
+    }
+
+    #[test]
+    fn canonicalize() {
+        let cases = [
+            ("any(unix)", "unix"),
+            ("not(not(windows))", "windows"),
+            (
+                "all(unix, target_os = \"linux\")",
+                "all(target_os = \"linux\", unix)",
+            ),
+            ("all(unix, unix, foo)", "all(foo, unix)"),
+            ("any(all(foo, bar), all(bar, foo))", "all(bar, foo)"),
+            ("all(unix, any(unix, foo))", "unix"),
+            ("any(foo, all(bar, foo))", "foo"),
+            ("all(foo, not(foo))", "any()"),
+            ("any(foo, not(foo))", "all()"),
+            // Subexpressions that are always false on builtin targets are kept as is.
+            ("all(windows, unix)", "all(unix, windows)"),
+            (
+                "any(test, target_os = \"linux\")",
+                "any(target_os = \"linux\", test)",
+            ),
+            ("not(any(unix, not(unix)))", "any()"),
+            (
+                "all(any(b, a), not(all(d, c)), all(any(e), f))",
+                "all(e, f, not(all(c, d)), any(a, b))",
+            ),
+        ];
+        for (input, expected) in cases {
+            let canonical = tree(input).canonicalize();
+            assert_eq!(canonical.to_string(), expected, "{input} canonicalized");
+            assert_eq!(
+                tree(expected).canonicalize(),
+                canonical,
+                "canonicalizing {expected} is idempotent"
+            );
+        }
+    }
+}
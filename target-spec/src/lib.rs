@@ -40,11 +40,14 @@
 //!
 //! * **`custom-cfg`**: Adds support for custom targets via
 //!   `rustc --print=cfg` output, through
-//!   [`Platform::new_custom_cfg`].
+//!   [`Platform::new_custom_cfg`]. Also adds [`PlatformRegistry`], which loads platforms
+//!   captured from a specific toolchain and looks them up ahead of the builtin target table.
 //! * **`custom`**: Adds support for [custom
 //!   targets](https://docs.rust-embedded.org/embedonomicon/custom-target.html) via
 //!   target JSON, through [`Platform::new_custom`]. Implies
 //!   `custom-cfg`.
+//! * **`rustflags`**: Adds support for deriving flags from `RUSTFLAGS` and Cargo configuration,
+//!   through [`RustFlags`] and [`Platform::new_with_rustflags`].
 //! * **`summaries`**: Adds the [`summaries`] module to enable serialization of [`Platform`] and
 //!   [`TargetFeatures`].
 //! * **`proptest1`**: Enables support for property-based testing of [`Platform`] and
@@ -77,17 +80,29 @@ mod custom;
 #[cfg(feature = "custom-cfg")]
 mod custom_cfg;
 pub mod errors;
+mod expr_tree;
+mod lint;
 mod platform;
 #[cfg(feature = "proptest1")]
 mod proptest_helpers;
+#[cfg(feature = "custom-cfg")]
+mod registry;
+#[cfg(feature = "rustflags")]
+mod rustflags;
 mod simple_eval;
 mod spec;
 #[cfg(feature = "summaries")]
 pub mod summaries;
+mod target_features;
 mod triple;
 
 pub use errors::Error;
+pub use lint::*;
 pub use platform::*;
+#[cfg(feature = "custom-cfg")]
+pub use registry::*;
+#[cfg(feature = "rustflags")]
+pub use rustflags::*;
 pub use simple_eval::*;
 pub use spec::*;
 pub use triple::*;
@@ -0,0 +1,464 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{TargetSpec, TargetSpecExpression};
+use cfg_expr::{
+    expr::lexer::{Lexer, Token},
+    targets::ALL_BUILTINS,
+};
+use std::{collections::BTreeSet, fmt, ops::Range};
+
+/// Keys other than `target_*` that rustc or Cargo set.
+static KNOWN_KEYS: &[&str] = &[
+    "feature",
+    "fmt_debug",
+    "panic",
+    "relocation_model",
+    "sanitize",
+    "target_abi",
+    "target_arch",
+    "target_endian",
+    "target_env",
+    "target_family",
+    "target_feature",
+    "target_has_atomic",
+    "target_os",
+    "target_pointer_width",
+    "target_vendor",
+];
+
+/// Flags that rustc, Cargo or rustdoc set.
+static KNOWN_FLAGS: &[&str] = &[
+    "debug_assertions",
+    "doc",
+    "docsrs",
+    "doctest",
+    "miri",
+    "overflow_checks",
+    "proc_macro",
+    "target_thread_local",
+    "test",
+    "ub_checks",
+    "unix",
+    "windows",
+];
+
+impl TargetSpec {
+    /// Checks this specification for likely mistakes.
+    ///
+    /// Plain strings are not checked. For expressions, see [`TargetSpecExpression::lint`].
+    pub fn lint(&self) -> Vec<ExpressionLint> {
+        match self {
+            TargetSpec::PlainString(_) => Vec::new(),
+            TargetSpec::Expression(expr) => expr.lint(),
+        }
+    }
+}
+
+impl TargetSpecExpression {
+    /// Checks this expression for likely mistakes, such as misspelled values.
+    ///
+    /// Such expressions are valid, but typically evaluate to false on every platform. This reports:
+    ///
+    /// * `target_os`, `target_arch`, `target_env`, `target_vendor` and `target_family` values that
+    ///   don't match any builtin platform known to `target-spec`;
+    /// * keys unknown to rustc and Cargo, such as `target_oss`. (Keys set through `--cfg` are
+    ///   also reported, and can be ignored.)
+    /// * flags that are likely misspellings of flags set by rustc or Cargo, such as `unx`.
+    ///
+    /// Where possible, the closest known value is suggested.
+    ///
+    /// Values specific to custom platforms are also reported, so callers that use custom platforms
+    /// may wish to filter results.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpecExpression;
+    ///
+    /// let expr = TargetSpecExpression::new(r#"cfg(any(target_os = "linuz", unx))"#).unwrap();
+    /// let lints = expr.lint();
+    /// assert_eq!(lints.len(), 2);
+    /// assert_eq!(lints[0].to_string(), "unknown `target_os` value `linuz`");
+    /// assert_eq!(lints[0].suggestion(), Some("linux"));
+    /// assert_eq!(&lints[0].input[lints[0].span.clone()], "\"linuz\"");
+    /// assert_eq!(lints[1].suggestion(), Some("unix"));
+    /// ```
+    pub fn lint(&self) -> Vec<ExpressionLint> {
+        let original = self.expression_str();
+        let input = if original.starts_with("cfg(") && original.ends_with(')') {
+            original.to_owned()
+        } else {
+            format!("cfg({original})")
+        };
+        // Spans returned by the lexer are relative to the string inside `cfg()`.
+        let offset = 4;
+        let shift = |span: Range<usize>| (span.start + offset)..(span.end + offset);
+
+        let tokens: Vec<_> = Lexer::new(&input)
+            .map(|token| token.expect("expression was already parsed successfully"))
+            .collect();
+        let known_values = KnownValues::new();
+
+        let mut lints = Vec::new();
+        let mut idx = 0;
+        while idx < tokens.len() {
+            let Token::Key(key) = tokens[idx].token else {
+                idx += 1;
+                continue;
+            };
+            let key_span = shift(tokens[idx].span.clone());
+
+            let value = match (tokens.get(idx + 1), tokens.get(idx + 2)) {
+                (Some(equals), Some(value)) if equals.token == Token::Equals => match value.token {
+                    Token::Value(value_str) => Some((value_str, shift(value.span.clone()))),
+                    _ => None,
+                },
+                _ => None,
+            };
+
+            let kind = match value {
+                Some((value, value_span)) => {
+                    idx += 3;
+                    if let Some(values) = known_values.for_key(key) {
+                        (!values.contains(value)).then(|| {
+                            let suggestion = closest(value, values.iter().copied());
+                            (
+                                value_span,
+                                ExpressionLintKind::UnknownValue {
+                                    key: key.to_owned(),
+                                    value: value.to_owned(),
+                                    suggestion,
+                                },
+                            )
+                        })
+                    } else if KNOWN_KEYS.contains(&key) {
+                        None
+                    } else {
+                        Some((
+                            key_span,
+                            ExpressionLintKind::UnknownKey {
+                                key: key.to_owned(),
+                                suggestion: closest(key, KNOWN_KEYS.iter().copied()),
+                            },
+                        ))
+                    }
+                }
+                None => {
+                    idx += 1;
+                    if KNOWN_FLAGS.contains(&key) {
+                        None
+                    } else {
+                        // Arbitrary flags can be set through --cfg, so only report flags that
+                        // look like misspellings of known ones.
+                        closest(key, KNOWN_FLAGS.iter().copied()).map(|suggestion| {
+                            (
+                                key_span,
+                                ExpressionLintKind::MisspelledFlag {
+                                    flag: key.to_owned(),
+                                    suggestion,
+                                },
+                            )
+                        })
+                    }
+                }
+            };
+
+            if let Some((span, kind)) = kind {
+                lints.push(ExpressionLint {
+                    input: input.clone(),
+                    span,
+                    kind,
+                });
+            }
+        }
+
+        lints
+    }
+}
+
+/// A likely mistake in a `cfg()` expression, returned by [`TargetSpecExpression::lint`].
+///
+/// To pretty-print lints, consider using
+/// [target-spec-miette](https://crates.io/crates/target-spec-miette).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExpressionLint {
+    /// The expression that was checked, in `cfg()` form.
+    pub input: String,
+
+    /// The range of characters in `input` that this lint applies to.
+    pub span: Range<usize>,
+
+    /// The kind of lint.
+    pub kind: ExpressionLintKind,
+}
+
+impl ExpressionLint {
+    /// Returns the closest known replacement, if any.
+    pub fn suggestion(&self) -> Option<&str> {
+        match &self.kind {
+            ExpressionLintKind::UnknownValue { suggestion, .. }
+            | ExpressionLintKind::UnknownKey { suggestion, .. } => suggestion.as_deref(),
+            ExpressionLintKind::MisspelledFlag { suggestion, .. } => Some(suggestion),
+        }
+    }
+}
+
+impl fmt::Display for ExpressionLint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            ExpressionLintKind::UnknownValue { key, value, .. } => {
+                write!(f, "unknown `{key}` value `{value}`")
+            }
+            ExpressionLintKind::UnknownKey { key, .. } => write!(f, "unknown key `{key}`"),
+            ExpressionLintKind::MisspelledFlag { flag, .. } => {
+                write!(f, "unknown flag `{flag}`")
+            }
+        }
+    }
+}
+
+/// The kind of [`ExpressionLint`] found.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpressionLintKind {
+    /// A `target_os`, `target_arch`, `target_env`, `target_vendor` or `target_family` value that
+    /// doesn't match any builtin platform.
+    UnknownValue {
+        /// The key, e.g. `target_os`.
+        key: String,
+
+        /// The value.
+        value: String,
+
+        /// The closest known value, if any.
+        suggestion: Option<String>,
+    },
+
+    /// A key that isn't set by rustc or Cargo.
+    UnknownKey {
+        /// The key.
+        key: String,
+
+        /// The closest known key, if any.
+        suggestion: Option<String>,
+    },
+
+    /// A flag that is close to, but not the same as, a flag set by rustc or Cargo.
+    MisspelledFlag {
+        /// The flag.
+        flag: String,
+
+        /// The known flag it's close to.
+        suggestion: String,
+    },
+}
+
+impl fmt::Display for ExpressionLintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionLintKind::UnknownValue { key, .. } => {
+                write!(f, "no builtin platform has this `{key}`")
+            }
+            ExpressionLintKind::UnknownKey { .. } => f.write_str("not set by rustc or Cargo"),
+            ExpressionLintKind::MisspelledFlag { .. } => {
+                f.write_str("similar to a flag set by rustc or Cargo")
+            }
+        }
+    }
+}
+
+/// Values for target predicates across all builtin platforms.
+struct KnownValues {
+    os: BTreeSet<&'static str>,
+    arch: BTreeSet<&'static str>,
+    env: BTreeSet<&'static str>,
+    vendor: BTreeSet<&'static str>,
+    family: BTreeSet<&'static str>,
+}
+
+impl KnownValues {
+    fn new() -> Self {
+        // Platforms without an OS, environment or vendor have these values.
+        let mut values = Self {
+            os: ["none"].into_iter().collect(),
+            arch: BTreeSet::new(),
+            env: [""].into_iter().collect(),
+            vendor: ["unknown"].into_iter().collect(),
+            family: BTreeSet::new(),
+        };
+        for info in ALL_BUILTINS {
+            values.arch.insert(info.arch.as_str());
+            values.os.extend(info.os.as_ref().map(|os| os.as_str()));
+            values.env.extend(info.env.as_ref().map(|env| env.as_str()));
+            values
+                .vendor
+                .extend(info.vendor.as_ref().map(|vendor| vendor.as_str()));
+            values
+                .family
+                .extend(info.families.iter().map(|family| family.as_str()));
+        }
+        values
+    }
+
+    fn for_key(&self, key: &str) -> Option<&BTreeSet<&'static str>> {
+        match key {
+            "target_os" => Some(&self.os),
+            "target_arch" => Some(&self.arch),
+            "target_env" => Some(&self.env),
+            "target_vendor" => Some(&self.vendor),
+            "target_family" => Some(&self.family),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the candidate closest to `input`, if it's close enough to be a likely misspelling.
+fn closest<'a>(input: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let input_lower = input.to_lowercase();
+    let max_distance = (input.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| !candidate.is_empty() && *candidate != input)
+        .map(|candidate| (edit_distance(&input_lower, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min()
+        .map(|(_, candidate)| candidate.to_owned())
+}
+
+/// Returns the edit distance between `a` and `b`, counting insertions, deletions, substitutions
+/// and transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // distances[i][j] is the distance between the first i characters of a and the first j
+    // characters of b.
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lints() {
+        // Each case is an input, along with the spans, messages and suggestions of lints.
+        type Case<'a> = (&'a str, &'a [(&'a str, &'a str, Option<&'a str>)]);
+        let cases: &[Case<'_>] = &[
+            ("cfg(all(unix, target_os = \"linux\", tokio_unstable))", &[]),
+            (
+                "cfg(target_os = \"linuz\")",
+                &[(
+                    "\"linuz\"",
+                    "unknown `target_os` value `linuz`",
+                    Some("linux"),
+                )],
+            ),
+            (
+                "cfg(target_arch = \"x86-64\")",
+                &[(
+                    "\"x86-64\"",
+                    "unknown `target_arch` value `x86-64`",
+                    Some("x86_64"),
+                )],
+            ),
+            (
+                "cfg(not(target_os = \"Windows\"))",
+                &[(
+                    "\"Windows\"",
+                    "unknown `target_os` value `Windows`",
+                    Some("windows"),
+                )],
+            ),
+            (
+                "cfg(any(target_env = \"gnux\", target_vendor = \"aple\", target_family = \"unx\"))",
+                &[
+                    ("\"gnux\"", "unknown `target_env` value `gnux`", Some("gnu")),
+                    (
+                        "\"aple\"",
+                        "unknown `target_vendor` value `aple`",
+                        Some("apple"),
+                    ),
+                    (
+                        "\"unx\"",
+                        "unknown `target_family` value `unx`",
+                        Some("unix"),
+                    ),
+                ],
+            ),
+            (
+                "cfg(target_os = \"my-custom-os\")",
+                &[(
+                    "\"my-custom-os\"",
+                    "unknown `target_os` value `my-custom-os`",
+                    None,
+                )],
+            ),
+            (
+                "cfg(all(pnaic = \"abort\", unx, debug_assertion))",
+                &[
+                    ("pnaic", "unknown key `pnaic`", Some("panic")),
+                    ("unx", "unknown flag `unx`", Some("unix")),
+                    (
+                        "debug_assertion",
+                        "unknown flag `debug_assertion`",
+                        Some("debug_assertions"),
+                    ),
+                ],
+            ),
+            (
+                "cfg(all(target_abi = \"eabihf\", panic = \"abort\", target_feature = \"sse2\"))",
+                &[],
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let expr = TargetSpecExpression::new(input).expect("valid expression");
+            let lints = expr.lint();
+            let actual: Vec<_> = lints
+                .iter()
+                .map(|lint| {
+                    (
+                        &lint.input[lint.span.clone()],
+                        lint.to_string(),
+                        lint.suggestion(),
+                    )
+                })
+                .collect();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|(span, message, suggestion)| (*span, message.to_string(), *suggestion))
+                .collect();
+            assert_eq!(actual, expected, "lints for {input}");
+        }
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("linux", "linux"), 0);
+        assert_eq!(edit_distance("linuz", "linux"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("pnaic", "panic"), 1);
+    }
+}
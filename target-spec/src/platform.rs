@@ -2,7 +2,11 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{Error, Triple};
-use std::{borrow::Cow, collections::BTreeSet, ops::Deref};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    ops::Deref,
+};
 
 // This is generated by the build script.
 include!(concat!(env!("OUT_DIR"), "/build_target.rs"));
@@ -36,6 +40,7 @@ pub struct Platform {
     triple: Triple,
     target_features: TargetFeatures,
     flags: BTreeSet<Cow<'static, str>>,
+    key_value_flags: BTreeMap<Cow<'static, str>, BTreeSet<Cow<'static, str>>>,
 }
 
 impl Platform {
@@ -110,6 +115,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            key_value_flags: BTreeMap::new(),
         })
     }
 
@@ -119,6 +125,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            key_value_flags: BTreeMap::new(),
         }
     }
 
@@ -134,6 +141,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            key_value_flags: BTreeMap::new(),
         })
     }
 
@@ -151,6 +159,7 @@ impl Platform {
             triple,
             target_features,
             flags: BTreeSet::new(),
+            key_value_flags: BTreeMap::new(),
         })
     }
 
@@ -165,6 +174,39 @@ impl Platform {
         self.flags.extend(flags.into_iter().map(|s| s.into()));
     }
 
+    /// Adds a set of key-value flags to accept.
+    ///
+    /// A key-value flag is a pair like the `foo = "bar"` in `cfg(foo = "bar")`, and is typically
+    /// set by passing `--cfg 'foo="bar"'` to rustc. A key may be set to several values at once,
+    /// in which case `cfg(key = "value")` is true for each of them.
+    ///
+    /// A default `cargo build` will always evaluate key-value flags to false.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{Platform, TargetFeatures, TargetSpec};
+    ///
+    /// let mut platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+    /// platform.add_key_value_flags([("foo", "bar")]);
+    ///
+    /// let spec = TargetSpec::new(r#"cfg(foo = "bar")"#).unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(true));
+    /// let spec = TargetSpec::new(r#"cfg(foo = "baz")"#).unwrap();
+    /// assert_eq!(spec.eval(&platform), Some(false));
+    /// ```
+    pub fn add_key_value_flags(
+        &mut self,
+        flags: impl IntoIterator<Item = (impl Into<Cow<'static, str>>, impl Into<Cow<'static, str>>)>,
+    ) {
+        for (key, value) in flags {
+            self.key_value_flags
+                .entry(key.into())
+                .or_default()
+                .insert(value.into());
+        }
+    }
+
     /// Returns the target triple string for this platform.
     pub fn triple_str(&self) -> &str {
         self.triple.as_str()
@@ -180,6 +222,20 @@ impl Platform {
         self.flags.contains(flag.as_ref())
     }
 
+    /// Returns the set of key-value flags enabled for this platform, in sorted order.
+    pub fn key_value_flags(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.key_value_flags
+            .iter()
+            .flat_map(|(key, values)| values.iter().map(move |value| (key.deref(), value.deref())))
+    }
+
+    /// Returns true if this key-value flag was set with `add_key_value_flags`.
+    pub fn has_key_value_flag(&self, key: impl AsRef<str>, value: impl AsRef<str>) -> bool {
+        self.key_value_flags
+            .get(key.as_ref())
+            .is_some_and(|values| values.contains(value.as_ref()))
+    }
+
     /// Returns true if this is a standard platform.
     ///
     /// A standard platform can be either builtin, or heuristically determined.
@@ -288,6 +344,9 @@ impl TargetFeatures {
 
     /// Returns `Some(true)` if this feature is a match, `Some(false)` if it isn't, and `None` if
     /// the set of target features is unknown.
+    ///
+    /// This doesn't account for features implied by other features. For that, use
+    /// [`Self::matches_for_triple`].
     pub fn matches(&self, feature: &str) -> Option<bool> {
         match self {
             TargetFeatures::Unknown => None,
@@ -0,0 +1,388 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A registry of platforms captured from a specific Rust toolchain.
+//!
+//! `target-spec` ships with a table of builtin targets that corresponds to a particular Rust
+//! release. Toolchains that are newer (or older) than that release may have targets that aren't
+//! in the table, or whose properties differ. A [`PlatformRegistry`] holds target information
+//! captured from a toolchain, and lookups through it take precedence over the builtin table.
+
+use crate::{
+    Error, Platform, TargetFeatures, Triple,
+    errors::{CustomTripleCreateError, PlatformRegistryError, TripleParseError},
+};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+};
+
+/// A registry of platforms, typically captured from a specific Rust toolchain.
+///
+/// Lookups through the registry return registered platforms if present, and fall back to
+/// `target-spec`'s builtin table otherwise.
+///
+/// The registry is only consulted by its own methods, such as [`Self::platform`]. Other APIs,
+/// such as [`Platform::new`] and [`TargetSpec::new`](crate::TargetSpec::new), continue to use the
+/// builtin table. To evaluate specs against a registered platform, create the platform through
+/// the registry and pass it into [`TargetSpec::eval`](crate::TargetSpec::eval).
+///
+/// # Snapshot directories
+///
+/// A registry can be loaded from a *snapshot directory* through [`Self::from_snapshot_dir`]. A
+/// snapshot directory contains, for each target, one or both of:
+///
+/// * `<triple>.cfg`: the output of `rustc --print cfg --target <triple>`.
+/// * `<triple>.json`: the output of
+///   `rustc -Z unstable-options --print target-spec-json --target <triple>` (this requires a
+///   nightly toolchain). These files are only loaded if the `custom` feature is enabled, and are
+///   ignored otherwise.
+///
+/// If both are present, the `.cfg` file is used, since it is exactly what rustc evaluates `cfg()`
+/// expressions against, and also lists the target features enabled by default.
+///
+/// A snapshot directory can be captured with a script like:
+///
+/// ```sh
+/// for target in $(rustc --print target-list); do
+///     rustc --print cfg --target "$target" > "snapshot/$target.cfg"
+/// done
+/// ```
+///
+/// Requires the `custom-cfg` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{PlatformRegistry, TargetFeatures, TargetSpec};
+///
+/// let mut registry = PlatformRegistry::new();
+/// registry
+///     .add_cfg(
+///         "x86_64-acme-linux-gnu",
+///         r#"panic="unwind"
+/// target_arch="x86_64"
+/// target_endian="little"
+/// target_env="gnu"
+/// target_family="unix"
+/// target_feature="sse2"
+/// target_os="linux"
+/// target_pointer_width="64"
+/// target_vendor="acme"
+/// "#,
+///     )
+///     .unwrap();
+///
+/// // Registered platforms are returned from the registry, along with their default features.
+/// let platform = registry.platform_with_default_features("x86_64-acme-linux-gnu").unwrap();
+/// let spec = TargetSpec::new(r#"cfg(all(target_vendor = "acme", target_feature = "sse2"))"#).unwrap();
+/// assert_eq!(spec.eval(&platform), Some(true));
+///
+/// // Other platforms fall back to the builtin table.
+/// let platform = registry.platform("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+/// assert!(platform.is_standard());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PlatformRegistry {
+    entries: BTreeMap<String, RegistryEntry>,
+}
+
+#[derive(Clone, Debug)]
+struct RegistryEntry {
+    triple: Triple,
+    // Present if the entry was created from `rustc --print cfg` output.
+    default_features: Option<BTreeSet<String>>,
+}
+
+impl PlatformRegistry {
+    /// Creates a new, empty registry.
+    ///
+    /// An empty registry behaves identically to the builtin table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads a registry from a snapshot directory.
+    ///
+    /// Files other than `*.cfg` and `*.json` are ignored, as are `*.json` files if the `custom`
+    /// feature is not enabled. For more about the format of a snapshot directory, see the
+    /// [type-level documentation](Self).
+    ///
+    /// Returns an error if the directory could not be read, or if any of the files in it could
+    /// not be read or parsed.
+    pub fn from_snapshot_dir(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref();
+        let read_dir_err = |error| {
+            Error::PlatformRegistry(PlatformRegistryError::new(dir.to_owned(), Arc::new(error)))
+        };
+
+        // Gather triples along with their files, then load them in sorted order so that errors
+        // are deterministic.
+        let mut cfg_files = BTreeMap::new();
+        #[cfg(feature = "custom")]
+        let mut json_files = BTreeMap::new();
+        for entry in std::fs::read_dir(dir).map_err(read_dir_err)? {
+            let path = entry.map_err(read_dir_err)?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let (Some(triple), Some(extension)) = (
+                path.file_stem().and_then(|stem| stem.to_str()),
+                path.extension().and_then(|ext| ext.to_str()),
+            ) else {
+                continue;
+            };
+            match extension {
+                "cfg" => {
+                    cfg_files.insert(triple.to_owned(), path);
+                }
+                #[cfg(feature = "custom")]
+                "json" => {
+                    json_files.insert(triple.to_owned(), path);
+                }
+                _ => {}
+            }
+        }
+
+        let mut registry = Self::new();
+        for (triple, path) in &cfg_files {
+            let cfg_text = read_file(triple, path)?;
+            registry.add_cfg(triple.clone(), &cfg_text)?;
+        }
+        #[cfg(feature = "custom")]
+        for (triple, path) in &json_files {
+            if cfg_files.contains_key(triple) {
+                continue;
+            }
+            let json = read_file(triple, path)?;
+            registry.add_json(triple.clone(), &json)?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Adds a platform from the output of `rustc --print cfg`.
+    ///
+    /// The target features listed in the output are recorded as the platform's default features.
+    /// Any existing entry for the same triple is replaced.
+    pub fn add_cfg(
+        &mut self,
+        triple_str: impl Into<Cow<'static, str>>,
+        cfg_text: &str,
+    ) -> Result<&mut Self, Error> {
+        let triple_str = triple_str.into();
+        let (triple, features) = Triple::new_custom_cfg_with_features(triple_str.clone(), cfg_text)
+            .map_err(Error::CustomPlatformCreate)?;
+        self.entries.insert(
+            triple_str.into_owned(),
+            RegistryEntry {
+                triple,
+                default_features: Some(features),
+            },
+        );
+        Ok(self)
+    }
+
+    /// Adds a platform from target JSON, such as the output of `rustc --print target-spec-json`.
+    ///
+    /// Any existing entry for the same triple is replaced.
+    ///
+    /// Requires the `custom` feature to be enabled.
+    #[cfg(feature = "custom")]
+    pub fn add_json(
+        &mut self,
+        triple_str: impl Into<Cow<'static, str>>,
+        json: &str,
+    ) -> Result<&mut Self, Error> {
+        let triple_str = triple_str.into();
+        let triple =
+            Triple::new_custom(triple_str.clone(), json).map_err(Error::CustomPlatformCreate)?;
+        self.entries.insert(
+            triple_str.into_owned(),
+            RegistryEntry {
+                triple,
+                default_features: None,
+            },
+        );
+        Ok(self)
+    }
+
+    /// Returns the number of platforms in this registry.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true if this registry has no platforms.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns true if this registry contains the given triple.
+    ///
+    /// This does not consult the builtin table.
+    pub fn contains(&self, triple_str: &str) -> bool {
+        self.entries.contains_key(triple_str)
+    }
+
+    /// Returns the triples in this registry, in sorted order.
+    pub fn triples(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
+        self.entries.keys().map(|triple_str| triple_str.as_str())
+    }
+
+    /// Looks up a triple, first in this registry and then in the builtin table.
+    pub fn triple(&self, triple_str: &str) -> Result<Triple, TripleParseError> {
+        match self.entries.get(triple_str) {
+            Some(entry) => Ok(entry.triple.clone()),
+            None => Triple::new(triple_str.to_owned()),
+        }
+    }
+
+    /// Returns the target features enabled by default for a triple in this registry, if known.
+    ///
+    /// Default features are known for platforms added from `rustc --print cfg` output.
+    pub fn default_target_features(&self, triple_str: &str) -> Option<TargetFeatures> {
+        let features = self.entries.get(triple_str)?.default_features.as_ref()?;
+        Some(TargetFeatures::features(
+            features.iter().map(|feature| Cow::Owned(feature.clone())),
+        ))
+    }
+
+    /// Creates a platform for a triple, first looking in this registry and then in the builtin
+    /// table.
+    ///
+    /// Returns an error if the triple is in neither.
+    pub fn platform(
+        &self,
+        triple_str: &str,
+        target_features: TargetFeatures,
+    ) -> Result<Platform, Error> {
+        let triple = self
+            .triple(triple_str)
+            .map_err(Error::UnknownPlatformTriple)?;
+        Ok(Platform::from_triple(triple, target_features))
+    }
+
+    /// Creates a platform for a triple, using the [default target
+    /// features](Self::default_target_features) if known.
+    ///
+    /// If the default target features are not known, [`TargetFeatures::Unknown`] is used.
+    pub fn platform_with_default_features(&self, triple_str: &str) -> Result<Platform, Error> {
+        let target_features = self
+            .default_target_features(triple_str)
+            .unwrap_or(TargetFeatures::Unknown);
+        self.platform(triple_str, target_features)
+    }
+}
+
+fn read_file(triple: &str, path: &Path) -> Result<String, Error> {
+    std::fs::read_to_string(path).map_err(|error| {
+        Error::CustomPlatformCreate(CustomTripleCreateError::ReadFile {
+            triple: triple.to_owned(),
+            path: path.to_string_lossy().into_owned(),
+            error: Arc::new(error),
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TargetSpec;
+
+    static ACME_CFG: &str = indoc::indoc! {r#"
+        debug_assertions
+        panic="abort"
+        target_arch="arm"
+        target_endian="little"
+        target_feature="dsp"
+        target_feature="thumb2"
+        target_has_atomic="32"
+        target_os="none"
+        target_pointer_width="32"
+        target_vendor="acme"
+    "#};
+
+    // Same triple as a builtin, but with a different vendor to tell them apart.
+    static LINUX_OVERRIDE_CFG: &str = indoc::indoc! {r#"
+        panic="unwind"
+        target_arch="x86_64"
+        target_endian="little"
+        target_env="gnu"
+        target_family="unix"
+        target_os="linux"
+        target_pointer_width="64"
+        target_vendor="overridden"
+        unix
+    "#};
+
+    #[test]
+    fn snapshot_dir() {
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let dir = temp_dir.path();
+        std::fs::write(dir.join("thumbv7em-acme-none-eabihf.cfg"), ACME_CFG)
+            .expect("wrote cfg file");
+        std::fs::write(dir.join("x86_64-unknown-linux-gnu.cfg"), LINUX_OVERRIDE_CFG)
+            .expect("wrote cfg file");
+        std::fs::write(dir.join("README.md"), "ignored").expect("wrote readme");
+        // Without the custom feature, JSON files are ignored rather than causing an error.
+        #[cfg(not(feature = "custom"))]
+        std::fs::write(dir.join("json-only-target.json"), "{}").expect("wrote json file");
+
+        let registry = PlatformRegistry::from_snapshot_dir(dir).expect("loaded snapshot dir");
+        assert_eq!(
+            registry.triples().collect::<Vec<_>>(),
+            ["thumbv7em-acme-none-eabihf", "x86_64-unknown-linux-gnu"],
+        );
+
+        // A target that isn't builtin.
+        let platform = registry
+            .platform_with_default_features("thumbv7em-acme-none-eabihf")
+            .expect("acme platform found");
+        let expected_features = TargetFeatures::features(["dsp", "thumb2"]);
+        assert_eq!(platform.target_features(), &expected_features);
+        let spec = TargetSpec::new(
+            r#"cfg(all(target_vendor = "acme", target_feature = "thumb2", not(target_feature = "neon")))"#,
+        )
+        .unwrap();
+        assert_eq!(spec.eval(&platform), Some(true));
+
+        // Lookups through the registry prefer it over the builtin table.
+        let platform = registry
+            .platform("x86_64-unknown-linux-gnu", TargetFeatures::Unknown)
+            .expect("linux platform found");
+        let spec = TargetSpec::new(r#"cfg(target_vendor = "overridden")"#).unwrap();
+        assert_eq!(spec.eval(&platform), Some(true));
+
+        // Other triples fall back to the builtin table.
+        let platform = registry
+            .platform_with_default_features("aarch64-apple-darwin")
+            .expect("builtin platform found");
+        assert!(platform.is_standard(), "builtin platform is standard");
+        assert_eq!(platform.target_features(), &TargetFeatures::Unknown);
+
+        registry
+            .platform("unknown-triple-foo", TargetFeatures::Unknown)
+            .expect_err("unknown triple");
+
+        // An invalid file results in an error.
+        std::fs::write(dir.join("invalid.cfg"), "target_arch=\"arm\"\n").expect("wrote cfg file");
+        let error = PlatformRegistry::from_snapshot_dir(dir).expect_err("invalid cfg file");
+        assert!(
+            matches!(
+                error,
+                Error::CustomPlatformCreate(CustomTripleCreateError::ParseCfg { ref triple, .. })
+                    if triple == "invalid"
+            ),
+            "unexpected error: {error:?}"
+        );
+
+        let error =
+            PlatformRegistry::from_snapshot_dir(dir.join("missing")).expect_err("missing dir");
+        assert!(
+            matches!(error, Error::PlatformRegistry(_)),
+            "unexpected error: {error:?}"
+        );
+    }
+}
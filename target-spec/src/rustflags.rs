@@ -0,0 +1,493 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    Error, Platform, TargetFeatures, TargetSpec,
+    errors::{RustFlagsConfigError, RustFlagsConfigErrorKind},
+};
+use std::{
+    borrow::Cow,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// The flags Cargo passes to rustc when building for a platform.
+///
+/// Cargo determines these flags from, in order of precedence:
+///
+/// 1. the `CARGO_ENCODED_RUSTFLAGS` environment variable;
+/// 2. the `RUSTFLAGS` environment variable;
+/// 3. all `target.<triple>.rustflags` and matching `target.<cfg>.rustflags` entries in Cargo
+///    configuration, along with `CARGO_TARGET_<TRIPLE>_RUSTFLAGS`;
+/// 4. `build.rustflags` in Cargo configuration, along with `CARGO_BUILD_RUSTFLAGS`.
+///
+/// The first of these that is set is used, and the others are ignored.
+///
+/// Requires the `rustflags` feature to be enabled.
+///
+/// # Examples
+///
+/// ```
+/// use target_spec::{Platform, RustFlags, TargetFeatures, TargetSpec};
+///
+/// let rustflags = RustFlags::from_space_separated(r#"--cfg tokio_unstable --cfg foo="bar""#);
+/// let mut platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+/// platform.add_rustflags_cfgs(&rustflags);
+///
+/// let spec = TargetSpec::new(r#"cfg(all(tokio_unstable, foo = "bar"))"#).unwrap();
+/// assert_eq!(spec.eval(&platform), Some(true));
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RustFlags {
+    args: Vec<String>,
+}
+
+impl RustFlags {
+    /// Creates a new `RustFlags` from a list of arguments.
+    pub fn new(args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            args: args.into_iter().map(|arg| arg.into()).collect(),
+        }
+    }
+
+    /// Creates a new `RustFlags` from arguments separated by the ASCII unit separator (`0x1f`),
+    /// as in the `CARGO_ENCODED_RUSTFLAGS` environment variable.
+    pub fn from_encoded(encoded: &str) -> Self {
+        if encoded.is_empty() {
+            return Self::default();
+        }
+        Self::new(encoded.split('\x1f'))
+    }
+
+    /// Creates a new `RustFlags` from whitespace-separated arguments, as in the `RUSTFLAGS`
+    /// environment variable.
+    pub fn from_space_separated(flags: &str) -> Self {
+        Self::new(flags.split_whitespace())
+    }
+
+    /// Determines the flags Cargo would pass to rustc when building for `platform`, from the
+    /// environment and from Cargo configuration files.
+    ///
+    /// Configuration files are looked up the same way Cargo does: in `.cargo/config.toml` (or
+    /// `.cargo/config`) in `cwd` and each of its ancestors, then in `$CARGO_HOME/config.toml`.
+    ///
+    /// `target.<cfg>.rustflags` entries are evaluated against `platform`.
+    pub fn from_env_and_config(platform: &Platform, cwd: impl AsRef<Path>) -> Result<Self, Error> {
+        let cargo_home = std::env::var_os("CARGO_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(".cargo")));
+        let configs = read_configs(cwd.as_ref(), cargo_home.as_deref())?;
+        Self::from_sources(platform, &configs, |name| std::env::var(name).ok())
+    }
+
+    /// Returns the arguments in this set of flags.
+    pub fn args(&self) -> impl ExactSizeIterator<Item = &str> {
+        self.args.iter().map(|arg| arg.as_str())
+    }
+
+    /// Returns the `--cfg` arguments in this set of flags.
+    pub fn cfgs(&self) -> impl Iterator<Item = RustFlagsCfg<'_>> {
+        let mut args = self.args();
+        std::iter::from_fn(move || {
+            loop {
+                let arg = args.next()?;
+                let cfg = if arg == "--cfg" {
+                    args.next()?
+                } else if let Some(cfg) = arg.strip_prefix("--cfg=") {
+                    cfg
+                } else {
+                    continue;
+                };
+                return Some(RustFlagsCfg::parse(cfg));
+            }
+        })
+    }
+
+    fn from_sources(
+        platform: &Platform,
+        configs: &[(PathBuf, toml::Value)],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, Error> {
+        if let Some(encoded) = env("CARGO_ENCODED_RUSTFLAGS") {
+            return Ok(Self::from_encoded(&encoded));
+        }
+        if let Some(flags) = env("RUSTFLAGS") {
+            return Ok(Self::from_space_separated(&flags));
+        }
+
+        // Cargo joins the target.<triple> and all matching target.<cfg> entries together.
+        let mut target_args: Option<Vec<String>> = None;
+        for (path, config) in configs {
+            let Some(targets) = config.get("target").and_then(|target| target.as_table()) else {
+                continue;
+            };
+            for (key, target) in targets {
+                if !target_key_matches(key, platform) {
+                    continue;
+                }
+                if let Some(value) = target.get("rustflags") {
+                    let key = format!("target.{key}.rustflags");
+                    let args = config_args(path, &key, value)?;
+                    target_args.get_or_insert_with(Vec::new).extend(args);
+                }
+            }
+        }
+        let env_name = format!(
+            "CARGO_TARGET_{}_RUSTFLAGS",
+            platform
+                .triple_str()
+                .to_uppercase()
+                .replace(['-', '.'], "_")
+        );
+        if let Some(flags) = env(&env_name) {
+            target_args
+                .get_or_insert_with(Vec::new)
+                .extend(flags.split_whitespace().map(|arg| arg.to_owned()));
+        }
+        if let Some(args) = target_args {
+            return Ok(Self { args });
+        }
+
+        let mut build_args: Option<Vec<String>> = None;
+        for (path, config) in configs {
+            if let Some(value) = config.get("build").and_then(|build| build.get("rustflags")) {
+                let args = config_args(path, "build.rustflags", value)?;
+                build_args.get_or_insert_with(Vec::new).extend(args);
+            }
+        }
+        if let Some(flags) = env("CARGO_BUILD_RUSTFLAGS") {
+            build_args
+                .get_or_insert_with(Vec::new)
+                .extend(flags.split_whitespace().map(|arg| arg.to_owned()));
+        }
+
+        Ok(Self {
+            args: build_args.unwrap_or_default(),
+        })
+    }
+}
+
+/// A `--cfg` argument passed to rustc.
+///
+/// Returned by [`RustFlags::cfgs`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RustFlagsCfg<'a> {
+    /// A flag, e.g. `--cfg tokio_unstable`.
+    Flag(&'a str),
+
+    /// A key-value flag, e.g. `--cfg foo="bar"`.
+    KeyValue {
+        /// The key.
+        key: &'a str,
+
+        /// The value, without surrounding quotes.
+        value: &'a str,
+    },
+}
+
+impl<'a> RustFlagsCfg<'a> {
+    fn parse(cfg: &'a str) -> Self {
+        match cfg.split_once('=') {
+            Some((key, value)) => {
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|value| value.strip_suffix('"'))
+                    .unwrap_or(value);
+                RustFlagsCfg::KeyValue {
+                    key: key.trim(),
+                    value,
+                }
+            }
+            None => RustFlagsCfg::Flag(cfg.trim()),
+        }
+    }
+}
+
+impl Platform {
+    /// Creates a new standard `Platform` from the given triple and target features, with flags and
+    /// key-value flags set from the `--cfg` arguments Cargo would pass to rustc.
+    ///
+    /// The arguments are determined from the environment and from Cargo configuration files
+    /// relative to `cwd`. For more, see [`RustFlags::from_env_and_config`].
+    ///
+    /// Requires the `rustflags` feature to be enabled.
+    pub fn new_with_rustflags(
+        triple_str: impl Into<Cow<'static, str>>,
+        target_features: TargetFeatures,
+        cwd: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        let mut platform = Self::new(triple_str, target_features)?;
+        let rustflags = RustFlags::from_env_and_config(&platform, cwd)?;
+        platform.add_rustflags_cfgs(&rustflags);
+        Ok(platform)
+    }
+
+    /// Adds flags and key-value flags for each `--cfg` argument in `rustflags`.
+    ///
+    /// Requires the `rustflags` feature to be enabled.
+    pub fn add_rustflags_cfgs(&mut self, rustflags: &RustFlags) {
+        for cfg in rustflags.cfgs() {
+            match cfg {
+                RustFlagsCfg::Flag(flag) => self.add_flags([flag.to_owned()]),
+                RustFlagsCfg::KeyValue { key, value } => {
+                    self.add_key_value_flags([(key.to_owned(), value.to_owned())])
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if the `target.<key>` table in Cargo configuration applies to `platform`.
+fn target_key_matches(key: &str, platform: &Platform) -> bool {
+    if key.starts_with("cfg(") {
+        // Cargo doesn't consider flags while evaluating these, so use a platform without them.
+        let platform = Platform::from_triple(
+            platform.triple().clone(),
+            platform.target_features().clone(),
+        );
+        TargetSpec::new(key.to_owned())
+            .ok()
+            .and_then(|spec| spec.eval(&platform))
+            .unwrap_or(false)
+    } else {
+        key == platform.triple_str()
+    }
+}
+
+/// Parses a `rustflags` value in Cargo configuration, which is either a whitespace-separated
+/// string or an array of strings.
+fn config_args(path: &Path, key: &str, value: &toml::Value) -> Result<Vec<String>, Error> {
+    let invalid = || {
+        Error::RustFlagsConfig(RustFlagsConfigError::new(
+            path.to_owned(),
+            RustFlagsConfigErrorKind::InvalidValue {
+                key: key.to_owned(),
+            },
+        ))
+    };
+    match value {
+        toml::Value::String(flags) => {
+            Ok(flags.split_whitespace().map(|arg| arg.to_owned()).collect())
+        }
+        toml::Value::Array(args) => args
+            .iter()
+            .map(|arg| arg.as_str().map(|arg| arg.to_owned()).ok_or_else(invalid))
+            .collect(),
+        _ => Err(invalid()),
+    }
+}
+
+/// Reads Cargo configuration files, from lowest to highest precedence.
+fn read_configs(
+    cwd: &Path,
+    cargo_home: Option<&Path>,
+) -> Result<Vec<(PathBuf, toml::Value)>, Error> {
+    let mut dirs: Vec<PathBuf> = cwd
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .collect::<Vec<_>>();
+    if let Some(cargo_home) = cargo_home {
+        if !dirs.iter().any(|dir| dir == cargo_home) {
+            dirs.push(cargo_home.to_owned());
+        }
+    }
+
+    let mut configs = Vec::new();
+    // Cargo prefers `config` over `config.toml` if both exist.
+    for dir in dirs.iter().rev() {
+        for name in ["config", "config.toml"] {
+            let path = dir.join(name);
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => {
+                    return Err(Error::RustFlagsConfig(RustFlagsConfigError::new(
+                        path,
+                        RustFlagsConfigErrorKind::Read(Arc::new(error)),
+                    )));
+                }
+            };
+            let config = toml::from_str(&contents).map_err(|error| {
+                Error::RustFlagsConfig(RustFlagsConfigError::new(
+                    path.clone(),
+                    RustFlagsConfigErrorKind::Parse(error),
+                ))
+            })?;
+            configs.push((path, config));
+            break;
+        }
+    }
+    Ok(configs)
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(path: &str, contents: &str) -> (PathBuf, toml::Value) {
+        (
+            PathBuf::from(path),
+            toml::from_str(contents).expect("valid TOML"),
+        )
+    }
+
+    fn linux() -> Platform {
+        Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap()
+    }
+
+    #[test]
+    fn cfgs() {
+        let rustflags = RustFlags::new([
+            "--cfg",
+            "tokio_unstable",
+            "--cfg=foo=\"bar\"",
+            "-C",
+            "target-cpu=native",
+            "--cfg",
+            "baz = \"qux\"",
+            "--cfg",
+        ]);
+        assert_eq!(
+            rustflags.cfgs().collect::<Vec<_>>(),
+            vec![
+                RustFlagsCfg::Flag("tokio_unstable"),
+                RustFlagsCfg::KeyValue {
+                    key: "foo",
+                    value: "bar"
+                },
+                RustFlagsCfg::KeyValue {
+                    key: "baz",
+                    value: "qux"
+                },
+            ],
+        );
+
+        assert_eq!(
+            RustFlags::from_encoded("--cfg\x1ffoo bar")
+                .args()
+                .collect::<Vec<_>>(),
+            vec!["--cfg", "foo bar"],
+        );
+        assert_eq!(RustFlags::from_encoded("").args().len(), 0);
+    }
+
+    #[test]
+    fn from_sources() {
+        let configs = [
+            config(
+                "/home/.cargo/config.toml",
+                r#"
+                build.rustflags = "--cfg home_build"
+                target.x86_64-unknown-linux-gnu.rustflags = ["--cfg", "home_target"]
+                "#,
+            ),
+            config(
+                "/ws/.cargo/config.toml",
+                r#"
+                build.rustflags = ["--cfg", "ws_build"]
+                target.'cfg(unix)'.rustflags = "--cfg ws_unix"
+                target.'cfg(windows)'.rustflags = "--cfg ws_windows"
+                "#,
+            ),
+        ];
+        let no_env = |_: &str| None;
+
+        let rustflags = RustFlags::from_sources(&linux(), &configs, no_env).unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec!["--cfg", "home_target", "--cfg", "ws_unix"],
+            "target entries are joined, and build.rustflags is ignored"
+        );
+
+        let windows = Platform::new("x86_64-pc-windows-msvc", TargetFeatures::Unknown).unwrap();
+        let rustflags = RustFlags::from_sources(&windows, &configs, no_env).unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec!["--cfg", "ws_windows"]
+        );
+
+        let mac = Platform::new("aarch64-apple-darwin", TargetFeatures::Unknown).unwrap();
+        let rustflags = RustFlags::from_sources(&mac, &configs, no_env).unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec!["--cfg", "ws_unix"],
+        );
+
+        let rustflags = RustFlags::from_sources(&mac, &configs[..1], no_env).unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec!["--cfg", "home_build"],
+            "build.rustflags is used if no target entries match"
+        );
+
+        let rustflags = RustFlags::from_sources(&linux(), &configs, |name| match name {
+            "RUSTFLAGS" => Some("--cfg from_env".to_owned()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec!["--cfg", "from_env"],
+            "RUSTFLAGS overrides configuration"
+        );
+
+        let rustflags = RustFlags::from_sources(&linux(), &configs, |name| match name {
+            "RUSTFLAGS" => Some("--cfg from_env".to_owned()),
+            "CARGO_ENCODED_RUSTFLAGS" => Some(String::new()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            rustflags.args().len(),
+            0,
+            "CARGO_ENCODED_RUSTFLAGS overrides RUSTFLAGS"
+        );
+
+        let rustflags = RustFlags::from_sources(&linux(), &configs, |name| match name {
+            "CARGO_TARGET_X86_64_UNKNOWN_LINUX_GNU_RUSTFLAGS" => {
+                Some("--cfg target_env".to_owned())
+            }
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            rustflags.args().collect::<Vec<_>>(),
+            vec![
+                "--cfg",
+                "home_target",
+                "--cfg",
+                "ws_unix",
+                "--cfg",
+                "target_env"
+            ],
+        );
+
+        let invalid = [config("/ws/.cargo/config.toml", "build.rustflags = 1")];
+        let error = RustFlags::from_sources(&linux(), &invalid, no_env).unwrap_err();
+        assert!(
+            matches!(&error, Error::RustFlagsConfig(error) if error.path() == Path::new("/ws/.cargo/config.toml")),
+            "invalid value produces an error: {error:?}"
+        );
+    }
+
+    #[test]
+    fn add_rustflags_cfgs() {
+        let mut platform = linux();
+        platform.add_rustflags_cfgs(&RustFlags::from_space_separated(
+            r#"--cfg tokio_unstable --cfg foo="bar" --cfg foo="baz""#,
+        ));
+        assert_eq!(platform.flags().collect::<Vec<_>>(), vec!["tokio_unstable"]);
+        assert_eq!(
+            platform.key_value_flags().collect::<Vec<_>>(),
+            vec![("foo", "bar"), ("foo", "baz")],
+        );
+    }
+}
@@ -4,6 +4,7 @@
 use crate::{
     Error, Platform, Triple,
     errors::{ExpressionParseError, PlainStringParseError},
+    expr_tree::{ExprTree, TreePredicate, check_all},
 };
 use cfg_expr::{Expression, Predicate};
 use std::{borrow::Cow, fmt, str::FromStr, sync::Arc};
@@ -146,6 +147,74 @@ impl TargetSpec {
             TargetSpec::Expression(expr) => expr.eval(platform),
         }
     }
+
+    /// Evaluates this specification against every builtin platform known to `target-spec`.
+    ///
+    /// Target features and flags are treated as unknown, so expressions that depend on them may
+    /// evaluate to unknown for some platforms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpec;
+    ///
+    /// let spec: TargetSpec = "cfg(all(windows, target_arch = \"aarch64\"))".parse().unwrap();
+    /// let matches = spec.matching_builtin_platforms();
+    /// assert!(matches.matching().iter().any(|triple| triple.as_str() == "aarch64-pc-windows-msvc"));
+    /// assert!(matches.unknown().is_empty());
+    /// ```
+    pub fn matching_builtin_platforms(&self) -> BuiltinPlatformMatches {
+        match self {
+            TargetSpec::PlainString(plain_str) => {
+                BuiltinPlatformMatches::new(|triple| Some(triple.as_str() == plain_str.as_str()))
+            }
+            TargetSpec::Expression(expr) => expr.matching_builtin_platforms(),
+        }
+    }
+
+    /// Returns `Some(true)` if, whenever `self` matches a platform, `other` also matches it.
+    ///
+    /// For the precise semantics of this check, see [`TargetSpecExpression::implies`].
+    pub fn implies(&self, other: &TargetSpec) -> Option<bool> {
+        check_all(&self.tree(), &other.tree(), |a, b| !a || b)
+    }
+
+    /// Returns `Some(true)` if `self` and `other` match exactly the same platforms.
+    ///
+    /// For the precise semantics of this check, see [`TargetSpecExpression::implies`].
+    pub fn is_equivalent(&self, other: &TargetSpec) -> Option<bool> {
+        if self.to_string() == other.to_string() {
+            return Some(true);
+        }
+        check_all(&self.tree(), &other.tree(), |a, b| a == b)
+    }
+
+    /// Returns `Some(true)` if no platform is matched by both `self` and `other`.
+    ///
+    /// For the precise semantics of this check, see [`TargetSpecExpression::implies`].
+    pub fn is_disjoint(&self, other: &TargetSpec) -> Option<bool> {
+        check_all(&self.tree(), &other.tree(), |a, b| !(a && b))
+    }
+
+    /// Returns the canonical form of this specification.
+    ///
+    /// Plain strings are returned as is. For expressions, see
+    /// [`TargetSpecExpression::canonicalize`].
+    pub fn canonicalize(&self) -> TargetSpec {
+        match self {
+            TargetSpec::PlainString(_) => self.clone(),
+            TargetSpec::Expression(expr) => TargetSpec::Expression(expr.canonicalize()),
+        }
+    }
+
+    fn tree(&self) -> ExprTree {
+        match self {
+            TargetSpec::PlainString(plain_str) => {
+                ExprTree::Pred(TreePredicate::triple(plain_str.as_str()))
+            }
+            TargetSpec::Expression(expr) => expr.tree(),
+        }
+    }
 }
 
 impl FromStr for TargetSpec {
@@ -196,10 +265,201 @@ impl TargetSpecExpression {
     /// Returns `Some(true)` if there's a match, `Some(false)` if there's none, or `None` if the
     /// result of the evaluation is unknown (typically found if target features are involved).
     pub fn eval(&self, platform: &Platform) -> Option<bool> {
+        self.eval_with(
+            platform.triple(),
+            |feature| {
+                platform
+                    .target_features()
+                    .matches_for_triple(platform.triple(), feature)
+            },
+            |flag| Some(platform.has_flag(flag)),
+            |key, value| Some(platform.has_key_value_flag(key, value)),
+        )
+    }
+
+    /// Partially evaluates this expression against the given platform.
+    ///
+    /// Predicates that can be evaluated against the platform are replaced with their values, and
+    /// the expression is simplified. If the result is still unknown, the returned
+    /// [`PartialEval::Residual`] contains an expression over only the unknown predicates: target
+    /// features if the platform's target features are [unknown](crate::TargetFeatures::Unknown), and
+    /// flags if `flag_eval` is [`FlagEval::Unknown`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{FlagEval, PartialEval, Platform, TargetFeatures, TargetSpecExpression};
+    ///
+    /// let platform = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+    /// let expr = TargetSpecExpression::new(
+    ///     "cfg(any(windows, all(unix, target_feature = \"avx2\"), tokio_unstable))",
+    /// ).unwrap();
+    ///
+    /// // With flags known, tokio_unstable evaluates to false.
+    /// match expr.partial_eval(&platform, FlagEval::Known) {
+    ///     PartialEval::Residual(residual) => {
+    ///         assert_eq!(residual.to_string(), "target_feature = \"avx2\"");
+    ///     }
+    ///     PartialEval::Known(value) => panic!("expected residual, found {value}"),
+    /// }
+    ///
+    /// // With flags unknown, tokio_unstable is retained.
+    /// let residual = expr.partial_eval(&platform, FlagEval::Unknown);
+    /// assert_eq!(
+    ///     residual.to_string(),
+    ///     "any(target_feature = \"avx2\", tokio_unstable)",
+    /// );
+    ///
+    /// let platform = Platform::new("x86_64-pc-windows-msvc", TargetFeatures::Unknown).unwrap();
+    /// assert_eq!(expr.partial_eval(&platform, FlagEval::Unknown).known(), Some(true));
+    /// ```
+    pub fn partial_eval(&self, platform: &Platform, flag_eval: FlagEval) -> PartialEval {
+        let residual = self
+            .tree()
+            .substitute(&mut |pred| pred.eval_platform(platform, flag_eval));
+        match residual.as_const() {
+            Some(value) => PartialEval::Known(value),
+            None => PartialEval::Residual(Self::from_tree(&residual)),
+        }
+    }
+
+    /// Returns `Some(true)` if, whenever `self` matches a platform, `other` also matches it, and
+    /// `Some(false)` if there's a platform that `self` matches but `other` doesn't.
+    ///
+    /// This check is exact over all builtin platforms known to `target-spec`. Flags (including
+    /// key-value flags) and target features are handled symbolically: `self` implies `other` only
+    /// if that holds for every combination of flags and target features. Combinations that
+    /// contradict implications between target features (e.g. `avx2` without `sse2` on x86_64) are
+    /// skipped, matching [`Self::eval`]. Target predicates such as `target_os = "my-custom-os"`,
+    /// which don't match any builtin platform, are also handled symbolically.
+    ///
+    /// Returns `None` if no counterexample was found, but there are too many flags and target
+    /// features to check exhaustively (more than 16 at once).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpecExpression;
+    ///
+    /// let linux = TargetSpecExpression::new("cfg(target_os = \"linux\")").unwrap();
+    /// let unix = TargetSpecExpression::new("cfg(unix)").unwrap();
+    /// let windows = TargetSpecExpression::new("cfg(windows)").unwrap();
+    ///
+    /// assert_eq!(linux.implies(&unix), Some(true));
+    /// assert_eq!(unix.implies(&linux), Some(false));
+    /// assert_eq!(linux.is_disjoint(&windows), Some(true));
+    ///
+    /// // An expression that's disjoint with itself never matches any platform.
+    /// let dead = TargetSpecExpression::new("cfg(all(unix, windows))").unwrap();
+    /// assert_eq!(dead.is_disjoint(&dead), Some(true));
+    ///
+    /// // Flags are treated symbolically.
+    /// let flag = TargetSpecExpression::new("cfg(all(unix, tokio_unstable))").unwrap();
+    /// assert_eq!(flag.implies(&unix), Some(true));
+    /// assert_eq!(unix.implies(&flag), Some(false));
+    ///
+    /// // Implications between target features are taken into account.
+    /// let avx2 =
+    ///     TargetSpecExpression::new("cfg(all(target_arch = \"x86_64\", target_feature = \"avx2\"))")
+    ///         .unwrap();
+    /// let sse2 = TargetSpecExpression::new("cfg(target_feature = \"sse2\")").unwrap();
+    /// assert_eq!(avx2.implies(&sse2), Some(true));
+    /// ```
+    pub fn implies(&self, other: &TargetSpecExpression) -> Option<bool> {
+        check_all(&self.tree(), &other.tree(), |a, b| !a || b)
+    }
+
+    /// Returns `Some(true)` if `self` and `other` match exactly the same platforms.
+    ///
+    /// For the precise semantics of this check, see [`Self::implies`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpecExpression;
+    ///
+    /// let a = TargetSpecExpression::new("cfg(not(not(any(windows, unix))))").unwrap();
+    /// let b = TargetSpecExpression::new("cfg(any(unix, windows))").unwrap();
+    /// assert_eq!(a.is_equivalent(&b), Some(true));
+    /// ```
+    pub fn is_equivalent(&self, other: &TargetSpecExpression) -> Option<bool> {
+        if self.expression_str() == other.expression_str() {
+            return Some(true);
+        }
+        check_all(&self.tree(), &other.tree(), |a, b| a == b)
+    }
+
+    /// Returns `Some(true)` if no platform is matched by both `self` and `other`.
+    ///
+    /// For the precise semantics of this check, see [`Self::implies`].
+    pub fn is_disjoint(&self, other: &TargetSpecExpression) -> Option<bool> {
+        check_all(&self.tree(), &other.tree(), |a, b| !(a && b))
+    }
+
+    /// Returns the canonical form of this expression.
+    ///
+    /// Expressions that are spelled differently but have the same structure, like
+    /// `cfg(not(not(any(unix))))` and `cfg(unix)`, have the same canonical form. The canonical
+    /// form is computed by:
+    ///
+    /// * flattening nested `all()` and `any()` operations, and removing double negations;
+    /// * sorting and deduplicating operands;
+    /// * replacing operations that contain both an operand and its negation with constants, e.g.
+    ///   `any(foo, not(foo))` becomes `all()`;
+    /// * removing absorbed operands, e.g. `all(unix, any(unix, foo))` becomes `unix`.
+    ///
+    /// Only syntactic rewrites are performed, so the [`Display`](fmt::Display) output of the result
+    /// doesn't depend on the builtin target table, and can be used as a key. Two equivalent
+    /// expressions may still have different canonical forms, e.g. `cfg(target_os = "linux")` and
+    /// `cfg(all(unix, target_os = "linux"))`. To check for equivalence, use
+    /// [`Self::is_equivalent`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::TargetSpecExpression;
+    ///
+    /// let a = TargetSpecExpression::new("cfg(any(all(unix, foo), all(foo, unix, unix)))").unwrap();
+    /// let b = TargetSpecExpression::new("cfg(all(not(not(foo)), any(unix)))").unwrap();
+    /// assert_eq!(a.canonicalize().to_string(), "all(foo, unix)");
+    /// assert_eq!(b.canonicalize().to_string(), "all(foo, unix)");
+    ///
+    /// let dead = TargetSpecExpression::new("cfg(all(foo, not(foo)))").unwrap();
+    /// assert_eq!(dead.canonicalize().to_string(), "any()");
+    /// ```
+    pub fn canonicalize(&self) -> TargetSpecExpression {
+        Self::from_tree(&self.tree().canonicalize())
+    }
+
+    fn tree(&self) -> ExprTree {
+        ExprTree::new(&self.inner)
+    }
+
+    fn from_tree(tree: &ExprTree) -> Self {
+        Self::new(&format!("cfg({tree})")).expect("expression trees produce valid expressions")
+    }
+
+    /// Evaluates this expression against every builtin platform known to `target-spec`.
+    ///
+    /// Target features, flags and key-value flags are treated as unknown, so expressions that
+    /// depend on them may evaluate to unknown for some platforms.
+    pub fn matching_builtin_platforms(&self) -> BuiltinPlatformMatches {
+        BuiltinPlatformMatches::new(|triple| {
+            self.eval_with(triple, |_| None, |_| None, |_, _| None)
+        })
+    }
+
+    fn eval_with(
+        &self,
+        triple: &Triple,
+        target_feature: impl Fn(&str) -> Option<bool>,
+        flag: impl Fn(&str) -> Option<bool>,
+        key_value: impl Fn(&str, &str) -> Option<bool>,
+    ) -> Option<bool> {
         self.inner.eval(|pred| {
             match pred {
-                Predicate::Target(target) => Some(platform.triple().matches(target)),
-                Predicate::TargetFeature(feature) => platform.target_features().matches(feature),
+                Predicate::Target(target) => Some(triple.matches(target)),
+                Predicate::TargetFeature(feature) => target_feature(feature),
                 Predicate::Test | Predicate::DebugAssertions | Predicate::ProcMacro => {
                     // Known families that always evaluate to false. See
                     // https://docs.rs/cargo-platform/0.1.1/src/cargo_platform/lib.rs.html#76.
@@ -210,13 +470,13 @@ impl TargetSpecExpression {
                     // https://github.com/rust-lang/cargo/issues/7442 for more details.
                     Some(false)
                 }
-                Predicate::Flag(flag) => {
+                Predicate::Flag(name) => {
                     // This returns false by default but true in some cases.
-                    Some(platform.has_flag(flag))
+                    flag(name)
                 }
-                Predicate::KeyValue { .. } => {
-                    // This is always interpreted by Cargo as false.
-                    Some(false)
+                Predicate::KeyValue { key, val } => {
+                    // Like flags, this returns false by default but true in some cases.
+                    key_value(key, val)
                 }
             }
         })
@@ -237,6 +497,104 @@ impl fmt::Display for TargetSpecExpression {
     }
 }
 
+/// How [`TargetSpecExpression::partial_eval`] treats flags.
+///
+/// A flag is a single token like the `foo` in `cfg(not(foo))`. Key-value flags like the
+/// `foo = "bar"` in `cfg(foo = "bar")` are treated the same way.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FlagEval {
+    /// Flags added through [`Platform::add_flags`] and [`Platform::add_key_value_flags`] are true,
+    /// and all other flags are false.
+    ///
+    /// This matches [`TargetSpecExpression::eval`].
+    Known,
+
+    /// Flags added through [`Platform::add_flags`] and [`Platform::add_key_value_flags`] are true,
+    /// and all other flags are unknown.
+    Unknown,
+}
+
+/// The result of [`TargetSpecExpression::partial_eval`].
+#[derive(Clone, Debug)]
+pub enum PartialEval {
+    /// The expression evaluated to a known value.
+    Known(bool),
+
+    /// The result is unknown, and depends on the contained expression.
+    ///
+    /// The expression only contains predicates whose values were unknown.
+    Residual(TargetSpecExpression),
+}
+
+impl PartialEval {
+    /// Returns the value of the expression if it's known, or `None` if it isn't.
+    pub fn known(&self) -> Option<bool> {
+        match self {
+            PartialEval::Known(value) => Some(*value),
+            PartialEval::Residual(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for PartialEval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PartialEval::Known(true) => f.write_str("all()"),
+            PartialEval::Known(false) => f.write_str("any()"),
+            PartialEval::Residual(residual) => write!(f, "{residual}"),
+        }
+    }
+}
+
+/// The result of evaluating a [`TargetSpec`] or [`TargetSpecExpression`] against every builtin
+/// platform.
+///
+/// Returned by [`TargetSpec::matching_builtin_platforms`] and
+/// [`TargetSpecExpression::matching_builtin_platforms`]. Triples within each list are sorted by
+/// their string representation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuiltinPlatformMatches {
+    matching: Vec<Triple>,
+    non_matching: Vec<Triple>,
+    unknown: Vec<Triple>,
+}
+
+impl BuiltinPlatformMatches {
+    fn new(mut eval: impl FnMut(&Triple) -> Option<bool>) -> Self {
+        let mut matching = Vec::new();
+        let mut non_matching = Vec::new();
+        let mut unknown = Vec::new();
+        for triple in Triple::all_builtins() {
+            match eval(&triple) {
+                Some(true) => matching.push(triple),
+                Some(false) => non_matching.push(triple),
+                None => unknown.push(triple),
+            }
+        }
+        Self {
+            matching,
+            non_matching,
+            unknown,
+        }
+    }
+
+    /// Returns the builtin triples for which the expression evaluates to true.
+    pub fn matching(&self) -> &[Triple] {
+        &self.matching
+    }
+
+    /// Returns the builtin triples for which the expression evaluates to false.
+    pub fn non_matching(&self) -> &[Triple] {
+        &self.non_matching
+    }
+
+    /// Returns the builtin triples for which the result depends on target features or flags.
+    pub fn unknown(&self) -> &[Triple] {
+        &self.unknown
+    }
+}
+
 /// A plain string as contained within a [`TargetSpec::PlainString`].
 ///
 /// For more information, see [`TargetSpec`].
@@ -285,6 +643,7 @@ impl fmt::Display for TargetSpecPlainString {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::TargetFeatures;
     use cfg_expr::{
         Predicate, TargetPredicate,
         targets::{Abi, Arch, Family, Os},
@@ -428,14 +787,297 @@ mod tests {
             }],
         );
 
-        let platform = Platform::build_target().unwrap();
-        // This should always evaluate to false.
+        let mut platform = Platform::build_target().unwrap();
+        // This should evaluate to false unless the key-value flag is set.
         assert_eq!(expr.eval(&platform), Some(false));
 
-        let expr = TargetSpec::new("cfg(not(bogus_key = \"bogus_value\"))")
+        let not_expr = TargetSpec::new("cfg(not(bogus_key = \"bogus_value\"))")
             .expect("unknown predicate should parse");
-        // This is a cfg(not()), so it should always evaluate to true.
+        // This is a cfg(not()), so it should evaluate to true.
+        assert_eq!(not_expr.eval(&platform), Some(true));
+
+        platform.add_key_value_flags([("bogus_key", "other_value")]);
+        assert_eq!(expr.eval(&platform), Some(false));
+        platform.add_key_value_flags([("bogus_key", "bogus_value")]);
         assert_eq!(expr.eval(&platform), Some(true));
+        assert_eq!(not_expr.eval(&platform), Some(false));
+    }
+
+    #[test]
+    fn test_matching_builtin_platforms() {
+        let total = Triple::all_builtins().len();
+
+        let spec = TargetSpec::new("cfg(all(target_os = \"linux\", target_arch = \"x86_64\"))")
+            .expect("valid expression");
+        let matches = spec.matching_builtin_platforms();
+        assert!(
+            matches
+                .matching()
+                .iter()
+                .any(|triple| triple.as_str() == "x86_64-unknown-linux-gnu"),
+            "x86_64 Linux matches"
+        );
+        assert!(
+            matches
+                .non_matching()
+                .iter()
+                .any(|triple| triple.as_str() == "x86_64-pc-windows-msvc"),
+            "x86_64 Windows doesn't match"
+        );
+        assert!(matches.unknown().is_empty(), "no unknowns");
+        assert_eq!(
+            matches.matching().len() + matches.non_matching().len(),
+            total
+        );
+
+        // Target features and flags are unknown, but short-circuiting still applies.
+        let spec = TargetSpec::new("cfg(all(windows, any(target_feature = \"sse2\", foo)))")
+            .expect("valid expression");
+        let matches = spec.matching_builtin_platforms();
+        assert!(matches.matching().is_empty(), "nothing definitely matches");
+        assert!(
+            matches
+                .unknown()
+                .iter()
+                .all(|triple| triple.as_str().contains("windows")),
+            "only Windows platforms are unknown"
+        );
+        assert!(!matches.unknown().is_empty(), "some platforms are unknown");
+
+        let spec = TargetSpec::new("x86_64-unknown-linux-gnu").expect("valid triple");
+        let matches = spec.matching_builtin_platforms();
+        assert_eq!(matches.matching().len(), 1);
+        assert_eq!(matches.non_matching().len(), total - 1);
+    }
+
+    #[test]
+    fn test_partial_eval() {
+        let linux = Platform::new("x86_64-unknown-linux-gnu", TargetFeatures::Unknown).unwrap();
+        let mut linux_sse2 = Platform::new(
+            "x86_64-unknown-linux-gnu",
+            TargetFeatures::features(["sse2"]),
+        )
+        .unwrap();
+        linux_sse2.add_flags(["foo"]);
+
+        let cases: &[(&str, &Platform, FlagEval, &str)] = &[
+            ("cfg(unix)", &linux, FlagEval::Unknown, "all()"),
+            ("cfg(not(unix))", &linux, FlagEval::Unknown, "any()"),
+            (
+                "cfg(all(unix, target_feature = \"sse2\"))",
+                &linux,
+                FlagEval::Known,
+                "target_feature = \"sse2\"",
+            ),
+            (
+                "cfg(all(unix, target_feature = \"sse2\"))",
+                &linux_sse2,
+                FlagEval::Known,
+                "all()",
+            ),
+            (
+                "cfg(all(not(windows), any(foo, bar), not(target_feature = \"avx\")))",
+                &linux,
+                FlagEval::Unknown,
+                "all(any(foo, bar), not(target_feature = \"avx\"))",
+            ),
+            (
+                "cfg(all(not(windows), any(foo, bar), not(target_feature = \"avx\")))",
+                &linux_sse2,
+                FlagEval::Unknown,
+                "all()",
+            ),
+            (
+                "cfg(all(not(windows), any(bar, baz), not(target_feature = \"avx\")))",
+                &linux_sse2,
+                FlagEval::Unknown,
+                "any(bar, baz)",
+            ),
+            (
+                "cfg(any(bogus = \"value\", test, target_feature = \"avx\"))",
+                &linux,
+                FlagEval::Known,
+                "target_feature = \"avx\"",
+            ),
+            (
+                "cfg(any(bogus = \"value\", test, target_feature = \"avx\"))",
+                &linux,
+                FlagEval::Unknown,
+                "any(bogus = \"value\", target_feature = \"avx\")",
+            ),
+        ];
+
+        for (input, platform, flag_eval, expected) in cases {
+            let expr = TargetSpecExpression::new(input).expect("valid expression");
+            let result = expr.partial_eval(platform, *flag_eval);
+            assert_eq!(
+                result.to_string(),
+                *expected,
+                "partial evaluation of {input} against {} ({flag_eval:?})",
+                platform.triple_str(),
+            );
+            if *flag_eval == FlagEval::Known {
+                if let Some(value) = expr.eval(platform) {
+                    assert_eq!(result.known(), Some(value), "consistent with eval");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_implies() {
+        // (a, b, a implies b, b implies a, disjoint)
+        let cases = [
+            ("cfg(unix)", "cfg(unix)", true, true, false),
+            (
+                "cfg(target_os = \"linux\")",
+                "cfg(unix)",
+                true,
+                false,
+                false,
+            ),
+            ("cfg(unix)", "cfg(windows)", false, false, true),
+            (
+                "cfg(all(unix, not(target_os = \"linux\")))",
+                "cfg(target_os = \"linux\")",
+                false,
+                false,
+                true,
+            ),
+            (
+                "cfg(any(unix, windows))",
+                "cfg(any(windows, unix))",
+                true,
+                true,
+                false,
+            ),
+            ("cfg(all(unix, foo))", "cfg(unix)", true, false, false),
+            ("cfg(foo)", "cfg(bar)", false, false, false),
+            ("cfg(foo)", "cfg(not(foo))", false, false, true),
+            (
+                "cfg(target_feature = \"sse2\")",
+                "cfg(any(target_feature = \"sse2\", target_feature = \"avx\"))",
+                true,
+                false,
+                false,
+            ),
+            // Target predicates that don't match any builtin platform are symbolic.
+            (
+                "cfg(target_os = \"my-os\")",
+                "cfg(target_os = \"other-os\")",
+                false,
+                false,
+                false,
+            ),
+            // Predicates that are always false.
+            ("cfg(test)", "cfg(any())", true, true, true),
+            (
+                "x86_64-unknown-linux-gnu",
+                "cfg(target_os = \"linux\")",
+                true,
+                false,
+                false,
+            ),
+            (
+                "x86_64-unknown-linux-gnu",
+                "cfg(windows)",
+                false,
+                false,
+                true,
+            ),
+            // Custom triples are symbolic.
+            (
+                "my-custom-triple",
+                "other-custom-triple",
+                false,
+                false,
+                false,
+            ),
+            ("my-custom-triple", "my-custom-triple", true, true, false),
+            // Implications between target features are respected.
+            (
+                "cfg(all(target_arch = \"x86_64\", target_feature = \"avx2\"))",
+                "cfg(target_feature = \"sse2\")",
+                true,
+                false,
+                false,
+            ),
+            (
+                "cfg(all(target_arch = \"x86_64\", target_feature = \"avx2\"))",
+                "cfg(not(target_feature = \"sse\"))",
+                false,
+                false,
+                true,
+            ),
+            // avx2 doesn't imply sse2 on other architectures.
+            (
+                "cfg(target_feature = \"avx2\")",
+                "cfg(target_feature = \"sse2\")",
+                false,
+                false,
+                false,
+            ),
+        ];
+
+        for (a, b, a_implies_b, b_implies_a, disjoint) in cases {
+            let a_spec = TargetSpec::new(a).expect("valid spec");
+            let b_spec = TargetSpec::new(b).expect("valid spec");
+            assert_eq!(
+                a_spec.implies(&b_spec),
+                Some(a_implies_b),
+                "{a} implies {b}"
+            );
+            assert_eq!(
+                b_spec.implies(&a_spec),
+                Some(b_implies_a),
+                "{b} implies {a}"
+            );
+            assert_eq!(
+                a_spec.is_equivalent(&b_spec),
+                Some(a_implies_b && b_implies_a),
+                "{a} is equivalent to {b}"
+            );
+            assert_eq!(
+                a_spec.is_disjoint(&b_spec),
+                Some(disjoint),
+                "{a} is disjoint with {b}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_implies_undecided() {
+        let flags: Vec<_> = (0..17).map(|idx| format!("flag{idx}")).collect();
+        let many_flags = TargetSpec::new(format!("cfg(all({}))", flags.join(", "))).unwrap();
+        let one_flag = TargetSpec::new("cfg(flag0)").unwrap();
+        let other_flag = TargetSpec::new("cfg(other)").unwrap();
+
+        // all(flag0, ..., flag16) does imply flag0, but there are too many flags to check.
+        assert_eq!(many_flags.implies(&one_flag), None);
+        assert_eq!(one_flag.implies(&many_flags), None);
+        assert_eq!(many_flags.is_disjoint(&other_flag), None);
+        assert_eq!(many_flags.is_equivalent(&many_flags), Some(true));
+
+        // Counterexamples are still found on platforms where the residual is small.
+        let unix = TargetSpec::new(format!("cfg(any(unix, all({})))", flags.join(", "))).unwrap();
+        let linux = TargetSpec::new("cfg(target_os = \"linux\")").unwrap();
+        assert_eq!(unix.implies(&linux), Some(false));
+    }
+
+    #[test]
+    fn test_implies_agrees_with_eval() {
+        let avx2 = TargetSpec::new("cfg(all(target_arch = \"x86_64\", target_feature = \"avx2\"))")
+            .unwrap();
+        let sse2 = TargetSpec::new("cfg(target_feature = \"sse2\")").unwrap();
+        assert_eq!(avx2.implies(&sse2), Some(true));
+
+        let platform = Platform::new(
+            "x86_64-unknown-linux-gnu",
+            TargetFeatures::features(["avx2"]),
+        )
+        .unwrap();
+        assert_eq!(avx2.eval(&platform), Some(true));
+        assert_eq!(sse2.eval(&platform), Some(true));
     }
 
     #[test]
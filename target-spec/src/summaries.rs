@@ -10,7 +10,12 @@
 
 use crate::{Error, Platform, TargetFeatures};
 use serde::{Deserialize, Serialize};
-use std::{borrow::Cow, collections::BTreeSet};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+    sync::Arc,
+};
 
 impl Platform {
     /// Converts this `Platform` to a serializable form.
@@ -42,12 +47,30 @@ pub struct PlatformSummary {
     #[serde(skip_serializing_if = "Option::is_none", default)]
     pub custom_cfg: Option<String>,
 
+    /// The path to a target definition JSON file for custom platforms.
+    ///
+    /// Relative paths are resolved against the current directory, or against the directory
+    /// passed into [`Self::resolve_paths`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub custom_json_path: Option<String>,
+
+    /// The path to a file containing `rustc --print=cfg` output for custom platforms.
+    ///
+    /// Relative paths are resolved against the current directory, or against the directory
+    /// passed into [`Self::resolve_paths`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub custom_cfg_path: Option<String>,
+
     /// The target features used.
     pub target_features: TargetFeaturesSummary,
 
     /// The flags enabled.
     #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
     pub flags: BTreeSet<String>,
+
+    /// The key-value flags enabled, as a map of keys to the values they're set to.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub key_value_flags: BTreeMap<String, BTreeSet<String>>,
 }
 
 impl PlatformSummary {
@@ -57,45 +80,120 @@ impl PlatformSummary {
     ///
     /// * `custom_json` is set to `None`.
     /// * `custom_cfg` is set to `None`.
+    /// * `custom_json_path` and `custom_cfg_path` are set to `None`.
     /// * `target_features` is set to [`TargetFeaturesSummary::Unknown`].
     /// * `flags` is empty.
+    /// * `key_value_flags` is empty.
     pub fn new(triple_str: impl Into<String>) -> Self {
         Self {
             triple: triple_str.into(),
             custom_json: None,
             custom_cfg: None,
+            custom_json_path: None,
+            custom_cfg_path: None,
             target_features: TargetFeaturesSummary::Unknown,
             flags: BTreeSet::new(),
+            key_value_flags: BTreeMap::new(),
         }
     }
 
     /// If this represents a custom platform, sets the target
     /// definition JSON for it.
     ///
-    /// This clears any previously set `custom_cfg`, since only
-    /// one custom platform source is allowed.
+    /// This clears any other previously set custom platform source,
+    /// since only one custom platform source is allowed.
     ///
     /// For more about target definition JSON, see [Creating a
     /// custom
     /// target](https://docs.rust-embedded.org/embedonomicon/custom-target.html)
     /// in the Rust Embedonomicon.
     pub fn with_custom_json(mut self, custom_json: impl Into<String>) -> Self {
+        self.clear_custom_sources();
         self.custom_json = Some(custom_json.into());
-        self.custom_cfg = None;
         self
     }
 
     /// If this represents a custom platform created from
     /// `rustc --print=cfg` output, sets that output.
     ///
-    /// This clears any previously set `custom_json`, since only
-    /// one custom platform source is allowed.
+    /// This clears any other previously set custom platform source,
+    /// since only one custom platform source is allowed.
     pub fn with_custom_cfg(mut self, custom_cfg: impl Into<String>) -> Self {
+        self.clear_custom_sources();
         self.custom_cfg = Some(custom_cfg.into());
-        self.custom_json = None;
         self
     }
 
+    /// If this represents a custom platform, sets the path to a file
+    /// containing the target definition JSON for it.
+    ///
+    /// The file is read when [`Self::to_platform`] is called.
+    ///
+    /// This clears any other previously set custom platform source,
+    /// since only one custom platform source is allowed.
+    pub fn with_custom_json_path(mut self, path: impl Into<String>) -> Self {
+        self.clear_custom_sources();
+        self.custom_json_path = Some(path.into());
+        self
+    }
+
+    /// If this represents a custom platform, sets the path to a file
+    /// containing `rustc --print=cfg` output for it.
+    ///
+    /// The file is read when [`Self::to_platform`] is called.
+    ///
+    /// This clears any other previously set custom platform source,
+    /// since only one custom platform source is allowed.
+    pub fn with_custom_cfg_path(mut self, path: impl Into<String>) -> Self {
+        self.clear_custom_sources();
+        self.custom_cfg_path = Some(path.into());
+        self
+    }
+
+    fn clear_custom_sources(&mut self) {
+        self.custom_json = None;
+        self.custom_cfg = None;
+        self.custom_json_path = None;
+        self.custom_cfg_path = None;
+    }
+
+    /// Resolves relative `custom_json_path` and `custom_cfg_path` values against `base_dir`.
+    ///
+    /// Configuration files that refer to custom platforms typically want paths to be interpreted
+    /// relative to the directory the configuration file is in. Call this method with that
+    /// directory after deserializing a summary.
+    ///
+    /// Absolute paths are left unchanged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::summaries::PlatformSummary;
+    ///
+    /// let mut summary = PlatformSummary::new("thumbv7em-acme-none-eabihf")
+    ///     .with_custom_json_path("targets/thumbv7em-acme-none-eabihf.json");
+    /// summary.resolve_paths("/path/to/config");
+    /// assert_eq!(
+    ///     summary.custom_json_path.as_deref(),
+    ///     Some(
+    ///         std::path::Path::new("/path/to/config/targets/thumbv7em-acme-none-eabihf.json")
+    ///             .to_str()
+    ///             .unwrap()
+    ///     ),
+    /// );
+    /// ```
+    pub fn resolve_paths(&mut self, base_dir: impl AsRef<Path>) {
+        let base_dir = base_dir.as_ref();
+        for path in [&mut self.custom_json_path, &mut self.custom_cfg_path]
+            .into_iter()
+            .flatten()
+        {
+            if Path::new(path.as_str()).is_relative() {
+                *path = base_dir.join(path.as_str()).to_string_lossy().into_owned();
+            }
+        }
+    }
+
     /// Sets the target features for this platform.
     pub fn with_target_features(mut self, target_features: TargetFeaturesSummary) -> Self {
         self.target_features = target_features;
@@ -108,34 +206,96 @@ impl PlatformSummary {
         self
     }
 
+    /// Adds key-value flags for this platform.
+    pub fn with_added_key_value_flags(
+        mut self,
+        flags: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        for (key, value) in flags {
+            self.key_value_flags
+                .entry(key.into())
+                .or_default()
+                .insert(value.into());
+        }
+        self
+    }
+
     /// Creates a new `PlatformSummary` instance from a platform.
     pub fn from_platform(platform: &Platform) -> Self {
         Self {
             triple: platform.triple_str().to_string(),
             custom_json: platform.custom_json().map(|s| s.to_owned()),
             custom_cfg: platform.custom_cfg_text().map(|s| s.to_owned()),
+            custom_json_path: None,
+            custom_cfg_path: None,
             target_features: TargetFeaturesSummary::new(platform.target_features()),
             flags: platform.flags().map(|flag| flag.to_string()).collect(),
+            key_value_flags: platform.key_value_flags().fold(
+                BTreeMap::new(),
+                |mut acc: BTreeMap<String, BTreeSet<String>>, (key, value)| {
+                    acc.entry(key.to_owned())
+                        .or_default()
+                        .insert(value.to_owned());
+                    acc
+                },
+            ),
         }
     }
 
     /// Converts `self` to a `Platform`.
     ///
-    /// Returns an `Error` if the platform was unknown.
+    /// If `custom_json_path` or `custom_cfg_path` is set, the file it points to is read. Relative
+    /// paths are resolved against the current directory: to resolve them against some other
+    /// directory, call [`Self::resolve_paths`] first.
+    ///
+    /// Returns an `Error` if the platform was unknown, if more than one custom platform source was
+    /// specified, or if a custom platform file could not be read.
     pub fn to_platform(&self) -> Result<Platform, Error> {
-        if self.custom_json.is_some() && self.custom_cfg.is_some() {
+        use crate::errors::CustomTripleCreateError;
+
+        let source_count = [
+            self.custom_json.is_some(),
+            self.custom_cfg.is_some(),
+            self.custom_json_path.is_some(),
+            self.custom_cfg_path.is_some(),
+        ]
+        .into_iter()
+        .filter(|is_some| *is_some)
+        .count();
+        if source_count > 1 {
             return Err(Error::CustomPlatformCreate(
-                crate::errors::CustomTripleCreateError::ConflictingCustomPlatformSources {
+                CustomTripleCreateError::ConflictingCustomPlatformSources {
                     triple: self.triple.clone(),
                 },
             ));
         }
 
+        let read_file = |path: &str| {
+            std::fs::read_to_string(path).map_err(|error| {
+                Error::CustomPlatformCreate(CustomTripleCreateError::ReadFile {
+                    triple: self.triple.clone(),
+                    path: path.to_owned(),
+                    error: Arc::new(error),
+                })
+            })
+        };
+
+        let custom_json = match (&self.custom_json, &self.custom_json_path) {
+            (Some(json), _) => Some(Cow::Borrowed(json.as_str())),
+            (None, Some(path)) => Some(Cow::Owned(read_file(path)?)),
+            (None, None) => None,
+        };
+        let custom_cfg = match (&self.custom_cfg, &self.custom_cfg_path) {
+            (Some(cfg_text), _) => Some(Cow::Borrowed(cfg_text.as_str())),
+            (None, Some(path)) => Some(Cow::Owned(read_file(path)?)),
+            (None, None) => None,
+        };
+
         #[allow(unused_variables)] // in some feature branches, json/cfg aren't used
-        let mut platform = if let Some(json) = &self.custom_json {
+        let mut platform = if let Some(json) = &custom_json {
             #[cfg(not(feature = "custom"))]
             return Err(Error::CustomPlatformCreate(
-                crate::errors::CustomTripleCreateError::CustomJsonUnavailable,
+                CustomTripleCreateError::CustomJsonUnavailable,
             ));
 
             #[cfg(feature = "custom")]
@@ -144,10 +304,10 @@ impl PlatformSummary {
                 json,
                 self.target_features.to_target_features(),
             )?
-        } else if let Some(cfg_text) = &self.custom_cfg {
+        } else if let Some(cfg_text) = &custom_cfg {
             #[cfg(not(feature = "custom-cfg"))]
             return Err(Error::CustomPlatformCreate(
-                crate::errors::CustomTripleCreateError::CustomCfgUnavailable,
+                CustomTripleCreateError::CustomCfgUnavailable,
             ));
 
             #[cfg(feature = "custom-cfg")]
@@ -164,6 +324,9 @@ impl PlatformSummary {
         };
 
         platform.add_flags(self.flags.iter().cloned());
+        platform.add_key_value_flags(self.key_value_flags.iter().flat_map(|(key, values)| {
+            values.iter().map(move |value| (key.clone(), value.clone()))
+        }));
         Ok(platform)
     }
 }
@@ -231,21 +394,30 @@ mod platform_impl {
                     triple,
                     custom_json: None,
                     custom_cfg: None,
+                    custom_json_path: None,
+                    custom_cfg_path: None,
                     target_features: TargetFeaturesSummary::default(),
                     flags: BTreeSet::default(),
+                    key_value_flags: BTreeMap::default(),
                 }),
                 PlatformSummaryDeserialize::Full {
                     triple,
                     custom_json,
                     custom_cfg,
+                    custom_json_path,
+                    custom_cfg_path,
                     target_features,
                     flags,
+                    key_value_flags,
                 } => Ok(PlatformSummary {
                     triple,
                     custom_json,
                     custom_cfg,
+                    custom_json_path,
+                    custom_cfg_path,
                     target_features,
                     flags,
+                    key_value_flags,
                 }),
             }
         }
@@ -262,12 +434,19 @@ mod platform_impl {
             custom_json: Option<String>,
             #[serde(default)]
             custom_cfg: Option<String>,
+            #[serde(default)]
+            custom_json_path: Option<String>,
+            #[serde(default)]
+            custom_cfg_path: Option<String>,
             /// The target features used.
             #[serde(default)]
             target_features: TargetFeaturesSummary,
             /// The flags enabled.
             #[serde(skip_serializing_if = "BTreeSet::is_empty", default)]
             flags: BTreeSet<String>,
+            /// The key-value flags enabled.
+            #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+            key_value_flags: BTreeMap<String, BTreeSet<String>>,
         },
     }
 }
@@ -341,8 +520,11 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -351,8 +533,11 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -361,8 +546,11 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -371,8 +559,11 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::All,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
         valid.push((
@@ -381,8 +572,11 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Features(BTreeSet::new()),
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
 
@@ -397,8 +591,11 @@ mod tests {
                 triple: "x86_64-unknown-haiku".into(),
                 custom_json: Some(custom_json.to_owned()),
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
 
@@ -410,8 +607,30 @@ mod tests {
                 triple: "x86_64-unknown-linux-gnu".into(),
                 custom_json: None,
                 custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
                 target_features: TargetFeaturesSummary::Unknown,
                 flags,
+                key_value_flags: BTreeMap::new(),
+            },
+        ));
+
+        let mut key_value_flags = BTreeMap::new();
+        key_value_flags.insert(
+            "foo".to_owned(),
+            ["bar".to_owned(), "baz".to_owned()].into_iter().collect(),
+        );
+        valid.push((
+            r#"platform = { triple = "x86_64-unknown-linux-gnu", key-value-flags = { foo = ["bar", "baz"] } }"#,
+            PlatformSummary {
+                triple: "x86_64-unknown-linux-gnu".into(),
+                custom_json: None,
+                custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: None,
+                target_features: TargetFeaturesSummary::Unknown,
+                flags: BTreeSet::new(),
+                key_value_flags,
             },
         ));
 
@@ -437,8 +656,25 @@ mod tests {
                 triple: "my-custom-linux".into(),
                 custom_json: None,
                 custom_cfg: Some(custom_cfg.to_owned()),
+                custom_json_path: None,
+                custom_cfg_path: None,
+                target_features: TargetFeaturesSummary::Unknown,
+                flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
+            },
+        ));
+
+        valid.push((
+            r#"platform = { triple = "my-custom-linux", custom-cfg-path = "targets/my-custom-linux.cfg" }"#,
+            PlatformSummary {
+                triple: "my-custom-linux".into(),
+                custom_json: None,
+                custom_cfg: None,
+                custom_json_path: None,
+                custom_cfg_path: Some("targets/my-custom-linux.cfg".to_owned()),
                 target_features: TargetFeaturesSummary::Unknown,
                 flags: BTreeSet::new(),
+                key_value_flags: BTreeMap::new(),
             },
         ));
 
@@ -453,6 +689,23 @@ mod tests {
                 .unwrap_or_else(|err| panic!("serialized input: {input} is valid: {err}"));
             assert_eq!(actual, actual_2, "for input: {input}");
 
+            // Check that key-value flags are carried over.
+            if actual.platform.custom_json.is_none()
+                && actual.platform.custom_cfg.is_none()
+                && actual.platform.custom_json_path.is_none()
+                && actual.platform.custom_cfg_path.is_none()
+            {
+                let platform = actual
+                    .platform
+                    .to_platform()
+                    .expect("standard platform parsed successfully");
+                assert_eq!(
+                    PlatformSummary::from_platform(&platform),
+                    actual.platform,
+                    "for input: {input}"
+                );
+            }
+
             // Check that custom JSON functionality works.
             if actual.platform.custom_json.is_some() {
                 #[cfg(feature = "custom")]
@@ -506,6 +759,77 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn platform_custom_paths() {
+        use crate::errors::CustomTripleCreateError;
+
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let dir = temp_dir.path();
+        std::fs::create_dir(dir.join("targets")).expect("created targets dir");
+        let custom_cfg = indoc::indoc! {r#"
+            panic="abort"
+            target_arch="arm"
+            target_endian="little"
+            target_os="none"
+            target_pointer_width="32"
+        "#};
+        std::fs::write(dir.join("targets/my-firmware.cfg"), custom_cfg).expect("wrote cfg file");
+
+        let mut summary =
+            PlatformSummary::new("my-firmware").with_custom_cfg_path("targets/my-firmware.cfg");
+        summary.resolve_paths(dir);
+        let expected_path = dir.join("targets/my-firmware.cfg");
+        assert_eq!(
+            summary.custom_cfg_path.as_deref().map(Path::new),
+            Some(expected_path.as_path()),
+            "relative path resolved against base dir"
+        );
+
+        // Resolving again is a no-op since the path is now absolute.
+        let mut summary_2 = summary.clone();
+        summary_2.resolve_paths("/nonexistent");
+        assert_eq!(summary, summary_2, "absolute paths are unchanged");
+
+        #[cfg(feature = "custom-cfg")]
+        {
+            let platform = summary
+                .to_platform()
+                .expect("custom cfg platform read from file");
+            assert!(platform.is_custom(), "this is a custom platform");
+            assert_eq!(platform.custom_cfg_text(), Some(custom_cfg));
+            // Converting back embeds the file contents.
+            assert_eq!(
+                platform.to_summary().custom_cfg.as_deref(),
+                Some(custom_cfg),
+                "summary embeds cfg text"
+            );
+        }
+
+        let missing = PlatformSummary::new("my-firmware")
+            .with_custom_json_path(dir.join("missing.json").to_string_lossy());
+        let error = missing.to_platform().expect_err("missing file");
+        assert!(
+            matches!(
+                error,
+                Error::CustomPlatformCreate(CustomTripleCreateError::ReadFile { .. })
+            ),
+            "unexpected error: {error:?}"
+        );
+
+        let mut conflicting = PlatformSummary::new("my-firmware").with_custom_cfg(custom_cfg);
+        conflicting.custom_json_path = Some("targets/my-firmware.json".to_owned());
+        let error = conflicting.to_platform().expect_err("conflicting sources");
+        assert!(
+            matches!(
+                error,
+                Error::CustomPlatformCreate(
+                    CustomTripleCreateError::ConflictingCustomPlatformSources { .. }
+                )
+            ),
+            "unexpected error: {error:?}"
+        );
+    }
 }
 
 #[cfg(all(test, feature = "proptest1"))]
@@ -527,6 +851,7 @@ mod proptests {
             assert_eq!(platform.triple_str(), platform2.triple_str(), "triples match");
             assert_eq!(platform.target_features(), platform2.target_features(), "target features match");
             assert_eq!(platform.flags().collect::<HashSet<_>>(), platform2.flags().collect::<HashSet<_>>(), "flags match");
+            assert_eq!(platform.key_value_flags().collect::<Vec<_>>(), platform2.key_value_flags().collect::<Vec<_>>(), "key-value flags match");
         }
     }
 }
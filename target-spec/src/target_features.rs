@@ -0,0 +1,603 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Implications between target features, and the target features enabled by `-C target-cpu`.
+//!
+//! The tables in this module are based on `rustc_target::target_features` and LLVM's processor
+//! definitions. They only cover stable and commonly used features and CPUs.
+
+use crate::{TargetFeatures, Triple};
+use cfg_expr::{TargetPredicate, targets::Arch};
+use std::{borrow::Cow, collections::BTreeSet};
+
+impl TargetFeatures {
+    /// Creates a new `TargetFeatures` with the features enabled by passing `-C target-cpu=<cpu>`
+    /// to rustc while building for `triple`.
+    ///
+    /// The result includes all features implied by the CPU's features.
+    ///
+    /// Returns `None` if `cpu` isn't known to `target-spec` for the architecture of `triple`. In
+    /// particular, `native` always returns `None` since its features depend on the host.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{TargetFeatures, Triple};
+    ///
+    /// let triple = Triple::new("x86_64-unknown-linux-gnu").unwrap();
+    /// let features = TargetFeatures::for_target_cpu(&triple, "x86-64-v3").unwrap();
+    /// assert_eq!(features.matches("avx2"), Some(true));
+    /// assert_eq!(features.matches("sse4.1"), Some(true));
+    /// assert_eq!(features.matches("avx512f"), Some(false));
+    ///
+    /// assert!(TargetFeatures::for_target_cpu(&triple, "native").is_none());
+    /// ```
+    pub fn for_target_cpu(triple: &Triple, cpu: &str) -> Option<Self> {
+        let table = ArchTable::for_triple(triple)?;
+        let mut features = BTreeSet::new();
+        let mut next = Some(cpu);
+        while let Some(name) = next {
+            let cpu = table.cpus.iter().find(|cpu| cpu.name == name)?;
+            features.extend(cpu.features.iter().copied());
+            next = cpu.base;
+        }
+        let features = table.closure(features);
+        Some(TargetFeatures::features(features))
+    }
+
+    /// Returns `Some(true)` if this feature, or a feature that implies it on `triple`, is a match.
+    ///
+    /// Returns `Some(false)` if neither the feature nor any feature that implies it is a match,
+    /// and `None` if the set of target features is unknown.
+    ///
+    /// This is what [`TargetSpec::eval`](crate::TargetSpec::eval) uses to evaluate
+    /// `cfg(target_feature = "...")` predicates. Unlike [`Self::matches`], it accounts for
+    /// implications between features: for example, on x86_64, `avx2` implies `sse2`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use target_spec::{TargetFeatures, Triple};
+    ///
+    /// let triple = Triple::new("x86_64-unknown-linux-gnu").unwrap();
+    /// let features = TargetFeatures::features(["avx2"]);
+    /// assert_eq!(features.matches("sse2"), Some(false));
+    /// assert_eq!(features.matches_for_triple(&triple, "sse2"), Some(true));
+    /// assert_eq!(features.matches_for_triple(&triple, "avx512f"), Some(false));
+    /// ```
+    pub fn matches_for_triple(&self, triple: &Triple, feature: &str) -> Option<bool> {
+        match self {
+            TargetFeatures::Unknown => None,
+            TargetFeatures::All => Some(true),
+            TargetFeatures::Features(features) => {
+                if features.contains(feature) {
+                    return Some(true);
+                }
+                let Some(table) = ArchTable::for_triple(triple) else {
+                    return Some(false);
+                };
+                let implied = table.closure(features.iter().map(|feature| feature.as_ref()));
+                Some(implied.contains(feature))
+            }
+        }
+    }
+
+    /// Returns a copy of `self` with all features implied by the enabled features on `triple`
+    /// added.
+    pub fn with_implied_features(&self, triple: &Triple) -> Self {
+        match (self, ArchTable::for_triple(triple)) {
+            (TargetFeatures::Features(features), Some(table)) => {
+                let mut features = features.clone();
+                let implied: Vec<_> = table
+                    .closure(features.iter().map(|feature| feature.as_ref()))
+                    .into_iter()
+                    .filter(|feature| !features.contains(*feature))
+                    .collect();
+                features.extend(implied.into_iter().map(Cow::Borrowed));
+                TargetFeatures::Features(features)
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Returns the features that `feature` transitively implies on `triple`, not including `feature`
+/// itself.
+pub(crate) fn implied_features(triple: &Triple, feature: &str) -> BTreeSet<&'static str> {
+    let Some(table) = ArchTable::for_triple(triple) else {
+        return BTreeSet::new();
+    };
+    let mut implied = table.closure([feature]);
+    implied.remove(feature);
+    implied
+}
+
+struct ArchTable {
+    /// The `target_arch` values this table applies to.
+    arches: &'static [&'static str],
+    /// Features, and the features they directly imply.
+    implications: &'static [(&'static str, &'static [&'static str])],
+    cpus: &'static [Cpu],
+}
+
+impl ArchTable {
+    fn for_triple(triple: &Triple) -> Option<&'static Self> {
+        ARCH_TABLES.iter().find(|table| {
+            table
+                .arches
+                .iter()
+                .any(|arch| triple.matches(&TargetPredicate::Arch(Arch::new_const(arch))))
+        })
+    }
+
+    /// Returns `features` along with all the features they transitively imply.
+    ///
+    /// Features not mentioned in this table are dropped.
+    fn closure<'a>(&self, features: impl IntoIterator<Item = &'a str>) -> BTreeSet<&'static str> {
+        let mut result = BTreeSet::new();
+        let mut stack: Vec<&'static str> = features
+            .into_iter()
+            .filter_map(|feature| self.intern(feature))
+            .collect();
+        while let Some(feature) = stack.pop() {
+            if !result.insert(feature) {
+                continue;
+            }
+            if let Some((_, implied)) = self.implications.iter().find(|(name, _)| *name == feature)
+            {
+                stack.extend(implied.iter().copied());
+            }
+        }
+        result
+    }
+
+    /// Returns the `'static` version of a feature name mentioned in this table.
+    fn intern(&self, feature: &str) -> Option<&'static str> {
+        self.implications
+            .iter()
+            .flat_map(|(name, implied)| std::iter::once(name).chain(implied.iter()))
+            .chain(self.cpus.iter().flat_map(|cpu| cpu.features.iter()))
+            .find(|name| **name == feature)
+            .copied()
+    }
+}
+
+struct Cpu {
+    name: &'static str,
+    /// A CPU whose features this CPU also has.
+    base: Option<&'static str>,
+    features: &'static [&'static str],
+}
+
+impl Cpu {
+    const fn new(name: &'static str, features: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            base: None,
+            features,
+        }
+    }
+
+    const fn with_base(
+        name: &'static str,
+        base: &'static str,
+        features: &'static [&'static str],
+    ) -> Self {
+        Self {
+            name,
+            base: Some(base),
+            features,
+        }
+    }
+}
+
+static ARCH_TABLES: &[ArchTable] = &[
+    ArchTable {
+        arches: &["x86", "x86_64"],
+        implications: &[
+            ("sse2", &["sse"]),
+            ("sse3", &["sse2"]),
+            ("ssse3", &["sse3"]),
+            ("sse4.1", &["ssse3"]),
+            ("sse4.2", &["sse4.1"]),
+            ("sse4a", &["sse3"]),
+            ("avx", &["sse4.2"]),
+            ("avx2", &["avx"]),
+            ("fma", &["avx"]),
+            ("f16c", &["avx"]),
+            ("aes", &["sse2"]),
+            ("pclmulqdq", &["sse2"]),
+            ("sha", &["sse2"]),
+            ("gfni", &["sse2"]),
+            ("vaes", &["avx2", "aes"]),
+            ("vpclmulqdq", &["avx", "pclmulqdq"]),
+            ("sha512", &["avx2"]),
+            ("sm3", &["avx"]),
+            ("sm4", &["avx2"]),
+            ("avxvnni", &["avx2"]),
+            ("avxifma", &["avx2"]),
+            ("avxneconvert", &["avx2"]),
+            ("avxvnniint8", &["avx2"]),
+            ("avxvnniint16", &["avx2"]),
+            ("avx512f", &["avx2", "fma", "f16c"]),
+            ("avx512bw", &["avx512f"]),
+            ("avx512cd", &["avx512f"]),
+            ("avx512dq", &["avx512f"]),
+            ("avx512vl", &["avx512f"]),
+            ("avx512ifma", &["avx512f"]),
+            ("avx512vbmi", &["avx512bw"]),
+            ("avx512vbmi2", &["avx512bw"]),
+            ("avx512vnni", &["avx512f"]),
+            ("avx512bitalg", &["avx512bw"]),
+            ("avx512vpopcntdq", &["avx512f"]),
+            ("avx512bf16", &["avx512bw"]),
+            ("avx512fp16", &["avx512bw"]),
+            ("avx512vp2intersect", &["avx512f"]),
+            ("xsaveopt", &["xsave"]),
+            ("xsavec", &["xsave"]),
+            ("xsaves", &["xsave"]),
+            ("kl", &["sse2"]),
+            ("widekl", &["kl"]),
+        ],
+        cpus: &[
+            Cpu::new("x86-64", &["fxsr", "sse2"]),
+            Cpu::with_base(
+                "x86-64-v2",
+                "x86-64",
+                &["cmpxchg16b", "lahfsahf", "popcnt", "sse4.2"],
+            ),
+            Cpu::with_base(
+                "x86-64-v3",
+                "x86-64-v2",
+                &[
+                    "avx2", "bmi1", "bmi2", "f16c", "fma", "lzcnt", "movbe", "xsave",
+                ],
+            ),
+            Cpu::with_base(
+                "x86-64-v4",
+                "x86-64-v3",
+                &["avx512bw", "avx512cd", "avx512dq", "avx512f", "avx512vl"],
+            ),
+            Cpu::new("core2", &["cmpxchg16b", "fxsr", "ssse3"]),
+            Cpu::with_base("nehalem", "core2", &["lahfsahf", "popcnt", "sse4.2"]),
+            Cpu::with_base("westmere", "nehalem", &["aes", "pclmulqdq"]),
+            Cpu::with_base("sandybridge", "westmere", &["avx", "xsave", "xsaveopt"]),
+            Cpu::with_base("ivybridge", "sandybridge", &["f16c", "rdrand"]),
+            Cpu::with_base(
+                "haswell",
+                "ivybridge",
+                &["avx2", "bmi1", "bmi2", "fma", "lzcnt", "movbe"],
+            ),
+            Cpu::with_base("broadwell", "haswell", &["adx", "rdseed"]),
+            Cpu::with_base("skylake", "broadwell", &["xsavec", "xsaves"]),
+            Cpu::with_base(
+                "skylake-avx512",
+                "skylake",
+                &["avx512bw", "avx512cd", "avx512dq", "avx512f", "avx512vl"],
+            ),
+            Cpu::with_base(
+                "icelake-server",
+                "skylake-avx512",
+                &[
+                    "avx512bitalg",
+                    "avx512ifma",
+                    "avx512vbmi",
+                    "avx512vbmi2",
+                    "avx512vnni",
+                    "avx512vpopcntdq",
+                    "gfni",
+                    "sha",
+                    "vaes",
+                    "vpclmulqdq",
+                ],
+            ),
+            Cpu::new(
+                "znver1",
+                &[
+                    "adx",
+                    "aes",
+                    "avx2",
+                    "bmi1",
+                    "bmi2",
+                    "cmpxchg16b",
+                    "f16c",
+                    "fma",
+                    "fxsr",
+                    "lahfsahf",
+                    "lzcnt",
+                    "movbe",
+                    "pclmulqdq",
+                    "popcnt",
+                    "rdrand",
+                    "rdseed",
+                    "sha",
+                    "sse4a",
+                    "xsave",
+                    "xsavec",
+                    "xsaveopt",
+                    "xsaves",
+                ],
+            ),
+            Cpu::with_base("znver2", "znver1", &[]),
+            Cpu::with_base("znver3", "znver2", &["vaes", "vpclmulqdq"]),
+            Cpu::with_base(
+                "znver4",
+                "znver3",
+                &[
+                    "avx512bf16",
+                    "avx512bitalg",
+                    "avx512bw",
+                    "avx512cd",
+                    "avx512dq",
+                    "avx512f",
+                    "avx512ifma",
+                    "avx512vbmi",
+                    "avx512vbmi2",
+                    "avx512vl",
+                    "avx512vnni",
+                    "avx512vpopcntdq",
+                    "gfni",
+                ],
+            ),
+        ],
+    },
+    ArchTable {
+        arches: &["aarch64", "arm64ec"],
+        implications: &[
+            ("v8.1a", &["crc", "lse", "rdm", "pan", "lor", "vh"]),
+            ("v8.2a", &["v8.1a", "ras", "dpb"]),
+            ("v8.3a", &["v8.2a", "rcpc", "paca", "pacg", "jsconv"]),
+            ("v8.4a", &["v8.3a", "dotprod", "dit", "flagm"]),
+            ("v8.5a", &["v8.4a", "ssbs", "sb", "dpb2", "bti"]),
+            ("v8.6a", &["v8.5a", "bf16", "i8mm"]),
+            ("v8.7a", &["v8.6a", "wfxt"]),
+            ("rdm", &["neon"]),
+            ("dotprod", &["neon"]),
+            ("jsconv", &["neon"]),
+            ("fcma", &["neon"]),
+            ("fp16", &["neon"]),
+            ("fhm", &["fp16"]),
+            ("aes", &["neon"]),
+            ("sha2", &["neon"]),
+            ("sha3", &["sha2"]),
+            ("sm4", &["neon"]),
+            ("rcpc2", &["rcpc"]),
+            ("rcpc3", &["rcpc2"]),
+            ("sve", &["neon"]),
+            ("f32mm", &["sve"]),
+            ("f64mm", &["sve"]),
+            ("sve2", &["sve"]),
+            ("sve2-aes", &["sve2", "aes"]),
+            ("sve2-sm4", &["sve2", "sm4"]),
+            ("sve2-sha3", &["sve2", "sha3"]),
+            ("sve2-bitperm", &["sve2"]),
+        ],
+        cpus: &[
+            Cpu::new("generic", &["neon"]),
+            Cpu::new(
+                "neoverse-n1",
+                &["v8.2a", "aes", "dotprod", "fp16", "rcpc", "sha2", "ssbs"],
+            ),
+            Cpu::new(
+                "neoverse-v1",
+                &[
+                    "v8.4a", "aes", "bf16", "fp16", "i8mm", "rand", "sha3", "ssbs", "sve",
+                ],
+            ),
+            Cpu::new(
+                "neoverse-n2",
+                &[
+                    "v8.5a",
+                    "bf16",
+                    "fp16",
+                    "i8mm",
+                    "mte",
+                    "sve2",
+                    "sve2-bitperm",
+                ],
+            ),
+            Cpu::new(
+                "apple-m1",
+                &[
+                    "v8.4a", "aes", "fcma", "fhm", "fp16", "frintts", "sb", "sha3", "ssbs",
+                ],
+            ),
+            Cpu::with_base("apple-m2", "apple-m1", &["bf16", "i8mm"]),
+            Cpu::with_base("apple-m3", "apple-m2", &[]),
+        ],
+    },
+    ArchTable {
+        arches: &["arm"],
+        implications: &[
+            ("vfp3", &["vfp2"]),
+            ("vfp4", &["vfp3"]),
+            ("fp-armv8", &["vfp4"]),
+            ("neon", &["vfp3"]),
+            ("aes", &["neon"]),
+            ("sha2", &["neon"]),
+            ("dotprod", &["neon"]),
+            ("i8mm", &["neon"]),
+        ],
+        cpus: &[
+            Cpu::new("cortex-a7", &["vfp4", "neon", "d32"]),
+            Cpu::new("cortex-a9", &["vfp3", "neon", "d32"]),
+            Cpu::new(
+                "cortex-a53",
+                &["fp-armv8", "neon", "d32", "aes", "sha2", "crc"],
+            ),
+        ],
+    },
+    ArchTable {
+        arches: &["riscv32", "riscv64"],
+        implications: &[
+            ("d", &["f"]),
+            ("q", &["d"]),
+            ("f", &["zicsr"]),
+            ("zfhmin", &["f"]),
+            ("zfh", &["zfhmin"]),
+            ("zve32x", &["zvl32b", "zicsr"]),
+            ("zve32f", &["zve32x", "f"]),
+            ("zve64x", &["zve32x", "zvl64b"]),
+            ("zve64f", &["zve32f", "zve64x"]),
+            ("zve64d", &["zve64f", "d"]),
+            ("v", &["zve64d", "zvl128b"]),
+            ("zvl64b", &["zvl32b"]),
+            ("zvl128b", &["zvl64b"]),
+            ("b", &["zba", "zbb", "zbs"]),
+            ("zkn", &["zbkb", "zbkc", "zbkx", "zkne", "zknd", "zknh"]),
+            ("zks", &["zbkb", "zbkc", "zbkx", "zksed", "zksh"]),
+            ("zk", &["zkn", "zkr", "zkt"]),
+        ],
+        cpus: &[],
+    },
+    ArchTable {
+        arches: &["powerpc", "powerpc64"],
+        implications: &[
+            ("vsx", &["altivec"]),
+            ("power8-altivec", &["altivec"]),
+            ("power9-altivec", &["power8-altivec"]),
+            ("power8-vector", &["vsx", "power8-altivec"]),
+            ("power9-vector", &["power8-vector", "power9-altivec"]),
+            ("power10-vector", &["power9-vector"]),
+        ],
+        cpus: &[
+            Cpu::new("pwr8", &["power8-vector", "power8-crypto"]),
+            Cpu::with_base("pwr9", "pwr8", &["power9-vector"]),
+            Cpu::with_base("pwr10", "pwr9", &["power10-vector"]),
+        ],
+    },
+    ArchTable {
+        arches: &["wasm32", "wasm64"],
+        implications: &[("relaxed-simd", &["simd128"])],
+        cpus: &[
+            Cpu::new("mvp", &[]),
+            Cpu::new(
+                "generic",
+                &[
+                    "bulk-memory",
+                    "multivalue",
+                    "mutable-globals",
+                    "nontrapping-fptoint",
+                    "reference-types",
+                    "sign-ext",
+                ],
+            ),
+            Cpu::with_base("bleeding-edge", "generic", &["relaxed-simd", "simd128"]),
+        ],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Platform, TargetSpec};
+
+    #[test]
+    fn implications() {
+        let x86_64 = Triple::new("x86_64-unknown-linux-gnu").unwrap();
+        let aarch64 = Triple::new("aarch64-unknown-linux-gnu").unwrap();
+
+        let features = TargetFeatures::features(["avx512bw"]);
+        for feature in ["avx512bw", "avx512f", "avx2", "avx", "fma", "sse4.2", "sse"] {
+            assert_eq!(
+                features.matches_for_triple(&x86_64, feature),
+                Some(true),
+                "avx512bw implies {feature} on x86_64"
+            );
+        }
+        for feature in ["avx512vl", "aes", "neon"] {
+            assert_eq!(
+                features.matches_for_triple(&x86_64, feature),
+                Some(false),
+                "avx512bw doesn't imply {feature} on x86_64"
+            );
+        }
+
+        // Implications are per-architecture.
+        let features = TargetFeatures::features(["aes"]);
+        assert_eq!(features.matches_for_triple(&x86_64, "sse2"), Some(true));
+        assert_eq!(features.matches_for_triple(&x86_64, "neon"), Some(false));
+        assert_eq!(features.matches_for_triple(&aarch64, "neon"), Some(true));
+        assert_eq!(features.matches_for_triple(&aarch64, "sse2"), Some(false));
+
+        // Unknown features still match themselves.
+        let features = TargetFeatures::features(["my-feature"]);
+        assert_eq!(
+            features.matches_for_triple(&x86_64, "my-feature"),
+            Some(true)
+        );
+
+        assert_eq!(
+            TargetFeatures::Unknown.matches_for_triple(&x86_64, "sse2"),
+            None
+        );
+        assert_eq!(
+            TargetFeatures::All.matches_for_triple(&x86_64, "sse2"),
+            Some(true)
+        );
+
+        assert_eq!(
+            TargetFeatures::features(["sse4.1", "my-feature"]).with_implied_features(&x86_64),
+            TargetFeatures::features(["my-feature", "sse", "sse2", "sse3", "sse4.1", "ssse3"]),
+        );
+
+        // TargetSpec::eval consults implications.
+        let platform = Platform::new(
+            "x86_64-unknown-linux-gnu",
+            TargetFeatures::features(["avx2"]),
+        )
+        .unwrap();
+        let spec = TargetSpec::new("cfg(target_feature = \"sse2\")").unwrap();
+        assert_eq!(spec.eval(&platform), Some(true));
+    }
+
+    #[test]
+    fn target_cpus() {
+        let x86_64 = Triple::new("x86_64-unknown-linux-gnu").unwrap();
+        let aarch64 = Triple::new("aarch64-apple-darwin").unwrap();
+
+        let v2 = TargetFeatures::for_target_cpu(&x86_64, "x86-64-v2").unwrap();
+        assert_eq!(
+            v2,
+            TargetFeatures::features([
+                "cmpxchg16b",
+                "fxsr",
+                "lahfsahf",
+                "popcnt",
+                "sse",
+                "sse2",
+                "sse3",
+                "sse4.1",
+                "sse4.2",
+                "ssse3",
+            ]),
+        );
+
+        let znver4 = TargetFeatures::for_target_cpu(&x86_64, "znver4").unwrap();
+        for feature in ["avx512vl", "vaes", "sha", "sse4a", "aes", "sse2"] {
+            assert_eq!(znver4.matches(feature), Some(true), "znver4 has {feature}");
+        }
+
+        let m1 = TargetFeatures::for_target_cpu(&aarch64, "apple-m1").unwrap();
+        for feature in ["neon", "dotprod", "lse", "sha2", "fhm", "rcpc"] {
+            assert_eq!(m1.matches(feature), Some(true), "apple-m1 has {feature}");
+        }
+        assert_eq!(m1.matches("sve"), Some(false));
+
+        // Every base CPU must exist.
+        for table in ARCH_TABLES {
+            for cpu in table.cpus {
+                if let Some(base) = cpu.base {
+                    assert!(
+                        table.cpus.iter().any(|cpu| cpu.name == base),
+                        "base {base} of {} exists",
+                        cpu.name
+                    );
+                }
+            }
+        }
+
+        assert!(TargetFeatures::for_target_cpu(&x86_64, "apple-m1").is_none());
+        assert!(TargetFeatures::for_target_cpu(&aarch64, "native").is_none());
+    }
+}
@@ -9,7 +9,7 @@ use cfg_expr::{
     TargetPredicate,
     expr::TargetMatcher,
     target_lexicon,
-    targets::{TargetInfo, get_builtin_target_by_triple},
+    targets::{ALL_BUILTINS, TargetInfo, get_builtin_target_by_triple},
 };
 use std::{borrow::Cow, cmp::Ordering, hash, str::FromStr};
 
@@ -156,15 +156,34 @@ impl Triple {
         triple_str: impl Into<Cow<'static, str>>,
         cfg_text: &str,
     ) -> Result<Self, crate::errors::CustomTripleCreateError> {
+        Self::new_custom_cfg_with_features(triple_str, cfg_text).map(|(triple, _)| triple)
+    }
+
+    /// Like [`Self::new_custom_cfg`], but also returns the target features listed in the cfg
+    /// output.
+    #[cfg(feature = "custom-cfg")]
+    pub(crate) fn new_custom_cfg_with_features(
+        triple_str: impl Into<Cow<'static, str>>,
+        cfg_text: &str,
+    ) -> Result<(Self, std::collections::BTreeSet<String>), crate::errors::CustomTripleCreateError>
+    {
         let triple_str = triple_str.into();
-        let (target_info, _features) =
+        let (target_info, features) =
             crate::custom_cfg::parse_cfg_output(triple_str.clone(), cfg_text)?;
-        Ok(Self {
+        let triple = Self {
             inner: TripleInner::Custom {
                 target_info: Box::new(target_info),
                 #[cfg(feature = "summaries")]
                 custom_source: CustomSource::Cfg(cfg_text.to_string()),
             },
+        };
+        Ok((triple, features))
+    }
+
+    /// Returns all builtin triples known to `target-spec`, sorted by triple string.
+    pub(crate) fn all_builtins() -> impl ExactSizeIterator<Item = Self> {
+        ALL_BUILTINS.iter().map(|target_info| Self {
+            inner: TripleInner::Builtin(target_info),
         })
     }
 
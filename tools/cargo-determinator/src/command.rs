@@ -0,0 +1,647 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use clap::{Parser, ValueEnum};
+use color_eyre::eyre::{Result, WrapErr, bail, eyre};
+use determinator::{
+    AffectedReasons, Determinator, DeterminatorSet, Utf8Paths0, rules::DeterminatorRules,
+};
+use guppy::{
+    CargoMetadata, MetadataCommand,
+    graph::{DependencyDirection, PackageGraph, PackageSet},
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    process::Command,
+};
+use tempfile::TempDir;
+
+/// Figure out which workspace packages changed between two revisions.
+///
+/// For more about cargo-determinator, see <https://docs.rs/cargo-determinator>.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+pub struct Args {
+    #[clap(flatten)]
+    input: InputOpts,
+
+    /// Path to determinator rules [default: .config/determinator.toml in the workspace root, if
+    /// it exists]
+    #[clap(long, value_name = "PATH")]
+    rules: Option<Utf8PathBuf>,
+
+    /// Format to print affected packages in
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+
+    /// Explain why each package was affected (text and json formats only)
+    #[clap(long)]
+    explain: bool,
+
+    /// Which affected packages to print (text and package-args formats, and --explain)
+    ///
+    /// Selecting the build stage runs additional build simulations without dev-dependencies. The
+    /// nextest-filter format always selects tests for packages affected in either stage.
+    #[clap(long, value_enum, default_value_t = Stage::All)]
+    stage: Stage,
+}
+
+impl Args {
+    /// Executes the command, writing results to the given writer.
+    pub fn exec(self, out: &mut dyn Write) -> Result<()> {
+        match self.output_format {
+            OutputFormat::PackageArgs if self.explain => {
+                bail!("--explain is not supported with --output-format package-args");
+            }
+            OutputFormat::NextestFilter if self.explain => {
+                bail!("--explain is not supported with --output-format nextest-filter");
+            }
+            _ => {}
+        }
+        let inputs = self.input.load()?;
+
+        let rules = match &self.rules {
+            Some(path) => Some(read_rules(path)?),
+            None => {
+                let path = inputs
+                    .new
+                    .workspace()
+                    .root()
+                    .join(DeterminatorRules::DEFAULT_CONFIG_PATH);
+                if path.is_file() {
+                    Some(read_rules(&path)?)
+                } else {
+                    None
+                }
+            }
+        };
+
+        let mut determinator = Determinator::new(&inputs.old, &inputs.new);
+        if let Some(rules) = &rules {
+            determinator
+                .set_rules(rules)
+                .wrap_err("error applying determinator rules")?;
+        }
+        if let Some((old_root, new_root)) = &inputs.roots {
+            determinator.set_root_manifest_contents(
+                &read_workspace_file(old_root, ROOT_MANIFEST_PATHS)?,
+                &read_workspace_file(new_root, ROOT_MANIFEST_PATHS)?,
+            );
+            determinator.set_cargo_config_contents(
+                &read_workspace_file(old_root, CARGO_CONFIG_PATHS)?,
+                &read_workspace_file(new_root, CARGO_CONFIG_PATHS)?,
+            );
+        }
+        if matches!(self.output_format, OutputFormat::NextestFilter) {
+            determinator.set_build_target_granularity(true);
+        }
+        if matches!(self.stage, Stage::Build) {
+            determinator.set_separate_build_stage(true);
+        }
+        determinator.add_changed_paths(&inputs.changed_paths);
+        determinator.add_renamed_paths(inputs.renamed_paths.iter().map(|(old, new)| (old, new)));
+        let determinator_set = determinator.compute();
+
+        self.output_format
+            .write(&determinator_set, self.stage, self.explain, out)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Parser)]
+struct InputOpts {
+    /// Git revision to compare against
+    #[clap(
+        long,
+        value_name = "REV",
+        required_unless_present = "old_metadata",
+        conflicts_with = "old_metadata"
+    )]
+    old_rev: Option<String>,
+
+    /// Git revision to compare [default: the working directory]
+    #[clap(long, value_name = "REV", requires = "old_rev")]
+    new_rev: Option<String>,
+
+    /// Path to `cargo metadata` JSON output to compare against
+    #[clap(long, value_name = "PATH", requires_all = ["new_metadata", "changed_paths"])]
+    old_metadata: Option<Utf8PathBuf>,
+
+    /// Path to `cargo metadata` JSON output to compare
+    #[clap(long, value_name = "PATH", requires = "old_metadata")]
+    new_metadata: Option<Utf8PathBuf>,
+
+    /// File with null-separated changed paths, relative to the workspace root ("-" for stdin)
+    ///
+    /// This is typically the output of `git diff -z --name-only <old rev> <new rev>`. With
+    /// `--old-rev`, this defaults to running that command, along with
+    /// `git ls-files -z --others --exclude-standard` to include untracked files if `--new-rev`
+    /// isn't set.
+    #[clap(long, value_name = "PATH")]
+    changed_paths: Option<Utf8PathBuf>,
+
+    /// File with renamed paths, as produced by `git diff -z --name-status -M` ("-" for stdin)
+    ///
+    /// Renamed paths are used to match packages that were moved or renamed. Entries other than
+    /// renames are ignored. With `--old-rev`, this defaults to running that command.
+    #[clap(long, value_name = "PATH")]
+    renamed_paths: Option<Utf8PathBuf>,
+}
+
+impl InputOpts {
+    fn load(&self) -> Result<Inputs> {
+        match (&self.old_metadata, &self.new_metadata, &self.old_rev) {
+            (Some(old_metadata), Some(new_metadata), None) => {
+                let changed_paths = match &self.changed_paths {
+                    Some(path) => read_changed_paths(path)?,
+                    None => bail!("--changed-paths is required with --old-metadata"),
+                };
+                let renamed_paths = match &self.renamed_paths {
+                    Some(path) => read_renamed_paths(path)?,
+                    None => vec![],
+                };
+                Ok(Inputs {
+                    old: read_metadata(old_metadata)?,
+                    new: read_metadata(new_metadata)?,
+                    changed_paths,
+                    renamed_paths,
+                    roots: None,
+                    _worktrees: vec![],
+                })
+            }
+            (None, None, Some(old_rev)) => self.load_git(old_rev, self.new_rev.as_deref()),
+            _ => bail!("either --old-rev or both --old-metadata and --new-metadata must be set"),
+        }
+    }
+
+    fn load_git(&self, old_rev: &str, new_rev: Option<&str>) -> Result<Inputs> {
+        // The package graph for the current directory is only needed if it's being compared, so
+        // use the much cheaper `cargo locate-project` to find the workspace root.
+        let workspace_root = locate_workspace_root()?;
+        let repo_root = git_output(&workspace_root, ["rev-parse", "--show-toplevel"])?;
+        let repo_root = Utf8PathBuf::from(repo_root.trim_end());
+        let rel_root = workspace_root.strip_prefix(&repo_root).wrap_err_with(|| {
+            format!("workspace root {workspace_root} is not within repository root {repo_root}")
+        })?;
+
+        let mut worktrees = vec![];
+        let old_worktree = GitWorktree::new(&repo_root, old_rev)?;
+        let old = old_worktree.build_graph(rel_root)?;
+        let old_root = old_worktree.path.join(rel_root);
+        worktrees.push(old_worktree);
+
+        let (new, new_root) = match new_rev {
+            Some(new_rev) => {
+                let new_worktree = GitWorktree::new(&repo_root, new_rev)?;
+                let new = new_worktree.build_graph(rel_root)?;
+                let new_root = new_worktree.path.join(rel_root);
+                worktrees.push(new_worktree);
+                (new, new_root)
+            }
+            None => {
+                let current = MetadataCommand::new()
+                    .current_dir(&workspace_root)
+                    .build_graph()
+                    .wrap_err("building package graph failed")?;
+                (current, workspace_root.clone())
+            }
+        };
+
+        let changed_paths = match &self.changed_paths {
+            Some(path) => read_changed_paths(path)?,
+            None => {
+                // --relative makes paths relative to the workspace root if it isn't the same as the
+                // repository root.
+                // --end-of-options ensures that revisions starting with - aren't read as options.
+                let mut args = vec![
+                    "diff",
+                    "-z",
+                    "--name-only",
+                    "--relative",
+                    "--end-of-options",
+                    old_rev,
+                ];
+                args.extend(new_rev);
+                let mut changed_paths = git_output(&workspace_root, args)?;
+                if new_rev.is_none() {
+                    // Untracked files in the working directory are changes too. ls-files prints
+                    // paths relative to the current directory, like --relative above.
+                    changed_paths.push_str(&git_output(
+                        &workspace_root,
+                        ["ls-files", "-z", "--others", "--exclude-standard"],
+                    )?);
+                }
+                parse_changed_paths(changed_paths.into_bytes())?
+            }
+        };
+        let renamed_paths = match &self.renamed_paths {
+            Some(path) => read_renamed_paths(path)?,
+            None => {
+                let mut args = vec![
+                    "diff",
+                    "-z",
+                    "--name-status",
+                    "-M",
+                    "--diff-filter=R",
+                    "--relative",
+                    "--end-of-options",
+                    old_rev,
+                ];
+                args.extend(new_rev);
+                parse_renamed_paths(git_output(&workspace_root, args)?.into_bytes())?
+            }
+        };
+
+        Ok(Inputs {
+            old,
+            new,
+            changed_paths,
+            renamed_paths,
+            roots: Some((old_root, new_root)),
+            _worktrees: worktrees,
+        })
+    }
+}
+
+struct Inputs {
+    old: PackageGraph,
+    new: PackageGraph,
+    changed_paths: Utf8Paths0,
+    // Pairs of old and new paths, used to match packages that were moved or renamed.
+    renamed_paths: Vec<(Utf8PathBuf, Utf8PathBuf)>,
+    // The old and new workspace roots on disk, if available. Used to read the root manifest and
+    // Cargo config for a finer-grained analysis of changes to them.
+    roots: Option<(Utf8PathBuf, Utf8PathBuf)>,
+    // Worktrees are kept around until the determinator is done, so that rules can be read from
+    // the new one.
+    _worktrees: Vec<GitWorktree>,
+}
+
+/// A temporary Git worktree checked out at a particular revision.
+struct GitWorktree {
+    repo_root: Utf8PathBuf,
+    path: Utf8PathBuf,
+    // Held so that the directory is cleaned up on drop.
+    _dir: TempDir,
+}
+
+impl GitWorktree {
+    fn new(repo_root: &Utf8Path, rev: &str) -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("cargo-determinator-")
+            .tempdir()
+            .wrap_err("error creating temporary directory")?;
+        let path = Utf8Path::from_path(dir.path())
+            .ok_or_else(|| {
+                eyre!(
+                    "temporary directory {} is not valid UTF-8",
+                    dir.path().display()
+                )
+            })?
+            .join("worktree");
+        git_output(
+            repo_root,
+            [
+                "worktree",
+                "add",
+                "--detach",
+                "--end-of-options",
+                path.as_str(),
+                rev,
+            ],
+        )
+        .wrap_err_with(|| format!("error checking out revision {rev}"))?;
+
+        Ok(Self {
+            repo_root: repo_root.to_owned(),
+            path,
+            _dir: dir,
+        })
+    }
+
+    fn build_graph(&self, rel_root: &Utf8Path) -> Result<PackageGraph> {
+        let mut command = MetadataCommand::new();
+        command.current_dir(self.path.join(rel_root));
+        command
+            .build_graph()
+            .wrap_err_with(|| format!("building package graph at {} failed", self.path))
+    }
+}
+
+impl Drop for GitWorktree {
+    fn drop(&mut self) {
+        // Errors are ignored here: at worst, a stale entry is left behind for `git worktree prune`
+        // to clean up.
+        let _ = git_output(
+            &self.repo_root,
+            ["worktree", "remove", "--force", self.path.as_str()],
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Stage {
+    /// Packages that need to be built or tested
+    All,
+    /// Packages that need to be built, not counting changes that only affect tests
+    Build,
+    /// Packages whose tests need to be run
+    Test,
+}
+
+impl Stage {
+    fn affected_set<'a, 'g>(self, determinator_set: &'a DeterminatorSet<'g>) -> &'a PackageSet<'g> {
+        match self {
+            Stage::All => &determinator_set.affected_set,
+            Stage::Build => determinator_set
+                .build_affected_set
+                .as_ref()
+                .expect("separate build stage is enabled for --stage build"),
+            Stage::Test => &determinator_set.test_affected_set,
+        }
+    }
+
+    fn reasons<'a, 'g>(self, determinator_set: &'a DeterminatorSet<'g>) -> &'a AffectedReasons<'g> {
+        match self {
+            Stage::All | Stage::Test => &determinator_set.reasons,
+            Stage::Build => determinator_set
+                .build_reasons
+                .as_ref()
+                .expect("separate build stage is enabled for --stage build"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum OutputFormat {
+    /// Affected package names, one per line
+    Text,
+    /// A JSON object with affected, build-affected (with --stage build), test-affected,
+    /// path-changed and summary-changed package names, along with renamed and deleted packages
+    Json,
+    /// Affected packages as `-p` arguments to Cargo commands
+    PackageArgs,
+    /// A nextest filterset selecting affected test binaries
+    NextestFilter,
+}
+
+impl OutputFormat {
+    fn write(
+        self,
+        determinator_set: &DeterminatorSet<'_>,
+        stage: Stage,
+        explain: bool,
+        out: &mut dyn Write,
+    ) -> Result<()> {
+        let affected_set = stage.affected_set(determinator_set);
+        let reasons = stage.reasons(determinator_set);
+        match self {
+            OutputFormat::Text => {
+                if explain {
+                    for (name, reasons) in package_reasons(reasons, affected_set) {
+                        writeln!(out, "{name}")?;
+                        for reason in reasons {
+                            writeln!(out, "  - {reason}")?;
+                        }
+                    }
+                } else {
+                    for name in package_names(affected_set) {
+                        writeln!(out, "{name}")?;
+                    }
+                }
+            }
+            OutputFormat::Json => {
+                let output = JsonOutput {
+                    affected: package_names(&determinator_set.affected_set),
+                    build_affected: determinator_set
+                        .build_affected_set
+                        .as_ref()
+                        .map(package_names),
+                    test_affected: package_names(&determinator_set.test_affected_set),
+                    path_changed: package_names(&determinator_set.path_changed_set),
+                    summary_changed: package_names(&determinator_set.summary_changed_set),
+                    renamed: determinator_set
+                        .renamed_packages
+                        .iter()
+                        .map(|renamed| JsonRenamed {
+                            name: renamed.package.name(),
+                            path: renamed.package.source().workspace_path(),
+                            old_name: &renamed.old_name,
+                            old_path: &renamed.old_workspace_path,
+                        })
+                        .collect(),
+                    deleted: determinator_set
+                        .deleted_packages
+                        .iter()
+                        .map(|deleted| JsonDeleted {
+                            name: &deleted.name,
+                            path: &deleted.workspace_path,
+                        })
+                        .collect(),
+                    reasons: explain.then(|| package_reasons(reasons, affected_set)),
+                };
+                serde_json::to_writer_pretty(&mut *out, &output)?;
+                writeln!(out)?;
+            }
+            OutputFormat::PackageArgs => {
+                let args: Vec<_> = package_names(affected_set)
+                    .into_iter()
+                    .map(|name| format!("-p {name}"))
+                    .collect();
+                writeln!(out, "{}", args.join(" "))?;
+            }
+            OutputFormat::NextestFilter => {
+                let affected_targets = determinator_set
+                    .affected_targets
+                    .as_ref()
+                    .expect("build target granularity is enabled for this format");
+                writeln!(out, "{}", affected_targets.nextest_filter())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonOutput<'g, 'a> {
+    affected: Vec<&'g str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    build_affected: Option<Vec<&'g str>>,
+    test_affected: Vec<&'g str>,
+    path_changed: Vec<&'g str>,
+    summary_changed: Vec<&'g str>,
+    renamed: Vec<JsonRenamed<'g, 'a>>,
+    deleted: Vec<JsonDeleted<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasons: Option<BTreeMap<&'g str, Vec<String>>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonRenamed<'g, 'a> {
+    name: &'g str,
+    path: Option<&'g Utf8Path>,
+    old_name: &'a str,
+    old_path: &'a Utf8Path,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonDeleted<'a> {
+    name: &'a str,
+    path: &'a Utf8Path,
+}
+
+static ROOT_MANIFEST_PATHS: &[&str] = &["Cargo.toml"];
+static CARGO_CONFIG_PATHS: &[&str] = &[".cargo/config.toml", ".cargo/config"];
+
+/// Reads the first of these paths that exists within the workspace root, or returns an empty string
+/// if none of them exist.
+fn read_workspace_file(root: &Utf8Path, paths: &[&str]) -> Result<String> {
+    for path in paths {
+        let path = root.join(path);
+        if path.is_file() {
+            return std::fs::read_to_string(&path)
+                .wrap_err_with(|| format!("error reading {path}"));
+        }
+    }
+    Ok(String::new())
+}
+
+fn package_names<'g>(package_set: &PackageSet<'g>) -> Vec<&'g str> {
+    let mut names: Vec<_> = package_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| package.name())
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+fn package_reasons<'g>(
+    reasons: &AffectedReasons<'g>,
+    affected_set: &PackageSet<'g>,
+) -> BTreeMap<&'g str, Vec<String>> {
+    affected_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| {
+            let reasons = reasons
+                .reasons(package.id())
+                .iter()
+                .map(|reason| reason.to_string())
+                .collect();
+            (package.name(), reasons)
+        })
+        .collect()
+}
+
+fn read_metadata(path: &Utf8Path) -> Result<PackageGraph> {
+    let json = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("error reading metadata from {path}"))?;
+    CargoMetadata::parse_json(json)
+        .and_then(|metadata| metadata.build_graph())
+        .wrap_err_with(|| format!("building package graph from {path} failed"))
+}
+
+fn read_rules(path: &Utf8Path) -> Result<DeterminatorRules> {
+    let contents = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("error reading determinator rules from {path}"))?;
+    DeterminatorRules::parse(&contents)
+        .wrap_err_with(|| format!("error parsing determinator rules at {path}"))
+}
+
+fn read_changed_paths(path: &Utf8Path) -> Result<Utf8Paths0> {
+    let mut buf = vec![];
+    if path == "-" {
+        io::stdin()
+            .read_to_end(&mut buf)
+            .wrap_err("error reading changed paths from stdin")?;
+    } else {
+        buf = std::fs::read(path)
+            .wrap_err_with(|| format!("error reading changed paths from {path}"))?;
+    }
+    parse_changed_paths(buf)
+}
+
+fn parse_changed_paths(buf: Vec<u8>) -> Result<Utf8Paths0> {
+    let buf = String::from_utf8(buf).wrap_err("changed paths are not valid UTF-8")?;
+    // Git produces paths with forward slashes on all platforms.
+    Ok(Utf8Paths0::new_forward_slashes(buf))
+}
+
+fn read_renamed_paths(path: &Utf8Path) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    let mut buf = vec![];
+    if path == "-" {
+        io::stdin()
+            .read_to_end(&mut buf)
+            .wrap_err("error reading renamed paths from stdin")?;
+    } else {
+        buf = std::fs::read(path)
+            .wrap_err_with(|| format!("error reading renamed paths from {path}"))?;
+    }
+    parse_renamed_paths(buf)
+}
+
+/// Parses the output of `git diff -z --name-status`, returning pairs of old and new paths for
+/// renames.
+fn parse_renamed_paths(buf: Vec<u8>) -> Result<Vec<(Utf8PathBuf, Utf8PathBuf)>> {
+    let buf = String::from_utf8(buf).wrap_err("renamed paths are not valid UTF-8")?;
+    let mut fields = buf.split_terminator('\0');
+    let mut renamed_paths = vec![];
+    while let Some(status) = fields.next() {
+        // Renames and copies are followed by two paths, and everything else by one.
+        let path_count = if status.starts_with(['R', 'C']) { 2 } else { 1 };
+        let paths: Vec<_> = fields.by_ref().take(path_count).collect();
+        if paths.len() != path_count {
+            bail!("renamed paths ended unexpectedly after status {status}");
+        }
+        if status.starts_with('R') {
+            renamed_paths.push((paths[0].into(), paths[1].into()));
+        }
+    }
+    Ok(renamed_paths)
+}
+
+fn locate_workspace_root() -> Result<Utf8PathBuf> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .args(["locate-project", "--workspace", "--message-format", "plain"])
+        .output()
+        .wrap_err("error running cargo locate-project")?;
+    if !output.status.success() {
+        bail!(
+            "`cargo locate-project` failed with {}:\n{}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+    let manifest_path = String::from_utf8(output.stdout)
+        .wrap_err("`cargo locate-project` output is not valid UTF-8")?;
+    let manifest_path = Utf8Path::new(manifest_path.trim_end());
+    manifest_path
+        .parent()
+        .map(|root| root.to_owned())
+        .ok_or_else(|| eyre!("workspace manifest path {manifest_path} has no parent"))
+}
+
+fn git_output<'a>(dir: &Utf8Path, args: impl IntoIterator<Item = &'a str>) -> Result<String> {
+    let args: Vec<_> = args.into_iter().collect();
+    let output = Command::new("git")
+        .args(&args)
+        .current_dir(dir)
+        .output()
+        .wrap_err("error running git")?;
+    if !output.status.success() {
+        bail!(
+            "`git {}` failed with {}:\n{}",
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr),
+        );
+    }
+    String::from_utf8(output.stdout)
+        .wrap_err_with(|| format!("`git {}` output is not valid UTF-8", args.join(" ")))
+}
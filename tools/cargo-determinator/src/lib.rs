@@ -0,0 +1,130 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `cargo determinator` is a command-line interface to the
+//! [`determinator`](https://docs.rs/determinator) crate. Use it to figure out which workspace
+//! packages need to be rebuilt and retested after a change.
+//!
+//! # Installation
+//!
+//! To install or update `cargo-determinator`, run:
+//!
+//! ```sh
+//! cargo install cargo-determinator --locked
+//! ```
+//!
+//! If `$HOME/.cargo/bin` is in your `PATH`, the `cargo determinator` command will be available.
+//!
+//! # Usage
+//!
+//! ## Comparing Git revisions
+//!
+//! To list packages affected by changes in the working directory since the merge base with
+//! `origin/main`, run this from within the workspace:
+//!
+//! ```sh
+//! cargo determinator --old-rev $(git merge-base origin/main HEAD)
+//! ```
+//!
+//! To compare two revisions, also pass in `--new-rev`:
+//!
+//! ```sh
+//! cargo determinator --old-rev origin/main --new-rev HEAD
+//! ```
+//!
+//! Each revision is checked out into a temporary [Git
+//! worktree](https://git-scm.com/docs/git-worktree) to compute its package graph, and changed paths
+//! are obtained with `git diff -z --name-only`. When comparing against the working directory,
+//! untracked files that aren't ignored are also treated as changed.
+//!
+//! In this mode, the old and new versions of the root `Cargo.toml` and `.cargo/config.toml` are
+//! also compared key by key, so that formatting changes or edits to a single entry in
+//! `[workspace.dependencies]` don't cause every package to be marked changed. (This analysis isn't
+//! done when comparing metadata files.)
+//!
+//! ## Comparing metadata files
+//!
+//! If the package graphs and changed paths have already been computed, for example by an earlier CI
+//! step, pass them in directly:
+//!
+//! ```sh
+//! cargo determinator --old-metadata old.json --new-metadata new.json --changed-paths paths.out
+//! ```
+//!
+//! Metadata files are produced by `cargo metadata --format-version 1 --all-features`, and changed
+//! paths are read as null-separated paths (see
+//! [`Utf8Paths0`](https://docs.rs/determinator/latest/determinator/struct.Utf8Paths0.html)). Use
+//! `--changed-paths -` to read changed paths from standard input.
+//!
+//! ## Moved and renamed packages
+//!
+//! Packages are matched across revisions by name and path. To also match packages that were both
+//! moved and renamed, the determinator uses renamed paths as hints. When comparing Git revisions,
+//! these are obtained with `git diff -z --name-status -M`; when comparing metadata files, pass in
+//! that command's output with `--renamed-paths <PATH>`.
+//!
+//! ## Rules
+//!
+//! By default, [determinator rules](https://docs.rs/determinator/latest/determinator/rules) are
+//! read from `.config/determinator.toml` in the new workspace root, if that file exists. To use a
+//! different file, pass in `--rules <PATH>`.
+//!
+//! ## Output formats
+//!
+//! The format is controlled through `--output-format`:
+//!
+//! * `text` (the default): affected package names, one per line.
+//! * `json`: a JSON object with `affected`, `test-affected`, `path-changed` and `summary-changed`
+//!   lists of package names (and `build-affected` with `--stage build`), along with `renamed` and
+//!   `deleted` lists of packages that were moved, renamed or deleted. See the
+//!   [`DeterminatorSet`](https://docs.rs/determinator/latest/determinator/struct.DeterminatorSet.html)
+//!   documentation for what each list means.
+//! * `package-args`: affected packages as `-p` arguments to Cargo commands. For example:
+//!
+//!   ```sh
+//!   cargo test $(cargo determinator --old-rev origin/main --output-format package-args)
+//!   ```
+//!
+//!   If no packages are affected, this prints an empty line. Be sure to handle that case: a plain
+//!   `cargo test` with no `-p` arguments tests the default workspace members.
+//! * `nextest-filter`: a [nextest filterset](https://nexte.st/docs/filtersets/) that selects the
+//!   tests in affected build targets. A change to a package's `tests/foo.rs` only selects the `foo`
+//!   integration test, rather than all of the package's tests. For example:
+//!
+//!   ```sh
+//!   filter=$(cargo determinator --old-rev origin/main --output-format nextest-filter)
+//!   cargo nextest run -E "$filter"
+//!   ```
+//!
+//!   If no packages are affected, this prints `none()`.
+//!
+//! ## Build and test stages
+//!
+//! Some changes only affect tests: for example, a change to a dev-dependency requires the package's
+//! tests to be rerun, but not the package itself to be rebuilt. If CI builds and tests packages in
+//! separate stages, pass in `--stage build` or `--stage test` to print the packages affected for that
+//! stage. The default, `--stage all`, prints packages affected for either stage.
+//!
+//! `--stage build` runs a second set of build simulations without dev-dependencies, so it takes
+//! roughly twice as long as the other stages. With `--explain`, it only explains packages through
+//! dependencies that are built in that stage.
+//!
+//! ## Explaining results
+//!
+//! Pass in `--explain` with the `text` or `json` formats to also print why each package was
+//! affected: the changed path and rule responsible, the dependencies that changed in a simulated
+//! build, or the chain of dependencies and package rules leading from a changed package.
+//!
+//! # Limitations
+//!
+//! Paths outside the workspace root are not considered when comparing Git revisions. For best
+//! results, place the workspace root at the root of the repository.
+//!
+//! For other limitations, see the [`determinator`
+//! documentation](https://docs.rs/determinator/latest/determinator/#limitations).
+
+mod command;
+
+// Not part of the stable API.
+#[doc(hidden)]
+pub use command::Args;
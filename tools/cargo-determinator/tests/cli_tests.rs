@@ -0,0 +1,376 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_determinator::Args;
+use cfg_if::cfg_if;
+use clap::Parser;
+use std::process::Command;
+
+fn fixture_path(name: &str) -> String {
+    let mut path = Utf8PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("../../fixtures/determinator-paths");
+    path.push(name);
+    path.into_string()
+}
+
+fn metadata_path() -> String {
+    cfg_if! {
+        if #[cfg(windows)] {
+            fixture_path("guppy-win.json")
+        } else {
+            fixture_path("guppy-linux.json")
+        }
+    }
+}
+
+fn run(extra_args: &[&str]) -> String {
+    run_with_changed_paths(&fixture_path("git-diff.out"), extra_args)
+}
+
+fn run_with_changed_paths(changed_paths: &str, extra_args: &[&str]) -> String {
+    let metadata = metadata_path();
+    let mut args = vec![
+        "cargo-determinator",
+        "--old-metadata",
+        &metadata,
+        "--new-metadata",
+        &metadata,
+        "--changed-paths",
+        changed_paths,
+    ];
+    args.extend_from_slice(extra_args);
+
+    let mut out = vec![];
+    Args::try_parse_from(args)
+        .expect("arguments parsed")
+        .exec(&mut out)
+        .expect("cargo determinator succeeded");
+    String::from_utf8(out).expect("output is valid UTF-8")
+}
+
+static EXPECTED_AFFECTED: &[&str] = &[
+    "cargo-compare",
+    "cargo-guppy",
+    "determinator",
+    "fixture-manager",
+    "fixtures",
+    "guppy",
+    "guppy-benchmarks",
+    "guppy-cmdlib",
+    "hakari",
+    "target-spec",
+];
+
+#[test]
+fn output_text() {
+    let output = run(&[]);
+    assert_eq!(output.lines().collect::<Vec<_>>(), EXPECTED_AFFECTED);
+}
+
+#[test]
+fn output_json() {
+    let output = run(&["--output-format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&output).expect("valid JSON");
+    assert_eq!(json["affected"], serde_json::json!(EXPECTED_AFFECTED));
+    assert_eq!(
+        json["path-changed"],
+        serde_json::json!([
+            "cargo-guppy",
+            "determinator",
+            "guppy",
+            "hakari",
+            "target-spec"
+        ]),
+    );
+    assert_eq!(json["summary-changed"], serde_json::json!([]));
+    // build-affected is only computed with --stage build.
+    assert_eq!(json.get("build-affected"), None);
+    assert_eq!(json["test-affected"], serde_json::json!(EXPECTED_AFFECTED));
+    assert_eq!(json["renamed"], serde_json::json!([]));
+    assert_eq!(json["deleted"], serde_json::json!([]));
+}
+
+#[test]
+fn output_package_args() {
+    let output = run(&["--output-format", "package-args"]);
+    let expected: Vec<_> = EXPECTED_AFFECTED
+        .iter()
+        .map(|name| format!("-p {name}"))
+        .collect();
+    assert_eq!(output, format!("{}\n", expected.join(" ")));
+}
+
+#[test]
+fn explain() {
+    let output = run(&["--explain"]);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(
+        &lines[..4],
+        [
+            "cargo-compare",
+            "  - affected through target-spec -> cargo-compare",
+            "cargo-guppy",
+            "  - path cargo-guppy/Cargo.toml is within the package",
+        ]
+    );
+
+    let output = run(&["--explain", "--output-format", "json"]);
+    let json: serde_json::Value = serde_json::from_str(&output).expect("valid JSON");
+    assert_eq!(
+        json["reasons"]["target-spec"],
+        serde_json::json!([
+            "path target-spec/CHANGELOG.md is within the package",
+            "path target-spec/Cargo.toml is within the package",
+        ]),
+    );
+}
+
+#[test]
+fn stage() {
+    let dir = tempfile::tempdir().expect("temp dir created");
+    let changed_paths =
+        Utf8PathBuf::try_from(dir.path().join("changed-paths")).expect("temp dir is valid UTF-8");
+    std::fs::write(&changed_paths, "fixtures/src/details.rs\0").expect("changed paths written");
+
+    // fixtures is only a dev-dependency of determinator, guppy and hakari, so their tests need to be
+    // run but they don't need to be rebuilt.
+    let output = run_with_changed_paths(changed_paths.as_str(), &[]);
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "determinator",
+            "fixture-manager",
+            "fixtures",
+            "guppy",
+            "hakari"
+        ]
+    );
+    let output = run_with_changed_paths(changed_paths.as_str(), &["--stage", "test"]);
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        [
+            "determinator",
+            "fixture-manager",
+            "fixtures",
+            "guppy",
+            "hakari"
+        ]
+    );
+    let output = run_with_changed_paths(
+        changed_paths.as_str(),
+        &["--stage", "build", "--output-format", "package-args"],
+    );
+    assert_eq!(output, "-p fixture-manager -p fixtures\n");
+
+    let output = run_with_changed_paths(
+        changed_paths.as_str(),
+        &["--stage", "build", "--explain", "--output-format", "json"],
+    );
+    let json: serde_json::Value = serde_json::from_str(&output).expect("valid JSON");
+    assert_eq!(
+        json["build-affected"],
+        serde_json::json!(["fixture-manager", "fixtures"])
+    );
+    assert_eq!(
+        json["reasons"],
+        serde_json::json!({
+            "fixture-manager": ["affected through fixtures -> fixture-manager"],
+            "fixtures": ["path fixtures/src/details.rs is within the package"],
+        }),
+    );
+}
+
+#[test]
+fn output_nextest_filter() {
+    let dir = tempfile::tempdir().expect("temp dir created");
+    let changed_paths =
+        Utf8PathBuf::try_from(dir.path().join("changed-paths")).expect("temp dir is valid UTF-8");
+    std::fs::write(
+        &changed_paths,
+        "guppy/tests/graph-tests/graph_tests.rs\0tools/determinator/src/lib.rs\0",
+    )
+    .expect("changed paths written");
+
+    let output = run_with_changed_paths(
+        changed_paths.as_str(),
+        &["--output-format", "nextest-filter"],
+    );
+    assert_eq!(
+        output,
+        "package(determinator) | binary_id(guppy::graph-tests)\n"
+    );
+}
+
+#[test]
+fn custom_rules() {
+    let dir = tempfile::tempdir().expect("temp dir created");
+    let rules_path = Utf8PathBuf::try_from(dir.path().join("determinator.toml"))
+        .expect("temp dir is valid UTF-8");
+    std::fs::write(
+        &rules_path,
+        r#"
+        [[path-rule]]
+        globs = ["guppy/**", "target-spec/**"]
+        mark-changed = []
+        "#,
+    )
+    .expect("rules written");
+
+    // Changes to guppy and target-spec are now ignored.
+    let output = run(&["--rules", rules_path.as_str()]);
+    assert_eq!(
+        output.lines().collect::<Vec<_>>(),
+        ["cargo-guppy", "determinator", "fixture-manager", "hakari"]
+    );
+}
+
+#[test]
+fn renamed_paths() {
+    let dir = tempfile::tempdir().expect("temporary directory created");
+    let renamed_paths = Utf8PathBuf::try_from(dir.path().join("renamed.out")).expect("UTF-8 path");
+
+    // The old and new metadata are the same, so every package is matched to itself regardless of
+    // renamed paths.
+    std::fs::write(
+        &renamed_paths,
+        "M\0guppy/src/lib.rs\0R095\0guppy/src/lib.rs\0target-spec/src/lib.rs\0",
+    )
+    .expect("renamed paths written");
+    let output = run(&[
+        "--renamed-paths",
+        renamed_paths.as_str(),
+        "--output-format",
+        "json",
+    ]);
+    let json: serde_json::Value = serde_json::from_str(&output).expect("valid JSON");
+    assert_eq!(json["affected"], serde_json::json!(EXPECTED_AFFECTED));
+    assert_eq!(json["renamed"], serde_json::json!([]));
+    assert_eq!(json["deleted"], serde_json::json!([]));
+
+    // A rename must be followed by two paths.
+    std::fs::write(&renamed_paths, "R100\0guppy/src/lib.rs\0").expect("renamed paths written");
+    let metadata = metadata_path();
+    let changed_paths = fixture_path("git-diff.out");
+    let args = Args::try_parse_from([
+        "cargo-determinator",
+        "--old-metadata",
+        &metadata,
+        "--new-metadata",
+        &metadata,
+        "--changed-paths",
+        &changed_paths,
+        "--renamed-paths",
+        renamed_paths.as_str(),
+    ])
+    .expect("arguments parsed");
+    let err = args
+        .exec(&mut vec![])
+        .expect_err("truncated renamed paths are rejected");
+    assert!(
+        err.to_string().contains("ended unexpectedly"),
+        "unexpected error: {err}"
+    );
+}
+
+#[test]
+fn missing_inputs() {
+    Args::try_parse_from(["cargo-determinator"]).expect_err("an input is required");
+    Args::try_parse_from([
+        "cargo-determinator",
+        "--old-metadata",
+        "old.json",
+        "--new-metadata",
+        "new.json",
+    ])
+    .expect_err("--changed-paths is required with metadata");
+    Args::try_parse_from([
+        "cargo-determinator",
+        "--old-rev",
+        "HEAD",
+        "--old-metadata",
+        "old.json",
+        "--new-metadata",
+        "new.json",
+        "--changed-paths",
+        "paths.out",
+    ])
+    .expect_err("--old-rev conflicts with --old-metadata");
+}
+
+#[test]
+fn git_working_tree() {
+    let temp_dir = tempfile::tempdir().expect("created temp dir");
+    let root = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+    let write = |path: &str, contents: &str| {
+        let path = root.join(path);
+        std::fs::create_dir_all(path.parent().expect("path has a parent")).expect("created dir");
+        std::fs::write(&path, contents).expect("wrote file");
+    };
+    write(
+        "Cargo.toml",
+        "[workspace]\nmembers = [\"a\", \"b\"]\nresolver = \"2\"\n",
+    );
+    write(".gitignore", "/target\n*.log\n");
+    for name in ["a", "b"] {
+        write(
+            &format!("{name}/Cargo.toml"),
+            &format!("[package]\nname = \"{name}\"\nversion = \"0.1.0\"\nedition = \"2021\"\n"),
+        );
+        write(&format!("{name}/src/lib.rs"), "");
+    }
+
+    let run_command = |program: &str, args: &[&str]| {
+        let output = Command::new(program)
+            .args(args)
+            .current_dir(root)
+            .output()
+            .unwrap_or_else(|err| panic!("error running {program}: {err}"));
+        assert!(
+            output.status.success(),
+            "{program} {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).expect("output is valid UTF-8")
+    };
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    run_command(&cargo, &["generate-lockfile", "--offline"]);
+    run_command("git", &["init", "--quiet"]);
+    run_command("git", &["add", "."]);
+    run_command(
+        "git",
+        &[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "--quiet",
+            "--message",
+            "initial",
+        ],
+    );
+
+    // Untracked files count as changes, but ignored ones don't.
+    write("b/src/new.rs", "");
+    write("a/debug.log", "");
+
+    let output = run_command(
+        env!("CARGO_BIN_EXE_cargo-determinator"),
+        &["--old-rev", "HEAD"],
+    );
+    assert_eq!(output, "b\n", "only b is affected");
+
+    // Revisions that start with - aren't passed to git as options.
+    let output = Command::new(env!("CARGO_BIN_EXE_cargo-determinator"))
+        .arg("--old-rev=--orphan")
+        .current_dir(root)
+        .output()
+        .expect("ran cargo-determinator");
+    assert!(!output.status.success(), "--orphan is not a valid revision");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("error checking out revision --orphan"),
+        "unexpected error: {stderr}"
+    );
+}
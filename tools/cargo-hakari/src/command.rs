@@ -481,10 +481,14 @@ fn make_builder_and_output(
     let config: HakariConfig = contents
         .parse()
         .wrap_err_with(|| format!("error deserializing Hakari config at {config_path}"))?;
+    // Custom platform paths are relative to the directory the config is in.
+    let config_dir = config_path
+        .parent()
+        .expect("config path is within the workspace root");
 
     let builder = config
         .builder
-        .to_hakari_builder(package_graph)
+        .to_hakari_builder_relative_to(package_graph, config_dir)
         .wrap_err_with(|| format!("error resolving Hakari config at {config_path}"))?;
     let hakari_output = config.output.to_options();
 
@@ -97,6 +97,25 @@
 //! ]
 //! ```
 //!
+//! Custom platforms, such as embedded targets not built into `rustc`, can be specified as tables
+//! that refer to either a [target definition
+//! JSON](https://docs.rust-embedded.org/embedonomicon/custom-target.html) file, or a file
+//! containing the output of `rustc --print cfg --target <target>`. Paths are relative to the
+//! directory containing `hakari.toml`.
+//!
+//! ```toml
+//! [[platforms]]
+//! triple = "x86_64-unknown-linux-gnu"
+//!
+//! [[platforms]]
+//! triple = "thumbv7em-acme-none-eabihf"
+//! custom-json-path = "../targets/thumbv7em-acme-none-eabihf.json"
+//!
+//! [[platforms]]
+//! triple = "riscv32imc-acme-none-elf"
+//! custom-cfg-path = "../targets/riscv32imc-acme-none-elf.cfg"
+//! ```
+//!
 //! ## traversal-excludes
 //!
 //! Crates to exclude while traversing the dependency graph.
@@ -0,0 +1,418 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Semantic analysis of changes to the root `Cargo.toml` and `.cargo/config.toml`.
+
+use crate::rules::MarkChangedImpl;
+use guppy::{
+    PackageId,
+    graph::{DependencyDirection, PackageGraph, PackageMetadata},
+};
+use std::collections::{BTreeMap, BTreeSet};
+use toml::{Value, value::Table};
+
+/// The result of comparing the old and new contents of a workspace file.
+#[derive(Clone, Debug)]
+pub(crate) struct ContentsAnalysis<'g> {
+    pub(crate) changes: Vec<ContentsChange<'g>>,
+}
+
+/// A single changed key within a workspace file, along with the packages it marks changed.
+#[derive(Clone, Debug)]
+pub(crate) struct ContentsChange<'g> {
+    /// The dotted path to the changed key, or `None` if the file couldn't be parsed.
+    pub(crate) key: Option<String>,
+    pub(crate) mark_changed: MarkChangedImpl<'g>,
+}
+
+impl<'g> ContentsAnalysis<'g> {
+    /// Analyzes changes to the root `Cargo.toml` of a workspace.
+    pub(crate) fn root_manifest(
+        old_graph: &PackageGraph,
+        new_graph: &'g PackageGraph,
+        old: &str,
+        new: &str,
+    ) -> Self {
+        Self::new(old_graph, new_graph, old, new, FileKind::RootManifest)
+    }
+
+    /// Analyzes changes to the `.cargo/config.toml` at the root of a workspace.
+    pub(crate) fn cargo_config(
+        old_graph: &PackageGraph,
+        new_graph: &'g PackageGraph,
+        old: &str,
+        new: &str,
+    ) -> Self {
+        Self::new(old_graph, new_graph, old, new, FileKind::CargoConfig)
+    }
+
+    fn new(
+        old_graph: &PackageGraph,
+        new_graph: &'g PackageGraph,
+        old: &str,
+        new: &str,
+        kind: FileKind,
+    ) -> Self {
+        let (old, new) = match (parse(old), parse(new)) {
+            (Some(old), Some(new)) => (old, new),
+            _ => {
+                // Don't try to be clever about files that can't be parsed.
+                return Self {
+                    changes: vec![ContentsChange {
+                        key: None,
+                        mark_changed: MarkChangedImpl::All,
+                    }],
+                };
+            }
+        };
+
+        let mut analyzer = Analyzer {
+            old_graph,
+            new_graph,
+            changes: vec![],
+        };
+        for (key, old_value, new_value) in changed_entries(Some(&old), Some(&new)) {
+            match (kind, key) {
+                (_, "patch") => analyzer.patch(old_value, new_value),
+                (_, "profile") => analyzer.profile(old_value, new_value),
+                (FileKind::RootManifest, "workspace") => analyzer.workspace(old_value, new_value),
+                (FileKind::RootManifest, "replace") => analyzer.replace(old_value, new_value),
+                (FileKind::RootManifest, key) if ROOT_PACKAGE_KEYS.contains(&key) => {
+                    analyzer.root_package(key)
+                }
+                (FileKind::CargoConfig, key) if CONFIG_IGNORED_KEYS.contains(&key) => {}
+                (_, key) => analyzer.mark_all(key.to_owned()),
+            }
+        }
+
+        Self {
+            changes: analyzer.changes,
+        }
+    }
+
+    /// Returns the first change that marks all packages changed, if any.
+    pub(crate) fn marks_all(&self) -> Option<&ContentsChange<'g>> {
+        self.changes
+            .iter()
+            .find(|change| matches!(change.mark_changed, MarkChangedImpl::All))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum FileKind {
+    RootManifest,
+    CargoConfig,
+}
+
+/// Keys in the root `Cargo.toml` that only apply to the package at the workspace root.
+static ROOT_PACKAGE_KEYS: &[&str] = &[
+    "package",
+    "project",
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+    "target",
+    "features",
+    "badges",
+    "lints",
+];
+
+/// Keys in `.cargo/config.toml` that don't influence builds.
+static CONFIG_IGNORED_KEYS: &[&str] = &[
+    "alias",
+    "cache",
+    "cargo-new",
+    "credential-alias",
+    "doc",
+    "future-incompat-report",
+    "http",
+    "install",
+    "net",
+    "registries",
+    "registry",
+    "term",
+];
+
+struct Analyzer<'g, 'a> {
+    old_graph: &'a PackageGraph,
+    new_graph: &'g PackageGraph,
+    changes: Vec<ContentsChange<'g>>,
+}
+
+impl<'g> Analyzer<'g, '_> {
+    fn workspace(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        let Some(entries) = changed_table_entries(old, new) else {
+            return self.mark_all("workspace".to_owned());
+        };
+        for (key, old_value, new_value) in entries {
+            match key {
+                // These don't influence how any package is built.
+                "members" | "exclude" | "default-members" | "metadata" => {}
+                "dependencies" => {
+                    let Some(deps) = changed_table_entries(old_value, new_value) else {
+                        self.mark_all("workspace.dependencies".to_owned());
+                        continue;
+                    };
+                    for (dep_name, _, _) in deps {
+                        // Only members that list this dependency can inherit it.
+                        let packages = self
+                            .new_graph
+                            .workspace()
+                            .iter()
+                            .filter(|package| {
+                                package
+                                    .direct_links()
+                                    .any(|link| link.dep_name() == dep_name)
+                            })
+                            .collect();
+                        self.mark(format!("workspace.dependencies.{dep_name}"), packages);
+                    }
+                }
+                "package" => {
+                    let Some(fields) = changed_table_entries(old_value, new_value) else {
+                        self.mark_all("workspace.package".to_owned());
+                        continue;
+                    };
+                    for (field, _, _) in fields {
+                        let packages = self.metadata_changed_members();
+                        self.mark(format!("workspace.package.{field}"), packages);
+                    }
+                }
+                other => self.mark_all(format!("workspace.{other}")),
+            }
+        }
+    }
+
+    fn patch(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        let Some(sources) = changed_table_entries(old, new) else {
+            return self.mark_all("patch".to_owned());
+        };
+        for (source, old_value, new_value) in sources {
+            let Some(patches) = changed_table_entries(old_value, new_value) else {
+                self.mark_all(format!("patch.{source}"));
+                continue;
+            };
+            for (name, old_patch, new_patch) in patches {
+                // A patch can be renamed through the `package` key.
+                let mut names = BTreeSet::from([name]);
+                names.extend(
+                    [old_patch, new_patch]
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|patch| patch.get("package")?.as_str()),
+                );
+                let packages = self.dependents(names);
+                self.mark(format!("patch.{source}.{name}"), packages);
+            }
+        }
+    }
+
+    fn replace(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        let Some(specs) = changed_table_entries(old, new) else {
+            return self.mark_all("replace".to_owned());
+        };
+        for (spec, _, _) in specs {
+            let key = format!("replace.{spec}");
+            match spec_name(spec) {
+                Some(name) => {
+                    let packages = self.dependents([name]);
+                    self.mark(key, packages);
+                }
+                None => self.mark_all(key),
+            }
+        }
+    }
+
+    fn profile(&mut self, old: Option<&Value>, new: Option<&Value>) {
+        let Some(profiles) = changed_table_entries(old, new) else {
+            return self.mark_all("profile".to_owned());
+        };
+        for (profile, old_value, new_value) in profiles {
+            let Some(settings) = changed_table_entries(old_value, new_value) else {
+                self.mark_all(format!("profile.{profile}"));
+                continue;
+            };
+            for (setting, old_setting, new_setting) in settings {
+                if setting != "package" {
+                    self.mark_all(format!("profile.{profile}.{setting}"));
+                    continue;
+                }
+                let Some(overrides) = changed_table_entries(old_setting, new_setting) else {
+                    self.mark_all(format!("profile.{profile}.package"));
+                    continue;
+                };
+                for (spec, _, _) in overrides {
+                    let key = format!("profile.{profile}.package.{spec}");
+                    match spec_name(spec) {
+                        Some(name) => {
+                            let packages = self.dependents([name]);
+                            self.mark(key, packages);
+                        }
+                        // This override applies to all dependencies, or to packages that can't
+                        // be determined.
+                        None => self.mark_all(key),
+                    }
+                }
+            }
+        }
+    }
+
+    fn root_package(&mut self, key: &str) {
+        match self.new_graph.workspace().member_by_path("") {
+            Ok(package) => self.mark(key.to_owned(), vec![package]),
+            // Without a root package, these keys are invalid. Be conservative.
+            Err(_) => self.mark_all(key.to_owned()),
+        }
+    }
+
+    fn mark(&mut self, key: String, packages: Vec<PackageMetadata<'g>>) {
+        self.changes.push(ContentsChange {
+            key: Some(key),
+            mark_changed: MarkChangedImpl::Packages(packages),
+        });
+    }
+
+    fn mark_all(&mut self, key: String) {
+        self.changes.push(ContentsChange {
+            key: Some(key),
+            mark_changed: MarkChangedImpl::All,
+        });
+    }
+
+    /// Returns workspace members in the new graph that depend on any package with one of these
+    /// names, in either the old or the new graph. Members with these names are included as well.
+    fn dependents<'n>(&self, names: impl IntoIterator<Item = &'n str>) -> Vec<PackageMetadata<'g>> {
+        let names: BTreeSet<_> = names.into_iter().collect();
+        let new_workspace = self.new_graph.workspace();
+
+        let mut dependents = BTreeMap::new();
+        for package in workspace_dependents(self.new_graph, &names) {
+            dependents.insert(package.id(), package);
+        }
+        for old_package in workspace_dependents(self.old_graph, &names) {
+            if let Ok(package) = new_workspace.member_by_name(old_package.name()) {
+                dependents.insert(package.id(), package);
+            }
+        }
+        dependents.into_values().collect()
+    }
+
+    /// Returns workspace members whose metadata differs between the old and new graphs.
+    ///
+    /// Changes to `[workspace.package]` only affect members that inherit the changed fields, which
+    /// shows up as different metadata.
+    fn metadata_changed_members(&self) -> Vec<PackageMetadata<'g>> {
+        let old_workspace = self.old_graph.workspace();
+        self.new_graph
+            .workspace()
+            .iter()
+            .filter(
+                |package| match old_workspace.member_by_name(package.name()) {
+                    Ok(old_package) => !same_metadata(old_package, *package),
+                    Err(_) => true,
+                },
+            )
+            .collect()
+    }
+}
+
+fn parse(contents: &str) -> Option<Table> {
+    toml::from_str(contents).ok()
+}
+
+type ChangedEntry<'t> = (&'t str, Option<&'t Value>, Option<&'t Value>);
+
+/// Returns the keys whose values differ between two tables, in sorted order.
+///
+/// A missing table is treated as empty.
+fn changed_entries<'t>(old: Option<&'t Table>, new: Option<&'t Table>) -> Vec<ChangedEntry<'t>> {
+    let keys: BTreeSet<_> = old
+        .into_iter()
+        .chain(new)
+        .flat_map(|table| table.keys())
+        .collect();
+    keys.into_iter()
+        .filter_map(|key| {
+            let old_value = old.and_then(|table| table.get(key));
+            let new_value = new.and_then(|table| table.get(key));
+            (old_value != new_value).then_some((key.as_str(), old_value, new_value))
+        })
+        .collect()
+}
+
+/// Like `changed_entries`, but for values that may be missing or may not be tables.
+///
+/// Returns `None` if either value is present but isn't a table.
+fn changed_table_entries<'t>(
+    old: Option<&'t Value>,
+    new: Option<&'t Value>,
+) -> Option<Vec<ChangedEntry<'t>>> {
+    let as_table = |value: Option<&'t Value>| match value {
+        Some(value) => value.as_table().map(Some),
+        None => Some(None),
+    };
+    Some(changed_entries(as_table(old)?, as_table(new)?))
+}
+
+/// Returns the package name from a package ID specification like `foo@1.0.0`, `foo:1.0.0` or
+/// `https://github.com/foo/bar#foo@1.0.0`.
+///
+/// Returns `None` if the specification doesn't name a package, e.g. if it's a URL whose fragment
+/// is missing or only has a version.
+fn spec_name(spec: &str) -> Option<&str> {
+    let name_and_version = if spec.contains("://") {
+        // Without a name in the fragment, the name would have to be inferred from the URL.
+        spec.split_once('#')?.1
+    } else {
+        spec
+    };
+    let name = name_and_version.split(['@', ':']).next()?;
+    let is_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    is_name.then_some(name)
+}
+
+fn workspace_dependents<'g>(
+    graph: &'g PackageGraph,
+    names: &BTreeSet<&str>,
+) -> impl Iterator<Item = PackageMetadata<'g>> {
+    let ids: Vec<&PackageId> = graph
+        .packages()
+        .filter(|package| names.contains(package.name()))
+        .map(|package| package.id())
+        .collect();
+    graph
+        .query_reverse(ids)
+        .expect("package IDs are valid")
+        .resolve()
+        .packages(DependencyDirection::Forward)
+        .filter(|package| package.in_workspace())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn same_metadata(old: PackageMetadata<'_>, new: PackageMetadata<'_>) -> bool {
+    old.version() == new.version()
+        && old.authors() == new.authors()
+        && old.description() == new.description()
+        && old.license() == new.license()
+        && old.license_file() == new.license_file()
+        && old.categories() == new.categories()
+        && old.keywords() == new.keywords()
+        && old.readme() == new.readme()
+        && old.repository() == new.repository()
+        && old.homepage() == new.homepage()
+        && old.documentation() == new.documentation()
+        && old.edition() == new.edition()
+        && old.publish() == new.publish()
+        && old.minimum_rust_version() == new.minimum_rust_version()
+}
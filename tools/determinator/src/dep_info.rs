@@ -0,0 +1,474 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Map files outside packages to the packages that use them, through build outputs.
+//!
+//! By default, a changed file is matched to its nearest package, and a file that isn't inside any
+//! package causes all packages to be marked changed. But many files outside packages are used by
+//! only a few of them: for example, assets included through `include_str!`, protobuf definitions
+//! compiled by build scripts, or SQL migrations shared across services.
+//!
+//! Cargo and rustc already know about these files. A [`DepInfoIndex`] reads them out of a local
+//! target directory:
+//! * dep-info (`.d`) files written by rustc, which list every source file a crate was compiled
+//!   from;
+//! * build script `output` files, which list paths passed to `cargo:rerun-if-changed`.
+//!
+//! Pass the index to [`Determinator::set_dep_info_index`](crate::Determinator::set_dep_info_index)
+//! to use it while matching changed paths, or turn it into
+//! [suggested path rules](DepInfoIndex::suggested_rules) to check into the repository.
+//!
+//! The index is only as good as the target directory it was read from. Packages, features or
+//! platforms that weren't built won't be represented in it, and stale entries from older builds
+//! may be present. Build the workspace with the same options used in CI before reading the index.
+//!
+//! # Examples
+//!
+//! ```
+//! use determinator::dep_info::DepInfoIndex;
+//! use guppy::CargoMetadata;
+//!
+//! // guppy accepts `cargo metadata` JSON output. Use a pre-existing fixture for this example.
+//! let metadata = CargoMetadata::parse_json(include_str!("../../../fixtures/guppy/metadata_guppy_c9b4f76.json")).unwrap();
+//! let package_graph = metadata.build_graph().unwrap();
+//!
+//! // Typically, the index is read from the workspace's target directory. This fixture has a few
+//! // dep-info files and build script outputs.
+//! let target_dir = concat!(
+//!     env!("CARGO_MANIFEST_DIR"),
+//!     "/../../fixtures/determinator-dep-info/target-dir",
+//! );
+//! let index = DepInfoIndex::from_target_dir(&package_graph, target_dir).unwrap();
+//!
+//! let names: Vec<_> = index
+//!     .packages_for_path("shared/assets/schema.sql")
+//!     .into_iter()
+//!     .map(|package| package.name())
+//!     .collect();
+//! assert_eq!(names.len(), 2);
+//! assert!(names.contains(&"cargo-guppy"));
+//! assert!(names.contains(&"target-spec"));
+//!
+//! // Dep-info files can also be added directly.
+//! let mut index = DepInfoIndex::new(&package_graph);
+//! index.add_dep_info(
+//!     "target/debug/deps/guppy-0123456789abcdef.d: guppy/src/lib.rs shared/schema.sql",
+//! );
+//! let packages = index.packages_for_path("shared/schema.sql");
+//! assert_eq!(packages.len(), 1);
+//! assert_eq!(packages[0].name(), "guppy");
+//! ```
+
+use crate::{
+    errors::DepInfoError,
+    rules::{DeterminatorMarkChanged, DeterminatorPostRule, DeterminatorRules, PathRule},
+};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use guppy::{
+    PackageId,
+    graph::{PackageGraph, PackageMetadata},
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+/// An index of files used by workspace packages, built from dep-info files and build script
+/// outputs.
+///
+/// Paths in the index are relative to the workspace root. Files inside a package's own directory
+/// aren't recorded for that package, since they're already matched to it.
+///
+/// For more, see the [module-level documentation](crate::dep_info).
+#[derive(Clone, Debug)]
+pub struct DepInfoIndex<'g> {
+    graph: &'g PackageGraph,
+    // Directories relative to the workspace root that are never indexed, such as the target
+    // directory.
+    excluded: Vec<Utf8PathBuf>,
+    entries: BTreeMap<Utf8PathBuf, DepInfoEntry<'g>>,
+    // Files that couldn't be read and were skipped.
+    read_errors: Vec<DepInfoError>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct DepInfoEntry<'g> {
+    packages: BTreeSet<&'g PackageId>,
+    // Paths from rerun-if-changed may be directories, and cover everything inside them.
+    rerun_if_changed: bool,
+}
+
+impl<'g> DepInfoIndex<'g> {
+    /// Creates a new, empty index for the workspace in this package graph.
+    pub fn new(graph: &'g PackageGraph) -> Self {
+        let workspace = graph.workspace();
+        let excluded = workspace
+            .target_directory()
+            .strip_prefix(workspace.root())
+            .map(|dir| dir.to_owned())
+            .into_iter()
+            .collect();
+        Self {
+            graph,
+            excluded,
+            entries: BTreeMap::new(),
+            read_errors: vec![],
+        }
+    }
+
+    /// Creates a new index by reading dep-info files and build script outputs from a Cargo target
+    /// directory.
+    ///
+    /// Every profile directory is read, including those for other target platforms: for example,
+    /// `target/debug`, `target/release` and `target/x86_64-unknown-linux-gnu/debug`.
+    ///
+    /// Individual files that couldn't be read, for example because they aren't valid UTF-8, are
+    /// skipped and recorded in [`read_errors`](Self::read_errors).
+    ///
+    /// Returns an error if the target directory, or a directory within it, couldn't be read.
+    pub fn from_target_dir(
+        graph: &'g PackageGraph,
+        target_dir: impl AsRef<Utf8Path>,
+    ) -> Result<Self, DepInfoError> {
+        let target_dir = target_dir.as_ref();
+        let mut index = Self::new(graph);
+        if let Ok(dir) = target_dir.strip_prefix(graph.workspace().root()) {
+            index.excluded.push(dir.to_owned());
+        }
+
+        for profile_dir in profile_dirs(target_dir)? {
+            for path in dir_entries(&profile_dir.join("deps"))? {
+                if path.extension() == Some("d") {
+                    if let Some(contents) = index.read_file(&path) {
+                        index.add_dep_info(&contents);
+                    }
+                }
+            }
+
+            // Each build script has two directories: one where it's compiled (with a dep-info
+            // file), and one where it's run (with an output file).
+            for build_dir in dir_entries(&profile_dir.join("build"))? {
+                if !build_dir.is_dir() {
+                    continue;
+                }
+                let package = build_dir
+                    .file_name()
+                    .and_then(|name| name.rsplit_once('-'))
+                    .and_then(|(name, _hash)| graph.workspace().member_by_name(name).ok());
+                for path in dir_entries(&build_dir)? {
+                    if path.extension() == Some("d") {
+                        if let Some(contents) = index.read_file(&path) {
+                            index.add_dep_info(&contents);
+                        }
+                    } else if let (Some("output"), Some(package)) = (path.file_name(), package) {
+                        if let Some(contents) = index.read_file(&path) {
+                            index.add_build_script_output(package, &contents);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Adds the contents of a dep-info (`.d`) file written by rustc to the index.
+    ///
+    /// The first file listed is taken to be the crate root, and determines the package that the
+    /// other files are recorded for. Dep-info files for crates outside the workspace are ignored.
+    ///
+    /// Relative paths are interpreted relative to the workspace root, which is the directory Cargo
+    /// invokes rustc from.
+    pub fn add_dep_info(&mut self, contents: &str) -> &mut Self {
+        let paths: Vec<_> = contents
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(|line| line.split_once(": "))
+            .flat_map(|(_target, deps)| split_deps(deps))
+            .collect();
+
+        let package = paths
+            .first()
+            .and_then(|path| self.workspace_relative(Utf8Path::new(path)))
+            .and_then(|path| self.member_for_path(&path));
+        if let Some(package) = package {
+            for path in &paths {
+                if let Some(path) = self.workspace_relative(Utf8Path::new(path)) {
+                    self.insert(path, package, false);
+                }
+            }
+        }
+        self
+    }
+
+    /// Adds the contents of a build script's `output` file for this workspace package to the
+    /// index.
+    ///
+    /// Paths passed to `cargo:rerun-if-changed` are recorded for the package. Relative paths are
+    /// interpreted relative to the package's directory, and paths to directories cover every file
+    /// inside them.
+    pub fn add_build_script_output(
+        &mut self,
+        package: PackageMetadata<'g>,
+        contents: &str,
+    ) -> &mut Self {
+        let Some(package_path) = package.source().workspace_path() else {
+            return self;
+        };
+        for line in contents.lines() {
+            let path = line
+                .strip_prefix("cargo::rerun-if-changed=")
+                .or_else(|| line.strip_prefix("cargo:rerun-if-changed="));
+            if let Some(path) = path {
+                let path = package_path.join(path);
+                if let Some(path) = self.workspace_relative(&path) {
+                    self.insert(path, package, true);
+                }
+            }
+        }
+        self
+    }
+
+    /// Returns the workspace packages that use the given path, relative to the workspace root.
+    ///
+    /// The packages are returned in order of their package IDs.
+    pub fn packages_for_path(&self, path: impl AsRef<Utf8Path>) -> Vec<PackageMetadata<'g>> {
+        let mut package_ids = BTreeSet::new();
+        for (idx, ancestor) in path.as_ref().ancestors().enumerate() {
+            if let Some(entry) = self.entries.get(ancestor) {
+                // Ancestors only match directories from rerun-if-changed.
+                if idx == 0 || entry.rerun_if_changed {
+                    package_ids.extend(entry.packages.iter().copied());
+                }
+            }
+        }
+        package_ids
+            .into_iter()
+            .map(|package_id| self.metadata(package_id))
+            .collect()
+    }
+
+    /// Returns errors for files that couldn't be read while creating this index through
+    /// [`from_target_dir`](Self::from_target_dir).
+    ///
+    /// These files were skipped, so the index may be missing paths used by some packages.
+    pub fn read_errors(&self) -> &[DepInfoError] {
+        &self.read_errors
+    }
+
+    /// Iterates over all indexed paths and the packages that use them, in order of path.
+    pub fn iter(&self) -> impl Iterator<Item = (&Utf8Path, Vec<PackageMetadata<'g>>)> + '_ {
+        self.entries.iter().map(|(path, entry)| {
+            let packages = entry
+                .packages
+                .iter()
+                .map(|package_id| self.metadata(package_id))
+                .collect();
+            (path.as_path(), packages)
+        })
+    }
+
+    /// Returns path rules that mark packages changed based on this index.
+    ///
+    /// These rules can be serialized and checked into the repository, so that CI doesn't need a
+    /// warm target directory. Paths used by the same set of packages are grouped into one rule.
+    ///
+    /// * Paths outside every package mark the packages that use them, and skip further processing.
+    /// * Paths inside another package mark the packages that use them, and fall through to
+    ///   further rules and matching the path to its own package.
+    ///
+    /// Directories from `rerun-if-changed` are turned into `dir/**` globs, and paths inside them
+    /// that are used by the same packages are left out. `rerun-if-changed` paths that aren't
+    /// present in the workspace may be files or directories, so both `path` and `path/**` are
+    /// emitted for them.
+    ///
+    /// The returned rules use the default rules as well.
+    pub fn suggested_rules(&self) -> DeterminatorRules {
+        let workspace_root = self.graph.workspace().root();
+        // For each group, the globs and the rerun-if-changed paths that cover their contents.
+        let mut groups: BTreeMap<_, (Vec<String>, BTreeSet<&Utf8Path>)> = BTreeMap::new();
+        for (path, entry) in &self.entries {
+            let mut names: Vec<_> = entry
+                .packages
+                .iter()
+                .map(|package_id| self.metadata(package_id).name().to_owned())
+                .collect();
+            names.sort_unstable();
+            let in_package = self.member_for_path(path).is_some();
+
+            let (globs, covered) = groups.entry((in_package, names)).or_default();
+            // Entries are in path order, so ancestors are seen before the paths inside them.
+            if path
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| covered.contains(ancestor))
+            {
+                continue;
+            }
+            let glob = globset::escape(path.as_str());
+            if entry.rerun_if_changed {
+                covered.insert(path);
+                let full_path = workspace_root.join(path);
+                if !full_path.is_file() {
+                    globs.push(format!("{glob}/**"));
+                }
+                if full_path.is_dir() {
+                    continue;
+                }
+            }
+            globs.push(glob);
+        }
+
+        let path_rules = groups
+            .into_iter()
+            .map(|((in_package, names), (globs, _))| PathRule {
+                globs,
+                mark_changed: DeterminatorMarkChanged::Packages(names),
+                features: vec![],
+                post_rule: if in_package {
+                    DeterminatorPostRule::Fallthrough
+                } else {
+                    DeterminatorPostRule::Skip
+                },
+            })
+            .collect();
+        let mut rules = DeterminatorRules::default();
+        rules.path_rules = path_rules;
+        rules
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    // Reads a file, recording an error and returning None if it couldn't be read.
+    fn read_file(&mut self, path: &Utf8Path) -> Option<String> {
+        match fs::read_to_string(path) {
+            Ok(contents) => Some(contents),
+            Err(err) => {
+                self.read_errors.push(DepInfoError::new(path, err));
+                None
+            }
+        }
+    }
+
+    fn insert(&mut self, path: Utf8PathBuf, package: PackageMetadata<'g>, rerun_if_changed: bool) {
+        // Paths within the package are already matched to it.
+        if self.member_for_path(&path).map(|member| member.id()) == Some(package.id()) {
+            return;
+        }
+        let entry = self.entries.entry(path).or_default();
+        entry.packages.insert(package.id());
+        entry.rerun_if_changed |= rerun_if_changed;
+    }
+
+    // Converts a path to one relative to the workspace root, or returns None if it isn't in the
+    // workspace or is excluded.
+    fn workspace_relative(&self, path: &Utf8Path) -> Option<Utf8PathBuf> {
+        let path = normalize(path)?;
+        let path = if path.is_absolute() {
+            let root = normalize(self.graph.workspace().root())?;
+            path.strip_prefix(root).ok()?.to_owned()
+        } else {
+            path
+        };
+        if path.as_str().is_empty() || self.excluded.iter().any(|dir| path.starts_with(dir)) {
+            return None;
+        }
+        Some(path)
+    }
+
+    // Returns the workspace member nearest to this path, relative to the workspace root.
+    fn member_for_path(&self, path: &Utf8Path) -> Option<PackageMetadata<'g>> {
+        let workspace = self.graph.workspace();
+        path.ancestors()
+            .find_map(|ancestor| workspace.member_by_path(ancestor).ok())
+    }
+
+    fn metadata(&self, package_id: &PackageId) -> PackageMetadata<'g> {
+        self.graph
+            .metadata(package_id)
+            .expect("package IDs in the index are valid")
+    }
+}
+
+/// Returns directories within the target directory that Cargo writes build outputs for a profile
+/// to, such as `debug` or `x86_64-unknown-linux-gnu/release`.
+fn profile_dirs(target_dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>, DepInfoError> {
+    let mut profile_dirs = vec![];
+    for dir in dir_entries(target_dir)? {
+        if dir.join("deps").is_dir() {
+            profile_dirs.push(dir);
+        } else if dir.is_dir() {
+            // This may be a directory for a target platform.
+            profile_dirs.extend(
+                dir_entries(&dir)?
+                    .into_iter()
+                    .filter(|dir| dir.join("deps").is_dir()),
+            );
+        }
+    }
+    Ok(profile_dirs)
+}
+
+/// Returns the entries of a directory in sorted order, or an empty list if it doesn't exist.
+///
+/// Entries that aren't valid UTF-8 are skipped.
+fn dir_entries(dir: &Utf8Path) -> Result<Vec<Utf8PathBuf>, DepInfoError> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir).map_err(|err| DepInfoError::new(dir, err))? {
+        let entry = entry.map_err(|err| DepInfoError::new(dir, err))?;
+        if let Ok(path) = Utf8PathBuf::from_path_buf(entry.path()) {
+            entries.push(path);
+        }
+    }
+    entries.sort_unstable();
+    Ok(entries)
+}
+
+/// Splits the dependencies in a dep-info rule. Spaces within paths are escaped as `\ `.
+fn split_deps(deps: &str) -> Vec<String> {
+    let mut paths = vec![];
+    let mut current = String::new();
+    let mut chars = deps.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&' ') => {
+                current.push(' ');
+                chars.next();
+            }
+            ' ' => {
+                if !current.is_empty() {
+                    paths.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        paths.push(current);
+    }
+    paths
+}
+
+/// Lexically normalizes a path, removing `.` and `..` components.
+///
+/// Returns `None` if a `..` component would go past the start of the path.
+fn normalize(path: &Utf8Path) -> Option<Utf8PathBuf> {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            other => normalized.push(other.as_str()),
+        }
+    }
+    Some(normalized)
+}
@@ -2,27 +2,32 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use crate::{
+    contents::ContentsAnalysis,
+    dep_info::DepInfoIndex,
     errors::RulesError,
+    reasons::{AffectedLink, AffectedLinkKind, AffectedReason, AffectedReasons, SummaryChange},
+    renames::{DeletedPackage, PackageMatches, RenamedPackage},
     rules::{
         DeterminatorPostRule, DeterminatorRules, MarkChangedImpl, PathMatch, PathRuleImpl,
-        RulesImpl,
+        RuleIndex, RulesImpl,
     },
+    targets::{AffectedPackageTargets, AffectedTargets, targets_for_path},
 };
 use ahash::AHashMap;
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use globset::Candidate;
 use guppy::{
     PackageId,
     graph::{
-        DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
+        BuildTargetId, DependencyDirection, PackageGraph, PackageMetadata, PackageSet, Workspace,
         cargo::{CargoOptions, CargoSet},
-        feature::{FeatureFilter, FeatureSet, StandardFeatures},
+        feature::{FeatureFilter, FeatureId, FeatureSet, StandardFeatures, named_feature_filter},
     },
-    platform::PlatformSpec,
+    platform::{EnabledTernary, PlatformSpec, PlatformStatus},
 };
 use petgraph::{Directed, graphmap::GraphMap};
 use rayon::prelude::*;
-use std::collections::{HashSet, hash_map::Entry};
+use std::collections::{BTreeSet, HashSet, hash_map::Entry};
 
 /// Determine target dependencies from changed files and packages in a workspace.
 ///
@@ -42,6 +47,13 @@ pub struct Determinator<'g, 'a> {
     old_features_only: Option<FeatureSet<'a>>,
     new_features_only: Option<FeatureSet<'g>>,
     changed_paths: Vec<&'a Utf8Path>,
+    renamed_paths: Vec<(&'a Utf8Path, &'a Utf8Path)>,
+    package_matches: PackageMatches<'g, 'a>,
+    root_manifest_contents: Option<ContentsAnalysis<'g>>,
+    cargo_config_contents: Option<ContentsAnalysis<'g>>,
+    dep_info: Option<&'a DepInfoIndex<'g>>,
+    build_target_granularity: bool,
+    separate_build_stage: bool,
 }
 
 impl<'g, 'a> Determinator<'g, 'a> {
@@ -56,6 +68,13 @@ impl<'g, 'a> Determinator<'g, 'a> {
             old_features_only: None,
             new_features_only: None,
             changed_paths: vec![],
+            renamed_paths: vec![],
+            package_matches: PackageMatches::new(old, new, &[]),
+            root_manifest_contents: None,
+            cargo_config_contents: None,
+            dep_info: None,
+            build_target_granularity: false,
+            separate_build_stage: false,
         }
     }
 
@@ -100,6 +119,38 @@ impl<'g, 'a> Determinator<'g, 'a> {
         self
     }
 
+    /// Adds a list of renamed paths, as pairs of old and new paths.
+    ///
+    /// Renamed paths are used as hints to match workspace packages in the old graph to their
+    /// counterparts in the new graph, if packages were moved or renamed. For example, if most
+    /// files in `old/foo` were renamed to paths in `new/foo`, the package at `new/foo` is
+    /// compared against the package formerly at `old/foo`, even if its name changed as well.
+    ///
+    /// Without hints, packages are matched by name, and then by path. Packages that were moved or
+    /// renamed are reported in [`DeterminatorSet::renamed_packages`], and packages without
+    /// counterparts in the new graph are reported in [`DeterminatorSet::deleted_packages`].
+    ///
+    /// Renamed paths can be obtained from Git with `git diff -z --name-status -M`, in the form
+    /// `R<score>\0<old path>\0<new path>\0`. They are not treated as changed paths: those
+    /// must be added separately through [`add_changed_paths`](Self::add_changed_paths).
+    pub fn add_renamed_paths(
+        &mut self,
+        renames: impl IntoIterator<
+            Item = (
+                &'a (impl AsRef<Utf8Path> + ?Sized + 'a),
+                &'a (impl AsRef<Utf8Path> + ?Sized + 'a),
+            ),
+        >,
+    ) -> &mut Self {
+        self.renamed_paths.extend(
+            renames
+                .into_iter()
+                .map(|(old, new)| (old.as_ref(), new.as_ref())),
+        );
+        self.package_matches = PackageMatches::new(self.old, self.new, &self.renamed_paths);
+        self
+    }
+
     /// Returns what *would* happen if a given path was added to the changed set.
     ///
     /// This does not add any path to the changed set, but indicates what *would* happen if a path
@@ -112,16 +163,71 @@ impl<'g, 'a> Determinator<'g, 'a> {
     pub fn match_path(
         &self,
         path: impl AsRef<Utf8Path>,
-        match_cb: impl FnMut(&'g PackageId),
+        mut match_cb: impl FnMut(&'g PackageId),
     ) -> PathMatch {
+        let path = path.as_ref();
         process_path(
-            path.as_ref(),
+            path,
             &self.new.workspace(),
+            &self.package_matches,
             &self.rules.path_rules,
-            match_cb,
+            self.contents_for(path),
+            self.dep_info,
+            |marked| match marked {
+                PathMarked::Rule(id, _)
+                | PathMarked::RuleFeatures(id, _, _)
+                | PathMarked::Ancestor(id)
+                | PathMarked::OldAncestor(id)
+                | PathMarked::Contents(id, _)
+                | PathMarked::DepInfo(id) => match_cb(id),
+                PathMarked::RuleAll(_) | PathMarked::ContentsAll(_) => {}
+            },
         )
     }
 
+    /// Provides the old and new contents of the root `Cargo.toml` of the workspace.
+    ///
+    /// By default, any change to the root `Cargo.toml` marks all packages changed. With the
+    /// contents available, the determinator compares the two versions key by key instead:
+    ///
+    /// * Formatting and comment changes are ignored.
+    /// * A change to an entry in `[workspace.dependencies]` marks the members that depend on it.
+    /// * A change to `[workspace.package]` marks the members whose metadata changed as a result.
+    /// * A change to `[workspace]` `members`, `exclude`, `default-members` or `metadata` marks
+    ///   nothing.
+    /// * A change to an entry in `[patch]` or `[replace]`, or to a `[profile.*.package.<name>]`
+    ///   override, marks the members that depend on the named package.
+    /// * A change to the root package, if there is one, marks that package.
+    /// * Any other change, for example to a profile setting or to `workspace.resolver`, marks all
+    ///   packages changed.
+    ///
+    /// If either version can't be parsed, all packages are marked changed.
+    ///
+    /// The analysis is only used if `Cargo.toml` is in the list of changed paths, and it takes the
+    /// place of the default rules for that path. Custom path rules that match `Cargo.toml` are still
+    /// applied first. An empty string may be passed in if the file doesn't exist.
+    pub fn set_root_manifest_contents(&mut self, old: &str, new: &str) -> &mut Self {
+        self.root_manifest_contents = Some(ContentsAnalysis::root_manifest(
+            self.old, self.new, old, new,
+        ));
+        self
+    }
+
+    /// Provides the old and new contents of the Cargo configuration at the root of the workspace,
+    /// `.cargo/config.toml` or `.cargo/config`.
+    ///
+    /// This works like [`set_root_manifest_contents`](Self::set_root_manifest_contents).
+    /// Changes to `[patch]` and `[profile]` are analyzed the same way, changes to settings that
+    /// don't influence builds (such as `[alias]`, `[http]`, `[net]` or `[term]`) mark nothing, and
+    /// any other change marks all packages changed.
+    ///
+    /// Configuration files in other directories are not analyzed.
+    pub fn set_cargo_config_contents(&mut self, old: &str, new: &str) -> &mut Self {
+        self.cargo_config_contents =
+            Some(ContentsAnalysis::cargo_config(self.old, self.new, old, new));
+        self
+    }
+
     /// Processes and configures determinator rules.
     ///
     /// Returns an error if the rules were invalid in some way.
@@ -131,12 +237,58 @@ impl<'g, 'a> Determinator<'g, 'a> {
         Ok(self)
     }
 
+    /// Configures an index of files used by workspace packages, read from build outputs.
+    ///
+    /// Changed paths that aren't matched by a rule are looked up in the index, and the packages
+    /// that use them are marked changed. This is in addition to matching paths to their nearest
+    /// package. Paths that aren't inside any package, but are in the index, no longer cause all
+    /// packages to be marked changed.
+    ///
+    /// The index should be built against the new package graph. For more, see the documentation
+    /// for the [`dep_info` module](crate::dep_info).
+    pub fn set_dep_info_index(&mut self, index: &'a DepInfoIndex<'g>) -> &mut Self {
+        self.dep_info = Some(index);
+        self
+    }
+
+    /// Configures whether to compute the build targets affected within each package.
+    ///
+    /// If enabled, [`DeterminatorSet::affected_targets`] is populated. Changes to files that only
+    /// belong to specific test, benchmark or example targets of a package, such as
+    /// `tests/foo.rs`, then affect just those targets, and no longer cause packages that depend on
+    /// that package to be affected. For more, see the documentation for the
+    /// [`targets` module](crate::targets).
+    ///
+    /// This is disabled by default.
+    pub fn set_build_target_granularity(&mut self, enabled: bool) -> &mut Self {
+        self.build_target_granularity = enabled;
+        self
+    }
+
+    /// Configures whether to compute a separate set of packages whose builds were affected, not
+    /// counting their tests.
+    ///
+    /// If enabled, the determinator also simulates builds without dev-dependencies, and reports
+    /// the results as [`DeterminatorSet::build_affected_set`] and
+    /// [`DeterminatorSet::build_reasons`]. This is useful for CI systems that build and test
+    /// packages in separate stages, but doubles the number of build simulations.
+    ///
+    /// This is disabled by default.
+    pub fn set_separate_build_stage(&mut self, enabled: bool) -> &mut Self {
+        self.separate_build_stage = enabled;
+        self
+    }
+
     /// Configures Cargo options.
     ///
     /// These options are used to determine if the build for a particular package has changed.
     ///
     /// If no options are specified, the default `CargoOptions`, as specified by
     /// `CargoOptions::new`, are used, with one exception: dev-dependencies are built by default.
+    ///
+    /// The determinator always simulates builds with dev-dependencies, and also without them if
+    /// [`set_separate_build_stage`](Self::set_separate_build_stage) is enabled, so the
+    /// dev-dependencies setting in these options is ignored.
     pub fn set_cargo_options(&mut self, cargo_options: &'a CargoOptions<'a>) -> &mut Self {
         self.cargo_options = Some(cargo_options);
         self
@@ -168,6 +320,9 @@ impl<'g, 'a> Determinator<'g, 'a> {
     /// old `PackageGraph`.
     /// * If a package name isn't in the *new* graph, this method returns an error.
     /// * If a package name isn't in the *old* graph, it is ignored.
+    ///
+    /// This replaces any features-only packages set previously, including those added through
+    /// [`add_features_only`](Self::add_features_only).
     pub fn set_features_only<'b>(
         &mut self,
         workspace_names: impl IntoIterator<Item = &'b str>,
@@ -200,26 +355,169 @@ impl<'g, 'a> Determinator<'g, 'a> {
         Ok(self)
     }
 
+    /// Adds named features of a single workspace package to the features-only set used in build
+    /// simulations.
+    ///
+    /// Unlike [`set_features_only`](Self::set_features_only), which enables the same standard
+    /// features for every package it's passed, this enables particular features of one package.
+    /// For example, if CI always builds a package with an optional `gpu` feature, adding that
+    /// feature here means that simulated builds see it as enabled, so
+    /// [feature-gated path rules](crate::rules#feature-gated-path-rules) on it take effect.
+    ///
+    /// Only the named features are added, not the package's default features. This can be called
+    /// several times, and the results are unioned with each other and with any packages set
+    /// through `set_features_only`.
+    ///
+    /// * If the package name isn't in the *new* graph, or the package doesn't have one of the
+    ///   features, this method returns an error.
+    /// * If the package name isn't in the *old* graph, it is ignored there, as are features that
+    ///   the old package doesn't have.
+    pub fn add_features_only<'b>(
+        &mut self,
+        workspace_name: &str,
+        features: impl IntoIterator<Item = &'b str>,
+    ) -> Result<&mut Self, guppy::Error> {
+        let features: Vec<_> = features.into_iter().collect();
+
+        // Missing package name or feature in new workspace => error.
+        let new_package = self.new.workspace().member_by_name(workspace_name)?;
+        if let Some(feature) = features
+            .iter()
+            .find(|&&feature| !new_package.named_features().any(|named| named == feature))
+        {
+            return Err(guppy::Error::UnknownFeatureId(
+                new_package.id().clone(),
+                (*feature).to_owned(),
+            ));
+        }
+        let new_features_only = new_package.to_feature_set(named_feature_filter(
+            StandardFeatures::None,
+            features.iter().copied(),
+        ));
+        self.new_features_only = Some(match &self.new_features_only {
+            Some(features_only) => features_only.union(&new_features_only),
+            None => new_features_only,
+        });
+
+        if let Ok(old_package) = self.old.workspace().member_by_name(workspace_name) {
+            // named_feature_filter ignores features that aren't present.
+            let old_features_only = old_package.to_feature_set(named_feature_filter(
+                StandardFeatures::None,
+                features.iter().copied(),
+            ));
+            self.old_features_only = Some(match &self.old_features_only {
+                Some(features_only) => features_only.union(&old_features_only),
+                None => old_features_only,
+            });
+        }
+        Ok(self)
+    }
+
+    // Returns the contents analysis for this path, if one was provided.
+    fn contents_for(&self, path: &Utf8Path) -> Option<&ContentsAnalysis<'g>> {
+        if path == Utf8Path::new("Cargo.toml") {
+            self.root_manifest_contents.as_ref()
+        } else if path == Utf8Path::new(".cargo/config.toml")
+            || path == Utf8Path::new(".cargo/config")
+        {
+            self.cargo_config_contents.as_ref()
+        } else {
+            None
+        }
+    }
+
     /// Uses the old and new sets and the list of changed files to compute the list
     /// of projects that is affected.
     pub fn compute(&self) -> DeterminatorSet<'g> {
+        self.compute_impl(None)
+            .pop()
+            .expect("one set is computed without platforms")
+    }
+
+    /// Computes the list of projects that is affected separately for each of the given platforms.
+    ///
+    /// For each platform, build simulations are run with both the host and target platforms set to
+    /// it, so dependencies and changes that only apply to other platforms are ignored. For example,
+    /// a change to a `cfg(windows)` dependency is reflected in the set for a Windows platform, but
+    /// not in the set for a Linux one. Other Cargo options are taken from
+    /// [`set_cargo_options`](Self::set_cargo_options), if specified.
+    ///
+    /// Changed paths are processed once and shared between platforms. A simulated build of a
+    /// package is also shared between platforms that evaluate every platform-specific dependency
+    /// reachable from that package the same way, so adding platforms costs little if they only
+    /// differ in a few dependencies. Other build simulations are run in parallel.
+    ///
+    /// Returns one `DeterminatorSet` per platform, in the same order as `platforms`.
+    pub fn compute_for_platforms(
+        &self,
+        platforms: impl IntoIterator<Item = PlatformSpec>,
+    ) -> Vec<DeterminatorSet<'g>> {
+        self.compute_impl(Some(platforms.into_iter().collect()))
+    }
+
+    // Returns the options set through set_cargo_options, or the default ones.
+    fn base_cargo_options(&self) -> CargoOptions<'a> {
+        match self.cargo_options {
+            Some(cargo_options) => cargo_options.clone(),
+            None => Self::default_cargo_options(),
+        }
+    }
+
+    // Computes a DeterminatorSet for each of the given platforms, or a single one with the base
+    // options if no platforms are given.
+    fn compute_impl(&self, platforms: Option<Vec<PlatformSpec>>) -> Vec<DeterminatorSet<'g>> {
+        let base_options = self.base_cargo_options();
+        let cargo_options: Vec<_> = match &platforms {
+            Some(platforms) => platforms
+                .iter()
+                .map(|platform| {
+                    let mut cargo_options = base_options.clone();
+                    cargo_options.set_platform(platform.clone());
+                    cargo_options
+                })
+                .collect(),
+            None => vec![base_options],
+        };
+
         let mut build_state = BuildState::new(self);
 
-        // 1-2. Process every changed path.
+        // 1-2. Process every changed path. Paths don't depend on Cargo options, so this is shared
+        // across all of them.
         for path in &self.changed_paths {
             build_state = match build_state.process_path(path) {
-                Some(build_state) => build_state,
-                None => {
+                Ok(build_state) => build_state,
+                Err(err) => {
+                    let (mut reasons, all_reason) = *err;
                     // The build state was discarded, which means that the entire workspace is
                     // changed and affected.
                     let path_changed_set = self.new.resolve_workspace();
-                    let affected_set = path_changed_set.clone();
-                    return DeterminatorSet {
+                    for package_id in path_changed_set.package_ids(DependencyDirection::Forward) {
+                        reasons.add(package_id, all_reason.clone());
+                    }
+                    let affected_targets = self.build_target_granularity.then(|| {
+                        let mut affected_targets = AffectedTargets::new(self.new);
+                        for package_id in path_changed_set.package_ids(DependencyDirection::Forward)
+                        {
+                            affected_targets.insert(package_id, AffectedPackageTargets::All);
+                        }
+                        affected_targets
+                    });
+                    let determinator_set = DeterminatorSet {
+                        affected_set: path_changed_set.clone(),
+                        affected_targets,
+                        build_affected_set: self
+                            .separate_build_stage
+                            .then(|| path_changed_set.clone()),
+                        test_affected_set: path_changed_set.clone(),
                         path_changed_set,
                         // This is an empty set.
                         summary_changed_set: self.new.resolve_none(),
-                        affected_set,
+                        renamed_packages: self.package_matches.renamed_packages(),
+                        deleted_packages: self.package_matches.deleted_packages(),
+                        build_reasons: self.separate_build_stage.then(|| reasons.clone()),
+                        reasons,
                     };
+                    return vec![determinator_set; cargo_options.len()];
                 }
             }
         }
@@ -230,26 +528,21 @@ impl<'g, 'a> Determinator<'g, 'a> {
             .resolve_ids(build_state.path_changed_ids.iter().copied())
             .expect("package IDs are all valid");
 
-        // 4. Use build summaries as another source of changes.
-        build_state.process_build_summaries();
-        let summary_changed_set = self
-            .new
-            .resolve_ids(build_state.summary_changed_ids.iter().copied())
-            .expect("package IDs are all valid");
-
-        // 5. The affected set is the transitive closure of the graph constructed by looking at both
-        // the build cache and Cargo rules.
-        let affected_set = build_state.reverse_index.affected_closure(
-            self.new,
-            &build_state.path_changed_ids,
-            &build_state.summary_changed_ids,
+        // 4-6. Run build simulations for each set of options, sharing them between options where
+        // possible.
+        let builds = SimulatedBuilds::new(
+            self,
+            &cargo_options,
+            platforms.as_deref(),
+            &build_state.root_ids(),
         );
-
-        DeterminatorSet {
-            path_changed_set,
-            summary_changed_set,
-            affected_set,
-        }
+        cargo_options
+            .par_iter()
+            .enumerate()
+            .map(|(pass, cargo_options)| {
+                build_state.simulate(&builds, pass, cargo_options, path_changed_set.clone())
+            })
+            .collect()
     }
 }
 
@@ -263,19 +556,77 @@ pub struct DeterminatorSet<'g> {
     ///
     /// A package is in this set if it was marked changed due to a path or summaries changing, or if
     /// a simulated Cargo build or package rule indicated that it is affected.
+    ///
+    /// This is the union of `test_affected_set` and, if computed, `build_affected_set`.
     pub affected_set: PackageSet<'g>,
 
+    /// The packages whose builds were affected, not counting their tests.
+    ///
+    /// This set is computed through simulated Cargo builds without dev-dependencies. It's useful
+    /// for CI systems that build and test packages in separate stages. With build target
+    /// granularity, packages where only tests, benchmarks or examples changed aren't included.
+    ///
+    /// This is only computed if a separate build stage is enabled through
+    /// [`Determinator::set_separate_build_stage`].
+    pub build_affected_set: Option<PackageSet<'g>>,
+
+    /// The packages whose tests were affected.
+    ///
+    /// This set is computed through simulated Cargo builds with dev-dependencies. For example, if
+    /// a dev-dependency of a package changed, the package is in this set but not in
+    /// `build_affected_set`.
+    pub test_affected_set: PackageSet<'g>,
+
+    /// The build targets affected within each package in `affected_set`.
+    ///
+    /// This is only computed if build target granularity is enabled through
+    /// [`Determinator::set_build_target_granularity`].
+    pub affected_targets: Option<AffectedTargets<'g>>,
+
     /// The packages that were marked changed because a file changed.
     ///
-    /// Either a file inside this package changed or a path rule was matched.
+    /// Either a file inside this package changed or a path rule was matched. Packages marked by
+    /// [feature-gated path rules](crate::rules#feature-gated-path-rules) are only included if one
+    /// of the features is built.
     pub path_changed_set: PackageSet<'g>,
 
     /// The packages that were marked changed becuase a simulated Cargo build's summary showed
-    /// changes in dependencies.
+    /// changes in dependencies, either with or without dev-dependencies, or because they directly
+    /// depended on a deleted package.
     ///
     /// This does not include packages marked changed through a path. For example, if a path rule
     /// caused all packages to be marked changed, further steps aren't run and this set is empty.
     pub summary_changed_set: PackageSet<'g>,
+
+    /// Workspace packages that were renamed or moved between the old and new graphs, sorted by
+    /// their new names.
+    ///
+    /// Packages are matched across graphs by name and path, and through hints passed in to
+    /// [`Determinator::add_renamed_paths`].
+    pub renamed_packages: Vec<RenamedPackage<'g>>,
+
+    /// Workspace packages in the old graph that don't have a counterpart in the new graph, sorted
+    /// by name.
+    ///
+    /// Packages that directly depended on a deleted package are marked changed.
+    pub deleted_packages: Vec<DeletedPackage>,
+
+    /// Why each package in `affected_set` was marked changed or affected.
+    ///
+    /// This records the changed paths and rules responsible for `path_changed_set`, the
+    /// differences in simulated builds responsible for `summary_changed_set`, and the chain of
+    /// dependencies or package rules through which every other package was affected. Packages
+    /// affected in both stages are explained through simulated builds with dev-dependencies.
+    pub reasons: AffectedReasons<'g>,
+
+    /// Why each package in `build_affected_set` was marked changed or affected.
+    ///
+    /// Unlike `reasons`, this only takes simulated builds without dev-dependencies into account,
+    /// so chains of dependencies never go through dev-dependency edges.
+    ///
+    /// This is only computed if a separate build stage is enabled through
+    /// [`Determinator::set_separate_build_stage`].
+    pub build_reasons: Option<AffectedReasons<'g>>,
 }
 
 // ---
@@ -286,109 +637,537 @@ pub struct DeterminatorSet<'g> {
 struct BuildState<'g, 'a, 'b> {
     determinator: &'b Determinator<'g, 'a>,
     path_changed_ids: HashSet<&'g PackageId>,
-    summary_changed_ids: HashSet<&'g PackageId>,
-    build_cache: CargoBuildCache<'g>,
-    reverse_index: ReverseIndex<'g>,
+    reasons: AffectedReasons<'g>,
+    // With build target granularity, the targets within each path-changed package that were
+    // changed. None means that all targets were changed.
+    target_changes: AHashMap<&'g PackageId, Option<BTreeSet<BuildTargetId<'g>>>>,
+    // Packages marked by feature-gated path rules. Whether they're changed depends on the
+    // simulated builds.
+    feature_marks: Vec<FeatureMark<'g, 'b>>,
 }
 
 impl<'g, 'a, 'b> BuildState<'g, 'a, 'b> {
     fn new(determinator: &'b Determinator<'g, 'a>) -> Self {
-        let build_cache = CargoBuildCache::new(determinator);
-        let reverse_index = ReverseIndex::new(determinator, &build_cache);
         Self {
             determinator,
             path_changed_ids: HashSet::new(),
-            summary_changed_ids: HashSet::new(),
-            build_cache,
-            reverse_index,
+            reasons: AffectedReasons::new(),
+            target_changes: AHashMap::new(),
+            feature_marks: vec![],
         }
     }
 
-    // A return value of None stands for all packages in the workspace changed.
-    fn process_path(mut self, path: &Utf8Path) -> Option<Self> {
+    // An error stands for all packages in the workspace changed. It carries the reasons collected
+    // so far, and the reason all packages were changed.
+    fn process_path(
+        mut self,
+        path: &Utf8Path,
+    ) -> Result<Self, Box<(AffectedReasons<'g>, AffectedReason<'g>)>> {
+        let determinator = self.determinator;
+        let mut all_reason = AffectedReason::PathAll {
+            path: path.to_path_buf(),
+            rule_index: None,
+        };
         let status = process_path(
             path,
             &self.determinator.new.workspace(),
+            &self.determinator.package_matches,
             &self.determinator.rules.path_rules,
-            |id| {
+            self.determinator.contents_for(path),
+            self.determinator.dep_info,
+            |marked| {
+                // Targets within the package that the path belongs to, or None for all targets.
+                let mut targets = None;
+                let (id, reason) = match marked {
+                    PathMarked::Rule(id, rule_index) => (
+                        id,
+                        AffectedReason::PathRule {
+                            path: path.to_path_buf(),
+                            rule_index,
+                        },
+                    ),
+                    PathMarked::RuleFeatures(package_id, rule_index, features) => {
+                        self.feature_marks.push(FeatureMark {
+                            package_id,
+                            features,
+                            path: path.to_path_buf(),
+                            rule_index,
+                        });
+                        return;
+                    }
+                    PathMarked::Ancestor(id) => {
+                        if determinator.build_target_granularity {
+                            let package = determinator
+                                .new
+                                .metadata(id)
+                                .expect("marked package ID is valid");
+                            targets = targets_for_path(package, path);
+                        }
+                        (
+                            id,
+                            AffectedReason::PathAncestor {
+                                path: path.to_path_buf(),
+                            },
+                        )
+                    }
+                    PathMarked::OldAncestor(id) => (
+                        id,
+                        AffectedReason::PathOldAncestor {
+                            path: path.to_path_buf(),
+                        },
+                    ),
+                    PathMarked::DepInfo(id) => (
+                        id,
+                        AffectedReason::PathDepInfo {
+                            path: path.to_path_buf(),
+                        },
+                    ),
+                    PathMarked::Contents(id, key) => (
+                        id,
+                        AffectedReason::ContentsChanged {
+                            path: path.to_path_buf(),
+                            key: key.to_owned(),
+                        },
+                    ),
+                    PathMarked::RuleAll(rule_index) => {
+                        all_reason = AffectedReason::PathAll {
+                            path: path.to_path_buf(),
+                            rule_index: Some(rule_index),
+                        };
+                        return;
+                    }
+                    PathMarked::ContentsAll(key) => {
+                        all_reason = AffectedReason::ContentsChangedAll {
+                            path: path.to_path_buf(),
+                            key: key.map(|key| key.to_owned()),
+                        };
+                        return;
+                    }
+                };
                 self.path_changed_ids.insert(id);
+                self.reasons.add(id, reason);
+                if determinator.build_target_granularity {
+                    match self.target_changes.entry(id) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(targets);
+                        }
+                        Entry::Occupied(mut entry) => match (entry.get_mut(), targets) {
+                            (Some(changed), Some(targets)) => changed.extend(targets),
+                            (changed, _) => *changed = None,
+                        },
+                    }
+                }
             },
         );
         match status {
-            PathMatch::RuleMatchedAll | PathMatch::NoMatches => None,
-            PathMatch::RuleMatched(_) | PathMatch::AncestorMatched => Some(self),
+            PathMatch::RuleMatchedAll | PathMatch::ContentsMatchedAll | PathMatch::NoMatches => {
+                Err(Box::new((self.reasons, all_reason)))
+            }
+            PathMatch::RuleMatched(_)
+            | PathMatch::AncestorMatched
+            | PathMatch::OldAncestorMatched
+            | PathMatch::DepInfoMatched
+            | PathMatch::ContentsMatched => Ok(self),
+        }
+    }
+
+    // Packages where only specific targets changed don't affect anything else, so they aren't
+    // roots for the affected closures.
+    fn target_only_ids(&self) -> HashSet<&'g PackageId> {
+        self.target_changes
+            .iter()
+            .filter_map(|(&id, targets)| targets.is_some().then_some(id))
+            .collect()
+    }
+
+    // Returns the packages marked changed through paths that are roots for the affected closures.
+    fn root_ids(&self) -> HashSet<&'g PackageId> {
+        self.path_changed_ids
+            .difference(&self.target_only_ids())
+            .copied()
+            .collect()
+    }
+
+    // Runs simulated builds with the given options, and computes the affected sets from them.
+    fn simulate(
+        &self,
+        builds: &SimulatedBuilds<'g, 'a>,
+        pass: usize,
+        cargo_options: &CargoOptions<'a>,
+        path_changed_set: PackageSet<'g>,
+    ) -> DeterminatorSet<'g> {
+        let determinator = self.determinator;
+        let target_only_ids = self.target_only_ids();
+        let root_ids = self.root_ids();
+
+        // Simulated builds with dev-dependencies, as for tests, and if requested without them.
+        let (test, build) = rayon::join(
+            || self.simulate_stage(builds, pass, cargo_options, true, &root_ids),
+            || {
+                determinator
+                    .separate_build_stage
+                    .then(|| self.simulate_stage(builds, pass, cargo_options, false, &root_ids))
+            },
+        );
+
+        let target_only_set = determinator
+            .new
+            .resolve_ids(target_only_ids.iter().copied())
+            .expect("package IDs are all valid");
+        let test_affected_set = test.affected_set.union(&target_only_set);
+        // Packages where only tests, benchmarks or examples changed don't need to be rebuilt.
+        let build_affected_set = build.as_ref().map(|build| build.affected_set.clone());
+        let affected_set = match &build_affected_set {
+            Some(build_affected_set) => test_affected_set.union(build_affected_set),
+            None => test_affected_set.clone(),
+        };
+
+        // Packages affected in both stages are explained through the test stage.
+        let mut reasons = self.reasons.clone();
+        for (package_id, reason) in &test.reasons {
+            reasons.add(package_id, reason.clone());
+        }
+        let build_reasons = build.as_ref().map(|build| {
+            let mut build_reasons = self.reasons.clone();
+            for (package_id, reason) in &build.reasons {
+                if !test.explained.contains(package_id) {
+                    reasons.add(package_id, reason.clone());
+                }
+                build_reasons.add(package_id, reason.clone());
+            }
+            build_reasons
+        });
+
+        let mut explained = test.explained;
+        let mut summary_changed_ids = test.summary_changed_ids;
+        let mut feature_changed_ids = test.feature_changed_ids;
+        if let Some(build) = build {
+            explained.extend(build.explained);
+            summary_changed_ids.extend(build.summary_changed_ids);
+            feature_changed_ids.extend(build.feature_changed_ids);
+        }
+        let path_changed_set = path_changed_set.union(
+            &determinator
+                .new
+                .resolve_ids(feature_changed_ids)
+                .expect("package IDs are all valid"),
+        );
+        let summary_changed_set = determinator
+            .new
+            .resolve_ids(summary_changed_ids)
+            .expect("package IDs are all valid");
+
+        // 7. With build target granularity, packages outside the closures only have specific
+        // targets affected.
+        let affected_targets = determinator.build_target_granularity.then(|| {
+            let mut affected_targets = AffectedTargets::new(determinator.new);
+            for package_id in affected_set.package_ids(DependencyDirection::Forward) {
+                let targets = match &self.target_changes.get(package_id) {
+                    Some(Some(targets)) if !explained.contains(package_id) => {
+                        AffectedPackageTargets::Only(targets.clone())
+                    }
+                    _ => AffectedPackageTargets::All,
+                };
+                affected_targets.insert(package_id, targets);
+            }
+            affected_targets
+        });
+
+        DeterminatorSet {
+            affected_set,
+            build_affected_set,
+            test_affected_set,
+            affected_targets,
+            path_changed_set,
+            summary_changed_set,
+            renamed_packages: determinator.package_matches.renamed_packages(),
+            deleted_packages: determinator.package_matches.deleted_packages(),
+            reasons,
+            build_reasons,
         }
     }
 
-    fn process_build_summaries(&mut self) {
+    // Runs simulated builds either with or without dev-dependencies, and computes the packages
+    // affected through them starting from the given roots.
+    fn simulate_stage(
+        &self,
+        builds: &SimulatedBuilds<'g, 'a>,
+        pass: usize,
+        cargo_options: &CargoOptions<'a>,
+        include_dev: bool,
+        root_ids: &HashSet<&'g PackageId>,
+    ) -> StageResult<'g> {
+        let determinator = self.determinator;
+        let mut simulation =
+            Simulation::new(determinator, builds, pass, cargo_options, include_dev);
+        let mut root_ids = root_ids.clone();
+        // Packages with a reason recorded for why all of their targets are affected.
+        let mut explained = root_ids.clone();
+        let mut reasons = vec![];
+
+        // Packages marked by feature-gated path rules are changed if this simulation builds one of
+        // the features.
+        let mut feature_changed_ids = HashSet::new();
+        for mark in &self.feature_marks {
+            if let Some(feature) = simulation.built_feature(mark.package_id, mark.features) {
+                root_ids.insert(mark.package_id);
+                feature_changed_ids.insert(mark.package_id);
+                explained.insert(mark.package_id);
+                reasons.push((
+                    mark.package_id,
+                    AffectedReason::PathRuleFeature {
+                        path: mark.path.clone(),
+                        rule_index: mark.rule_index,
+                        feature: feature.to_owned(),
+                    },
+                ));
+            }
+        }
+
+        // 4. Use build summaries as another source of changes. Packages that depended on a deleted
+        // package had their dependencies change.
+        let deleted_dependents = determinator.package_matches.deleted_dependents(include_dev);
+        for (id, name) in deleted_dependents {
+            if root_ids.contains(id) {
+                continue;
+            }
+            simulation.summary_changed_ids.insert(id);
+            if explained.insert(id) {
+                reasons.push((
+                    id,
+                    AffectedReason::DependencyDeleted {
+                        name: name.to_owned(),
+                    },
+                ));
+            }
+        }
+
+        let summary_changed = simulation.summaries_changed(determinator, &root_ids);
+        for (id, reason) in summary_changed {
+            simulation.summary_changed_ids.insert(id);
+            if explained.insert(id) {
+                reasons.push((id, reason));
+            }
+        }
+
+        // 5. The affected set is the transitive closure of the graph constructed by looking at
+        // both the build cache and package rules.
+        let closure = simulation.affected_closure(determinator.new, &root_ids);
+
+        // 6. Explain how every package not directly changed was affected.
+        for package_id in closure
+            .affected_set
+            .package_ids(DependencyDirection::Forward)
+        {
+            if explained.insert(package_id) {
+                reasons.push((
+                    package_id,
+                    closure.downstream_reason(determinator.new, package_id),
+                ));
+            }
+        }
+
+        StageResult {
+            affected_set: closure.affected_set,
+            summary_changed_ids: simulation.summary_changed_ids,
+            feature_changed_ids,
+            explained,
+            reasons,
+        }
+    }
+}
+
+/// The packages affected through simulated builds either with or without dev-dependencies.
+#[derive(Debug)]
+struct StageResult<'g> {
+    affected_set: PackageSet<'g>,
+    summary_changed_ids: HashSet<&'g PackageId>,
+    // Packages marked by feature-gated path rules whose features are built.
+    feature_changed_ids: HashSet<&'g PackageId>,
+    // Packages with a reason recorded for why all of their targets are affected.
+    explained: HashSet<&'g PackageId>,
+    // The reasons found through these builds, in addition to those recorded for changed paths.
+    reasons: Vec<(&'g PackageId, AffectedReason<'g>)>,
+}
+
+/// Simulated Cargo builds of every workspace package, either with or without dev-dependencies.
+#[derive(Debug)]
+struct Simulation<'g, 'a, 'b> {
+    include_dev: bool,
+    cargo_options: CargoOptions<'a>,
+    summary_changed_ids: HashSet<&'g PackageId>,
+    build_cache: CargoBuildCache<'g, 'g, 'b>,
+    old_build_cache: CargoBuildCache<'g, 'a, 'b>,
+    reverse_index: ReverseIndex<'g>,
+}
+
+impl<'g, 'a, 'b> Simulation<'g, 'a, 'b> {
+    fn new(
+        determinator: &Determinator<'g, 'a>,
+        builds: &'b SimulatedBuilds<'g, 'a>,
+        pass: usize,
+        cargo_options: &CargoOptions<'a>,
+        include_dev: bool,
+    ) -> Self {
+        let mut cargo_options = cargo_options.clone();
+        cargo_options.set_include_dev(include_dev);
+
+        let build_cache = CargoBuildCache::new(&builds.new, pass, include_dev);
+        let old_build_cache = CargoBuildCache::new(&builds.old, pass, include_dev);
+        let reverse_index = ReverseIndex::new(determinator, &build_cache);
+        Self {
+            include_dev,
+            cargo_options,
+            summary_changed_ids: HashSet::new(),
+            build_cache,
+            old_build_cache,
+            reverse_index,
+        }
+    }
+
+    // Returns workspace packages whose build summaries changed, along with the reasons.
+    fn summaries_changed(
+        &self,
+        determinator: &Determinator<'g, '_>,
+        path_changed_ids: &HashSet<&'g PackageId>,
+    ) -> Vec<(&'g PackageId, AffectedReason<'g>)> {
         // For each workspace package, if its build summaries have changed mark it changed.
-        let summary_changed_ids: Vec<_> = self
-            .determinator
+        determinator
             .new
             .workspace()
-            .par_iter_by_name()
-            .filter_map(|(name, package)| {
+            .par_iter()
+            .filter_map(|package| {
                 // Don't include packages already marked as changed through paths. (This is documented.)
-                if !self.path_changed_ids.contains(package.id())
-                    && self.build_summaries_changed(name, package)
-                {
-                    Some(package.id())
-                } else {
-                    None
+                if path_changed_ids.contains(package.id()) {
+                    return None;
                 }
+                self.build_summaries_changed(determinator, package)
+                    .map(|reason| (package.id(), reason))
             })
-            .collect();
-        self.summary_changed_ids.extend(summary_changed_ids);
-    }
-
-    fn build_summaries_changed(&self, name: &str, package: PackageMetadata<'g>) -> bool {
-        // Look up the package in the old metadata by path. (Workspace packages are uniquely
-        // identified by both name and path -- this could be done by name as well).
-        let old_workspace = self.determinator.old.workspace();
-        let old_package = match old_workspace.member_by_name(name) {
-            Ok(package) => package,
-            Err(_) => {
-                // Member not found: this is new or renamed.
-                return true;
-            }
-        };
+            .collect()
+    }
+
+    // Returns the first of these features of the package that's enabled in a simulated build of any
+    // workspace package with default features.
+    fn built_feature<'f>(&self, package_id: &PackageId, features: &'f [String]) -> Option<&'f str> {
+        features
+            .iter()
+            .map(|feature| feature.as_str())
+            .find(|feature| {
+                let feature_id = FeatureId::named(package_id, feature);
+                self.build_cache.result_cache.values().any(|build_result| {
+                    let cargo_set = &build_result.default;
+                    [cargo_set.target_features(), cargo_set.host_features()]
+                        .into_iter()
+                        .any(|feature_set| {
+                            feature_set
+                                .contains(feature_id)
+                                .expect("features in rules were checked already")
+                        })
+                })
+            })
+    }
 
-        let default_options = Determinator::default_cargo_options();
-        let cargo_options = self.determinator.cargo_options.unwrap_or(&default_options);
+    // Returns the reason the build summaries changed, or None if they didn't.
+    fn build_summaries_changed(
+        &self,
+        determinator: &Determinator<'g, '_>,
+        package: PackageMetadata<'g>,
+    ) -> Option<AffectedReason<'g>> {
+        // The package's counterpart in the old metadata accounts for packages that were moved or
+        // renamed.
+        if determinator
+            .package_matches
+            .old_package(package.id())
+            .is_none()
+        {
+            // No counterpart found: this is new.
+            return Some(AffectedReason::NewPackage);
+        }
 
-        let default_features_only = self.determinator.old.feature_graph().resolve_none();
-        let features_only = self
-            .determinator
-            .old_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
+        let old_result = self.old_build_cache.result_cache[package.id()];
+        let new_result = self.build_cache.result_cache[package.id()];
+        let changes = new_result.changes_from(old_result, &self.cargo_options, self.include_dev);
+        if changes.is_empty() {
+            None
+        } else {
+            Some(AffectedReason::SummaryChanged(changes))
+        }
+    }
 
-        let old_result = BuildResult::new(old_package, cargo_options, features_only);
-        let new_result = &self.build_cache.result_cache[package.id()];
-        new_result.is_changed(&old_result, cargo_options)
+    fn affected_closure(
+        &self,
+        package_graph: &'g PackageGraph,
+        path_changed_ids: &HashSet<&'g PackageId>,
+    ) -> AffectedClosure<'g> {
+        self.reverse_index.affected_closure(
+            package_graph,
+            path_changed_ids,
+            &self.summary_changed_ids,
+        )
     }
 }
 
-fn process_path<'g>(
+/// How a changed path caused packages to be marked changed. Passed into the callback for
+/// `process_path`.
+#[derive(Copy, Clone, Debug)]
+enum PathMarked<'g, 'p> {
+    /// A path rule marked this package changed.
+    Rule(&'g PackageId, RuleIndex),
+    /// A path rule marked this package changed, if one of these features is built.
+    RuleFeatures(&'g PackageId, RuleIndex, &'p [String]),
+    /// The path was matched to this package through its nearest ancestor.
+    Ancestor(&'g PackageId),
+    /// The path was matched to this package through its nearest ancestor in the old workspace.
+    OldAncestor(&'g PackageId),
+    /// The path is used by this package, according to the dep-info index.
+    DepInfo(&'g PackageId),
+    /// A change to this key in the file's contents marked this package changed.
+    Contents(&'g PackageId, &'p str),
+    /// A path rule marked all packages changed.
+    RuleAll(RuleIndex),
+    /// A change to this key in the file's contents (or a parse error, if `None`) marked all
+    /// packages changed.
+    ContentsAll(Option<&'p str>),
+}
+
+fn process_path<'g, 'p>(
     path: &Utf8Path,
     workspace: &Workspace<'g>,
-    path_rules: &[PathRuleImpl<'g>],
-    mut match_cb: impl FnMut(&'g PackageId),
+    package_matches: &PackageMatches<'g, '_>,
+    path_rules: &'p [PathRuleImpl<'g>],
+    contents: Option<&'p ContentsAnalysis<'g>>,
+    dep_info: Option<&DepInfoIndex<'g>>,
+    mut match_cb: impl FnMut(PathMarked<'g, 'p>),
 ) -> PathMatch {
     let candidate = Candidate::new(path);
+    let mut skip_rules = false;
 
     // 1. Apply any rules that match the path.
     for rule in path_rules {
+        if let (Some(contents), RuleIndex::DefaultPath(_)) = (contents, rule.rule_index) {
+            // The contents analysis takes the place of the default rules.
+            return process_contents(contents, match_cb);
+        }
         if rule.glob_set.is_match_candidate(&candidate) {
             // This glob matches this rule, so execute it.
             match &rule.mark_changed {
                 MarkChangedImpl::Packages(packages) => {
                     for package in packages {
-                        match_cb(package.id());
+                        if rule.features.is_empty() {
+                            match_cb(PathMarked::Rule(package.id(), rule.rule_index));
+                        } else {
+                            match_cb(PathMarked::RuleFeatures(
+                                package.id(),
+                                rule.rule_index,
+                                &rule.features,
+                            ));
+                        }
                     }
                 }
                 MarkChangedImpl::All => {
                     // Mark all packages changed.
+                    match_cb(PathMarked::RuleAll(rule.rule_index));
                     return PathMatch::RuleMatchedAll;
                 }
             }
@@ -402,6 +1181,7 @@ fn process_path<'g>(
                 DeterminatorPostRule::SkipRules => {
                     // Skip further rule processing but continue to step 2 to match to the
                     // nearest package.
+                    skip_rules = true;
                     break;
                 }
                 DeterminatorPostRule::Fallthrough => {
@@ -412,49 +1192,335 @@ fn process_path<'g>(
         }
     }
 
-    // 2. Map the path to its nearest ancestor package.
+    if let (Some(contents), false) = (contents, skip_rules) {
+        // No default rules were applied, so use the contents analysis.
+        return process_contents(contents, match_cb);
+    }
+
+    // 2. Mark packages that use the path in their builds, according to the dep-info index.
+    let mut dep_info_matched = false;
+    if let Some(dep_info) = dep_info {
+        for package in dep_info.packages_for_path(path) {
+            match_cb(PathMarked::DepInfo(package.id()));
+            dep_info_matched = true;
+        }
+    }
+
+    // 3. Map the path to its nearest ancestor package.
     for ancestor in path.ancestors() {
         if let Ok(package) = workspace.member_by_path(ancestor) {
-            match_cb(package.id());
+            match_cb(PathMarked::Ancestor(package.id()));
             return PathMatch::AncestorMatched;
         }
     }
 
-    // 3. If a file didn't match anything so far, rebuild everything.
+    // 4. Map the path to its nearest ancestor package in the old workspace, in case that package
+    // was moved, renamed or deleted. Paths within deleted packages don't mark anything changed.
+    if let Some(package_id) = package_matches.old_ancestor(path) {
+        if let Some(package_id) = package_id {
+            match_cb(PathMarked::OldAncestor(package_id));
+        }
+        return PathMatch::OldAncestorMatched;
+    }
+
+    if dep_info_matched {
+        return PathMatch::DepInfoMatched;
+    }
+
+    // 5. If a file didn't match anything so far, rebuild everything.
     PathMatch::NoMatches
 }
 
-/// Stores a build cache of every package in a workspace.
+fn process_contents<'g, 'p>(
+    contents: &'p ContentsAnalysis<'g>,
+    mut match_cb: impl FnMut(PathMarked<'g, 'p>),
+) -> PathMatch {
+    if let Some(change) = contents.marks_all() {
+        match_cb(PathMarked::ContentsAll(change.key.as_deref()));
+        return PathMatch::ContentsMatchedAll;
+    }
+    for change in &contents.changes {
+        if let (Some(key), MarkChangedImpl::Packages(packages)) =
+            (&change.key, &change.mark_changed)
+        {
+            for package in packages {
+                match_cb(PathMarked::Contents(package.id(), key));
+            }
+        }
+    }
+    PathMatch::ContentsMatched
+}
+
+/// A package marked by a feature-gated path rule.
 #[derive(Debug)]
-struct CargoBuildCache<'g> {
-    result_cache: AHashMap<&'g PackageId, BuildResult<'g>>,
+struct FeatureMark<'g, 'b> {
+    package_id: &'g PackageId,
+    features: &'b [String],
+    path: Utf8PathBuf,
+    rule_index: RuleIndex,
 }
 
-impl<'g> CargoBuildCache<'g> {
-    fn new(determinator: &Determinator<'g, '_>) -> Self {
-        let default_options = Determinator::default_cargo_options();
-        let cargo_options = determinator.cargo_options.unwrap_or(&default_options);
+/// Stores a build cache of every package in a workspace, for one set of Cargo options.
+///
+/// The keys are package IDs in the new graph, even for builds of their counterparts in the old
+/// graph.
+#[derive(Debug)]
+struct CargoBuildCache<'g, 'x, 'b> {
+    result_cache: AHashMap<&'g PackageId, &'b BuildResult<'x>>,
+}
+
+impl<'g, 'x, 'b> CargoBuildCache<'g, 'x, 'b> {
+    fn new(build_results: &'b BuildResults<'g, 'x>, pass: usize, include_dev: bool) -> Self {
+        let result_cache = build_results
+            .indexes
+            .iter()
+            .filter(|&(&(key_pass, key_include_dev, _), _)| {
+                key_pass == pass && key_include_dev == include_dev
+            })
+            .map(|(&(_, _, id), &index)| (id, &build_results.results[index]))
+            .collect();
+        Self { result_cache }
+    }
+}
+
+/// Simulated builds of every workspace package in the new graph and its counterpart in the old
+/// graph, for each set of Cargo options and with or without dev-dependencies.
+#[derive(Debug)]
+struct SimulatedBuilds<'g, 'a> {
+    new: BuildResults<'g, 'g>,
+    old: BuildResults<'g, 'a>,
+}
 
+impl<'g, 'a> SimulatedBuilds<'g, 'a> {
+    fn new(
+        determinator: &Determinator<'g, 'a>,
+        cargo_options: &[CargoOptions<'a>],
+        platforms: Option<&[PlatformSpec]>,
+        root_ids: &HashSet<&'g PackageId>,
+    ) -> Self {
+        let include_devs: &[bool] = if determinator.separate_build_stage {
+            &[true, false]
+        } else {
+            &[true]
+        };
         let workspace = determinator.new.workspace();
-        let default_features_only = determinator.new.feature_graph().resolve_none();
-        let features_only = determinator
-            .new_features_only
-            .as_ref()
-            .unwrap_or(&default_features_only);
+        let new_packages: Vec<_> = workspace
+            .iter()
+            .map(|package| (package.id(), package))
+            .collect();
+        // Builds of old packages are only compared against for packages that aren't already
+        // changed through paths.
+        let old_packages: Vec<_> = workspace
+            .iter()
+            .filter(|package| !root_ids.contains(package.id()))
+            .filter_map(|package| {
+                let old_package = determinator.package_matches.old_package(package.id())?;
+                Some((package.id(), old_package))
+            })
+            .collect();
 
-        let result_cache: ahash::HashMap<_, _> = workspace
+        let (new, old) = rayon::join(
+            || {
+                BuildResults::new(
+                    determinator.new,
+                    determinator.new_features_only.as_ref(),
+                    &new_packages,
+                    cargo_options,
+                    platforms,
+                    include_devs,
+                )
+            },
+            || {
+                BuildResults::new(
+                    determinator.old,
+                    determinator.old_features_only.as_ref(),
+                    &old_packages,
+                    cargo_options,
+                    platforms,
+                    include_devs,
+                )
+            },
+        );
+        Self { new, old }
+    }
+}
+
+/// Simulated builds of packages in one graph.
+///
+/// Other than their platforms, the Cargo options used for each platform are the same. A
+/// simulated build of a package only depends on the platform through the platform-specific
+/// dependencies reachable from it, so platforms that evaluate all of those the same way share a
+/// single build.
+#[derive(Debug)]
+struct BuildResults<'g, 'x> {
+    results: Vec<BuildResult<'x>>,
+    // Maps (index of Cargo options, include_dev, package ID in the new graph) to an index into
+    // results.
+    indexes: AHashMap<(usize, bool, &'g PackageId), usize>,
+}
+
+impl<'g, 'x> BuildResults<'g, 'x> {
+    fn new(
+        graph: &'x PackageGraph,
+        features_only: Option<&FeatureSet<'x>>,
+        packages: &[(&'g PackageId, PackageMetadata<'x>)],
+        cargo_options: &[CargoOptions<'_>],
+        platforms: Option<&[PlatformSpec]>,
+        include_devs: &[bool],
+    ) -> Self {
+        let default_features_only = graph.feature_graph().resolve_none();
+        let features_only = features_only.unwrap_or(&default_features_only);
+
+        // Without platforms, there's only one set of options and nothing to share.
+        let platform_deps = platforms.map(|platforms| {
+            let platform_deps = PlatformDeps::new(graph, features_only, packages);
+            let evals: Vec<_> = platforms
+                .iter()
+                .map(|platform| platform_deps.eval(platform))
+                .collect();
+            (platform_deps, evals)
+        });
+
+        let mut job_indexes = AHashMap::new();
+        let mut jobs = vec![];
+        let mut indexes = AHashMap::new();
+        for (pass, cargo_options) in cargo_options.iter().enumerate() {
+            for &(id, package) in packages {
+                let signature = match &platform_deps {
+                    Some((platform_deps, evals)) => {
+                        platform_deps.signature(package.id(), &evals[pass])
+                    }
+                    None => vec![],
+                };
+                for &include_dev in include_devs {
+                    let index = *job_indexes
+                        .entry((package.id(), include_dev, signature.clone()))
+                        .or_insert_with(|| {
+                            jobs.push((package, cargo_options, include_dev));
+                            jobs.len() - 1
+                        });
+                    indexes.insert((pass, include_dev, id), index);
+                }
+            }
+        }
+
+        let results = jobs
+            .into_par_iter()
+            .map(|(package, cargo_options, include_dev)| {
+                let mut cargo_options = cargo_options.clone();
+                cargo_options.set_include_dev(include_dev);
+                BuildResult::new(package, &cargo_options, features_only)
+            })
+            .collect();
+
+        Self { results, indexes }
+    }
+}
+
+/// The platform-specific dependencies reachable from each package, used to tell whether simulated
+/// builds can be shared between platforms.
+#[derive(Debug)]
+struct PlatformDeps<'x> {
+    statuses: Vec<PlatformStatus<'x>>,
+    // Sorted indexes into statuses for each package, including the statuses reachable from
+    // features-only packages.
+    package_statuses: AHashMap<&'x PackageId, Vec<usize>>,
+}
+
+impl<'x> PlatformDeps<'x> {
+    fn new(
+        graph: &'x PackageGraph,
+        features_only: &FeatureSet<'x>,
+        packages: &[(&PackageId, PackageMetadata<'x>)],
+    ) -> Self {
+        let mut statuses = vec![];
+        // Statuses are deduplicated by the specs backing them.
+        let mut status_indexes = AHashMap::new();
+        let mut direct_statuses: AHashMap<&'x PackageId, Vec<usize>> = AHashMap::new();
+        for link in graph.resolve_all().links(DependencyDirection::Forward) {
+            let link_statuses = direct_statuses.entry(link.from().id()).or_default();
+            for req in [link.normal(), link.build(), link.dev()] {
+                let enabled_statuses = [
+                    req.status(),
+                    req.default_features(),
+                    req.no_default_features(),
+                ]
+                .into_iter()
+                .chain(req.features().map(|feature| req.feature_status(feature)));
+                for enabled_status in enabled_statuses {
+                    for status in [
+                        enabled_status.required_status(),
+                        enabled_status.optional_status(),
+                    ] {
+                        if let PlatformStatus::PlatformDependent { eval } = status {
+                            let specs = eval.target_specs();
+                            let index = *status_indexes
+                                .entry((specs.as_ptr(), specs.len()))
+                                .or_insert_with(|| {
+                                    statuses.push(status);
+                                    statuses.len() - 1
+                                });
+                            link_statuses.push(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        let reachable_statuses = |ids: Vec<&PackageId>| {
+            let mut indexes: Vec<_> = graph
+                .query_forward(ids)
+                .expect("package IDs are all valid")
+                .resolve()
+                .package_ids(DependencyDirection::Forward)
+                .filter_map(|id| direct_statuses.get(id))
+                .flatten()
+                .copied()
+                .collect();
+            indexes.sort_unstable();
+            indexes.dedup();
+            indexes
+        };
+        let features_only_statuses = reachable_statuses(
+            features_only
+                .to_package_set()
+                .package_ids(DependencyDirection::Forward)
+                .collect(),
+        );
+        let package_statuses: ahash::HashMap<_, _> = packages
             .par_iter()
-            .map(|package| {
-                let id = package.id();
-                let build_result = BuildResult::new(package, cargo_options, features_only);
-                (id, build_result)
+            .map(|(_, package)| {
+                let mut indexes = reachable_statuses(vec![package.id()]);
+                indexes.extend(&features_only_statuses);
+                indexes.sort_unstable();
+                indexes.dedup();
+                (package.id(), indexes)
             })
             .collect();
 
         Self {
-            result_cache: result_cache.into(),
+            statuses,
+            package_statuses: package_statuses.into(),
         }
     }
+
+    // Evaluates every status against the platform.
+    fn eval(&self, platform: &PlatformSpec) -> Vec<EnabledTernary> {
+        self.statuses
+            .iter()
+            .map(|status| status.enabled_on(platform))
+            .collect()
+    }
+
+    // Returns the results of evaluating the statuses reachable from this package, given the
+    // results for every status. Simulated builds of the package are identical for platforms with
+    // the same signature.
+    fn signature(&self, package_id: &PackageId, evals: &[EnabledTernary]) -> Vec<EnabledTernary> {
+        self.package_statuses[package_id]
+            .iter()
+            .map(|&index| evals[index])
+            .collect()
+    }
 }
 
 #[derive(Debug)]
@@ -509,38 +1575,54 @@ impl<'g> BuildResult<'g> {
     fn unified_workspace_set(&self, workspace_set: &PackageSet<'g>) -> PackageSet<'g> {
         let target_set = self
             .all_cargo_sets()
-            .map(|x| x.target_features().to_package_set())
+            .map(|(_, x)| x.target_features().to_package_set())
             .reduce(|a, b| a.union(&b))
             .expect("at least one set");
         let host_set = self
             .all_cargo_sets()
-            .map(|x| x.host_features().to_package_set())
+            .map(|(_, x)| x.host_features().to_package_set())
             .reduce(|a, b| a.union(&b))
             .expect("at least one set");
 
         target_set.union(&host_set).intersection(workspace_set)
     }
 
-    fn is_changed(&self, other: &BuildResult<'_>, cargo_options: &CargoOptions<'_>) -> bool {
-        for (a, b) in self.all_cargo_sets().zip(other.all_cargo_sets()) {
-            let a_summary = a
+    /// Returns the feature sets for which the build changed from `old`, along with summaries.
+    fn changes_from(
+        &self,
+        old: &BuildResult<'_>,
+        cargo_options: &CargoOptions<'_>,
+        include_dev: bool,
+    ) -> Vec<SummaryChange> {
+        let mut changes = vec![];
+        for ((features, new_set), (_, old_set)) in self.all_cargo_sets().zip(old.all_cargo_sets()) {
+            let new_summary = new_set
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let b_summary = b
+            let old_summary = old_set
                 .to_summary(cargo_options)
                 .expect("custom platforms currently unsupported");
-            let diff = a_summary.diff(&b_summary);
-            if diff.is_changed() {
-                return true;
+            if old_summary.diff(&new_summary).is_changed() {
+                changes.push(SummaryChange {
+                    features,
+                    include_dev,
+                    old: old_summary,
+                    new: new_summary,
+                });
             }
         }
-        false
+        changes
     }
 
-    fn all_cargo_sets<'a>(&'a self) -> impl Iterator<Item = &'a CargoSet<'g>> + 'a {
-        std::iter::once(&self.none)
-            .chain(std::iter::once(&self.default))
-            .chain(std::iter::once(&self.all))
+    fn all_cargo_sets<'a>(
+        &'a self,
+    ) -> impl Iterator<Item = (StandardFeatures, &'a CargoSet<'g>)> + 'a {
+        [
+            (StandardFeatures::None, &self.none),
+            (StandardFeatures::Default, &self.default),
+            (StandardFeatures::All, &self.all),
+        ]
+        .into_iter()
     }
 }
 
@@ -567,13 +1649,13 @@ struct ReverseIndex<'g> {
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 enum ReverseIndexEdge {
     /// This edge was added as a package rule. This always takes precedence over `CargoBuild`.
-    PackageRule,
+    PackageRule(RuleIndex),
     /// This edge was added through the Cargo build cache.
     CargoBuild,
 }
 
 impl<'g> ReverseIndex<'g> {
-    fn new(determinator: &Determinator<'g, '_>, build_cache: &CargoBuildCache<'g>) -> Self {
+    fn new(determinator: &Determinator<'g, '_>, build_cache: &CargoBuildCache<'g, 'g, '_>) -> Self {
         let mut reverse_index = GraphMap::new();
 
         let workspace_set = determinator.new.resolve_workspace();
@@ -602,7 +1684,7 @@ impl<'g> ReverseIndex<'g> {
                             (
                                 Some(on_affected),
                                 Some(package.id()),
-                                ReverseIndexEdge::PackageRule,
+                                ReverseIndexEdge::PackageRule(package_rule.rule_index),
                             )
                         }));
                     }
@@ -611,7 +1693,7 @@ impl<'g> ReverseIndex<'g> {
                         reverse_index.add_edge(
                             Some(on_affected),
                             None,
-                            ReverseIndexEdge::PackageRule,
+                            ReverseIndexEdge::PackageRule(package_rule.rule_index),
                         );
                     }
                 }
@@ -626,30 +1708,33 @@ impl<'g> ReverseIndex<'g> {
         package_graph: &'g PackageGraph,
         path_changed: &HashSet<&'g PackageId>,
         summary_changed: &HashSet<&'g PackageId>,
-    ) -> PackageSet<'g> {
+    ) -> AffectedClosure<'g> {
         // This is a *really* interesting DFS, in that there's one restriction: you can't follow
         // two CargoBuild edges consecutively. Also, in the initial set, path_changed allows
         // CargoBuild to be followed once while summary_changed doesn't allow it to be followed.
 
-        #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-        enum FollowCargoBuild {
-            Allowed,
-            NotAllowed,
-        }
-
         use FollowCargoBuild::*;
 
         // The order of what goes in the stack doesn't matter for correctness, but putting Allowed
         // at the end (and therefore popping it first) lowers the chance of an upgrade re-traversal.
+        // Sorting makes the explanations in `parents` deterministic.
+        let mut summary_changed: Vec<_> = summary_changed.iter().copied().collect();
+        summary_changed.sort_unstable();
+        let mut path_changed: Vec<_> = path_changed.iter().copied().collect();
+        path_changed.sort_unstable();
         let mut stack: Vec<_> = summary_changed
-            .iter()
-            .map(|id| (*id, NotAllowed))
-            .chain(path_changed.iter().map(|id| (*id, Allowed)))
+            .into_iter()
+            .map(|id| (id, NotAllowed))
+            .chain(path_changed.into_iter().map(|id| (id, Allowed)))
             .collect();
+        let initial: HashSet<_> = stack.iter().copied().collect();
 
         // Do a DFS with two maps, in case there are cycles (can happen with dev deps).
         let mut discovered = AHashMap::new();
         let mut finished = HashSet::new();
+        // For explanations: the state and edge through which each non-initial state was first
+        // pushed. Using the first time means that following parents never results in a cycle.
+        let mut parents = AHashMap::new();
 
         while let Some(&(id, follow)) = stack.last() {
             let push_neighbors = match discovered.entry(id) {
@@ -676,7 +1761,9 @@ impl<'g> ReverseIndex<'g> {
             };
 
             if push_neighbors {
-                for (_, neighbor, &edge) in self.reverse_index.edges(Some(id)) {
+                let mut edges: Vec<_> = self.reverse_index.edges(Some(id)).collect();
+                edges.sort_unstable_by_key(|(_, neighbor, _)| *neighbor);
+                for (_, neighbor, &edge) in edges {
                     if edge == ReverseIndexEdge::CargoBuild && follow == NotAllowed {
                         // Can't follow two consecutive CargoBuild edges.
                         continue;
@@ -685,27 +1772,42 @@ impl<'g> ReverseIndex<'g> {
                         Some(neighbor) => {
                             let neighbor_follow = match edge {
                                 ReverseIndexEdge::CargoBuild => NotAllowed,
-                                ReverseIndexEdge::PackageRule => Allowed,
+                                ReverseIndexEdge::PackageRule(_) => Allowed,
                             };
 
-                            match (discovered.get(&neighbor), neighbor_follow) {
-                                (None, _) => {
-                                    // Node has not been discovered yet. Add it to the stack to
-                                    // be visited.
-                                    stack.push((neighbor, neighbor_follow))
-                                }
-                                (Some(NotAllowed), Allowed) => {
-                                    // Node was previously discovered with NotAllowed but is
-                                    // now discovered with Allowed. This is an upgrade. Add it to
-                                    // the stack to be visited.
-                                    stack.push((neighbor, neighbor_follow))
+                            let push = match (discovered.get(&neighbor), neighbor_follow) {
+                                // Node has not been discovered yet. Add it to the stack to be
+                                // visited.
+                                (None, _) => true,
+                                // Node was previously discovered with NotAllowed but is now
+                                // discovered with Allowed. This is an upgrade. Add it to the stack
+                                // to be visited.
+                                (Some(NotAllowed), Allowed) => true,
+                                _ => false,
+                            };
+                            if push {
+                                if !initial.contains(&(neighbor, neighbor_follow)) {
+                                    parents
+                                        .entry((neighbor, neighbor_follow))
+                                        .or_insert(((id, follow), edge));
                                 }
-                                _ => {}
+                                stack.push((neighbor, neighbor_follow));
                             }
                         }
                         None => {
                             // Build everything, can just exit here.
-                            return package_graph.resolve_workspace();
+                            let rule_index = match edge {
+                                ReverseIndexEdge::PackageRule(rule_index) => rule_index,
+                                ReverseIndexEdge::CargoBuild => {
+                                    unreachable!("only package rules mark all packages changed")
+                                }
+                            };
+                            return AffectedClosure {
+                                affected_set: package_graph.resolve_workspace(),
+                                discovered,
+                                parents,
+                                all_from: Some(((id, follow), rule_index)),
+                            };
                         }
                     }
                 }
@@ -716,8 +1818,75 @@ impl<'g> ReverseIndex<'g> {
         }
 
         // At the end of this process, finished contains all nodes discovered.
-        package_graph
-            .resolve_ids(finished.iter().copied())
-            .expect("all IDs are valid")
+        AffectedClosure {
+            affected_set: package_graph
+                .resolve_ids(finished.iter().copied())
+                .expect("all IDs are valid"),
+            discovered,
+            parents,
+            all_from: None,
+        }
+    }
+}
+
+/// Whether a `CargoBuild` edge may be followed from a node in `ReverseIndex::affected_closure`.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+enum FollowCargoBuild {
+    Allowed,
+    NotAllowed,
+}
+
+type ClosureState<'g> = (&'g PackageId, FollowCargoBuild);
+
+/// The result of `ReverseIndex::affected_closure`.
+#[derive(Debug)]
+struct AffectedClosure<'g> {
+    affected_set: PackageSet<'g>,
+    discovered: AHashMap<&'g PackageId, FollowCargoBuild>,
+    parents: AHashMap<ClosureState<'g>, (ClosureState<'g>, ReverseIndexEdge)>,
+    // If a package rule caused all packages to be affected, the state and rule responsible.
+    all_from: Option<(ClosureState<'g>, RuleIndex)>,
+}
+
+impl<'g> AffectedClosure<'g> {
+    /// Returns the chain through which a package not directly changed was affected.
+    fn downstream_reason(
+        &self,
+        package_graph: &'g PackageGraph,
+        package_id: &'g PackageId,
+    ) -> AffectedReason<'g> {
+        let link = |id, kind| AffectedLink {
+            package: package_graph.metadata(id).expect("valid package ID"),
+            kind,
+        };
+
+        let mut chain = vec![];
+        let mut current = match self.discovered.get(package_id) {
+            Some(&follow) => (package_id, follow),
+            None => {
+                // This package was only affected through a package rule that marked all packages.
+                let (from, rule_index) = self
+                    .all_from
+                    .expect("undiscovered package was affected through a package rule");
+                chain.push(link(package_id, AffectedLinkKind::PackageRule(rule_index)));
+                from
+            }
+        };
+        while let Some(&(parent, edge)) = self.parents.get(&current) {
+            let kind = match edge {
+                ReverseIndexEdge::CargoBuild => AffectedLinkKind::Dependency,
+                ReverseIndexEdge::PackageRule(rule_index) => {
+                    AffectedLinkKind::PackageRule(rule_index)
+                }
+            };
+            chain.push(link(current.0, kind));
+            current = parent;
+        }
+        chain.reverse();
+
+        AffectedReason::Downstream {
+            changed: package_graph.metadata(current.0).expect("valid package ID"),
+            chain,
+        }
     }
 }
@@ -4,7 +4,8 @@
 //! Error types returned by the determinator.
 
 use crate::rules::RuleIndex;
-use std::{error, fmt};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{error, fmt, io, sync::Arc};
 
 /// An error that occurred while resolving a set of determinator rules.
 #[derive(Debug)]
@@ -35,6 +36,33 @@ impl RulesError {
         }
     }
 
+    pub(crate) fn invalid_selector(
+        rule_index: RuleIndex,
+        selector: &str,
+        message: &'static str,
+    ) -> Self {
+        let kind = RulesErrorKind::InvalidSelector {
+            selector: selector.to_owned(),
+            message,
+        };
+        Self { rule_index, kind }
+    }
+
+    pub(crate) fn unknown_feature(rule_index: RuleIndex, package: &str, feature: &str) -> Self {
+        let kind = RulesErrorKind::UnknownFeature {
+            package: package.to_owned(),
+            feature: feature.to_owned(),
+        };
+        Self { rule_index, kind }
+    }
+
+    pub(crate) fn features_with_mark_all(rule_index: RuleIndex) -> Self {
+        Self {
+            rule_index,
+            kind: RulesErrorKind::FeaturesWithMarkAll,
+        }
+    }
+
     pub(crate) fn glob_parse(rule_index: RuleIndex, err: globset::Error) -> Self {
         let kind = RulesErrorKind::GlobParse {
             glob: err.glob().map(|s| s.to_owned()),
@@ -59,6 +87,9 @@ impl error::Error for RulesError {
         match &self.kind {
             RulesErrorKind::ResolveRef(err) => Some(err),
             RulesErrorKind::GlobParse { err, .. } => Some(&**err),
+            RulesErrorKind::InvalidSelector { .. }
+            | RulesErrorKind::UnknownFeature { .. }
+            | RulesErrorKind::FeaturesWithMarkAll => None,
         }
     }
 }
@@ -77,6 +108,25 @@ pub enum RulesErrorKind {
         /// The error that occurred while parsing the glob.
         err: Box<dyn error::Error + Send + Sync>,
     },
+
+    /// A package selector was invalid or didn't match any workspace packages.
+    InvalidSelector {
+        /// The selector, as written in the rules.
+        selector: String,
+        /// A description of the problem.
+        message: &'static str,
+    },
+
+    /// A feature-gated path rule named a feature that a marked package doesn't have.
+    UnknownFeature {
+        /// The name of the package.
+        package: String,
+        /// The name of the feature.
+        feature: String,
+    },
+
+    /// A path rule with `features` set marks all packages changed.
+    FeaturesWithMarkAll,
 }
 
 impl fmt::Display for RulesErrorKind {
@@ -90,6 +140,96 @@ impl fmt::Display for RulesErrorKind {
             RulesErrorKind::GlobParse { glob: None, err } => {
                 write!(f, "while parsing a glob: {err}")
             }
+            RulesErrorKind::InvalidSelector { selector, message } => {
+                write!(f, "package selector '{selector}' {message}")
+            }
+            RulesErrorKind::UnknownFeature { package, feature } => {
+                write!(f, "package '{package}' doesn't have feature '{feature}'")
+            }
+            RulesErrorKind::FeaturesWithMarkAll => {
+                write!(f, "features can't be used with mark-changed = \"all\"")
+            }
         }
     }
 }
+
+/// An error that occurred while parsing a `CODEOWNERS` file.
+#[derive(Debug)]
+pub struct CodeOwnersError {
+    line_number: usize,
+    err: globset::Error,
+}
+
+impl CodeOwnersError {
+    /// Returns the line number (starting from 1) that failed to parse.
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Returns the glob that failed to parse, if one was present.
+    pub fn glob(&self) -> Option<&str> {
+        self.err.glob()
+    }
+
+    // ---
+    // Internal constructors
+    // ---
+
+    pub(crate) fn new(line_number: usize, err: globset::Error) -> Self {
+        Self { line_number, err }
+    }
+}
+
+impl fmt::Display for CodeOwnersError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "error while parsing CODEOWNERS: line {}: {}",
+            self.line_number, self.err
+        )
+    }
+}
+
+impl error::Error for CodeOwnersError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+/// An error that occurred while reading build outputs into a
+/// [`DepInfoIndex`](crate::dep_info::DepInfoIndex).
+#[derive(Clone, Debug)]
+pub struct DepInfoError {
+    path: Utf8PathBuf,
+    err: Arc<io::Error>,
+}
+
+impl DepInfoError {
+    /// Returns the path that couldn't be read.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    // ---
+    // Internal constructors
+    // ---
+
+    pub(crate) fn new(path: impl Into<Utf8PathBuf>, err: io::Error) -> Self {
+        Self {
+            path: path.into(),
+            err: Arc::new(err),
+        }
+    }
+}
+
+impl fmt::Display for DepInfoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error while reading build outputs at {}", self.path)
+    }
+}
+
+impl error::Error for DepInfoError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.err)
+    }
+}
@@ -43,6 +43,9 @@
 //! }
 //! ```
 //!
+//! For a command-line interface to the determinator, see
+//! [`cargo-determinator`](https://docs.rs/cargo-determinator).
+//!
 //! # Platform support
 //!
 //! * **Unix platforms**: The determinator works and is supported.
@@ -77,6 +80,10 @@
 //! determinator lets you specify *custom rules*. See the
 //! [Customizing behavior](#customizing-behavior) section below for more.
 //!
+//! Files outside packages, such as shared assets or protobuf definitions, are often only used by a
+//! few packages. The [`dep_info` module](crate::dep_info) can map them to those packages
+//! automatically, using dep-info files and build script outputs from a local target directory.
+//!
 //! ## Dependency changes
 //!
 //! A dependency is assumed to have changed if one or more of the following change:
@@ -88,9 +95,10 @@
 //! The determinator runs Cargo build simulations on every package in the workspace. For each
 //! package, the determinator figures out whether any of its dependencies (including feature sets)
 //! have changed. These simulations are done with:
-//! * dev-dependencies enabled (by default; this can be customized)
+//! * dev-dependencies enabled, and also disabled if a separate build stage is enabled
 //! * both the host and target platforms set to the current platform (by default; this can be
-//!   customized)
+//!   customized, and [`Determinator::compute_for_platforms`] computes separate results for several
+//!   platforms at once)
 //! * three sets of features for each package:
 //!   * no features enabled
 //!   * default features
@@ -99,6 +107,34 @@
 //! If any of these simulated builds indicates that a workspace package has had any dependency
 //! changes, then it is marked changed.
 //!
+//! Builds with dev-dependencies determine which packages' tests are affected, and are reported as
+//! [`DeterminatorSet::test_affected_set`]. For CI systems that build and test packages in separate
+//! stages, [`Determinator::set_separate_build_stage`] also runs builds without dev-dependencies to
+//! determine which packages need to be rebuilt, and reports them as
+//! [`DeterminatorSet::build_affected_set`].
+//!
+//! ## Workspace configuration changes
+//!
+//! By default, a change to the root `Cargo.toml` or to `.cargo/config.toml` causes everything to
+//! be rebuilt, since these files can affect every build. If the old and new contents of these
+//! files are provided through [`Determinator::set_root_manifest_contents`] and
+//! [`Determinator::set_cargo_config_contents`], the determinator compares them key by key instead.
+//! Formatting and comment changes are then ignored, and changes to entries in
+//! `[workspace.dependencies]`, `[patch]` or per-package profile overrides only mark the packages
+//! that use those entries.
+//!
+//! ## Moved, renamed and deleted packages
+//!
+//! Workspace packages in the old and new graphs are matched by name, and then by path. Packages that
+//! were both moved and renamed can be matched through renamed paths passed in to
+//! [`Determinator::add_renamed_paths`], for example from `git diff --name-status -M`. Changed paths
+//! within a package's old directory are then matched to the package at its new location. Moved and
+//! renamed packages are reported in [`DeterminatorSet::renamed_packages`].
+//!
+//! Workspace packages in the old graph without a counterpart in the new graph are reported in
+//! [`DeterminatorSet::deleted_packages`], and the packages that directly depended on them are marked
+//! changed.
+//!
 //! ## Environment changes
 //!
 //! The *environment* of a build or test run is anything not part of the source code that may
@@ -130,6 +166,8 @@
 //! * Some files should be ignored.
 //! * If some files or packages change, a full test run may be necessary.
 //! * *Virtual dependencies* that Cargo isn't aware of may need to be inserted.
+//! * Some files may only matter if a particular feature is built, for example an optional
+//!   subsystem that most builds don't enable.
 //!
 //! For these situations, the determinator allows for custom *rules* to be specified. The
 //! determinator also ships with
@@ -138,6 +176,26 @@
 //!
 //! For more about custom rules, see the documentation for the [`rules` module](crate::rules).
 //!
+//! # Explaining results
+//!
+//! [`DeterminatorSet::reasons`] records why each package was affected: the changed path and the
+//! rule that matched it, the dependencies that changed in a simulated build, or the chain of
+//! dependencies and package rules leading from a changed package.
+//!
+//! # Build targets
+//!
+//! By default, the determinator works at the granularity of packages. With
+//! [`Determinator::set_build_target_granularity`], it also computes the build targets affected within
+//! each package. A change to `tests/foo.rs` then only affects the `foo` integration test, while
+//! changes to a package's library affect all of its targets. The [`targets` module](crate::targets)
+//! also produces [nextest](https://nexte.st) filter expressions for affected targets.
+//!
+//! # Code owners
+//!
+//! The [`owners` module](crate::owners) maps workspace packages to their owners as listed in a
+//! `CODEOWNERS` file. Combined with a [`DeterminatorSet`], this lists the owners of changed
+//! packages, along with the owners of packages downstream of the change.
+//!
 //! # Limitations
 //!
 //! While the determinator can bring significant benefits to CI and local workflows, its model is
@@ -244,9 +302,15 @@
 //! This determinator is inspired by, and shares its name with, the target determinator used in
 //! Facebook's main source repository.
 
+mod contents;
+pub mod dep_info;
 mod determinator;
 pub mod errors;
+pub mod owners;
 mod paths0;
+mod reasons;
+mod renames;
 pub mod rules;
+pub mod targets;
 
-pub use crate::{determinator::*, paths0::*};
+pub use crate::{determinator::*, paths0::*, reasons::*, renames::*};
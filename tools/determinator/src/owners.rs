@@ -0,0 +1,313 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Map workspace packages to their owners through a `CODEOWNERS` file.
+//!
+//! [`CodeOwners`] parses a `CODEOWNERS` file in the format used by
+//! [GitHub](https://docs.github.com/en/repositories/managing-your-repositorys-settings-and-features/customizing-your-repository/about-code-owners)
+//! and GitLab. [`PackageOwners`] maps each workspace package to its owners, and can be combined
+//! with a [`DeterminatorSet`] to find the owners affected by a change.
+//!
+//! # Examples
+//!
+//! ```
+//! use determinator::owners::CodeOwners;
+//!
+//! let code_owners = CodeOwners::parse(
+//!     "# Default owners
+//!      *            @org/everyone
+//!      /tools/      @org/tools-team
+//!      docs/*.md    @docs-writer",
+//! )
+//! .expect("CODEOWNERS parsed successfully");
+//!
+//! assert_eq!(code_owners.owners_for_path("README.md"), ["@org/everyone"]);
+//! assert_eq!(code_owners.owners_for_path("tools/foo/src/lib.rs"), ["@org/tools-team"]);
+//! assert_eq!(code_owners.owners_for_path("docs/guide.md"), ["@docs-writer"]);
+//! ```
+
+use crate::{DeterminatorSet, errors::CodeOwnersError};
+use camino::Utf8Path;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use guppy::{
+    PackageId,
+    graph::{DependencyDirection, PackageGraph, PackageMetadata},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A parsed `CODEOWNERS` file.
+///
+/// Patterns follow `.gitignore` rules, and the last matching pattern in the file takes precedence.
+/// A pattern without owners unsets ownership for matching paths.
+#[derive(Clone, Debug)]
+pub struct CodeOwners {
+    entries: Vec<CodeOwnersEntry>,
+}
+
+#[derive(Clone, Debug)]
+struct CodeOwnersEntry {
+    glob_set: GlobSet,
+    owners: Vec<String>,
+}
+
+impl CodeOwners {
+    /// Locations, relative to the repository root, that `CODEOWNERS` files are typically found in.
+    ///
+    /// These are listed in the order GitHub searches for them.
+    pub const DEFAULT_LOCATIONS: &'static [&'static str] =
+        &[".github/CODEOWNERS", "CODEOWNERS", "docs/CODEOWNERS"];
+
+    /// Parses a `CODEOWNERS` file.
+    ///
+    /// Blank lines, comments and GitLab-style `[Section]` headers are skipped.
+    pub fn parse(s: &str) -> Result<Self, CodeOwnersError> {
+        let mut entries = vec![];
+        for (idx, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || is_section_header(line) {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let pattern = tokens.next().expect("line is non-empty");
+            let owners = tokens
+                .take_while(|token| !token.starts_with('#'))
+                .map(|token| token.to_owned())
+                .collect();
+
+            let mut builder = GlobSetBuilder::new();
+            for glob in pattern_to_globs(pattern) {
+                let glob = GlobBuilder::new(&glob)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|err| CodeOwnersError::new(idx + 1, err))?;
+                builder.add(glob);
+            }
+            let glob_set = builder
+                .build()
+                .map_err(|err| CodeOwnersError::new(idx + 1, err))?;
+
+            entries.push(CodeOwnersEntry { glob_set, owners });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Returns the owners for the given path, relative to the root of the repository.
+    ///
+    /// Returns an empty slice if the path has no owners.
+    pub fn owners_for_path(&self, path: impl AsRef<Utf8Path>) -> &[String] {
+        let path = path.as_ref();
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.glob_set.is_match(path))
+            .map_or(&[], |entry| entry.owners.as_slice())
+    }
+
+    /// Maps every workspace package in this graph to its owners.
+    ///
+    /// The owners of a package are the owners of its `Cargo.toml`.
+    ///
+    /// The `CODEOWNERS` file is assumed to be at the root of the workspace.
+    pub fn package_owners<'g>(&self, graph: &'g PackageGraph) -> PackageOwners<'g> {
+        let owners = graph
+            .workspace()
+            .iter_by_path()
+            .map(|(workspace_path, package)| {
+                let owners = self
+                    .owners_for_path(workspace_path.join("Cargo.toml"))
+                    .to_vec();
+                (package.id(), owners)
+            })
+            .collect();
+        PackageOwners { graph, owners }
+    }
+}
+
+/// A mapping of workspace packages to their owners.
+///
+/// Created by [`CodeOwners::package_owners`].
+#[derive(Clone, Debug)]
+pub struct PackageOwners<'g> {
+    graph: &'g PackageGraph,
+    owners: BTreeMap<&'g PackageId, Vec<String>>,
+}
+
+impl<'g> PackageOwners<'g> {
+    /// Returns the owners of the given workspace package.
+    ///
+    /// Returns `None` if the package ID isn't a workspace package.
+    pub fn owners(&self, package_id: &PackageId) -> Option<&[String]> {
+        self.owners.get(package_id).map(|owners| owners.as_slice())
+    }
+
+    /// Returns workspace packages that don't have any owners.
+    pub fn unowned(&self) -> impl Iterator<Item = PackageMetadata<'g>> + '_ {
+        self.owners
+            .iter()
+            .filter(|(_, owners)| owners.is_empty())
+            .map(|(id, _)| self.graph.metadata(id).expect("valid package ID"))
+    }
+
+    /// Returns the owners affected by a determinator computation.
+    pub fn affected_owners(&self, determinator_set: &DeterminatorSet<'g>) -> AffectedOwners<'g> {
+        let changed_set = determinator_set
+            .path_changed_set
+            .union(&determinator_set.summary_changed_set);
+
+        let mut changed: BTreeMap<&str, BTreeSet<&'g PackageId>> = BTreeMap::new();
+        let mut downstream: BTreeMap<&str, BTreeSet<&'g PackageId>> = BTreeMap::new();
+        for package_id in determinator_set
+            .affected_set
+            .package_ids(DependencyDirection::Forward)
+        {
+            let map = if changed_set.contains(package_id).expect("valid package ID") {
+                &mut changed
+            } else {
+                &mut downstream
+            };
+            for owner in self.owners(package_id).unwrap_or_default() {
+                map.entry(owner).or_default().insert(package_id);
+            }
+        }
+
+        let to_owned = |map: BTreeMap<&str, BTreeSet<&'g PackageId>>| {
+            map.into_iter()
+                .map(|(owner, ids)| {
+                    let packages = self
+                        .graph
+                        .resolve_ids(ids)
+                        .expect("valid package IDs")
+                        .packages(DependencyDirection::Forward)
+                        .collect();
+                    (owner.to_owned(), packages)
+                })
+                .collect()
+        };
+
+        AffectedOwners {
+            changed: to_owned(changed),
+            downstream: to_owned(downstream),
+        }
+    }
+}
+
+/// The owners affected by a change, as returned by [`PackageOwners::affected_owners`].
+#[derive(Clone, Debug)]
+pub struct AffectedOwners<'g> {
+    /// Owners of packages that were directly changed, either through a changed path or a changed
+    /// build summary, along with those packages.
+    pub changed: BTreeMap<String, Vec<PackageMetadata<'g>>>,
+
+    /// Owners of packages that were affected but not directly changed, along with those packages.
+    ///
+    /// These are typically reverse dependencies of changed packages, or packages marked through
+    /// package rules. An owner may appear both here and in `changed`.
+    pub downstream: BTreeMap<String, Vec<PackageMetadata<'g>>>,
+}
+
+impl AffectedOwners<'_> {
+    /// Returns all affected owners, whether directly or through reverse dependencies.
+    pub fn all_owners(&self) -> BTreeSet<&str> {
+        self.changed
+            .keys()
+            .chain(self.downstream.keys())
+            .map(|owner| owner.as_str())
+            .collect()
+    }
+}
+
+fn is_section_header(line: &str) -> bool {
+    let line = line.strip_prefix('^').unwrap_or(line);
+    line.starts_with('[')
+}
+
+/// Converts a `.gitignore`-style pattern into globs relative to the root of the repository.
+fn pattern_to_globs(pattern: &str) -> Vec<String> {
+    // A leading slash, or a slash in the middle of the pattern, anchors it to the root.
+    let (anchored, pattern) = match pattern.strip_prefix('/') {
+        Some(rest) => (true, rest),
+        None => (pattern.trim_end_matches('/').contains('/'), pattern),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+
+    let base = if anchored || pattern.starts_with("**") {
+        pattern.to_owned()
+    } else {
+        format!("**/{pattern}")
+    };
+
+    let last_component = base
+        .rsplit('/')
+        .next()
+        .expect("rsplit returns at least one element");
+    if dir_only {
+        vec![format!("{base}/**")]
+    } else if last_component.contains('*') {
+        // As with GitHub, wildcards such as in `docs/*` don't match nested directories.
+        vec![base]
+    } else {
+        // The pattern may match either a file or a directory.
+        vec![format!("{base}/**"), base]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owners_for_path() {
+        let code_owners = CodeOwners::parse(
+            r#"
+            # This is a comment.
+            *                   @everyone
+            *.md                @docs # inline comment
+            /build/logs/        @logs
+            apps/               @apps
+            /docs/**/*.rs       @doc-examples
+            /scripts/*          @scripts
+            /scripts/unowned
+
+            [Section]
+            /tools/hakari       @hakari @tools
+            "#,
+        )
+        .expect("parsed successfully");
+
+        let cases: &[(&str, &[&str])] = &[
+            ("README.md", &["@docs"]),
+            ("foo/bar/README.md", &["@docs"]),
+            ("src/lib.rs", &["@everyone"]),
+            ("build/logs/x.log", &["@logs"]),
+            ("build/logs/nested/x.log", &["@logs"]),
+            ("foo/build/logs/x.log", &["@everyone"]),
+            ("apps/foo.rs", &["@apps"]),
+            ("nested/apps/foo.rs", &["@apps"]),
+            ("docs/a/b/c.rs", &["@doc-examples"]),
+            ("scripts/foo.sh", &["@scripts"]),
+            ("scripts/nested/foo.sh", &["@everyone"]),
+            ("scripts/unowned", &[]),
+            ("tools/hakari/Cargo.toml", &["@hakari", "@tools"]),
+            ("tools/hakari-other/Cargo.toml", &["@everyone"]),
+        ];
+
+        for &(path, expected) in cases {
+            assert_eq!(
+                code_owners.owners_for_path(path),
+                expected,
+                "owners for {path} match"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bad() {
+        let err = CodeOwners::parse("* @everyone\n/foo/[ @foo\n").expect_err("invalid glob");
+        assert_eq!(err.line_number(), 2, "line number is correct");
+    }
+}
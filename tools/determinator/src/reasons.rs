@@ -0,0 +1,318 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::rules::RuleIndex;
+use ahash::AHashMap;
+use camino::Utf8PathBuf;
+use guppy::{
+    PackageId,
+    graph::{
+        PackageMetadata,
+        feature::StandardFeatures,
+        summaries::{
+            Summary,
+            diff::{SummaryDiff, SummaryDiffTag},
+        },
+    },
+};
+use std::fmt;
+
+/// Explanations for why packages in a [`DeterminatorSet`](crate::DeterminatorSet) were marked
+/// changed or affected.
+///
+/// Every package in the affected set has at least one reason associated with it.
+#[derive(Clone, Debug)]
+pub struct AffectedReasons<'g> {
+    reasons: AHashMap<&'g PackageId, Vec<AffectedReason<'g>>>,
+}
+
+impl<'g> AffectedReasons<'g> {
+    pub(crate) fn new() -> Self {
+        Self {
+            reasons: AHashMap::new(),
+        }
+    }
+
+    /// Returns the reasons the given package was marked changed or affected.
+    ///
+    /// Returns an empty slice if the package wasn't affected.
+    pub fn reasons(&self, package_id: &PackageId) -> &[AffectedReason<'g>] {
+        self.reasons
+            .get(package_id)
+            .map_or(&[], |reasons| reasons.as_slice())
+    }
+
+    /// Iterates over all affected packages and the reasons they were affected, in no particular
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'g PackageId, &[AffectedReason<'g>])> + '_ {
+        self.reasons
+            .iter()
+            .map(|(id, reasons)| (*id, reasons.as_slice()))
+    }
+
+    pub(crate) fn add(&mut self, package_id: &'g PackageId, reason: AffectedReason<'g>) {
+        self.reasons.entry(package_id).or_default().push(reason);
+    }
+}
+
+/// The reason a package was marked changed or affected.
+///
+/// Returned by [`AffectedReasons::reasons`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum AffectedReason<'g> {
+    /// A changed path matched a path rule, which marked this package changed.
+    PathRule {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// The path rule that was matched.
+        rule_index: RuleIndex,
+    },
+
+    /// A changed path matched a feature-gated path rule, which marked this package changed because
+    /// one of the rule's features is built.
+    PathRuleFeature {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// The path rule that was matched.
+        rule_index: RuleIndex,
+
+        /// The feature of this package that is built.
+        feature: String,
+    },
+
+    /// A changed path was matched to this package through its nearest ancestor directory.
+    PathAncestor {
+        /// The changed path.
+        path: Utf8PathBuf,
+    },
+
+    /// A changed path was matched to this package through its nearest ancestor directory in the
+    /// old workspace, before the package was moved or renamed.
+    PathOldAncestor {
+        /// The changed path.
+        path: Utf8PathBuf,
+    },
+
+    /// A changed path is used by this package's build, according to the
+    /// [`DepInfoIndex`](crate::dep_info::DepInfoIndex).
+    PathDepInfo {
+        /// The changed path.
+        path: Utf8PathBuf,
+    },
+
+    /// A changed path caused every package in the workspace to be marked changed.
+    PathAll {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// The path rule that marked all packages changed, or `None` if the path didn't match any
+        /// rules or packages.
+        rule_index: Option<RuleIndex>,
+    },
+
+    /// A key changed in the contents of the root `Cargo.toml` or `.cargo/config.toml`, and this
+    /// package depends on that key.
+    ContentsChanged {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// The dotted path to the changed key, for example `workspace.dependencies.serde`.
+        key: String,
+    },
+
+    /// A change in the contents of the root `Cargo.toml` or `.cargo/config.toml` caused every
+    /// package in the workspace to be marked changed.
+    ContentsChangedAll {
+        /// The changed path.
+        path: Utf8PathBuf,
+
+        /// The dotted path to the changed key, or `None` if the file couldn't be parsed.
+        key: Option<String>,
+    },
+
+    /// The package isn't present in the old workspace.
+    NewPackage,
+
+    /// This package directly depended on a workspace package that was deleted.
+    DependencyDeleted {
+        /// The name of the deleted package.
+        name: String,
+    },
+
+    /// The result of one or more simulated Cargo builds of this package changed.
+    ///
+    /// There is one entry for each set of features whose simulated build changed.
+    SummaryChanged(Vec<SummaryChange>),
+
+    /// A package upstream of this one was marked changed, and this package was affected through a
+    /// chain of dependencies or package rules.
+    Downstream {
+        /// The package that was marked changed, through a path or a simulated build.
+        changed: PackageMetadata<'g>,
+
+        /// The chain of packages from `changed` to this package, in order.
+        ///
+        /// The last link in the chain is this package.
+        chain: Vec<AffectedLink<'g>>,
+    },
+}
+
+impl fmt::Display for AffectedReason<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AffectedReason::PathRule { path, rule_index } => {
+                write!(f, "path {path} matched {rule_index}")
+            }
+            AffectedReason::PathRuleFeature {
+                path,
+                rule_index,
+                feature,
+            } => write!(
+                f,
+                "path {path} matched {rule_index}, and feature {feature} is built"
+            ),
+            AffectedReason::PathAncestor { path } => {
+                write!(f, "path {path} is within the package")
+            }
+            AffectedReason::PathOldAncestor { path } => {
+                write!(
+                    f,
+                    "path {path} was within the package before it was moved or renamed"
+                )
+            }
+            AffectedReason::PathDepInfo { path } => {
+                write!(f, "path {path} is used by the package's build")
+            }
+            AffectedReason::PathAll { path, rule_index } => match rule_index {
+                Some(rule_index) => write!(
+                    f,
+                    "path {path} matched {rule_index}, which marked all packages changed"
+                ),
+                None => write!(
+                    f,
+                    "path {path} did not match any rules or packages, so all packages were \
+                     marked changed"
+                ),
+            },
+            AffectedReason::ContentsChanged { path, key } => {
+                write!(f, "path {path} changed at key {key}")
+            }
+            AffectedReason::ContentsChangedAll { path, key } => match key {
+                Some(key) => write!(
+                    f,
+                    "path {path} changed at key {key}, which marked all packages changed"
+                ),
+                None => write!(
+                    f,
+                    "path {path} could not be parsed, so all packages were marked changed"
+                ),
+            },
+            AffectedReason::NewPackage => write!(f, "package is new"),
+            AffectedReason::DependencyDeleted { name } => {
+                write!(f, "dependency {name} was deleted")
+            }
+            AffectedReason::SummaryChanged(changes) => {
+                write!(f, "simulated build changed")?;
+                for (idx, change) in changes.iter().enumerate() {
+                    let sep = if idx == 0 { ": " } else { "; " };
+                    write!(f, "{sep}{change}")?;
+                }
+                Ok(())
+            }
+            AffectedReason::Downstream { changed, chain } => {
+                write!(f, "affected through {}", changed.name())?;
+                for link in chain {
+                    write!(f, " -> {}", link.package.name())?;
+                    if let AffectedLinkKind::PackageRule(rule_index) = link.kind {
+                        write!(f, " ({rule_index})")?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A change in the simulated Cargo build of a package, with a particular set of features.
+#[derive(Clone, Debug)]
+pub struct SummaryChange {
+    /// The features the package was built with.
+    pub features: StandardFeatures,
+
+    /// Whether the package's dev-dependencies were included in the build, as for tests.
+    pub include_dev: bool,
+
+    /// The summary of the build against the old package graph.
+    pub old: Summary,
+
+    /// The summary of the build against the new package graph.
+    pub new: Summary,
+}
+
+impl SummaryChange {
+    /// Returns a diff of the old and new summaries.
+    ///
+    /// The diff lists the dependencies that were added, removed or modified, and the features that
+    /// changed for each of them.
+    pub fn diff(&self) -> SummaryDiff<'_> {
+        SummaryDiff::new(&self.old, &self.new)
+    }
+}
+
+impl fmt::Display for SummaryChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let features = match self.features {
+            StandardFeatures::None => "no",
+            StandardFeatures::Default => "default",
+            StandardFeatures::All => "all",
+        };
+        if self.include_dev {
+            write!(f, "with {features} features,")?;
+        } else {
+            write!(f, "with {features} features and without dev-dependencies,")?;
+        }
+
+        let diff = self.diff();
+        let changed = diff
+            .target_packages
+            .changed
+            .iter()
+            .chain(&diff.host_packages.changed);
+        for (idx, (summary_id, status)) in changed.enumerate() {
+            let sep = if idx == 0 { " " } else { ", " };
+            let tag = match status.tag() {
+                SummaryDiffTag::Added => "added",
+                SummaryDiffTag::Modified => "modified",
+                SummaryDiffTag::Removed => "removed",
+            };
+            write!(f, "{sep}{} {} {tag}", summary_id.name, summary_id.version)?;
+        }
+        Ok(())
+    }
+}
+
+/// A link in a chain of affected packages.
+///
+/// Part of [`AffectedReason::Downstream`].
+#[derive(Clone, Debug)]
+pub struct AffectedLink<'g> {
+    /// The package that was affected.
+    pub package: PackageMetadata<'g>,
+
+    /// How this package was affected by the previous package in the chain.
+    pub kind: AffectedLinkKind,
+}
+
+/// How a package in an [`AffectedLink`] was affected by the previous package in the chain.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum AffectedLinkKind {
+    /// A simulated Cargo build showed that this package depends on the previous package.
+    Dependency,
+
+    /// A package rule with the previous package in `on-affected` marked this package changed.
+    PackageRule(RuleIndex),
+}
@@ -0,0 +1,228 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use ahash::AHashMap;
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::{
+    PackageId,
+    graph::{PackageGraph, PackageMetadata, Workspace},
+};
+
+/// A workspace package that was renamed or moved between the old and new package graphs.
+///
+/// Returned in [`DeterminatorSet::renamed_packages`](crate::DeterminatorSet::renamed_packages).
+#[derive(Clone, Debug)]
+pub struct RenamedPackage<'g> {
+    /// The package in the new graph.
+    pub package: PackageMetadata<'g>,
+
+    /// The name of the package in the old graph.
+    ///
+    /// This is the same as the new name if the package was only moved.
+    pub old_name: String,
+
+    /// The path of the package in the old graph, relative to the workspace root.
+    ///
+    /// This is the same as the new path if the package was only renamed.
+    pub old_workspace_path: Utf8PathBuf,
+}
+
+/// A workspace package that was present in the old package graph but not in the new one.
+///
+/// Returned in [`DeterminatorSet::deleted_packages`](crate::DeterminatorSet::deleted_packages).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeletedPackage {
+    /// The name of the package in the old graph.
+    pub name: String,
+
+    /// The path of the package in the old graph, relative to the workspace root.
+    pub workspace_path: Utf8PathBuf,
+}
+
+/// Matches workspace packages in the old graph to their counterparts in the new graph.
+///
+/// Packages are matched, in order of priority:
+/// 1. by both name and path
+/// 2. through renamed paths, with each new package matched to the old package most of its files
+///    were renamed from
+/// 3. by name, for packages that were moved
+/// 4. by path, for packages that were renamed in place
+///
+/// Old packages that don't match anything were deleted.
+#[derive(Clone, Debug)]
+pub(crate) struct PackageMatches<'g, 'a> {
+    old: &'a PackageGraph,
+    new_to_old: AHashMap<&'g PackageId, PackageMetadata<'a>>,
+    old_to_new: AHashMap<&'a PackageId, PackageMetadata<'g>>,
+}
+
+impl<'g, 'a> PackageMatches<'g, 'a> {
+    pub(crate) fn new(
+        old: &'a PackageGraph,
+        new: &'g PackageGraph,
+        renamed_paths: &[(&Utf8Path, &Utf8Path)],
+    ) -> Self {
+        let old_workspace = old.workspace();
+        let new_workspace = new.workspace();
+        let mut matches = Self {
+            old,
+            new_to_old: AHashMap::new(),
+            old_to_new: AHashMap::new(),
+        };
+
+        // 1. Packages that didn't change.
+        for new_package in new_workspace.iter() {
+            if let Ok(old_package) = old_workspace.member_by_name(new_package.name()) {
+                if old_package.source().workspace_path() == new_package.source().workspace_path() {
+                    matches.try_insert(old_package, new_package);
+                }
+            }
+        }
+
+        // 2. Renamed paths. Each one is a vote for the old package that the path was in to match
+        // the new package that it's now in.
+        let mut votes: AHashMap<_, usize> = AHashMap::new();
+        for (old_path, new_path) in renamed_paths {
+            if let (Some(old_package), Some(new_package)) = (
+                nearest_member(&old_workspace, old_path),
+                nearest_member(&new_workspace, new_path),
+            ) {
+                *votes
+                    .entry((old_package.id(), new_package.id()))
+                    .or_default() += 1;
+            }
+        }
+        let mut votes: Vec<_> = votes.into_iter().collect();
+        // Sort by the number of votes, most first. Break ties by package ID so that results are
+        // deterministic.
+        votes.sort_unstable_by(|(ids_a, count_a), (ids_b, count_b)| {
+            count_b.cmp(count_a).then_with(|| ids_a.cmp(ids_b))
+        });
+        for ((old_id, new_id), _) in votes {
+            matches.try_insert(
+                old.metadata(old_id).expect("old package ID is valid"),
+                new.metadata(new_id).expect("new package ID is valid"),
+            );
+        }
+
+        // 3. Packages with the same name.
+        for new_package in new_workspace.iter() {
+            if let Ok(old_package) = old_workspace.member_by_name(new_package.name()) {
+                matches.try_insert(old_package, new_package);
+            }
+        }
+
+        // 4. Packages at the same path.
+        for new_package in new_workspace.iter() {
+            let old_package = new_package
+                .source()
+                .workspace_path()
+                .and_then(|path| old_workspace.member_by_path(path).ok());
+            if let Some(old_package) = old_package {
+                matches.try_insert(old_package, new_package);
+            }
+        }
+
+        matches
+    }
+
+    /// Returns the old package that the new package with this ID corresponds to, or `None` if the
+    /// package is new.
+    pub(crate) fn old_package(&self, package_id: &PackageId) -> Option<PackageMetadata<'a>> {
+        self.new_to_old.get(package_id).copied()
+    }
+
+    /// Maps a path to its nearest ancestor package in the old workspace.
+    ///
+    /// Returns `None` if the path wasn't in any old workspace package, `Some(None)` if that package
+    /// was deleted, and `Some(Some(id))` with the new package's ID otherwise.
+    pub(crate) fn old_ancestor(&self, path: &Utf8Path) -> Option<Option<&'g PackageId>> {
+        let old_package = nearest_member(&self.old.workspace(), path)?;
+        Some(
+            self.old_to_new
+                .get(old_package.id())
+                .map(|new_package| new_package.id()),
+        )
+    }
+
+    /// Returns the packages that were renamed or moved, sorted by their new names.
+    pub(crate) fn renamed_packages(&self) -> Vec<RenamedPackage<'g>> {
+        let mut renamed: Vec<_> = self
+            .new_to_old
+            .values()
+            .filter_map(|old_package| {
+                let new_package = self.old_to_new[old_package.id()];
+                let old_workspace_path = old_package.source().workspace_path()?;
+                (old_package.name() != new_package.name()
+                    || Some(old_workspace_path) != new_package.source().workspace_path())
+                .then(|| RenamedPackage {
+                    package: new_package,
+                    old_name: old_package.name().to_owned(),
+                    old_workspace_path: old_workspace_path.to_owned(),
+                })
+            })
+            .collect();
+        renamed.sort_unstable_by_key(|renamed| renamed.package.name());
+        renamed
+    }
+
+    /// Returns the packages that were deleted, sorted by name.
+    pub(crate) fn deleted_packages(&self) -> Vec<DeletedPackage> {
+        let mut deleted: Vec<_> = self
+            .deleted()
+            .filter_map(|old_package| {
+                Some(DeletedPackage {
+                    name: old_package.name().to_owned(),
+                    workspace_path: old_package.source().workspace_path()?.to_owned(),
+                })
+            })
+            .collect();
+        deleted.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        deleted
+    }
+
+    /// Returns new packages that directly depended on a deleted package, along with the name of
+    /// that package.
+    ///
+    /// If `include_dev` is false, packages that only had the deleted package as a dev-dependency
+    /// are skipped.
+    pub(crate) fn deleted_dependents(&self, include_dev: bool) -> Vec<(&'g PackageId, &'a str)> {
+        let mut dependents = vec![];
+        for old_package in self.deleted() {
+            for link in old_package.reverse_direct_links() {
+                if !include_dev && link.dev_only() {
+                    continue;
+                }
+                if let Some(new_package) = self.old_to_new.get(link.from().id()) {
+                    dependents.push((new_package.id(), old_package.name()));
+                }
+            }
+        }
+        dependents.sort_unstable();
+        dependents.dedup();
+        dependents
+    }
+
+    fn deleted(&self) -> impl Iterator<Item = PackageMetadata<'a>> + '_ {
+        self.old
+            .workspace()
+            .iter()
+            .filter(move |old_package| !self.old_to_new.contains_key(old_package.id()))
+    }
+
+    fn try_insert(&mut self, old_package: PackageMetadata<'a>, new_package: PackageMetadata<'g>) {
+        if self.new_to_old.contains_key(new_package.id())
+            || self.old_to_new.contains_key(old_package.id())
+        {
+            return;
+        }
+        self.new_to_old.insert(new_package.id(), old_package);
+        self.old_to_new.insert(old_package.id(), new_package);
+    }
+}
+
+/// Returns the workspace package nearest to this path, if any.
+fn nearest_member<'g>(workspace: &Workspace<'g>, path: &Utf8Path) -> Option<PackageMetadata<'g>> {
+    path.ancestors()
+        .find_map(|ancestor| workspace.member_by_path(ancestor).ok())
+}
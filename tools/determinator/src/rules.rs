@@ -22,7 +22,9 @@
 //! * **Package rules** match based on changed packages, and are applied as required until
 //!   exhausted (i.e. a fixpoint is reached).
 //!
-//! Determinator rules are a configuration file format and can be read from a TOML file.
+//! Determinator rules are a configuration file format and can be read from a TOML file. The
+//! standard location for this file is [`.config/determinator.toml`](DeterminatorRules::DEFAULT_CONFIG_PATH)
+//! in the workspace root.
 //!
 //! # Default path rules
 //!
@@ -38,6 +40,38 @@
 //! use-default-rules = false
 //! ```
 //!
+//! # Package selectors
+//!
+//! The `mark-changed` lists in path and package rules, and the `on-affected` list in package
+//! rules, consist of *package selectors*. Each selector is one of:
+//!
+//! * A workspace package name, such as `"guppy"`.
+//! * A glob matched against workspace package names, such as `"guppy-*"`. Selectors containing
+//!   any of `*`, `?`, `[` or `{` are treated as globs.
+//! * `path:` followed by a glob matched against the paths of workspace packages, relative to the
+//!   workspace root, such as `"path:services/**"`. `*` doesn't match `/`, so `"path:services/*"`
+//!   only selects packages directly within `services`.
+//! * `rdeps:` followed by another selector, which selects the packages it matches along with all
+//!   workspace packages that depend on them, directly or transitively. For example,
+//!   `"rdeps:guppy"` selects `guppy` and every workspace package that uses it. All kinds of
+//!   dependencies, including dev-dependencies, are followed.
+//! * `!` followed by another selector, which excludes the packages it matches from the list. A
+//!   list that only has exclusions selects every other workspace package.
+//!
+//! Selectors are validated when rules are passed in to
+//! [`Determinator::set_rules`](crate::Determinator::set_rules): a name that isn't in the
+//! workspace, an invalid glob, or a selector that doesn't match any packages, is reported as a
+//! [`RulesError`].
+//!
+//! For example, to mark all packages in the `services` directory except for `services/legacy`
+//! changed if a protobuf definition changes:
+//!
+//! ```toml
+//! [[path-rule]]
+//! globs = ["proto/**/*.proto"]
+//! mark-changed = ["path:services/**", "!path:services/legacy"]
+//! ```
+//!
 //! # Examples for path rules
 //!
 //! To ignore all files named `README.md` and `README.tpl`, and skip all further processing:
@@ -86,6 +120,28 @@
 //! mark-changed = ["guppy"]
 //! ```
 //!
+//! # Feature-gated path rules
+//!
+//! Large optional subsystems are often gated behind features that most builds don't enable. A path
+//! rule with `features` set only marks packages changed if one of those features is built:
+//!
+//! ```toml
+//! [[path-rule]]
+//! globs = ["gpu-support/src/gpu/**"]
+//! mark-changed = ["gpu-support"]
+//! features = ["gpu"]
+//! ```
+//!
+//! With this rule, a change to `gpu-support/src/gpu/kernel.rs` only marks `gpu-support` changed
+//! if its `gpu` feature is built. (Since `post-rule` defaults to `"skip"`, the path isn't matched
+//! to its nearest package.)
+//!
+//! A feature is considered built if it is enabled in the simulated build of any workspace package
+//! with default features, on the platforms the determinator simulates builds for. Builds with all
+//! features enabled are not considered, since they would always enable the feature. To model CI
+//! jobs that enable the feature, add it to the features-only set through
+//! [`Determinator::add_features_only`](crate::Determinator::add_features_only).
+//!
 //! # Examples for package rules
 //!
 //! To add a "virtual dependency" that Cargo may not know about:
@@ -103,13 +159,22 @@
 //! on-affected = ["guppy-benchmarks"]
 //! mark-changed = "all"
 //! ```
+//!
+//! To mark a separate `integration-tests` package changed whenever `guppy`, or any package that
+//! depends on `guppy` other than `guppy-benchmarks`, is affected:
+//!
+//! ```toml
+//! [[package-rule]]
+//! on-affected = ["rdeps:guppy", "!guppy-benchmarks"]
+//! mark-changed = ["integration-tests"]
+//! ```
 
 use crate::errors::RulesError;
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use guppy::graph::{PackageGraph, PackageMetadata, PackageSet, Workspace};
+use globset::{Glob, GlobBuilder, GlobMatcher, GlobSet, GlobSetBuilder};
+use guppy::graph::{DependencyDirection, PackageGraph, PackageMetadata, PackageSet};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{collections::HashSet, fmt};
 
 /// Rules for the target determinator.
 ///
@@ -126,14 +191,18 @@ pub struct DeterminatorRules {
     use_default_rules: bool,
 
     /// A list of rules that each changed file path is matched against.
-    #[serde(default, rename = "path-rule")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty", rename = "path-rule")]
     pub path_rules: Vec<PathRule>,
 
     /// A list of rules that each affected package is matched against.
     ///
     /// Sometimes, dependencies between workspace packages aren't expressed in Cargo.tomls. The
     /// packages here act as "virtual dependencies" for the determinator.
-    #[serde(default, rename = "package-rule")]
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        rename = "package-rule"
+    )]
     pub package_rules: Vec<PackageRule>,
 }
 
@@ -165,6 +234,10 @@ macro_rules! doc_comment {
 }
 
 impl DeterminatorRules {
+    /// The location, relative to the workspace root, that determinator rules are read from by
+    /// `cargo determinator` and other tools.
+    pub const DEFAULT_CONFIG_PATH: &'static str = ".config/determinator.toml";
+
     /// Deserializes determinator rules from the given TOML string.
     pub fn parse(s: &str) -> Result<Self, toml::de::Error> {
         toml::from_str(s)
@@ -212,8 +285,12 @@ The latest version of the default rules is available
 /// ```
 ///
 /// For more examples, see [the module-level documentation](index.html).
+///
+/// This struct is marked `#[non_exhaustive]` so that new options can be added to rules. To create
+/// one outside of a TOML file, use [`PathRule::new`] and set any other fields afterwards.
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+#[non_exhaustive]
 pub struct PathRule {
     /// The globs to match against.
     ///
@@ -238,19 +315,55 @@ pub struct PathRule {
     /// mark-changed = "all"
     /// ```
     ///
-    /// Alternatively, `mark-changed` may be an array of workspace package names:
+    /// Alternatively, `mark-changed` may be an array of [package
+    /// selectors](index.html#package-selectors):
     ///
     /// ```toml
-    /// mark-changed = ["guppy", "determinator"]
+    /// mark-changed = ["guppy", "determinator", "path:tools/*"]
     /// ```
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
 
+    /// Features that the packages in `mark-changed` must be built with for them to be marked
+    /// changed.
+    ///
+    /// If this is non-empty, each package in `mark-changed` is only marked changed if at least one
+    /// of these features of that package is built. For more, see [Feature-gated path
+    /// rules](index.html#feature-gated-path-rules).
+    ///
+    /// Every package in `mark-changed` must have all of these features, and this can't be combined
+    /// with `mark-changed = "all"`.
+    ///
+    /// # Examples
+    ///
+    /// In TOML format:
+    ///
+    /// ```toml
+    /// features = ["gpu"]
+    /// ```
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+
     /// The operation to perform after applying the rule. Set to "skip" by default.
     #[serde(default)]
     pub post_rule: DeterminatorPostRule,
 }
 
+impl PathRule {
+    /// Creates a new path rule that marks the given packages changed if a path matches any of the
+    /// globs.
+    ///
+    /// `features` is empty and `post_rule` is set to "skip", matching the defaults in TOML format.
+    pub fn new(globs: Vec<String>, mark_changed: DeterminatorMarkChanged) -> Self {
+        Self {
+            globs,
+            mark_changed,
+            features: vec![],
+            post_rule: DeterminatorPostRule::default(),
+        }
+    }
+}
+
 /// The operation to perform after applying the rule.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
@@ -312,16 +425,16 @@ pub enum DeterminatorPostRule {
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields, rename_all = "kebab-case")]
 pub struct PackageRule {
-    /// The package names to match against.
+    /// The packages to match against.
     ///
     /// If any of the packages in this list is affected, the given packages will be marked changed.
     ///
     /// # Examples
     ///
-    /// In TOML format, specified as an array of workspace package names:
+    /// In TOML format, specified as an array of [package selectors](index.html#package-selectors):
     ///
     /// ```toml
-    /// on-affected = ["target-spec", "guppy"]
+    /// on-affected = ["target-spec", "guppy-*"]
     /// ```
     pub on_affected: Vec<String>,
 
@@ -335,10 +448,10 @@ pub struct PackageRule {
     /// mark-changed = "all"
     /// ```
     ///
-    /// or an array of workspace package names:
+    /// or an array of [package selectors](index.html#package-selectors):
     ///
     /// ```toml
-    /// mark-changed = ["guppy", "determinator"]
+    /// mark-changed = ["guppy", "rdeps:determinator"]
     /// ```
     #[serde(with = "mark_changed_impl")]
     pub mark_changed: DeterminatorMarkChanged,
@@ -354,7 +467,8 @@ pub struct PackageRule {
 /// mark-changed = "all"
 /// ```
 ///
-/// Alternatively, `mark-changed` may be an array of workspace package names:
+/// Alternatively, `mark-changed` may be an array of [package
+/// selectors](index.html#package-selectors):
 ///
 /// ```toml
 /// mark-changed = ["guppy", "determinator"]
@@ -364,7 +478,8 @@ pub struct PackageRule {
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case", untagged)]
 pub enum DeterminatorMarkChanged {
-    /// Mark the workspace packages with the given names as changed.
+    /// Mark the workspace packages matched by the given [package
+    /// selectors](index.html#package-selectors) as changed.
     ///
     /// This may be empty:
     ///
@@ -384,6 +499,7 @@ pub enum DeterminatorMarkChanged {
 ///
 /// Returned by `Determinator::match_path`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum PathMatch {
     /// The path matched a rule, causing everything to be rebuilt.
     RuleMatchedAll,
@@ -393,8 +509,27 @@ pub enum PathMatch {
     RuleMatched(RuleIndex),
     /// The path was matched to a package through inspecting the parent directories of each path.
     AncestorMatched,
+    /// The path isn't inside any package, but was inside a package in the old workspace that was
+    /// moved, renamed or deleted.
+    ///
+    /// If the package was moved or renamed, its new counterpart is matched. Paths within deleted
+    /// packages don't match any packages.
+    OldAncestorMatched,
+    /// The path isn't inside any package, but packages that use it were found through the
+    /// [`DepInfoIndex`](crate::dep_info::DepInfoIndex).
+    DepInfoMatched,
     /// The path wasn't matched to a rule or a nearby package, causing everything to be rebuilt.
     NoMatches,
+    /// The path is the root `Cargo.toml` or `.cargo/config.toml`, and its old and new contents
+    /// were compared to figure out which packages changed.
+    ///
+    /// Only returned if the contents were provided through
+    /// [`Determinator::set_root_manifest_contents`](crate::Determinator::set_root_manifest_contents)
+    /// or
+    /// [`Determinator::set_cargo_config_contents`](crate::Determinator::set_cargo_config_contents).
+    ContentsMatched,
+    /// Like `ContentsMatched`, except that the changes caused everything to be rebuilt.
+    ContentsMatchedAll,
 }
 
 /// The index of a rule.
@@ -438,8 +573,6 @@ impl<'g> RulesImpl<'g> {
         graph: &'g PackageGraph,
         options: &DeterminatorRules,
     ) -> Result<Self, RulesError> {
-        let workspace = graph.workspace();
-
         let custom_path_rules = options
             .path_rules
             .iter()
@@ -467,6 +600,7 @@ impl<'g> RulesImpl<'g> {
                     PathRule {
                         globs,
                         mark_changed,
+                        features,
                         post_rule,
                     },
                 )| {
@@ -482,14 +616,35 @@ impl<'g> RulesImpl<'g> {
                         .build()
                         .map_err(|err| RulesError::glob_parse(rule_index, err))?;
 
-                    // Convert workspace paths to packages.
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                    // Convert package selectors to packages.
+                    let mark_changed = MarkChangedImpl::new(graph, rule_index, mark_changed)?;
+
+                    // Check that every marked package has the features that gate it.
+                    if !features.is_empty() {
+                        let packages = match &mark_changed {
+                            MarkChangedImpl::All => {
+                                return Err(RulesError::features_with_mark_all(rule_index));
+                            }
+                            MarkChangedImpl::Packages(packages) => packages,
+                        };
+                        for package in packages {
+                            for feature in features {
+                                if !package.named_features().any(|named| named == feature) {
+                                    return Err(RulesError::unknown_feature(
+                                        rule_index,
+                                        package.name(),
+                                        feature,
+                                    ));
+                                }
+                            }
+                        }
+                    }
 
                     Ok(PathRuleImpl {
                         rule_index,
                         glob_set,
                         mark_changed,
+                        features: features.clone(),
                         post_rule: *post_rule,
                     })
                 },
@@ -510,11 +665,15 @@ impl<'g> RulesImpl<'g> {
                 )| {
                     let rule_index = RuleIndex::Package(rule_index);
                     let on_affected = graph
-                        .resolve_workspace_names(on_affected)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
-                    let mark_changed = MarkChangedImpl::new(&workspace, mark_changed)
-                        .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+                        .resolve_ids(
+                            resolve_selectors(graph, rule_index, on_affected)?
+                                .iter()
+                                .map(|package| package.id()),
+                        )
+                        .expect("workspace package IDs are valid");
+                    let mark_changed = MarkChangedImpl::new(graph, rule_index, mark_changed)?;
                     Ok(PackageRuleImpl {
+                        rule_index,
                         on_affected,
                         mark_changed,
                     })
@@ -534,11 +693,14 @@ pub(crate) struct PathRuleImpl<'g> {
     pub(crate) rule_index: RuleIndex,
     pub(crate) glob_set: GlobSet,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
+    // If non-empty, marked packages are only changed if one of these features is built.
+    pub(crate) features: Vec<String>,
     pub(crate) post_rule: DeterminatorPostRule,
 }
 
 #[derive(Clone, Debug)]
 pub(crate) struct PackageRuleImpl<'g> {
+    pub(crate) rule_index: RuleIndex,
     pub(crate) on_affected: PackageSet<'g>,
     pub(crate) mark_changed: MarkChangedImpl<'g>,
 }
@@ -551,18 +713,133 @@ pub(crate) enum MarkChangedImpl<'g> {
 
 impl<'g> MarkChangedImpl<'g> {
     fn new(
-        workspace: &Workspace<'g>,
+        graph: &'g PackageGraph,
+        rule_index: RuleIndex,
         mark_changed: &DeterminatorMarkChanged,
-    ) -> Result<Self, guppy::Error> {
+    ) -> Result<Self, RulesError> {
         match mark_changed {
-            DeterminatorMarkChanged::Packages(names) => Ok(MarkChangedImpl::Packages(
-                workspace.members_by_names(names)?,
+            DeterminatorMarkChanged::Packages(selectors) => Ok(MarkChangedImpl::Packages(
+                resolve_selectors(graph, rule_index, selectors)?,
             )),
             DeterminatorMarkChanged::All => Ok(MarkChangedImpl::All),
         }
     }
 }
 
+/// Resolves a list of package selectors to workspace packages, in the order they were selected.
+fn resolve_selectors<'g>(
+    graph: &'g PackageGraph,
+    rule_index: RuleIndex,
+    selectors: &[String],
+) -> Result<Vec<PackageMetadata<'g>>, RulesError> {
+    let mut included = vec![];
+    let mut excluded = HashSet::new();
+    for selector in selectors {
+        match selector.strip_prefix('!') {
+            Some(inner) => {
+                let packages = resolve_selector(graph, rule_index, selector, inner)?;
+                excluded.extend(packages.iter().map(|package| package.id()));
+            }
+            None => {
+                included.extend(resolve_selector(graph, rule_index, selector, selector)?);
+            }
+        }
+    }
+
+    // A list consisting only of exclusions selects every other package.
+    if included.is_empty() && !excluded.is_empty() {
+        included.extend(graph.workspace().iter_by_name().map(|(_, package)| package));
+    }
+
+    let mut seen = HashSet::new();
+    Ok(included
+        .into_iter()
+        .filter(|package| !excluded.contains(package.id()) && seen.insert(package.id()))
+        .collect())
+}
+
+/// Resolves a single package selector, without a leading `!`, to workspace packages.
+///
+/// `selector` is the original selector, used for error reporting.
+fn resolve_selector<'g>(
+    graph: &'g PackageGraph,
+    rule_index: RuleIndex,
+    selector: &str,
+    pattern: &str,
+) -> Result<Vec<PackageMetadata<'g>>, RulesError> {
+    let workspace = graph.workspace();
+    if pattern.starts_with('!') {
+        return Err(RulesError::invalid_selector(
+            rule_index,
+            selector,
+            "exclusions can only be at the start of a selector",
+        ));
+    }
+
+    let packages: Vec<_> = if let Some(inner) = pattern.strip_prefix("rdeps:") {
+        let roots = resolve_selector(graph, rule_index, selector, inner)?;
+        let query = graph
+            .query_reverse(roots.iter().map(|package| package.id()))
+            .expect("workspace package IDs are valid");
+        query
+            .resolve()
+            .packages(DependencyDirection::Forward)
+            .filter(|package| package.in_workspace())
+            .collect()
+    } else if let Some(glob) = pattern.strip_prefix("path:") {
+        let matcher = selector_glob(rule_index, selector, glob)?;
+        workspace
+            .iter_by_path()
+            .filter_map(|(path, package)| matcher.is_match(path).then_some(package))
+            .collect()
+    } else if pattern.contains(GLOB_CHARS) {
+        let matcher = selector_glob(rule_index, selector, pattern)?;
+        workspace
+            .iter_by_name()
+            .filter_map(|(name, package)| matcher.is_match(name).then_some(package))
+            .collect()
+    } else {
+        if pattern.is_empty() {
+            return Err(RulesError::invalid_selector(
+                rule_index, selector, "is empty",
+            ));
+        }
+        let package = workspace
+            .member_by_name(pattern)
+            .map_err(|err| RulesError::resolve_ref(rule_index, err))?;
+        vec![package]
+    };
+
+    if packages.is_empty() {
+        return Err(RulesError::invalid_selector(
+            rule_index,
+            selector,
+            "did not match any workspace packages",
+        ));
+    }
+    Ok(packages)
+}
+
+/// Characters that cause a package name selector to be treated as a glob.
+static GLOB_CHARS: &[char] = &['*', '?', '[', '{'];
+
+fn selector_glob(
+    rule_index: RuleIndex,
+    selector: &str,
+    glob: &str,
+) -> Result<GlobMatcher, RulesError> {
+    if glob.is_empty() {
+        return Err(RulesError::invalid_selector(
+            rule_index, selector, "is empty",
+        ));
+    }
+    Ok(GlobBuilder::new(glob)
+        .literal_separator(true)
+        .build()
+        .map_err(|err| RulesError::glob_parse(rule_index, err))?
+        .compile_matcher())
+}
+
 mod mark_changed_impl {
     use super::*;
     use serde::{Deserializer, Serializer, de::Error};
@@ -641,16 +918,19 @@ mod tests {
                 PathRule {
                     globs: vec!["all/*".to_owned()],
                     mark_changed: DeterminatorMarkChanged::All,
+                    features: vec![],
                     post_rule: DeterminatorPostRule::Fallthrough,
                 },
                 PathRule {
                     globs: vec!["all/1/2/*".to_owned()],
                     mark_changed: DeterminatorMarkChanged::Packages(vec!["c".to_owned()]),
+                    features: vec![],
                     post_rule: DeterminatorPostRule::SkipRules,
                 },
                 PathRule {
                     globs: vec!["none/**/test".to_owned(), "foo/bar".to_owned()],
                     mark_changed: DeterminatorMarkChanged::Packages(vec![]),
+                    features: vec![],
                     post_rule: DeterminatorPostRule::Skip,
                 },
             ],
@@ -673,6 +953,32 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parse_features() {
+        let s = r#"[[path-rule]]
+        globs = ["gpu-support/src/gpu/**"]
+        mark-changed = ["gpu-support"]
+        features = ["gpu", "cuda"]
+        "#;
+
+        let mut path_rule = PathRule::new(
+            vec!["gpu-support/src/gpu/**".to_owned()],
+            DeterminatorMarkChanged::Packages(vec!["gpu-support".to_owned()]),
+        );
+        path_rule.features = vec!["gpu".to_owned(), "cuda".to_owned()];
+        let expected = DeterminatorRules {
+            use_default_rules: true,
+            path_rules: vec![path_rule],
+            package_rules: vec![],
+        };
+
+        assert_eq!(
+            DeterminatorRules::parse(s),
+            Ok(expected),
+            "parse_features() result matches"
+        );
+    }
+
     #[test]
     fn parse_empty() {
         let expected = DeterminatorRules::default();
@@ -0,0 +1,205 @@
+// Copyright (c) The cargo-guppy Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Affected build targets within packages.
+//!
+//! By default, the determinator works at the granularity of packages. With
+//! [`Determinator::set_build_target_granularity`](crate::Determinator::set_build_target_granularity),
+//! it also tracks which [build targets](BuildTarget) within each package are affected, and returns
+//! them as [`AffectedTargets`].
+//!
+//! A change to a file within a package's `tests`, `benches` or `examples` directory only affects
+//! the targets built from that file. For example, a change to `tests/foo.rs` or `tests/foo/util.rs`
+//! only affects the `foo` integration test. Files in those directories that don't belong to a
+//! specific target, such as `tests/common/mod.rs`, affect every target in that directory. Any
+//! other change, including changes to the library, dependency changes and package rules, affects
+//! all targets in the package.
+
+use camino::Utf8Path;
+use guppy::{
+    PackageId,
+    graph::{BuildTarget, BuildTargetId, PackageGraph, PackageMetadata},
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The build targets affected by a change, for each affected package.
+///
+/// Returned in [`DeterminatorSet::affected_targets`](crate::DeterminatorSet::affected_targets) if
+/// build target granularity is enabled.
+#[derive(Clone, Debug)]
+pub struct AffectedTargets<'g> {
+    graph: &'g PackageGraph,
+    packages: BTreeMap<&'g PackageId, AffectedPackageTargets<'g>>,
+}
+
+impl<'g> AffectedTargets<'g> {
+    pub(crate) fn new(graph: &'g PackageGraph) -> Self {
+        Self {
+            graph,
+            packages: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        package_id: &'g PackageId,
+        targets: AffectedPackageTargets<'g>,
+    ) {
+        self.packages.insert(package_id, targets);
+    }
+
+    /// Returns the number of affected packages.
+    pub fn len(&self) -> usize {
+        self.packages.len()
+    }
+
+    /// Returns true if no packages are affected.
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    /// Returns the affected packages, along with the targets affected within each of them.
+    pub fn packages(
+        &self,
+    ) -> impl ExactSizeIterator<Item = (PackageMetadata<'g>, &AffectedPackageTargets<'g>)> + '_
+    {
+        self.packages
+            .iter()
+            .map(move |(package_id, targets)| (self.metadata(package_id), targets))
+    }
+
+    /// Returns the targets affected within the given package, or `None` if the package isn't
+    /// affected.
+    pub fn targets_for(&self, package_id: &PackageId) -> Option<&AffectedPackageTargets<'g>> {
+        self.packages.get(package_id)
+    }
+
+    /// Returns every affected build target, across all affected packages.
+    pub fn build_targets(&self) -> impl Iterator<Item = BuildTarget<'g>> + '_ {
+        self.packages.iter().flat_map(move |(package_id, targets)| {
+            let package = self.metadata(package_id);
+            package.build_targets().filter(move |target| match targets {
+                AffectedPackageTargets::All => true,
+                AffectedPackageTargets::Only(ids) => ids.contains(&target.id()),
+            })
+        })
+    }
+
+    /// Returns a [nextest filterset](https://nexte.st/docs/filtersets/) that selects the tests
+    /// within affected targets.
+    ///
+    /// Packages with all targets affected are selected with `package()`, and individual targets
+    /// with `binary_id()`. If nothing is affected, this returns `none()`.
+    pub fn nextest_filter(&self) -> String {
+        let mut packages: Vec<_> = self.packages().collect();
+        packages.sort_by_key(|(package, _)| package.name());
+
+        let mut exprs = vec![];
+        for (package, targets) in packages {
+            let name = package.name();
+            match targets {
+                AffectedPackageTargets::All => exprs.push(format!("package({name})")),
+                AffectedPackageTargets::Only(ids) => {
+                    for id in ids {
+                        let binary_id = match id {
+                            BuildTargetId::Library => name.to_owned(),
+                            // Build scripts don't have tests.
+                            BuildTargetId::BuildScript => continue,
+                            BuildTargetId::Binary(target) => format!("{name}::bin/{target}"),
+                            BuildTargetId::Example(target) => format!("{name}::example/{target}"),
+                            BuildTargetId::Test(target) => format!("{name}::{target}"),
+                            BuildTargetId::Benchmark(target) => format!("{name}::bench/{target}"),
+                            _ => {
+                                // Other kinds of targets can't be selected individually.
+                                exprs.push(format!("package({name})"));
+                                break;
+                            }
+                        };
+                        exprs.push(format!("binary_id({binary_id})"));
+                    }
+                }
+            }
+        }
+
+        if exprs.is_empty() {
+            "none()".to_owned()
+        } else {
+            exprs.join(" | ")
+        }
+    }
+
+    fn metadata(&self, package_id: &PackageId) -> PackageMetadata<'g> {
+        self.graph
+            .metadata(package_id)
+            .expect("affected package IDs are valid")
+    }
+}
+
+/// The build targets affected within a package.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AffectedPackageTargets<'g> {
+    /// Every build target in the package is affected.
+    All,
+
+    /// Only these build targets are affected.
+    ///
+    /// This is the case if the only changes to the package were to files belonging to specific
+    /// test, benchmark or example targets.
+    Only(BTreeSet<BuildTargetId<'g>>),
+}
+
+/// Directories, relative to the package root, whose files only affect the targets built from them.
+static TARGET_DIRS: &[&str] = &["tests", "benches", "examples"];
+
+/// Returns the build targets within `package` that `path`, relative to the workspace root, belongs
+/// to. Returns `None` if `path` may affect every build target in the package.
+pub(crate) fn targets_for_path<'g>(
+    package: PackageMetadata<'g>,
+    path: &Utf8Path,
+) -> Option<BTreeSet<BuildTargetId<'g>>> {
+    let path = path.strip_prefix(package.source().workspace_path()?).ok()?;
+    let dir = path.components().next()?.as_str();
+    if !TARGET_DIRS.contains(&dir) {
+        return None;
+    }
+
+    let package_root = package.manifest_path().parent()?;
+    let mut in_dir = BTreeSet::new();
+    let mut matched = BTreeSet::new();
+    for target in package.build_targets() {
+        let Ok(target_path) = target.path().strip_prefix(package_root) else {
+            continue;
+        };
+        if !target_path.starts_with(dir) {
+            continue;
+        }
+        in_dir.insert(target.id());
+        if path == target_path || owns_path(target_path, dir, path) {
+            matched.insert(target.id());
+        }
+    }
+
+    if !matched.is_empty() {
+        Some(matched)
+    } else if !in_dir.is_empty() {
+        // This file doesn't belong to any target in particular, e.g. tests/common/mod.rs.
+        Some(in_dir)
+    } else {
+        // No targets are in this directory, so it's unclear what uses the file.
+        None
+    }
+}
+
+/// Returns true if `path` is within the directory owned by the target at `target_path`.
+fn owns_path(target_path: &Utf8Path, dir: &str, path: &Utf8Path) -> bool {
+    if target_path.file_name() == Some("main.rs") {
+        // A target at tests/foo/main.rs owns everything in tests/foo/.
+        match target_path.parent() {
+            Some(parent) if parent != dir => path.starts_with(parent),
+            _ => false,
+        }
+    } else {
+        // A target at tests/foo.rs can have modules in tests/foo/.
+        path.starts_with(target_path.with_extension(""))
+    }
+}
@@ -5,11 +5,22 @@
 
 use cfg_if::cfg_if;
 use determinator::{
-    Determinator, Utf8Paths0,
-    rules::{DeterminatorRules, PathMatch, RuleIndex},
+    AffectedLinkKind, AffectedReason, DeletedPackage, Determinator, DeterminatorSet, Utf8Paths0,
+    dep_info::DepInfoIndex,
+    errors::RulesErrorKind,
+    owners::CodeOwners,
+    rules::{
+        DeterminatorMarkChanged, DeterminatorPostRule, DeterminatorRules, PackageRule, PathMatch,
+        PathRule, RuleIndex,
+    },
+    targets::AffectedPackageTargets,
 };
 use fixtures::json::JsonFixture;
-use guppy::{CargoMetadata, graph::feature::StandardFeatures};
+use guppy::{
+    CargoMetadata,
+    graph::{BuildTargetId, DependencyDirection, feature::StandardFeatures},
+    platform::{Platform, PlatformSpec, TargetFeatures},
+};
 
 #[test]
 fn guppy_no_rules() {
@@ -281,6 +292,130 @@ fn guppy_package_rules() {
     );
 }
 
+#[test]
+fn guppy_package_selectors() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    let names_for = |mark_changed: &[&str]| -> Vec<&str> {
+        let mut rules = DeterminatorRules::default();
+        rules.path_rules = vec![PathRule::new(
+            vec!["proto/**".to_owned()],
+            DeterminatorMarkChanged::Packages(mark_changed.iter().map(|s| s.to_string()).collect()),
+        )];
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.set_rules(&rules).expect("rules are valid");
+        determinator.add_changed_paths(vec!["proto/foo.proto"]);
+        let mut names: Vec<_> = determinator
+            .compute()
+            .path_changed_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        names
+    };
+
+    assert_eq!(
+        names_for(&["guppy-*"]),
+        ["guppy-benchmarks", "guppy-cmdlib", "guppy-summaries"]
+    );
+    assert_eq!(
+        names_for(&["path:internal-tools/*", "!fixture-manager"]),
+        ["cargo-compare", "guppy-benchmarks", "proptest-ext"]
+    );
+    // * in path globs doesn't match nested paths.
+    assert_eq!(
+        names_for(&["path:*"]),
+        [
+            "cargo-guppy",
+            "fixtures",
+            "guppy",
+            "guppy-cmdlib",
+            "guppy-summaries",
+            "target-spec"
+        ]
+    );
+    // guppy has an optional dependency on guppy-summaries, so everything that depends on guppy is
+    // also selected.
+    assert_eq!(
+        names_for(&["rdeps:guppy-summaries", "!guppy"]),
+        [
+            "cargo-compare",
+            "cargo-guppy",
+            "fixture-manager",
+            "fixtures",
+            "guppy-benchmarks",
+            "guppy-cmdlib",
+            "guppy-summaries"
+        ]
+    );
+    assert_eq!(
+        names_for(&["!path:internal-tools/**", "!guppy*"]),
+        ["cargo-guppy", "fixtures", "target-spec"]
+    );
+
+    // Package rules accept selectors as well.
+    let rules = DeterminatorRules::parse(
+        r#"
+        [[package-rule]]
+        on-affected = ["path:target-*"]
+        mark-changed = ["fixture*"]
+        "#,
+    )
+    .expect("rules parsed");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules are valid");
+    determinator.add_changed_paths(vec!["target-spec/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+    for name in ["fixture-manager", "fixtures"] {
+        let package = new
+            .graph()
+            .workspace()
+            .member_by_name(name)
+            .expect("package is a workspace member");
+        assert!(
+            determinator_set
+                .affected_set
+                .contains(package.id())
+                .expect("valid package ID"),
+            "{name} affected through package rule"
+        );
+    }
+
+    // Invalid selectors are reported when rules are set.
+    let bads = [
+        ("unknown-package", "resolve-ref"),
+        ("unknown-*", "invalid-selector"),
+        ("path:[", "glob-parse"),
+        ("rdeps:!guppy", "invalid-selector"),
+        ("!", "invalid-selector"),
+    ];
+    for (selector, expected_kind) in bads {
+        let mut rules = DeterminatorRules::default();
+        rules.package_rules = vec![PackageRule {
+            on_affected: vec![selector.to_string()],
+            mark_changed: DeterminatorMarkChanged::All,
+        }];
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        let err = determinator
+            .set_rules(&rules)
+            .expect_err("invalid selector is rejected");
+        assert_eq!(err.rule_index(), RuleIndex::Package(0));
+        let kind = match err.kind() {
+            RulesErrorKind::ResolveRef(_) => "resolve-ref",
+            RulesErrorKind::GlobParse { .. } => "glob-parse",
+            RulesErrorKind::InvalidSelector { .. } => "invalid-selector",
+            _ => "other",
+        };
+        assert_eq!(
+            kind, expected_kind,
+            "for selector {selector}, unexpected error: {err}"
+        );
+    }
+}
+
 #[test]
 fn guppy_package_rules_2() {
     // There are no dependency changes between the old and new fixtures, only file changes.
@@ -386,6 +521,717 @@ fn guppy_deps() {
     );
 }
 
+#[test]
+fn guppy_build_test_sets() {
+    // new updates the version of toml, which is a normal dependency of some packages and a
+    // dev-dependency of others.
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let opts = read_options(new, "path-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+
+    // The build stage is only computed if requested.
+    let determinator_set = determinator.compute();
+    assert!(determinator_set.build_affected_set.is_none());
+    assert!(determinator_set.build_reasons.is_none());
+
+    determinator.set_separate_build_stage(true);
+    let determinator_set = determinator.compute();
+    let test_expected = new
+        .graph()
+        .resolve_workspace_names(vec![
+            "cargo-guppy",
+            "fixture-manager",
+            "guppy",
+            "guppy-summaries",
+            "cargo-compare",
+            "target-spec",
+        ])
+        .expect("workspace names resolved");
+    let build_expected = new
+        .graph()
+        .resolve_workspace_names(vec![
+            "cargo-guppy",
+            "fixture-manager",
+            "guppy",
+            "guppy-summaries",
+            "cargo-compare",
+            // toml is only a dev-dependency for target-spec, so it doesn't need to be rebuilt.
+        ])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.test_affected_set, test_expected);
+    assert_eq!(determinator_set.build_affected_set, Some(build_expected));
+    assert_eq!(determinator_set.affected_set, test_expected);
+
+    let target_spec = new
+        .graph()
+        .workspace()
+        .member_by_name("target-spec")
+        .expect("target-spec is a workspace member");
+    let reasons: Vec<_> = determinator_set
+        .reasons
+        .reasons(target_spec.id())
+        .iter()
+        .map(|reason| reason.to_string())
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            "simulated build changed: with no features, toml 0.5.7 modified; \
+             with default features, toml 0.5.7 modified; with all features, toml 0.5.7 modified"
+        ],
+        "target-spec was affected through a build with dev-dependencies"
+    );
+    let build_reasons = determinator_set
+        .build_reasons
+        .as_ref()
+        .expect("build stage is enabled");
+    assert!(
+        build_reasons.reasons(target_spec.id()).is_empty(),
+        "target-spec isn't affected in the build stage"
+    );
+
+    // A path change in fixtures, which is only a dev-dependency of guppy, affects guppy's tests
+    // but not its build.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["fixtures/src/details.rs"]);
+    determinator.set_separate_build_stage(true);
+
+    let determinator_set = determinator.compute();
+    let test_expected = new
+        .graph()
+        .resolve_workspace_names(vec!["fixtures", "guppy", "fixture-manager"])
+        .expect("workspace names resolved");
+    let build_expected = new
+        .graph()
+        .resolve_workspace_names(vec!["fixtures", "fixture-manager"])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.test_affected_set, test_expected);
+    assert_eq!(determinator_set.build_affected_set, Some(build_expected));
+    assert_eq!(determinator_set.affected_set, test_expected);
+
+    // Build stage reasons don't go through dev-dependency edges.
+    let build_reasons = determinator_set
+        .build_reasons
+        .as_ref()
+        .expect("build stage is enabled");
+    let fixture_manager = new
+        .graph()
+        .workspace()
+        .member_by_name("fixture-manager")
+        .expect("fixture-manager is a workspace member");
+    let reasons: Vec<_> = build_reasons
+        .reasons(fixture_manager.id())
+        .iter()
+        .map(|reason| reason.to_string())
+        .collect();
+    assert_eq!(reasons, ["affected through fixtures -> fixture-manager"]);
+}
+
+#[test]
+fn targets_platforms() {
+    // Enable a feature on lazy_static, which is only a dependency on non-Windows platforms.
+    let old_json = include_str!("../../../fixtures/small/metadata_targets1.json");
+    let new_json = old_json.replace(
+        r#""features":[],"target":"cfg(not(windows))""#,
+        r#""features":["nightly"],"target":"cfg(not(windows))""#,
+    );
+    assert_ne!(old_json, new_json, "metadata was changed");
+    let old = CargoMetadata::parse_json(old_json)
+        .expect("old metadata parsed")
+        .build_graph()
+        .expect("old graph built");
+    let new = CargoMetadata::parse_json(new_json)
+        .expect("new metadata parsed")
+        .build_graph()
+        .expect("new graph built");
+
+    let platforms = ["x86_64-unknown-linux-gnu", "x86_64-pc-windows-msvc"].map(|triple| {
+        PlatformSpec::from(Platform::new(triple, TargetFeatures::Unknown).expect("known triple"))
+    });
+    let determinator = Determinator::new(&old, &new);
+    let determinator_sets = determinator.compute_for_platforms(platforms);
+    assert_eq!(determinator_sets.len(), 2, "one set per platform");
+
+    let expected = new
+        .resolve_workspace_names(["testcrate-targets"])
+        .expect("workspace names resolved");
+    assert_eq!(
+        determinator_sets[0].summary_changed_set, expected,
+        "summary changed on Linux"
+    );
+    assert_eq!(
+        determinator_sets[0].affected_set, expected,
+        "testcrate-targets affected on Linux"
+    );
+    assert!(
+        determinator_sets[1].affected_set.is_empty(),
+        "nothing affected on Windows"
+    );
+}
+
+#[test]
+fn compute_for_platforms_matches_compute() {
+    // Simulated builds are shared between platforms where possible. Check that the results match
+    // computing each platform separately.
+    let fixtures = [
+        (
+            JsonFixture::metadata_guppy_78cb7e8(),
+            JsonFixture::metadata_guppy_869476c(),
+        ),
+        (
+            JsonFixture::metadata_guppy_869476c(),
+            JsonFixture::metadata_guppy_c9b4f76(),
+        ),
+    ];
+    let platforms: Vec<_> = [
+        "x86_64-unknown-linux-gnu",
+        "x86_64-pc-windows-msvc",
+        "aarch64-apple-darwin",
+        "wasm32-unknown-unknown",
+    ]
+    .into_iter()
+    .map(|triple| {
+        PlatformSpec::from(Platform::new(triple, TargetFeatures::Unknown).expect("known triple"))
+    })
+    .chain([PlatformSpec::Always, PlatformSpec::Any])
+    .collect();
+
+    for (old, new) in fixtures {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.set_separate_build_stage(true);
+        let determinator_sets = determinator.compute_for_platforms(platforms.iter().cloned());
+        assert_eq!(
+            determinator_sets.len(),
+            platforms.len(),
+            "one set per platform"
+        );
+
+        for (platform, determinator_set) in platforms.iter().zip(&determinator_sets) {
+            let mut cargo_options = Determinator::default_cargo_options();
+            cargo_options.set_platform(platform.clone());
+            let mut determinator = determinator.clone();
+            determinator.set_cargo_options(&cargo_options);
+            let expected = determinator.compute();
+
+            assert_eq!(
+                determinator_set.affected_set, expected.affected_set,
+                "affected set matches for {platform:?}"
+            );
+            assert_eq!(
+                determinator_set.build_affected_set, expected.build_affected_set,
+                "build affected set matches for {platform:?}"
+            );
+            assert_eq!(
+                determinator_set.test_affected_set, expected.test_affected_set,
+                "test affected set matches for {platform:?}"
+            );
+            assert_eq!(
+                determinator_set.summary_changed_set, expected.summary_changed_set,
+                "summary changed set matches for {platform:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn guppy_renamed_packages() {
+    let old_json = include_str!("../../../fixtures/guppy/metadata_guppy_c9b4f76.json");
+    let build_graph = |json: &str| {
+        CargoMetadata::parse_json(json)
+            .expect("metadata parsed")
+            .build_graph()
+            .expect("graph built")
+    };
+    let old = build_graph(old_json);
+
+    // Move guppy-summaries to tools/summaries, keeping its name.
+    let moved = build_graph(&old_json.replace("/guppy-summaries", "/tools/summaries"));
+    let mut determinator = Determinator::new(&old, &moved);
+    determinator.add_changed_paths(vec!["guppy-summaries/src/lib.rs"]);
+    assert_eq!(
+        determinator.match_path("guppy-summaries/src/lib.rs", |_| {}),
+        PathMatch::OldAncestorMatched,
+    );
+    let determinator_set = determinator.compute();
+    assert_eq!(
+        determinator_set.path_changed_set,
+        moved
+            .resolve_workspace_names(["guppy-summaries"])
+            .expect("workspace names resolved"),
+        "old path is matched to the moved package"
+    );
+    let renamed: Vec<_> = determinator_set
+        .renamed_packages
+        .iter()
+        .map(|renamed| {
+            (
+                renamed.old_name.as_str(),
+                renamed.old_workspace_path.as_str(),
+                renamed.package.name(),
+                renamed
+                    .package
+                    .source()
+                    .workspace_path()
+                    .map(|p| p.as_str()),
+            )
+        })
+        .collect();
+    assert_eq!(
+        renamed,
+        [(
+            "guppy-summaries",
+            "guppy-summaries",
+            "guppy-summaries",
+            Some("tools/summaries")
+        )],
+    );
+    assert!(determinator_set.deleted_packages.is_empty());
+
+    // Rename guppy-summaries to summaries-x, which also changes its path.
+    let renamed = build_graph(
+        &old_json
+            .replace("guppy-summaries", "summaries-x")
+            .replace("guppy_summaries", "summaries_x"),
+    );
+    let changed_paths = ["guppy-summaries/src/lib.rs", "summaries-x/src/lib.rs"];
+
+    // Without hints, guppy-summaries is deleted and its former dependents are marked changed.
+    let mut determinator = Determinator::new(&old, &renamed);
+    determinator.add_changed_paths(changed_paths);
+    let determinator_set = determinator.compute();
+    assert!(determinator_set.renamed_packages.is_empty());
+    assert_eq!(
+        determinator_set.deleted_packages,
+        [DeletedPackage {
+            name: "guppy-summaries".to_owned(),
+            workspace_path: "guppy-summaries".into(),
+        }],
+    );
+    assert_eq!(
+        determinator_set.path_changed_set,
+        renamed
+            .resolve_workspace_names(["summaries-x"])
+            .expect("workspace names resolved"),
+        "paths within deleted packages don't mark anything changed"
+    );
+    let guppy_id = renamed
+        .workspace()
+        .member_by_name("guppy")
+        .expect("guppy is a workspace member")
+        .id();
+    assert!(
+        matches!(
+            determinator_set.reasons.reasons(guppy_id),
+            [AffectedReason::DependencyDeleted { name }] if name == "guppy-summaries"
+        ),
+        "guppy was marked changed because guppy-summaries was deleted"
+    );
+    assert!(
+        determinator_set
+            .summary_changed_set
+            .contains(guppy_id)
+            .unwrap()
+    );
+
+    // With a hint, summaries-x is matched to guppy-summaries.
+    let mut determinator = Determinator::new(&old, &renamed);
+    determinator
+        .add_changed_paths(changed_paths)
+        .add_renamed_paths([("guppy-summaries/src/lib.rs", "summaries-x/src/lib.rs")]);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.renamed_packages.len(), 1);
+    let renamed_package = &determinator_set.renamed_packages[0];
+    assert_eq!(renamed_package.package.name(), "summaries-x");
+    assert_eq!(renamed_package.old_name, "guppy-summaries");
+    assert_eq!(renamed_package.old_workspace_path, "guppy-summaries");
+    assert!(determinator_set.deleted_packages.is_empty());
+    assert!(
+        determinator_set
+            .reasons
+            .iter()
+            .flat_map(|(_, reasons)| reasons)
+            .all(|reason| !matches!(reason, AffectedReason::DependencyDeleted { .. })),
+        "no packages were deleted"
+    );
+}
+
+#[test]
+fn guppy_feature_rules() {
+    // Add a gpu feature to target-spec that nothing in the workspace enables.
+    let json = include_str!("../../../fixtures/guppy/metadata_guppy_c9b4f76.json").replace(
+        r#""features":{"proptest010":["proptest"],"summaries":["serde"]}"#,
+        r#""features":{"gpu":[],"proptest010":["proptest"],"summaries":["serde"]}"#,
+    );
+    let graph = CargoMetadata::parse_json(json)
+        .expect("metadata parsed")
+        .build_graph()
+        .expect("graph built");
+    let target_spec_id = graph
+        .workspace()
+        .member_by_name("target-spec")
+        .expect("target-spec is a workspace member")
+        .id();
+    let rules_for = |features: &str| {
+        DeterminatorRules::parse(&format!(
+            r#"
+            [[path-rule]]
+            globs = ["target-spec/src/gpu/**"]
+            mark-changed = ["target-spec"]
+            features = {features}
+            "#
+        ))
+        .expect("rules parsed")
+    };
+    let changed_paths = ["target-spec/src/gpu/mod.rs"];
+
+    // The gpu feature isn't built, so target-spec isn't marked changed.
+    let gpu_rules = rules_for(r#"["gpu"]"#);
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator
+        .set_rules(&gpu_rules)
+        .expect("rules are valid")
+        .add_changed_paths(changed_paths);
+    let mut matched = vec![];
+    assert_eq!(
+        determinator.match_path(changed_paths[0], |id| matched.push(id)),
+        PathMatch::RuleMatched(RuleIndex::CustomPath(0)),
+    );
+    assert_eq!(matched, [target_spec_id]);
+    let determinator_set = determinator.compute();
+    assert!(determinator_set.path_changed_set.is_empty());
+    assert!(determinator_set.affected_set.is_empty());
+
+    // Once gpu is in the features-only set, it is built.
+    determinator
+        .add_features_only("target-spec", ["gpu"])
+        .expect("target-spec has the gpu feature");
+    let determinator_set = determinator.compute();
+    let expected = graph
+        .resolve_workspace_names(["target-spec"])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.path_changed_set, expected);
+    assert_eq!(
+        determinator_set.affected_set,
+        graph
+            .query_workspace()
+            .resolve()
+            .filter(DependencyDirection::Forward, |package| {
+                package.in_workspace()
+                    && package.name() != "guppy-summaries"
+                    && package.name() != "proptest-ext"
+            }),
+        "everything that depends on target-spec is affected",
+    );
+    let reasons: Vec<_> = determinator_set
+        .reasons
+        .reasons(target_spec_id)
+        .iter()
+        .map(|reason| reason.to_string())
+        .collect();
+    assert_eq!(
+        reasons,
+        ["path target-spec/src/gpu/mod.rs matched custom path rule 0, and feature gpu is built"],
+    );
+
+    // target-spec/summaries is enabled through guppy/summaries, which cargo-guppy enables.
+    let mut determinator = Determinator::new(&graph, &graph);
+    determinator
+        .set_rules(&rules_for(r#"["gpu", "summaries"]"#))
+        .expect("rules are valid")
+        .add_changed_paths(changed_paths);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.path_changed_set, expected);
+    assert!(matches!(
+        determinator_set.reasons.reasons(target_spec_id),
+        [AffectedReason::PathRuleFeature { feature, .. }] if feature == "summaries"
+    ));
+
+    // Features must be present on every marked package, and can't be used with "all".
+    let mut determinator = Determinator::new(&graph, &graph);
+    let err = determinator
+        .set_rules(&rules_for(r#"["nonexistent"]"#))
+        .expect_err("unknown feature is rejected");
+    assert!(
+        matches!(
+            err.kind(),
+            RulesErrorKind::UnknownFeature { package, feature }
+                if package == "target-spec" && feature == "nonexistent"
+        ),
+        "unexpected error: {err}"
+    );
+    let mut all_rules = gpu_rules.clone();
+    all_rules.path_rules[0].mark_changed = DeterminatorMarkChanged::All;
+    let err = determinator
+        .set_rules(&all_rules)
+        .expect_err("features with mark-changed = \"all\" is rejected");
+    assert!(
+        matches!(err.kind(), RulesErrorKind::FeaturesWithMarkAll),
+        "unexpected error: {err}"
+    );
+
+    determinator
+        .add_features_only("target-spec", ["nonexistent"])
+        .expect_err("unknown feature is rejected");
+    determinator
+        .add_features_only("nonexistent", ["gpu"])
+        .expect_err("unknown package is rejected");
+}
+
+#[test]
+fn guppy_build_targets() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let guppy = new
+        .graph()
+        .workspace()
+        .member_by_name("guppy")
+        .expect("guppy is a workspace member");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["guppy/tests/graph-tests/graph_tests.rs"]);
+    assert!(
+        determinator.compute().affected_targets.is_none(),
+        "targets not computed by default"
+    );
+
+    // A change to an integration test only affects that test, and not packages that depend on
+    // guppy.
+    determinator.set_build_target_granularity(true);
+    let determinator_set = determinator.compute();
+    let expected = new
+        .graph()
+        .resolve_workspace_names(["guppy"])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.path_changed_set, expected);
+    assert_eq!(determinator_set.affected_set, expected);
+    let affected_targets = determinator_set.affected_targets.expect("targets computed");
+    assert_eq!(
+        affected_targets.targets_for(guppy.id()),
+        Some(&AffectedPackageTargets::Only(
+            [BuildTargetId::Test("graph-tests")].into_iter().collect()
+        )),
+    );
+    assert_eq!(
+        affected_targets.nextest_filter(),
+        "binary_id(guppy::graph-tests)"
+    );
+
+    // guppy's own build isn't affected either.
+    determinator.set_separate_build_stage(true);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.test_affected_set, expected);
+    assert_eq!(
+        determinator_set.build_affected_set,
+        Some(new.graph().resolve_none()),
+        "only tests changed, so nothing needs to be rebuilt"
+    );
+    determinator.set_separate_build_stage(false);
+
+    // Add an example and a change to another package's library.
+    determinator.add_changed_paths(vec![
+        "guppy/examples/deps.rs",
+        "internal-tools/fixture-manager/src/lib.rs",
+    ]);
+    let determinator_set = determinator.compute();
+    let affected_targets = determinator_set.affected_targets.expect("targets computed");
+    assert_eq!(
+        affected_targets.nextest_filter(),
+        "package(fixture-manager) | binary_id(guppy::example/deps) | \
+         binary_id(guppy::graph-tests)"
+    );
+    let mut build_targets: Vec<_> = affected_targets
+        .build_targets()
+        .map(|target| target.name())
+        .collect();
+    build_targets.sort_unstable();
+    assert_eq!(
+        build_targets,
+        [
+            "deps",
+            "fixture-manager",
+            "fixture-manager",
+            "graph-tests",
+            "summary_tests"
+        ]
+    );
+
+    // A change to guppy's library affects all of its targets, and packages that depend on it.
+    determinator.add_changed_paths(vec!["guppy/src/lib.rs"]);
+    let determinator_set = determinator.compute();
+    assert!(
+        determinator_set
+            .affected_set
+            .contains(
+                new.graph()
+                    .workspace()
+                    .member_by_name("cargo-guppy")
+                    .expect("cargo-guppy is a workspace member")
+                    .id()
+            )
+            .expect("valid package ID"),
+        "cargo-guppy affected through guppy"
+    );
+    let affected_targets = determinator_set.affected_targets.expect("targets computed");
+    assert_eq!(affected_targets.len(), determinator_set.affected_set.len());
+    assert!(
+        affected_targets
+            .packages()
+            .all(|(_, targets)| *targets == AffectedPackageTargets::All),
+        "all targets affected in every package"
+    );
+}
+
+#[test]
+fn guppy_reasons() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let opts = read_options(new, "package-rules.toml");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["cargo-guppy/foo.rs", "fixtures/src/main.rs"]);
+    let determinator_set = determinator.compute();
+
+    let reasons = reasons_by_name(&determinator_set);
+    let reasons: Vec<(&str, Vec<&str>)> = reasons
+        .iter()
+        .map(|(name, reasons)| (*name, reasons.iter().map(String::as_str).collect()))
+        .collect();
+    assert_eq!(
+        reasons,
+        vec![
+            (
+                "cargo-compare",
+                vec!["affected through fixtures -> guppy-cmdlib (package rule 0) -> cargo-compare"]
+            ),
+            (
+                "cargo-guppy",
+                vec!["path cargo-guppy/foo.rs is within the package"]
+            ),
+            (
+                "fixture-manager",
+                vec!["affected through fixtures -> fixture-manager"]
+            ),
+            (
+                "fixtures",
+                vec!["path fixtures/src/main.rs is within the package"]
+            ),
+            ("guppy", vec!["affected through fixtures -> guppy"]),
+            (
+                "guppy-cmdlib",
+                vec!["affected through fixtures -> guppy-cmdlib (package rule 0)"]
+            ),
+        ],
+    );
+
+    let guppy_cmdlib = new
+        .graph()
+        .workspace()
+        .member_by_name("guppy-cmdlib")
+        .expect("guppy-cmdlib is a workspace member");
+    match determinator_set.reasons.reasons(guppy_cmdlib.id()) {
+        [AffectedReason::Downstream { changed, chain }] => {
+            assert_eq!(changed.name(), "fixtures");
+            assert_eq!(chain.len(), 1);
+            assert_eq!(chain[0].package.id(), guppy_cmdlib.id());
+            assert_eq!(
+                chain[0].kind,
+                AffectedLinkKind::PackageRule(RuleIndex::Package(0))
+            );
+        }
+        other => panic!("unexpected reasons for guppy-cmdlib: {other:?}"),
+    }
+
+    // A path rule that marks proptest-ext changed, followed by a package rule that marks all
+    // packages changed.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["foo/fake-trigger"]);
+    let determinator_set = determinator.compute();
+    let reasons = reasons_by_name(&determinator_set);
+    assert_eq!(reasons.len(), new.graph().workspace().iter().count());
+    for (name, reasons) in reasons {
+        let expected = match name {
+            "proptest-ext" => "path foo/fake-trigger matched custom path rule 0".to_owned(),
+            "guppy-benchmarks" => "affected through proptest-ext -> guppy-benchmarks".to_owned(),
+            other => format!(
+                "affected through proptest-ext -> guppy-benchmarks -> {other} (package rule 1)"
+            ),
+        };
+        assert_eq!(reasons, vec![expected], "reasons for {name} match");
+    }
+
+    // A path that isn't matched to any package causes everything to be marked changed.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&opts).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["Cargo.lock"]);
+    let determinator_set = determinator.compute();
+    for (name, reasons) in reasons_by_name(&determinator_set) {
+        assert_eq!(
+            reasons,
+            vec![
+                "path Cargo.lock did not match any rules or packages, so all packages were \
+                 marked changed"
+            ],
+            "reasons for {name} match"
+        );
+    }
+
+    // Summary changes list the dependencies that changed.
+    let old = JsonFixture::metadata_guppy_78cb7e8();
+    let new = JsonFixture::metadata_guppy_869476c();
+    let determinator = Determinator::new(old.graph(), new.graph());
+    let determinator_set = determinator.compute();
+    let target_spec = new
+        .graph()
+        .workspace()
+        .member_by_name("target-spec")
+        .expect("target-spec is a workspace member");
+    match determinator_set.reasons.reasons(target_spec.id()) {
+        [AffectedReason::SummaryChanged(changes)] => {
+            assert!(!changes.is_empty(), "at least one summary changed");
+            for change in changes {
+                let diff = change.diff();
+                assert!(
+                    diff.target_packages
+                        .changed
+                        .keys()
+                        .any(|summary_id| summary_id.name == "toml"),
+                    "toml changed with {:?}",
+                    change.features,
+                );
+            }
+        }
+        other => panic!("unexpected reasons for target-spec: {other:?}"),
+    }
+}
+
+fn reasons_by_name<'g>(determinator_set: &DeterminatorSet<'g>) -> Vec<(&'g str, Vec<String>)> {
+    let mut reasons: Vec<_> = determinator_set
+        .affected_set
+        .packages(DependencyDirection::Forward)
+        .map(|package| {
+            let reasons = determinator_set
+                .reasons
+                .reasons(package.id())
+                .iter()
+                .map(|reason| reason.to_string())
+                .collect();
+            (package.name(), reasons)
+        })
+        .collect();
+    reasons.sort_unstable();
+    reasons
+}
+
 #[test]
 fn guppy_match_paths() {
     let old = JsonFixture::metadata_guppy_869476c();
@@ -438,6 +1284,127 @@ fn guppy_match_paths() {
     }
 }
 
+#[test]
+fn guppy_dep_info() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+    let target_dir = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../fixtures/determinator-dep-info/target-dir"
+    );
+    let index =
+        DepInfoIndex::from_target_dir(new.graph(), target_dir).expect("target dir read correctly");
+    // Files that aren't valid UTF-8 are skipped rather than failing the whole index.
+    let read_errors: Vec<_> = index
+        .read_errors()
+        .iter()
+        .map(|error| error.path().file_name())
+        .collect();
+    assert_eq!(read_errors, [Some("invalid-0123456789abcdef.d")]);
+
+    let names_for_path = |path: &str| -> Vec<&str> {
+        let mut names: Vec<_> = index
+            .packages_for_path(path)
+            .into_iter()
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        names
+    };
+    // Dep-info files for crates on any platform and profile are read.
+    assert_eq!(
+        names_for_path("shared/assets/schema.sql"),
+        ["cargo-guppy", "target-spec"]
+    );
+    assert_eq!(names_for_path("shared/assets/data file.txt"), ["guppy"]);
+    // A file in a different package is recorded, but files in the same package aren't.
+    assert_eq!(names_for_path("fixtures/small/metadata1.json"), ["guppy"]);
+    assert!(names_for_path("guppy/src/lib.rs").is_empty());
+    // rerun-if-changed paths to directories cover everything in them.
+    assert_eq!(names_for_path("proto/foo.proto"), ["guppy-summaries"]);
+    assert_eq!(names_for_path("proto/codegen.rs"), ["guppy-summaries"]);
+    assert!(names_for_path("shared/assets/other.sql").is_empty());
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    let mut matched = vec![];
+    assert_eq!(
+        determinator.match_path("shared/assets/schema.sql", |id| matched.push(id)),
+        PathMatch::NoMatches,
+    );
+    determinator.set_dep_info_index(&index);
+    assert_eq!(
+        determinator.match_path("shared/assets/schema.sql", |id| matched.push(id)),
+        PathMatch::DepInfoMatched,
+    );
+    assert_eq!(matched.len(), 2, "cargo-guppy and target-spec matched");
+    assert_eq!(
+        determinator.match_path("fixtures/small/metadata1.json", |_| {}),
+        PathMatch::AncestorMatched,
+    );
+
+    determinator.add_changed_paths(vec!["shared/assets/schema.sql", "proto/foo.proto"]);
+    let determinator_set = determinator.compute();
+    let path_changed = new
+        .graph()
+        .resolve_workspace_names(["cargo-guppy", "guppy-summaries", "target-spec"])
+        .expect("workspace names resolved");
+    assert_eq!(determinator_set.path_changed_set, path_changed);
+    let reasons = reasons_by_name(&determinator_set);
+    assert_eq!(
+        reasons
+            .iter()
+            .find(|(name, _)| *name == "guppy-summaries")
+            .expect("guppy-summaries is affected")
+            .1,
+        ["path proto/foo.proto is used by the package's build"],
+    );
+
+    // Suggested rules group paths by the packages that use them, and leave out paths covered by
+    // rerun-if-changed directories. The workspace isn't present, so proto could also be a file.
+    let rules = index.suggested_rules();
+    let path_rule = |globs: &[&str], names: &[&str], post_rule| {
+        let mut path_rule = PathRule::new(
+            globs.iter().map(|glob| glob.to_string()).collect(),
+            DeterminatorMarkChanged::Packages(names.iter().map(|name| name.to_string()).collect()),
+        );
+        path_rule.post_rule = post_rule;
+        path_rule
+    };
+    let expected = [
+        path_rule(
+            &["shared/assets/schema.sql"],
+            &["cargo-guppy", "target-spec"],
+            DeterminatorPostRule::Skip,
+        ),
+        path_rule(
+            &["proto/**", "proto"],
+            &["guppy-summaries"],
+            DeterminatorPostRule::Skip,
+        ),
+        path_rule(
+            &["fixtures/small/metadata1.json"],
+            &["guppy"],
+            DeterminatorPostRule::Fallthrough,
+        ),
+    ];
+    for rule in &expected {
+        assert!(
+            rules.path_rules.contains(rule),
+            "suggested rules {:?} contain {rule:?}",
+            rules.path_rules,
+        );
+    }
+
+    // The suggested rules have the same effect as the index.
+    let rules = DeterminatorRules::parse(&toml::to_string(&rules).expect("rules serialized"))
+        .expect("rules parsed");
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_rules(&rules).expect("rules set correctly");
+    determinator.add_changed_paths(vec!["shared/assets/schema.sql", "proto/foo.proto"]);
+    let determinator_set = determinator.compute();
+    assert_eq!(determinator_set.path_changed_set, path_changed);
+}
+
 static GIT_MATCH_PATHS_DIFF: &str =
     include_str!("../../../fixtures/determinator-paths/git-diff.out");
 
@@ -521,3 +1488,237 @@ fn read_options(fixture: &JsonFixture, toml_name: &str) -> DeterminatorRules {
         std::fs::read_to_string(&toml_path).expect("determinator.toml was successfully read");
     DeterminatorRules::parse(&opts).expect("determinator.toml parsed")
 }
+
+#[test]
+fn guppy_owners() {
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    let code_owners = CodeOwners::parse(
+        "* @core
+         /fixtures/ @fixtures-team
+         /internal-tools/ @tools-team",
+    )
+    .expect("CODEOWNERS parsed");
+    let package_owners = code_owners.package_owners(new.graph());
+
+    let workspace = new.graph().workspace();
+    let fixtures = workspace
+        .member_by_name("fixtures")
+        .expect("fixtures found");
+    assert_eq!(
+        package_owners.owners(fixtures.id()),
+        Some(&["@fixtures-team".to_owned()][..]),
+    );
+    assert_eq!(package_owners.unowned().count(), 0, "all packages owned");
+
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.add_changed_paths(vec!["fixtures/src/details.rs"]);
+    let determinator_set = determinator.compute();
+
+    let affected_owners = package_owners.affected_owners(&determinator_set);
+    let changed: Vec<_> = affected_owners.changed.keys().map(|s| s.as_str()).collect();
+    assert_eq!(changed, ["@fixtures-team"], "fixtures changed");
+
+    // fixtures is depended on by guppy (owned by @core) and fixture-manager (owned by
+    // @tools-team).
+    let downstream: Vec<_> = affected_owners
+        .downstream
+        .iter()
+        .map(|(owner, packages)| {
+            let names: Vec<_> = packages.iter().map(|package| package.name()).collect();
+            (owner.as_str(), names)
+        })
+        .collect();
+    assert_eq!(
+        downstream,
+        [
+            ("@core", vec!["guppy"]),
+            ("@tools-team", vec!["fixture-manager"]),
+        ],
+        "downstream owners match"
+    );
+}
+
+#[test]
+fn guppy_contents() {
+    // There are no dependency changes between the old and new fixtures, only file changes.
+    let old = JsonFixture::metadata_guppy_869476c();
+    let new = JsonFixture::metadata_guppy_c9b4f76();
+
+    static OLD_MANIFEST: &str = r#"
+        [workspace]
+        members = ["guppy", "target-spec"]
+
+        [workspace.dependencies]
+        toml = "0.5"
+
+        [profile.release]
+        lto = true
+    "#;
+
+    let changed_names = |manifest: &str, config: Option<(&str, &str)>| {
+        let mut determinator = Determinator::new(old.graph(), new.graph());
+        determinator.set_root_manifest_contents(OLD_MANIFEST, manifest);
+        let mut changed_paths = vec!["Cargo.toml"];
+        if let Some((old_config, new_config)) = config {
+            determinator.set_cargo_config_contents(old_config, new_config);
+            changed_paths.push(".cargo/config.toml");
+        }
+        determinator.add_changed_paths(changed_paths);
+        let determinator_set = determinator.compute();
+        let mut names: Vec<_> = determinator_set
+            .path_changed_set
+            .packages(DependencyDirection::Forward)
+            .map(|package| package.name())
+            .collect();
+        names.sort_unstable();
+        (names, reasons_by_name(&determinator_set))
+    };
+
+    // Formatting and comment changes mark nothing.
+    let (names, _) = changed_names(
+        r#"
+        # The list of workspace members.
+        [workspace]
+        members = [
+            "guppy",
+            "target-spec",
+        ]
+        dependencies = { toml = "0.5" }
+
+        [profile.release]
+        lto = true  # this is slow
+        "#,
+        None,
+    );
+    assert!(names.is_empty(), "nothing marked changed: {names:?}");
+
+    // Adding a workspace member marks nothing by itself.
+    let manifest = OLD_MANIFEST.replace(
+        r#"["guppy", "target-spec"]"#,
+        r#"["guppy", "target-spec", "guppy-summaries"]"#,
+    );
+    let (names, _) = changed_names(&manifest, None);
+    assert!(names.is_empty(), "nothing marked changed: {names:?}");
+
+    // Changing a workspace dependency marks the members that depend on it.
+    let manifest = OLD_MANIFEST.replace(r#"toml = "0.5""#, r#"toml = "0.5.8""#);
+    let (names, reasons) = changed_names(&manifest, None);
+    assert_eq!(names, ["guppy-summaries", "target-spec"]);
+    assert_eq!(
+        reasons
+            .iter()
+            .find(|(name, _)| *name == "target-spec")
+            .expect("target-spec is affected")
+            .1,
+        ["path Cargo.toml changed at key workspace.dependencies.toml"],
+    );
+
+    // Adding a profile override for a package marks the members that depend on it.
+    let manifest = format!(
+        "{OLD_MANIFEST}
+[profile.dev.package.target-spec]
+opt-level = 3
+"
+    );
+    let (names, _) = changed_names(&manifest, None);
+    assert_eq!(
+        names,
+        [
+            "cargo-compare",
+            "cargo-guppy",
+            "fixture-manager",
+            "fixtures",
+            "guppy",
+            "guppy-benchmarks",
+            "guppy-cmdlib",
+            "target-spec",
+        ]
+    );
+
+    // Package ID specs in URL form are matched through the name in their fragment.
+    let manifest = format!(
+        r#"{OLD_MANIFEST}
+[replace."https://github.com/guppy-rs/guppy#target-spec@0.4.0"]
+path = "vendor/target-spec"
+"#
+    );
+    let (url_names, _) = changed_names(&manifest, None);
+    assert_eq!(url_names, names);
+
+    // A URL spec without a package name marks everything.
+    let manifest = format!(
+        r#"{OLD_MANIFEST}
+[replace."https://github.com/guppy-rs/guppy#0.4.0"]
+path = "vendor/target-spec"
+"#
+    );
+    let (names, reasons) = changed_names(&manifest, None);
+    assert_eq!(names.len(), new.graph().workspace().iter().count());
+    assert_eq!(
+        reasons[0].1,
+        [
+            "path Cargo.toml changed at key replace.https://github.com/guppy-rs/guppy#0.4.0, \
+             which marked all packages changed"
+        ],
+    );
+
+    // Any other profile change marks everything.
+    let manifest = OLD_MANIFEST.replace("lto = true", "lto = false");
+    let (names, reasons) = changed_names(&manifest, None);
+    assert_eq!(names.len(), new.graph().workspace().iter().count());
+    for (name, reasons) in reasons {
+        assert_eq!(
+            reasons,
+            vec![
+                "path Cargo.toml changed at key profile.release.lto, which marked all packages \
+                 changed"
+            ],
+            "reasons for {name} match"
+        );
+    }
+
+    // A manifest that can't be parsed marks everything.
+    let (names, reasons) = changed_names("[workspace", None);
+    assert_eq!(names.len(), new.graph().workspace().iter().count());
+    assert_eq!(
+        reasons[0].1,
+        ["path Cargo.toml could not be parsed, so all packages were marked changed"],
+    );
+
+    // Aliases in .cargo/config.toml don't influence builds, but build flags do.
+    let (names, _) = changed_names(
+        OLD_MANIFEST,
+        Some(("", "[alias]\nxtask = \"run -p xtask\"\n")),
+    );
+    assert!(names.is_empty(), "nothing marked changed: {names:?}");
+    let (names, _) = changed_names(
+        OLD_MANIFEST,
+        Some(("", "[build]\nrustflags = [\"--cfg\", \"foo\"]\n")),
+    );
+    assert_eq!(names.len(), new.graph().workspace().iter().count());
+
+    // Custom rules take precedence over the analysis, and default rules are replaced by it.
+    let mut determinator = Determinator::new(old.graph(), new.graph());
+    determinator.set_root_manifest_contents(OLD_MANIFEST, OLD_MANIFEST);
+    assert_eq!(
+        determinator.match_path("Cargo.toml", |_| {}),
+        PathMatch::ContentsMatched
+    );
+    let opts = DeterminatorRules::parse(
+        r#"
+        [[path-rule]]
+        globs = ["Cargo.toml"]
+        mark-changed = ["guppy"]
+        "#,
+    )
+    .expect("rules parsed");
+    determinator.set_rules(&opts).expect("rules set correctly");
+    let mut matched = vec![];
+    assert_eq!(
+        determinator.match_path("Cargo.toml", |id| matched.push(id)),
+        PathMatch::RuleMatched(RuleIndex::CustomPath(0))
+    );
+    assert_eq!(matched.len(), 1, "guppy matched");
+}
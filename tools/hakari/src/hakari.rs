@@ -37,6 +37,10 @@ pub struct HakariBuilder<'g> {
     graph: DebugIgnore<&'g PackageGraph>,
     hakari_package: Option<PackageMetadata<'g>>,
     pub(crate) platforms: Vec<Arc<Platform>>,
+    // The summaries that platforms were created from, if any. Used to write out custom platform
+    // paths as originally specified.
+    #[cfg(feature = "cli-support")]
+    pub(crate) platform_summaries: Vec<Option<guppy::platform::PlatformSummary>>,
     resolver: CargoResolverVersion,
     pub(crate) verify_mode: bool,
     pub(crate) traversal_excludes: HashSet<&'g PackageId>,
@@ -76,6 +80,8 @@ impl<'g> HakariBuilder<'g> {
             graph: DebugIgnore(graph),
             hakari_package,
             platforms: vec![],
+            #[cfg(feature = "cli-support")]
+            platform_summaries: vec![],
             resolver: CargoResolverVersion::V2,
             verify_mode: false,
             traversal_excludes: HashSet::new(),
@@ -126,9 +132,10 @@ impl<'g> HakariBuilder<'g> {
     /// builds are commonly performed on a few platforms, `hakari` can output platform-specific
     /// instructions for those builds.
     ///
-    /// This currently supports target triples only, without further customization around
-    /// target features or flags. In the future, this may support `cfg()` expressions using
-    /// an [SMT solver](https://en.wikipedia.org/wiki/Satisfiability_modulo_theories).
+    /// This method accepts builtin target triples only, without further customization around
+    /// target features or flags. To use custom platforms, call
+    /// [`add_platforms`](Self::add_platforms) instead. In the future, this may support `cfg()`
+    /// expressions using an [SMT solver](https://en.wikipedia.org/wiki/Satisfiability_modulo_theories).
     ///
     /// Call `set_platforms` with an empty list to reset to default behavior.
     ///
@@ -142,11 +149,31 @@ impl<'g> HakariBuilder<'g> {
             .into_iter()
             .map(|s| Ok(Arc::new(Platform::new(s.into(), TargetFeatures::Unknown)?)))
             .collect::<Result<Vec<_>, _>>()?;
+        #[cfg(feature = "cli-support")]
+        {
+            self.platform_summaries = vec![None; self.platforms.len()];
+        }
         Ok(self)
     }
 
-    /// Returns the platforms set through `set_platforms`, or an empty list if no platforms are
-    /// set.
+    /// Adds platforms for `hakari` to use, after any platforms already set.
+    ///
+    /// Unlike [`set_platforms`](Self::set_platforms), this accepts fully-specified
+    /// [`Platform`] instances, including custom platforms defined by target JSON or
+    /// `rustc --print=cfg` output.
+    pub fn add_platforms(
+        &mut self,
+        platforms: impl IntoIterator<Item = impl Into<Arc<Platform>>>,
+    ) -> &mut Self {
+        self.platforms
+            .extend(platforms.into_iter().map(|platform| platform.into()));
+        #[cfg(feature = "cli-support")]
+        self.platform_summaries.resize(self.platforms.len(), None);
+        self
+    }
+
+    /// Returns the triples for the platforms set through `set_platforms` or `add_platforms`, or
+    /// an empty list if no platforms are set.
     pub fn platforms(&self) -> impl ExactSizeIterator<Item = &str> + '_ {
         self.platforms.iter().map(|platform| platform.triple_str())
     }
@@ -377,18 +404,45 @@ impl<'g> HakariBuilder<'g> {
 mod summaries {
     use super::*;
     use crate::summaries::HakariBuilderSummary;
-    use guppy::platform::TargetFeatures;
+    use camino::Utf8Path;
 
     impl<'g> HakariBuilder<'g> {
         /// Constructs a `HakariBuilder` from a `PackageGraph` and a serialized summary.
         ///
         /// Requires the `cli-support` feature to be enabled.
         ///
+        /// Relative custom platform paths are resolved against the current directory. To resolve
+        /// them against some other directory, use [`Self::from_summary_relative_to`].
+        ///
         /// Returns an error if the summary references a package that's not present, or if there was
         /// some other issue while creating a `HakariBuilder` from the summary.
         pub fn from_summary(
             graph: &'g PackageGraph,
             summary: &HakariBuilderSummary,
+        ) -> Result<Self, guppy::Error> {
+            Self::from_summary_impl(graph, summary, None)
+        }
+
+        /// Constructs a `HakariBuilder` from a `PackageGraph` and a serialized summary, resolving
+        /// relative custom platform paths against `base_dir`.
+        ///
+        /// `base_dir` is typically the directory containing the configuration file the summary was
+        /// read from. The paths are kept as written in the summary, so
+        /// [`to_summary`](Self::to_summary) returns them unchanged.
+        ///
+        /// Requires the `cli-support` feature to be enabled.
+        pub fn from_summary_relative_to(
+            graph: &'g PackageGraph,
+            summary: &HakariBuilderSummary,
+            base_dir: &Utf8Path,
+        ) -> Result<Self, guppy::Error> {
+            Self::from_summary_impl(graph, summary, Some(base_dir))
+        }
+
+        fn from_summary_impl(
+            graph: &'g PackageGraph,
+            summary: &HakariBuilderSummary,
+            base_dir: Option<&Utf8Path>,
         ) -> Result<Self, guppy::Error> {
             let hakari_package = summary
                 .hakari_package
@@ -398,17 +452,25 @@ mod summaries {
             let platforms = summary
                 .platforms
                 .iter()
-                .map(|triple_str| {
-                    let platform = Platform::new(triple_str.clone(), TargetFeatures::Unknown)
-                        .map_err(|err| {
-                            guppy::Error::TargetSpecError(
-                                "while resolving hakari config or summary".to_owned(),
-                                err,
-                            )
-                        })?;
+                .map(|platform_summary| {
+                    let platform = match base_dir {
+                        Some(base_dir) => {
+                            let mut resolved = platform_summary.clone();
+                            resolved.resolve_paths(base_dir);
+                            resolved.to_platform()
+                        }
+                        None => platform_summary.to_platform(),
+                    };
+                    let platform = platform.map_err(|err| {
+                        guppy::Error::TargetSpecError(
+                            "while resolving hakari config or summary".to_owned(),
+                            err,
+                        )
+                    })?;
                     Ok(platform.into())
                 })
                 .collect::<Result<Vec<_>, _>>()?;
+            let platform_summaries = summary.platforms.iter().cloned().map(Some).collect();
 
             let registries: BiHashMap<_, ahash::RandomState> = summary
                 .registries
@@ -448,6 +510,7 @@ mod summaries {
                 dep_format_version: summary.dep_format_version,
                 workspace_hack_line_style: summary.workspace_hack_line_style,
                 platforms,
+                platform_summaries,
                 registries,
                 traversal_excludes,
                 final_excludes,
@@ -9,9 +9,11 @@ use crate::{
     HakariBuilder, HakariOutputOptions, TomlOutError, UnifyTargetHost,
     hakari::{DepFormatVersion, WorkspaceHackLineStyle},
 };
+use camino::Utf8Path;
 use guppy::{
     errors::TargetSpecError,
     graph::{PackageGraph, cargo::CargoResolverVersion, summaries::PackageSetSummary},
+    platform::PlatformSummary,
 };
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fmt, str::FromStr};
@@ -84,8 +86,14 @@ pub struct HakariBuilderSummary {
     pub workspace_hack_line_style: WorkspaceHackLineStyle,
 
     /// The platforms used by the `HakariBuilder`.
-    #[serde(default)]
-    pub platforms: Vec<String>,
+    ///
+    /// Each platform is either a target triple string, or a map in the format accepted by
+    /// [`PlatformSummary`]. Custom platforms can refer to a target definition JSON file through
+    /// `custom-json-path`, or to a file containing `rustc --print=cfg` output through
+    /// `custom-cfg-path`. Use [`Self::to_hakari_builder_relative_to`] to resolve those paths
+    /// relative to the configuration file.
+    #[serde(default, with = "platforms_impl")]
+    pub platforms: Vec<PlatformSummary>,
 
     /// The list of packages excluded during graph traversals.
     #[serde(default)]
@@ -112,16 +120,22 @@ impl HakariBuilderSummary {
     ///
     /// Requires the `cli-support` feature to be enabled.
     ///
-    /// Returns an error if there are any custom platforms. Serializing custom platforms is
-    /// currently unsupported.
+    /// Platforms that were read from a summary are written out as they were originally specified,
+    /// including any relative `custom-json-path` or `custom-cfg-path`. Other custom platforms are
+    /// serialized with their definitions embedded in the summary.
+    ///
+    /// This currently always returns `Ok`: serializing custom platforms used to be unsupported,
+    /// and the `Result` is retained for compatibility.
     pub fn new(builder: &HakariBuilder<'_>) -> Result<Self, TargetSpecError> {
         Ok(Self {
             hakari_package: builder
                 .hakari_package()
                 .map(|package| package.name().to_string()),
             platforms: builder
-                .platforms()
-                .map(|triple_str| triple_str.to_owned())
+                .platforms
+                .iter()
+                .zip(&builder.platform_summaries)
+                .map(|(platform, summary)| summary.clone().unwrap_or_else(|| platform.to_summary()))
                 .collect::<Vec<_>>(),
             resolver: builder.resolver(),
             traversal_excludes: PackageSetSummary::from_package_ids(
@@ -157,6 +171,19 @@ impl HakariBuilderSummary {
         HakariBuilder::from_summary(graph, self)
     }
 
+    /// Creates a `HakariBuilder` from this summary and a `PackageGraph`, resolving relative
+    /// custom platform paths against `base_dir`.
+    ///
+    /// `base_dir` is typically the directory containing the configuration file this summary was
+    /// read from. For more, see [`HakariBuilder::from_summary_relative_to`].
+    pub fn to_hakari_builder_relative_to<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        base_dir: &Utf8Path,
+    ) -> Result<HakariBuilder<'g>, guppy::Error> {
+        HakariBuilder::from_summary_relative_to(graph, self, base_dir)
+    }
+
     /// Serializes this summary to a TOML string.
     ///
     /// Returns an error if writing out the TOML was unsuccessful.
@@ -201,8 +228,8 @@ impl HakariBuilder<'_> {
     ///
     /// Requires the `cli-support` feature to be enabled.
     ///
-    /// Returns an error if there are any custom platforms. Serializing custom platforms is
-    /// currently unsupported.
+    /// For more about how platforms are serialized and when this returns an error, see
+    /// [`HakariBuilderSummary::new`].
     pub fn to_summary(&self) -> Result<HakariBuilderSummary, TargetSpecError> {
         HakariBuilderSummary::new(self)
     }
@@ -248,6 +275,41 @@ impl OutputOptionsSummary {
     }
 }
 
+mod platforms_impl {
+    use super::*;
+    use serde::{Deserializer, Serializer};
+
+    /// Serializes platforms as plain triple strings if none of them are customized, and as maps
+    /// otherwise.
+    ///
+    /// (toml 0.5 can't serialize arrays that mix strings and maps.)
+    pub fn serialize<S>(platforms: &[PlatformSummary], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let all_triples = platforms
+            .iter()
+            .all(|platform| platform == &PlatformSummary::new(platform.triple.as_str()));
+        if all_triples {
+            let triples: Vec<_> = platforms
+                .iter()
+                .map(|platform| platform.triple.as_str())
+                .collect();
+            triples.serialize(serializer)
+        } else {
+            platforms.serialize(serializer)
+        }
+    }
+
+    /// Deserializes platforms, each of which is either a triple string or a map.
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<PlatformSummary>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Vec::<PlatformSummary>::deserialize(deserializer)
+    }
+}
+
 mod registries_impl {
     use super::*;
     use serde::{Deserializer, Serializer};
@@ -354,4 +416,91 @@ mod tests {
             "summary => serialized => summary roundtrip"
         );
     }
+
+    #[test]
+    fn custom_platforms() {
+        static CUSTOM_PLATFORMS_INPUT: &str = r#"
+        resolver = "2"
+        platforms = [
+            "x86_64-unknown-linux-gnu",
+            { triple = "thumbv7em-acme-none-eabihf", custom-cfg-path = "targets/acme.cfg" },
+        ]
+        "#;
+        static ACME_CFG: &str = r#"panic="abort"
+target_arch="arm"
+target_endian="little"
+target_os="none"
+target_pointer_width="32"
+"#;
+
+        let temp_dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(temp_dir.path()).expect("temp dir is valid UTF-8");
+        std::fs::create_dir(dir.join("targets")).expect("created targets dir");
+        std::fs::write(dir.join("targets/acme.cfg"), ACME_CFG).expect("wrote cfg file");
+
+        let summary: HakariBuilderSummary =
+            toml::from_str(CUSTOM_PLATFORMS_INPUT).expect("failed to parse toml");
+        assert_eq!(
+            summary.platforms,
+            vec![
+                PlatformSummary::new("x86_64-unknown-linux-gnu"),
+                PlatformSummary::new("thumbv7em-acme-none-eabihf")
+                    .with_custom_cfg_path("targets/acme.cfg"),
+            ],
+        );
+
+        // Since there's a custom platform, all platforms are serialized as maps.
+        let serialized = summary.to_string().expect("serialized to TOML correctly");
+        assert!(
+            serialized.contains("[[platforms]]"),
+            "platforms serialized as maps: {serialized}"
+        );
+        let summary2: HakariBuilderSummary =
+            toml::from_str(&serialized).expect("deserialized from TOML correctly");
+        assert_eq!(
+            summary, summary2,
+            "summary => serialized => summary roundtrip"
+        );
+
+        let builder = summary
+            .to_hakari_builder_relative_to(JsonFixture::metadata1().graph(), dir)
+            .expect("summary => builder conversion");
+        assert_eq!(
+            builder.platforms().collect::<Vec<_>>(),
+            ["x86_64-unknown-linux-gnu", "thumbv7em-acme-none-eabihf"],
+        );
+        assert_eq!(
+            builder.platforms[1].to_summary().custom_cfg.as_deref(),
+            Some(ACME_CFG),
+            "cfg file read relative to base dir"
+        );
+
+        // Converting back keeps the original relative path rather than embedding the cfg text.
+        let summary3 = builder.to_summary().expect("builder => summary conversion");
+        assert_eq!(summary, summary3, "summary => builder => summary roundtrip");
+
+        // Custom platforms added directly to a builder have their definitions embedded.
+        let mut builder2 =
+            HakariBuilder::new(JsonFixture::metadata1().graph(), None).expect("builder created");
+        builder2.add_platforms(builder.platforms.iter().cloned());
+        let summary4 = builder2
+            .to_summary()
+            .expect("builder2 => summary conversion");
+        assert_eq!(
+            summary4.platforms[1],
+            PlatformSummary::new("thumbv7em-acme-none-eabihf").with_custom_cfg(ACME_CFG),
+        );
+        let builder3 = summary4
+            .to_hakari_builder(JsonFixture::metadata1().graph())
+            .expect("summary4 => builder3 conversion");
+        assert_eq!(builder.platforms, builder3.platforms, "platforms roundtrip");
+
+        // Resolving against the current directory fails since the file isn't there.
+        assert!(
+            summary
+                .to_hakari_builder(JsonFixture::metadata1().graph())
+                .is_err(),
+            "relative path not found in current directory"
+        );
+    }
 }
@@ -109,7 +109,13 @@ mod cli_support_tests {
             panic!("could not deserialize hakari config at {config_path}: {err}")
         });
 
-        let builder = config.builder.to_hakari_builder(&graph).unwrap();
+        let builder = config
+            .builder
+            .to_hakari_builder_relative_to(
+                &graph,
+                config_path.parent().expect("config path has a parent"),
+            )
+            .unwrap();
         if let Err(errs) = builder.verify() {
             panic!("verify failed: {}", errs.display());
         }